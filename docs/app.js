@@ -6,6 +6,7 @@ const info = document.getElementById("info");
 const btnPlay = document.getElementById("btnPlay");
 const btnPause = document.getElementById("btnPause");
 const audioEl = document.getElementById("audio");
+const trackSel = document.getElementById("track");
 const cv = document.getElementById("cv");
 const ctx = cv.getContext("2d");
 
@@ -21,7 +22,16 @@ let state = {
   frames: 0,
   headerSize: 10,
   packedLen: 0,
-  blob: null,     // decoded BA bytes (header+frames)
+  rowBits: 96,    // packed 프레임 한 행의 비트 수 (w * bitDepth, row-align이면 8의 배수)
+  bitDepth: 1,    // 1 = bitset (1=black), 2 = 4단계 gray (3=white), 4 = 16단계 gray nibble (15=white)
+  invert: false,  // 헤더 v2_flags bit0: 흑백 반전해서 그린다
+  lsbFirst: false, // 헤더 v2_flags2 bit0: 1-bit bitset의 앞 픽셀이 bit0 (--bit-order lsb)
+  blob: null,     // packed frames (frame0 + diffs, 고정 길이, 헤더 제외)
+  durations: null, // 저장된 프레임마다 재생 길이 (--drop-duplicates), null이면 모두 1
+  msStarts: null, // ms duration/timestamp table이 있으면 저장된 프레임마다 시작 시각 (ms, 끝에 전체 길이), 없으면 fps대로
+  total: 0,       // 재생되는 프레임 수 (durations 합)
+  stored: 0,      // 현재 보여주는 저장 프레임 index
+  nextChange: 0,  // 다음 저장 프레임으로 넘어가는 frameIndex
   cur: null,      // Uint8Array current bitset
   off: 0,
   frameIndex: 0,
@@ -38,19 +48,451 @@ function getBit(buf, i) {
   return (b >> shift) & 1;
 }
 
+// --bit-order lsb: 앞 픽셀이 bit0
+function getBitLsb(buf, i) {
+  return (buf[i >> 3] >> (i & 7)) & 1;
+}
+
 function xorInPlace(dst, src) {
   for (let i = 0; i < dst.length; i++) dst[i] ^= src[i];
 }
 
+// BA 헤더 (encoder/src/blob.rs와 동일 규약)
+// v1: "BAPF" u8 version u8 flags u16 w u16 h u16 fps_x100 u32 frames (16 bytes) + 끝에 CRC32 (flags bit0)
+//     flags bit4: 행마다 바이트 정렬 (stride = ceil(w/8))
+//     flags bit5: 헤더 뒤에 u32 timing_offset, 프레임 뒤에 u32 duration * frames (--drop-duplicates)
+//     flags bit6: frame1..은 타일 레코드 (u16 count, {u16 index, tile bits}*) (--tiles, encoder/src/tiles.rs)
+//     flags bit7: (timing_offset 뒤에) u32 frame_crc_offset, timing table 뒤에 u32 crc32 * frames (--checksums)
+// v2: v1 헤더 뒤(optional offset 앞)에 u8 bit_depth (1|2|4) + u8 v2_flags + u8 v2_flags2 + u8 reserved
+//     (--bit-depth 2/4, --invert, --polarity white/auto, --bit-order lsb, --encrypt, --frame-durations, --vfr, --threshold auto/mean, --adaptive, --repeat-records)
+//     v2_flags bit0: 그릴 때 흑백 반전 (payload는 그대로)
+//     v2_flags bit1-2: payload 암호화 (0 없음, 1 xor, 2 aes-ctr, --encrypt). aes-ctr이면 헤더 끝에 u8[16] IV.
+//     v2_flags bit3: (frame_crc_offset 뒤에) u32 ms_timing_offset, timing table 뒤에 u16 ms * frames (--frame-durations)
+//     v2_flags bit4-5: 이진화 방식 (0 fixed, 1 otsu, 2 mean, 3 adaptive). 참고용이라 재생에는 쓰지 않는다.
+//     v2_flags bit6: (ms_timing_offset 자리에) u32 timestamps_offset, timing table 뒤에 u32 ms * (frames + 1) (--vfr)
+//     v2_flags bit7: 1-bit payload의 켠 비트가 white (--polarity white/auto). 그릴 때 되돌린다 (bit0과 상쇄).
+//     v2_flags2 bit0: 1-bit bitset의 앞 픽셀이 bit0 (--bit-order lsb). 타일 레코드와는 같이 쓸 수 없다.
+//     v2_flags2 bit1: 레코드마다 tag byte (0x00 + 프레임 레코드, 0xFF + u16 repeat count) (--repeat-records).
+//                     frames는 repeat까지 센 재생 프레임 수이고, frame CRC table도 재생 프레임마다 한 칸이다.
+//     헤더 뒤부터 CRC32 앞까지가 암호화되어 있고, 키는 URL의 #k=<hex>에서 읽는다 (--embed-key-in-url)
+// legacy: u16 w u16 h u16 fps_x100 u32 frames (10 bytes)
+const BA_MAGIC = [0x42, 0x41, 0x50, 0x46]; // "BAPF"
+const BA_VERSION = 1;
+const BA_VERSION_2 = 2;
+const FLAG_HAS_CRC = 1 << 0;
+const FLAG_CODEC_SHIFT = 1;
+const FLAG_CODEC_MASK = 0b111 << FLAG_CODEC_SHIFT;
+const FLAG_ROW_ALIGN = 1 << 4;
+const FLAG_TIMING = 1 << 5;
+const FLAG_TILES = 1 << 6;
+const FLAG_FRAME_CRC = 1 << 7;
+const V2_FLAG_INVERT = 1 << 0;
+const V2_FLAG_CIPHER_SHIFT = 1;
+const V2_FLAG_CIPHER_MASK = 0b11 << V2_FLAG_CIPHER_SHIFT;
+const V2_FLAG_MS_TIMING = 1 << 3;
+const V2_FLAG_THRESHOLD_MASK = 0b11 << 4;
+const V2_FLAG_TIMESTAMPS = 1 << 6;
+const V2_FLAG_WHITE_INK = 1 << 7;
+const V2_FLAG2_LSB_FIRST = 1 << 0;
+const V2_FLAG2_REPEAT = 1 << 1;
+const RECORD_FRAME = 0x00;
+const RECORD_REPEAT = 0xFF;
+const CIPHER_NONE = 0;
+const CIPHER_XOR = 1;
+const CIPHER_AES_CTR = 2;
+const CIPHER_NAMES = ["none", "xor", "aes-ctr"];
+const IV_LEN = 16;
+const TILE_SIZE = 16;
+const CODEC_NONE = 0;
+const CODEC_RLE = 1;
+const CODEC_DEFLATE = 2;
+const CODEC_ZSTD = 3;
+const CODEC_BROTLI = 4;
+
 function parseHeader(u8) {
-  if (u8.byteLength < 10) throw new Error("BA stream too small for header");
   const dv = new DataView(u8.buffer, u8.byteOffset, u8.byteLength);
-  const w = dv.getUint16(0, true);
-  const h = dv.getUint16(2, true);
-  const fps_x100 = dv.getUint16(4, true);
-  const frames = dv.getUint32(6, true);
-  if (!w || !h || !frames) throw new Error("Invalid BA header values");
-  return { w, h, fps: fps_x100 / 100.0, frames, headerSize: 10 };
+  const hasMagic = u8.byteLength >= 4 && BA_MAGIC.every((b, i) => u8[i] === b);
+  let hdr;
+  if (hasMagic) {
+    if (u8.byteLength < 16) throw new Error("BA stream too small for header");
+    const version = u8[4];
+    if (version !== BA_VERSION && version !== BA_VERSION_2) throw new Error(`Unsupported BA version ${version}`);
+    hdr = {
+      version,
+      flags: u8[5],
+      w: dv.getUint16(6, true),
+      h: dv.getUint16(8, true),
+      fps: dv.getUint16(10, true) / 100.0,
+      frames: dv.getUint32(12, true),
+      headerSize: 16,
+      bitDepth: 1,
+      invert: false,
+      cipher: CIPHER_NONE,
+    };
+    if (version >= BA_VERSION_2) {
+      if (u8.byteLength < hdr.headerSize + 4) throw new Error("BA stream too small for header");
+      hdr.bitDepth = u8[16];
+      if (hdr.bitDepth !== 1 && hdr.bitDepth !== 2 && hdr.bitDepth !== 4) throw new Error(`Unsupported BA bit depth ${hdr.bitDepth}`);
+      if (u8[17] & ~(V2_FLAG_INVERT | V2_FLAG_CIPHER_MASK | V2_FLAG_MS_TIMING | V2_FLAG_THRESHOLD_MASK | V2_FLAG_TIMESTAMPS | V2_FLAG_WHITE_INK)) throw new Error(`Invalid BA v2 header: unknown v2 flags ${u8[17]}`);
+      hdr.invert = (u8[17] & V2_FLAG_INVERT) !== 0;
+      hdr.msTiming = (u8[17] & V2_FLAG_MS_TIMING) !== 0;
+      hdr.timestamps = (u8[17] & V2_FLAG_TIMESTAMPS) !== 0;
+      hdr.whiteInk = (u8[17] & V2_FLAG_WHITE_INK) !== 0;
+      if (hdr.whiteInk && hdr.bitDepth !== 1) throw new Error(`Invalid BA v2 header: white ink polarity on a ${hdr.bitDepth}-bit blob`);
+      if (hdr.msTiming && hdr.timestamps) throw new Error("Invalid BA v2 header: both an ms duration table and an ms timestamp table");
+      hdr.cipher = (u8[17] & V2_FLAG_CIPHER_MASK) >> V2_FLAG_CIPHER_SHIFT;
+      if (hdr.cipher > CIPHER_AES_CTR) throw new Error(`Unsupported BA payload cipher ${hdr.cipher}`);
+      if (u8[18] & ~(V2_FLAG2_LSB_FIRST | V2_FLAG2_REPEAT)) throw new Error(`Invalid BA v2 header: unknown v2 flags2 ${u8[18]}`);
+      if (u8[19]) throw new Error("Invalid BA v2 header: reserved byte is not zero");
+      hdr.lsbFirst = (u8[18] & V2_FLAG2_LSB_FIRST) !== 0;
+      if (hdr.lsbFirst && (hdr.bitDepth !== 1 || (hdr.flags & FLAG_TILES))) throw new Error("Invalid BA v2 header: LSB-first bit order needs an untiled 1-bit blob");
+      if (hdr.bitDepth !== 1 && (hdr.flags & FLAG_TILES)) throw new Error("Invalid BA header: tile records need a bit depth of 1");
+      hdr.repeat = (u8[18] & V2_FLAG2_REPEAT) !== 0;
+      if (hdr.repeat && ((hdr.flags & FLAG_TIMING) || hdr.msTiming || hdr.timestamps)) throw new Error("Invalid BA v2 header: repeat records cannot be combined with a per-frame timing table");
+      hdr.headerSize += 4;
+    }
+    if (hdr.flags & FLAG_TIMING) {
+      if (u8.byteLength < hdr.headerSize + 4) throw new Error("BA stream too small for header");
+      hdr.timingOffset = dv.getUint32(hdr.headerSize, true);
+      hdr.headerSize += 4;
+    }
+    if (hdr.flags & FLAG_FRAME_CRC) {
+      if (u8.byteLength < hdr.headerSize + 4) throw new Error("BA stream too small for header");
+      hdr.frameCrcOffset = dv.getUint32(hdr.headerSize, true);
+      hdr.headerSize += 4;
+    }
+    if (hdr.msTiming) {
+      if (u8.byteLength < hdr.headerSize + 4) throw new Error("BA stream too small for header");
+      hdr.msTimingOffset = dv.getUint32(hdr.headerSize, true);
+      hdr.headerSize += 4;
+    }
+    if (hdr.timestamps) {
+      if (u8.byteLength < hdr.headerSize + 4) throw new Error("BA stream too small for header");
+      hdr.timestampsOffset = dv.getUint32(hdr.headerSize, true);
+      hdr.headerSize += 4;
+    }
+    if (hdr.cipher === CIPHER_AES_CTR) {
+      if (u8.byteLength < hdr.headerSize + IV_LEN) throw new Error("BA stream too small for header");
+      hdr.iv = u8.slice(hdr.headerSize, hdr.headerSize + IV_LEN);
+      hdr.headerSize += IV_LEN;
+    }
+  } else {
+    if (u8.byteLength < 10) throw new Error("BA stream too small for header");
+    hdr = {
+      version: 0,
+      flags: 0,
+      w: dv.getUint16(0, true),
+      h: dv.getUint16(2, true),
+      fps: dv.getUint16(4, true) / 100.0,
+      frames: dv.getUint32(6, true),
+      headerSize: 10,
+      bitDepth: 1,
+      invert: false,
+      cipher: CIPHER_NONE,
+    };
+  }
+  if (!hdr.w || !hdr.h || !hdr.frames) throw new Error("Invalid BA header values");
+  hdr.crcSize = (hdr.flags & FLAG_HAS_CRC) ? 4 : 0;
+  hdr.codec = (hdr.flags & FLAG_CODEC_MASK) >> FLAG_CODEC_SHIFT;
+  if (hdr.codec === CODEC_ZSTD) {
+    // 브라우저에 zstd DecompressionStream이 없다
+    throw new Error("zstd-compressed BA blobs are not supported by this player; re-encode with --compress deflate");
+  }
+  if (hdr.codec === CODEC_BROTLI) {
+    // DecompressionStream은 gzip/deflate만 된다
+    throw new Error("brotli-compressed BA blobs are not supported by this player; re-encode with --compress deflate");
+  }
+  if (hdr.codec > CODEC_BROTLI) throw new Error(`Unsupported BA frame codec ${hdr.codec}`);
+  if (hdr.cipher && !hdr.crcSize) throw new Error("Invalid BA header: an encrypted stream needs a CRC trailer");
+  // 프레임 레코드가 끝나는 위치 (timing table이 있으면 그 앞)
+  hdr.timingSize = (hdr.flags & FLAG_TIMING) ? 4 * hdr.frames : 0;
+  hdr.frameCrcSize = (hdr.flags & FLAG_FRAME_CRC) ? 4 * hdr.frames : 0;
+  hdr.msTimingSize = hdr.msTiming ? 2 * hdr.frames : 0;
+  hdr.timestampsSize = hdr.timestamps ? 4 * (hdr.frames + 1) : 0;
+  // 끝쪽 table: [timing table][ms duration table 또는 ms timestamp table][frame CRC table][CRC32]
+  hdr.tablesEnd = hdr.frameCrcSize ? hdr.frameCrcOffset : u8.byteLength - hdr.crcSize;
+  hdr.timingEnd = hdr.msTimingSize ? hdr.msTimingOffset : hdr.timestampsSize ? hdr.timestampsOffset : hdr.tablesEnd;
+  hdr.dataEnd = hdr.timingSize ? hdr.timingOffset : hdr.timingEnd;
+  return hdr;
+}
+
+// 저장된 프레임마다 재생 길이 (1/fps 단위). 없으면 null (모두 1)
+function readDurations(u8, hdr) {
+  if (!hdr.timingSize) return null;
+  if (hdr.timingOffset + hdr.timingSize !== hdr.timingEnd) {
+    throw new Error(`Invalid BA timing table offset ${hdr.timingOffset}`);
+  }
+  const dv = new DataView(u8.buffer, u8.byteOffset + hdr.timingOffset, hdr.timingSize);
+  const out = new Uint32Array(hdr.frames);
+  for (let i = 0; i < hdr.frames; i++) {
+    out[i] = dv.getUint32(i * 4, true);
+    if (!out[i]) throw new Error(`BA timing table: frame ${i} has zero duration`);
+  }
+  return out;
+}
+
+// 저장된 프레임마다 시작 시각 (ms, 마지막 칸은 전체 길이). ms duration/timestamp table이 없으면 null (fps대로)
+function readMsStarts(u8, hdr) {
+  if (hdr.timestampsSize) return readTimestamps(u8, hdr);
+  if (!hdr.msTimingSize) return null;
+  if (hdr.msTimingOffset + hdr.msTimingSize !== hdr.tablesEnd) {
+    throw new Error(`Invalid BA ms duration table offset ${hdr.msTimingOffset}`);
+  }
+  const dv = new DataView(u8.buffer, u8.byteOffset + hdr.msTimingOffset, hdr.msTimingSize);
+  const out = new Float64Array(hdr.frames + 1);
+  for (let i = 0; i < hdr.frames; i++) {
+    const ms = dv.getUint16(i * 2, true);
+    if (!ms) throw new Error(`BA ms duration table: frame ${i} has zero duration`);
+    out[i + 1] = out[i] + ms;
+  }
+  return out;
+}
+
+// ms timestamp table (--vfr): 시작 시각이 그대로 적혀 있다 (마지막 칸은 끝 시각)
+function readTimestamps(u8, hdr) {
+  if (hdr.timestampsOffset + hdr.timestampsSize !== hdr.tablesEnd) {
+    throw new Error(`Invalid BA ms timestamp table offset ${hdr.timestampsOffset}`);
+  }
+  const dv = new DataView(u8.buffer, u8.byteOffset + hdr.timestampsOffset, hdr.timestampsSize);
+  const out = new Float64Array(hdr.frames + 1);
+  for (let i = 0; i <= hdr.frames; i++) {
+    out[i] = dv.getUint32(i * 4, true);
+    if (i === 0 && out[0]) throw new Error(`BA ms timestamp table: frame 0 starts at ${out[0]} ms (expected 0)`);
+    if (i > 0 && out[i] < out[i - 1]) throw new Error(`BA ms timestamp table: frame ${i} starts before frame ${i - 1}`);
+  }
+  return out;
+}
+
+// frame CRC table이 있으면 XOR chain을 한 번 따라가면서 복원한 프레임마다 CRC32를 확인한다.
+// 깨진 첫 프레임 번호(저장된 프레임 기준, repeat record blob이면 재생 프레임 기준)를 에러로 알려준다.
+function checkFrameCrcs(u8, hdr, frames, packedLen) {
+  if (!hdr.frameCrcSize) return;
+  if (hdr.frameCrcOffset + hdr.frameCrcSize !== u8.length - hdr.crcSize) {
+    throw new Error(`Invalid BA frame CRC table offset ${hdr.frameCrcOffset}`);
+  }
+  const dv = new DataView(u8.buffer, u8.byteOffset + hdr.frameCrcOffset, hdr.frameCrcSize);
+  const cur = new Uint8Array(packedLen);
+  // repeat된 프레임은 앞 프레임과 같으므로 저장된 프레임이 처음 보이는 칸만 본다
+  for (let i = 0, shown = 0; i < frames.length / packedLen; shown += hdr.repeat ? hdr.holds[i] : 1, i++) {
+    const rec = frames.subarray(i * packedLen, (i + 1) * packedLen);
+    if (i === 0) cur.set(rec);
+    else xorInPlace(cur, rec);
+    const expected = dv.getUint32(shown * 4, true), computed = crc32(cur);
+    if (expected !== computed) {
+      throw new Error(`BA frame ${shown} CRC mismatch (expected ${expected.toString(16)}, computed ${computed.toString(16)}): first corrupt frame`);
+    }
+  }
+}
+
+// 길이가 정해지지 않은 RLE 레코드 (타일 레코드)
+function rleDecodeAll(src) {
+  let n = 0;
+  for (let i = 0; i + 1 < src.length; i += 2) n += src[i];
+  const out = new Uint8Array(n);
+  let o = 0;
+  for (let i = 0; i + 1 < src.length; i += 2) {
+    out.fill(src[i + 1], o, o + src[i]);
+    o += src[i];
+  }
+  return out;
+}
+
+// 타일 레코드를 0으로 채운 diff 프레임(dst)에 풀어 넣는다.
+function applyTileDiff(dst, rec, w, h, rowBits) {
+  const tilesX = Math.ceil(w / TILE_SIZE);
+  const tileCount = tilesX * Math.ceil(h / TILE_SIZE);
+  const dv = new DataView(rec.buffer, rec.byteOffset, rec.byteLength);
+  if (rec.length < 2) throw new Error("Tile record truncated");
+  const count = dv.getUint16(0, true);
+  let off = 2;
+  for (let k = 0; k < count; k++) {
+    if (off + 2 > rec.length) throw new Error("Tile record truncated");
+    const index = dv.getUint16(off, true);
+    off += 2;
+    if (index >= tileCount) throw new Error(`Tile index ${index} out of range`);
+    const x0 = (index % tilesX) * TILE_SIZE, y0 = Math.floor(index / tilesX) * TILE_SIZE;
+    const tw = Math.min(TILE_SIZE, w - x0), th = Math.min(TILE_SIZE, h - y0);
+    const len = Math.ceil((tw * th) / 8);
+    if (off + len > rec.length) throw new Error("Tile record truncated");
+    const tile = rec.subarray(off, off + len);
+    off += len;
+    for (let y = 0; y < th; y++) {
+      for (let x = 0; x < tw; x++) {
+        if (getBit(tile, y * tw + x)) {
+          const i = (y0 + y) * rowBits + x0 + x;
+          dst[i >> 3] ^= 1 << (7 - (i & 7));
+        }
+      }
+    }
+  }
+}
+
+function rleDecode(src, dst) {
+  let o = 0;
+  for (let i = 0; i + 1 < src.length; i += 2) {
+    const run = src[i];
+    if (o + run > dst.length) throw new Error("RLE frame overflows packed size");
+    dst.fill(src[i + 1], o, o + run);
+    o += run;
+  }
+  if (o !== dst.length) throw new Error("RLE frame shorter than packed size");
+}
+
+async function inflate(src) {
+  const ds = new DecompressionStream("deflate");
+  const out = new Response(new Blob([src]).stream().pipeThrough(ds));
+  return new Uint8Array(await out.arrayBuffer());
+}
+
+// 코덱이 none이 아니거나 타일 레코드면 (u32 len + data) 레코드를 전부 풀어서
+// 고정 길이 프레임 배열(frame0 + 전체 XOR diff)로 만든다.
+// repeat record blob이면 저장된 프레임만 담고, 저장된 프레임마다 재생 길이를 hdr.holds에 남긴다.
+async function expandFrames(u8, hdr, packedLen) {
+  const start = hdr.headerSize;
+  const tiled = (hdr.flags & FLAG_TILES) !== 0;
+  const fixed = hdr.codec === CODEC_NONE && !tiled;
+  if (fixed && !hdr.repeat) return u8.subarray(start, start + packedLen * hdr.frames);
+  const rowBits = (hdr.flags & FLAG_ROW_ALIGN) ? Math.ceil(hdr.w * hdr.bitDepth / 8) * 8 : hdr.w * hdr.bitDepth;
+
+  const out = new Uint8Array(packedLen * hdr.frames);
+  const holds = hdr.repeat ? new Uint32Array(hdr.frames) : null;
+  const dv = new DataView(u8.buffer, u8.byteOffset, u8.byteLength);
+  const end = hdr.dataEnd;
+  let off = start;
+  let f = 0;
+  for (let shown = 0; shown < hdr.frames; shown++, f++) {
+    if (hdr.repeat) {
+      if (off + 1 > end) throw new Error(`BA stream truncated at frame ${shown}`);
+      const tag = u8[off++];
+      if (tag === RECORD_REPEAT) {
+        if (off + 2 > end) throw new Error(`BA stream truncated at frame ${shown}`);
+        const count = dv.getUint16(off, true);
+        off += 2;
+        if (!f || !count || shown + count > hdr.frames) throw new Error(`Invalid BA repeat record at frame ${shown}`);
+        // 앞 프레임을 count 프레임 더 보여준다
+        holds[f - 1] += count;
+        shown += count - 1;
+        f--;
+        continue;
+      }
+      if (tag !== RECORD_FRAME) throw new Error(`Unknown BA record tag ${tag} at frame ${shown}`);
+      holds[f] = 1;
+    }
+    let len = packedLen;
+    if (!fixed) {
+      if (off + 4 > end) throw new Error(`BA stream truncated at frame ${f}`);
+      len = dv.getUint32(off, true);
+      off += 4;
+    }
+    if (off + len > end) throw new Error(`BA stream truncated at frame ${f}`);
+    const rec = u8.subarray(off, off + len);
+    off += len;
+    const dst = out.subarray(f * packedLen, (f + 1) * packedLen);
+    if (tiled && f > 0) {
+      let data = rec;
+      if (hdr.codec === CODEC_RLE) data = rleDecodeAll(rec);
+      else if (hdr.codec === CODEC_DEFLATE) data = await inflate(rec);
+      applyTileDiff(dst, data, hdr.w, hdr.h, rowBits);
+    } else if (hdr.codec === CODEC_NONE) {
+      if (rec.length !== packedLen) throw new Error(`Frame ${f} has wrong size`);
+      dst.set(rec);
+    } else if (hdr.codec === CODEC_RLE) {
+      rleDecode(rec, dst);
+    } else {
+      const raw = await inflate(rec);
+      if (raw.length !== packedLen) throw new Error(`Deflate frame ${f} has wrong size`);
+      dst.set(raw);
+    }
+  }
+  if (holds) hdr.holds = holds.subarray(0, f);
+  return out.subarray(0, packedLen * f);
+}
+
+const CRC_TABLE = (() => {
+  const t = new Uint32Array(256);
+  for (let n = 0; n < 256; n++) {
+    let c = n;
+    for (let k = 0; k < 8; k++) c = (c & 1) ? (0xEDB88320 ^ (c >>> 1)) : (c >>> 1);
+    t[n] = c >>> 0;
+  }
+  return t;
+})();
+
+function crc32(u8) {
+  let c = 0xFFFFFFFF;
+  for (let i = 0; i < u8.length; i++) c = CRC_TABLE[(c ^ u8[i]) & 0xFF] ^ (c >>> 8);
+  return (c ^ 0xFFFFFFFF) >>> 0;
+}
+
+function checkCrc(u8, hdr) {
+  if (!hdr.crcSize) return;
+  const body = u8.subarray(0, u8.length - 4);
+  const dv = new DataView(u8.buffer, u8.byteOffset + u8.length - 4, 4);
+  const expected = dv.getUint32(0, true);
+  const computed = crc32(body);
+  if (expected !== computed) {
+    throw new Error(`BA stream CRC mismatch: expected ${expected.toString(16)}, computed ${computed.toString(16)}` +
+      (hdr.cipher ? " (wrong key?)" : ""));
+  }
+}
+
+// URL fragment의 k=<hex> (--embed-key-in-url). 없으면 null
+function keyFromHash() {
+  const m = /(?:^|&)k=([0-9a-fA-F]+)(?:&|$)/.exec(location.hash.slice(1));
+  if (!m || m[1].length % 2) return null;
+  return Uint8Array.from(m[1].match(/../g), h => parseInt(h, 16));
+}
+
+// 헤더 뒤부터 CRC32 앞까지 복호화한 사본 (encoder/src/crypt.rs와 같은 규약)
+async function decryptPayload(u8, hdr, key) {
+  const out = u8.slice();
+  const body = out.subarray(hdr.headerSize, out.length - hdr.crcSize);
+  if (hdr.cipher === CIPHER_XOR) {
+    for (let i = 0; i < body.length; i++) body[i] ^= key[i % key.length];
+    return out;
+  }
+  if (key.length !== 16 && key.length !== 32) throw new Error(`aes-ctr key must be 16 or 32 bytes, got ${key.length}`);
+  const k = await crypto.subtle.importKey("raw", key, "AES-CTR", false, ["decrypt"]);
+  const plain = await crypto.subtle.decrypt({ name: "AES-CTR", counter: hdr.iv, length: 128 }, k, body);
+  body.set(new Uint8Array(plain));
+  return out;
+}
+
+// --encrypt-audio: "BAAU" | u8 cipher | u8[3] 0 | (aes-ctr) u8[16] IV | 암호문 | u32 crc32(평문) (encoder/src/crypt.rs)
+const AUDIO_MAGIC = "BAAU";
+
+function isEncryptedAudio(u8) {
+  return u8.length >= 4 && String.fromCharCode(...u8.subarray(0, 4)) === AUDIO_MAGIC;
+}
+
+async function decryptAudio(u8, name) {
+  const cipher = u8[4];
+  if (cipher !== CIPHER_XOR && cipher !== CIPHER_AES_CTR) throw new Error(`${name}: unsupported audio cipher ${cipher}`);
+  const key = keyFromHash();
+  if (!key) throw new Error(`${name} is encrypted (${CIPHER_NAMES[cipher]}); open the player with #k=<hexkey> in the URL`);
+  const headerSize = 8 + (cipher === CIPHER_AES_CTR ? 16 : 0);
+  if (u8.length < headerSize + 4) throw new Error(`${name}: encrypted audio is truncated`);
+  const hdr = { cipher, headerSize, crcSize: 4, iv: u8.slice(8, 24) };
+  const plain = (await decryptPayload(u8, hdr, key)).subarray(headerSize, u8.length - 4);
+  const expected = new DataView(u8.buffer, u8.byteOffset + u8.length - 4, 4).getUint32(0, true);
+  const computed = crc32(plain);
+  if (expected !== computed) {
+    throw new Error(`${name}: audio CRC mismatch: expected ${expected.toString(16)}, computed ${computed.toString(16)} (wrong key?)`);
+  }
+  return plain;
+}
+
+// encoder/src/audio.rs detect_audio_mime와 같은 규칙
+function detectAudioMime(u8) {
+  const starts = (sig, off = 0) => sig.every((b, i) => u8[off + i] === b);
+  const ascii = (str) => [...str].map(c => c.charCodeAt(0));
+  if (starts(ascii("OggS"))) return "audio/ogg";
+  // ID3 없는 MP3는 frame sync (11비트 1, layer != 0; layer 0은 ADTS AAC)
+  if (starts(ascii("ID3")) || (u8[0] === 0xFF && (u8[1] & 0xE0) === 0xE0 && (u8[1] & 0x06) !== 0)) return "audio/mpeg";
+  if (starts(ascii("fLaC"))) return "audio/flac";
+  if (starts(ascii("RIFF")) && starts(ascii("WAVE"), 8)) return "audio/wav";
+  if (starts(ascii("ftyp"), 4)) return "audio/mp4";
+  return "application/octet-stream";
 }
 
 function renderFrame(bitset, w, h) {
@@ -59,10 +501,38 @@ function renderFrame(bitset, w, h) {
   }
   const data = state.img.data;
   let p = 0;
+  if (state.bitDepth === 2) {
+    // MSB-first 2비트씩 (앞 픽셀이 bit7-6), 0..3 → 0..255
+    const stride = state.rowBits >> 1;
+    for (let y = 0; y < h; y++) {
+      for (let x = 0; x < w; x++) {
+        const i = y * stride + x;
+        const v = (((bitset[i >> 2] >> (6 - 2 * (i & 3))) & 3) ^ (state.invert ? 3 : 0)) * 85;
+        data[p++] = v; data[p++] = v; data[p++] = v; data[p++] = 255;
+      }
+    }
+    ctx.putImageData(state.img, 0, 0);
+    return;
+  }
+  if (state.bitDepth === 4) {
+    // nibble 단위 (앞 픽셀이 high nibble), 0..15 → 0..255
+    const stride = state.rowBits >> 2;
+    for (let y = 0; y < h; y++) {
+      for (let x = 0; x < w; x++) {
+        const i = y * stride + x;
+        const b = bitset[i >> 1];
+        const v = (((i & 1) ? (b & 0x0f) : (b >> 4)) ^ (state.invert ? 0x0f : 0)) * 17;
+        data[p++] = v; data[p++] = v; data[p++] = v; data[p++] = 255;
+      }
+    }
+    ctx.putImageData(state.img, 0, 0);
+    return;
+  }
+  const bit = state.lsbFirst ? getBitLsb : getBit;
   for (let y = 0; y < h; y++) {
     for (let x = 0; x < w; x++) {
-      const i = y * w + x;
-      const v = getBit(bitset, i) ? 0 : 255;
+      const i = y * state.rowBits + x;
+      const v = (bit(bitset, i) ? 0 : 255) ^ (state.invert ? 255 : 0);
       data[p++] = v; data[p++] = v; data[p++] = v; data[p++] = 255;
     }
   }
@@ -74,15 +544,21 @@ function stopPlayback() {
   state.raf = 0;
 }
 
+function durationAt(i) {
+  return state.durations ? state.durations[i] : 1;
+}
+
 function startPlayback() {
   stopPlayback();
   state.frameIndex = 0;
-  state.off = state.headerSize;
+  state.off = 0;
 
   // frame0
   state.cur = new Uint8Array(state.packedLen);
   state.cur.set(state.blob.subarray(state.off, state.off + state.packedLen));
   state.off += state.packedLen;
+  state.stored = 0;
+  state.nextChange = durationAt(0);
 
   // 오디오를 “마스터 클럭”으로 사용 (동기화 안정)
   state.startClock = performance.now();
@@ -90,10 +566,29 @@ function startPlayback() {
   const tick = () => {
     // 오디오가 재생중이면 오디오 시간을 기준으로 프레임 맞추기
     let t = audioEl && !audioEl.paused ? audioEl.currentTime : ((performance.now() - state.startClock) / 1000);
+
+    if (state.msStarts) {
+      // 가변 frame rate: 저장된 프레임마다 시작 시각을 따른다
+      while (state.stored + 1 < state.frames && state.msStarts[state.stored + 1] <= t * 1000) {
+        state.stored++;
+        const diff = state.blob.subarray(state.off, state.off + state.packedLen);
+        state.off += state.packedLen;
+        xorInPlace(state.cur, diff);
+      }
+      renderFrame(state.cur, state.w, state.h);
+      if (state.stored + 1 >= state.frames) return;
+      state.raf = requestAnimationFrame(tick);
+      return;
+    }
+
     const target = Math.floor(t * state.fps);
 
-    while (state.frameIndex < target && state.frameIndex + 1 < state.frames) {
+    while (state.frameIndex < target && state.frameIndex + 1 < state.total) {
       state.frameIndex++;
+      // 합쳐진 프레임은 duration만큼 그대로 둔다
+      if (state.frameIndex < state.nextChange) continue;
+      state.stored++;
+      state.nextChange += durationAt(state.stored);
       const diff = state.blob.subarray(state.off, state.off + state.packedLen);
       state.off += state.packedLen;
       xorInPlace(state.cur, diff);
@@ -101,13 +596,114 @@ function startPlayback() {
 
     renderFrame(state.cur, state.w, state.h);
 
-    if (state.frameIndex + 1 >= state.frames) return;
+    if (state.frameIndex + 1 >= state.total) return;
     state.raf = requestAnimationFrame(tick);
   };
 
   state.raf = requestAnimationFrame(tick);
 }
 
+// BA.bin, 또는 --chunk-size로 나뉜 BA.000.bin, BA.001.bin, ...을 이어 붙인 것
+// 첨부 내용 (pdf.js는 Uint8Array, 복호화한 오디오는 ArrayBuffer일 수 있다). 복사하지 않는다
+function attachmentBytes(att) {
+  return ArrayBuffer.isView(att.content) ? att.content : new Uint8Array(att.content);
+}
+
+// manifest.json (없거나 JSON이 아니면 null)
+function readManifest(atts) {
+  const m = atts["manifest.json"];
+  if (!m) return null;
+  try {
+    return JSON.parse(new TextDecoder().decode(m.content));
+  } catch (e) {
+    console.warn("manifest.json is not valid JSON, looking for attachments by name", e);
+    return null;
+  }
+}
+
+// <stem>.000.<ext>, <stem>.001.<ext>, ... (encoder의 --chunk-size)
+function chunkNames(atts, first) {
+  const m = /^(.*)\.000(\.[^.]*)?$/.exec(first);
+  if (!m) return [first];
+  const names = [];
+  for (let i = 0; ; i++) {
+    const name = `${m[1]}.${String(i).padStart(3, "0")}${m[2] || ""}`;
+    if (!atts[name]) return names;
+    names.push(name);
+  }
+}
+
+// BA blob 첨부 이름 (조각이면 이어 붙일 순서대로). manifest.json의 video.name / chunks (--blob-name) →
+// BA.bin / BA.000.bin, ... → "BAPF" magic으로 시작하는 첫 첨부 순서로 찾는다.
+function findBlobNames(atts) {
+  const video = readManifest(atts)?.video;
+  if (video && Array.isArray(video.chunks)) return video.chunks;
+  if (video && typeof video.name === "string") return [video.name];
+  if (atts["BA.bin"]) return ["BA.bin"];
+  if (atts["BA.000.bin"]) return chunkNames(atts, "BA.000.bin");
+  const first = Object.keys(atts)
+    .sort()
+    .find(n => BA_MAGIC.every((b, i) => attachmentBytes(atts[n])[i] === b));
+  return first ? chunkNames(atts, first) : [];
+}
+
+function readBlobAttachment(atts) {
+  const names = findBlobNames(atts);
+  if (!names.length) throw new Error("Missing attachment: BA.bin");
+  const missing = names.find(n => !atts[n]);
+  if (missing) throw new Error(`Missing attachment: ${missing}`);
+  const parts = names.map(n => new Uint8Array(atts[n].content));
+  if (parts.length === 1) return parts[0];
+  const ba = new Uint8Array(parts.reduce((n, p) => n + p.length, 0));
+  let off = 0;
+  for (const p of parts) {
+    ba.set(p, off);
+    off += p.length;
+  }
+  return ba;
+}
+
+// 오디오 트랙 [{ name, title }]. manifest.json의 audio_tracks가 있으면 그 순서와 제목 (언어가 있으면 붙인다),
+// 없으면 첫 트랙 AU.ogg / AU.mp3 / ... 뒤에 --audio로 넣은 AU_<lang>.ogg, AU1.ogg, ... (이름 순).
+// 예전 인코더가 넣은 AU0.ogg, AU1.ogg, ...도 같은 규칙으로 잡힌다.
+// 그래도 없으면 (--audio-name에 manifest 없이) 오디오 magic bytes로 시작하는 첨부를 이름 순으로.
+function findAudioTracks(atts) {
+  const tracks = readManifest(atts)?.audio_tracks;
+  if (Array.isArray(tracks)) {
+    return tracks
+      .filter(t => atts[t.name])
+      .map(t => ({ name: t.name, title: (t.title || t.name) + (t.language ? ` (${t.language})` : "") }));
+  }
+  const exts = ["ogg", "mp3", "flac", "wav", "m4a"];
+  const first = exts.map(e => `AU.${e}`).filter(n => atts[n]).slice(0, 1);
+  const rest = Object.keys(atts)
+    .filter(n => /^AU(_[A-Za-z0-9-]+|\d+)\.(ogg|mp3|flac|wav|m4a)$/.test(n))
+    .sort((a, b) => a.localeCompare(b, undefined, { numeric: true }));
+  let names = [...first, ...rest];
+  if (!names.length) {
+    const blob = new Set(findBlobNames(atts));
+    names = Object.keys(atts)
+      .filter(n => !blob.has(n) && detectAudioMime(attachmentBytes(atts[n])) !== "application/octet-stream")
+      .sort((a, b) => a.localeCompare(b, undefined, { numeric: true }));
+  }
+  return names.map(name => ({ name, title: name }));
+}
+
+// 트랙을 바꿔도 재생 위치는 그대로 둔다
+function setAudioTrack(atts, name, keepTime) {
+  const t = keepTime ? audioEl.currentTime : 0;
+  const wasPlaying = !audioEl.paused;
+  if (state.audioUrl) URL.revokeObjectURL(state.audioUrl);
+  const au = new Uint8Array(atts[name].content);
+  // 오디오 포맷은 encoder의 --audio-format (기본 ogg opus). MIME은 magic bytes로 판별
+  state.audioUrl = URL.createObjectURL(new Blob([au], { type: detectAudioMime(au) }));
+  audioEl.src = state.audioUrl;
+  if (keepTime) {
+    audioEl.currentTime = t;
+    if (wasPlaying) audioEl.play().catch(() => {});
+  }
+}
+
 async function loadPdfFile(file) {
   stopPlayback();
   audioEl.pause();
@@ -129,25 +725,51 @@ async function loadPdfFile(file) {
   const atts = await pdf.getAttachments();
   if (!atts) throw new Error("No attachments found in this PDF.");
 
-  const baAtt = atts["BA.bin"];
-  const auAtt = atts["AU.ogg"];
-  if (!baAtt) throw new Error("Missing attachment: BA.bin");
-  if (!auAtt) throw new Error("Missing attachment: AU.ogg");
+  // --audio-format mp3면 AU.mp3. 입력에 오디오가 없으면 AU 첨부도 없다 (무음 재생)
+  const tracks = findAudioTracks(atts);
 
-  const ba = new Uint8Array(baAtt.content);
+  let ba = readBlobAttachment(atts);
   const hdr = parseHeader(ba);
+  if (hdr.cipher) {
+    const key = keyFromHash();
+    if (!key) throw new Error(`This BA stream is encrypted (${CIPHER_NAMES[hdr.cipher]}); open the player with #k=<hexkey> in the URL`);
+    ba = await decryptPayload(ba, hdr, key);
+  }
 
   state.w = hdr.w;
   state.h = hdr.h;
   state.fps = hdr.fps;
   state.frames = hdr.frames;
   state.headerSize = hdr.headerSize;
-  state.packedLen = Math.ceil((state.w * state.h) / 8);
-  state.blob = ba;
-  const expected = state.headerSize + (state.packedLen * state.frames);
-  if (state.blob.length < expected) {
-    throw new Error(`BA stream truncated: expected ${expected} bytes, got ${state.blob.length}`);
+  state.bitDepth = hdr.bitDepth;
+  // white 극성이면 켠 비트가 white라 반전 플래그와 XOR해서 그린다
+  state.invert = hdr.invert !== (hdr.whiteInk === true);
+  state.lsbFirst = hdr.lsbFirst === true;
+  state.rowBits = (hdr.flags & FLAG_ROW_ALIGN) ? Math.ceil(state.w * state.bitDepth / 8) * 8 : state.w * state.bitDepth;
+  state.packedLen = Math.ceil((state.rowBits * state.h) / 8);
+  if (hdr.codec === CODEC_NONE && !(hdr.flags & FLAG_TILES) && !hdr.repeat) {
+    const expected = state.headerSize + (state.packedLen * state.frames) + hdr.timingSize + hdr.msTimingSize + hdr.timestampsSize + hdr.frameCrcSize + hdr.crcSize;
+    // magic 없는 legacy blob은 크기가 정확히 맞아야 한다 (아니면 BA 파일이 아님)
+    if (hdr.version === 0 && ba.length !== expected) {
+      throw new Error(`Not a BA stream: missing "BAPF" magic and size ${ba.length} does not match a legacy blob (${expected})`);
+    }
+    if (ba.length < expected) {
+      throw new Error(`BA stream truncated: expected ${expected} bytes, got ${ba.length}`);
+    }
+  }
+  // frame CRC table이 있으면 그쪽을 먼저 확인해서 깨진 프레임 위치를 알려준다
+  if (!hdr.frameCrcSize) checkCrc(ba, hdr);
+  state.durations = readDurations(ba, hdr);
+  state.total = state.durations ? state.durations.reduce((a, d) => a + d, 0) : state.frames;
+  state.msStarts = readMsStarts(ba, hdr);
+  state.blob = await expandFrames(ba, hdr, state.packedLen);
+  // repeat record는 --drop-duplicates의 timing table처럼 저장된 프레임의 재생 길이로 재생한다
+  if (hdr.repeat) {
+    state.durations = hdr.holds;
+    state.frames = hdr.holds.length;
   }
+  checkFrameCrcs(ba, hdr, state.blob, state.packedLen);
+  if (hdr.frameCrcSize) checkCrc(ba, hdr);
 
   // canvas 설정
   cv.width = state.w;
@@ -155,19 +777,22 @@ async function loadPdfFile(file) {
   cv.style.width = (state.w * 6) + "px";
   cv.style.height = (state.h * 6) + "px";
 
-  // 2) AU 추출 (오디오)
-  const au = new Uint8Array(auAtt.content);
-
-  // 오디오 포맷은 우리가 encoder에서 정할 것(추천: audio/ogg; codecs=opus)
-  const audioBlob = new Blob([au], { type: "audio/ogg" });
-  state.audioUrl = URL.createObjectURL(audioBlob);
-  audioEl.src = state.audioUrl;
+  // 2) AU 추출 (오디오). 트랙이 여러 개면 고를 수 있게 한다. 암호화된 트랙은 미리 복호화해 둔다
+  for (const t of tracks) {
+    const au = new Uint8Array(atts[t.name].content);
+    if (isEncryptedAudio(au)) atts[t.name] = { ...atts[t.name], content: await decryptAudio(au, t.name) };
+  }
+  trackSel.replaceChildren(...tracks.map(t => new Option(t.title, t.name)));
+  trackSel.hidden = tracks.length < 2;
+  trackSel.onchange = () => setAudioTrack(atts, trackSel.value, true);
+  if (tracks.length) setAudioTrack(atts, tracks[0].name, false);
 
   state.loaded = true;
   btnPlay.disabled = false;
   btnPause.disabled = false;
 
-  info.textContent = `Loaded from your PDF — ${state.w}x${state.h}, fps=${state.fps}, frames=${state.frames}`;
+  info.textContent = `Loaded from your PDF — ${state.w}x${state.h}, fps=${state.fps}, frames=${state.total}` +
+    (tracks.length ? (tracks.length > 1 ? `, ${tracks.length} audio tracks` : "") : " (no audio)");
 
   // 자동 재생(사용자 제스처 직후라면 허용될 확률 높음)
   await audioEl.play().catch(() => {});
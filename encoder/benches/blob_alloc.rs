@@ -0,0 +1,139 @@
+//! 메모리 blob 버퍼 할당 벤치마크 (`cargo bench --bench blob_alloc`)
+//!
+//! CLI는 프레임 수를 알면 `max_blob_len`만큼 `Vec`을 미리 잡고 그 안에 인코딩한다.
+//! 빈 `Vec`에서 시작해 늘려가는 경우와 비교하려고, 시간 대신 allocator 호출 수(alloc + realloc)를 재는
+//! criterion `Measurement`를 쓴다. 결과의 단위는 반복 한 번(blob 하나)당 호출 수다.
+//! 입력은 hot_path와 같은 모양의 합성 프레임(지나가는 원)이라 ffmpeg가 필요 없다. rayon 스레드의 할당이 섞이지 않게
+//! 프레임 내부 병렬 처리(`parallel`)는 끈다.
+
+use badapple_encoder::{encode_blob_to, max_blob_len, EncodeOptions, RawSource};
+use criterion::{
+    criterion_group, criterion_main,
+    measurement::{Measurement, ValueFormatter},
+    BenchmarkId, Criterion, Throughput,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    io::Cursor,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 120;
+/// blob 하나의 프레임 수
+const FRAMES: usize = 300;
+
+/// 이 프로세스의 alloc/realloc 호출을 센다
+struct CountingAlloc;
+
+static CALLS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// criterion 측정값: 구간 동안의 allocator 호출 수
+struct Allocations;
+
+impl Measurement for Allocations {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> u64 {
+        CALLS.load(Ordering::Relaxed)
+    }
+
+    fn end(&self, start: u64) -> u64 {
+        CALLS.load(Ordering::Relaxed) - start
+    }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        a + b
+    }
+
+    fn zero(&self) -> u64 {
+        0
+    }
+
+    fn to_f64(&self, value: &u64) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &AllocationFormatter
+    }
+}
+
+struct AllocationFormatter;
+
+impl ValueFormatter for AllocationFormatter {
+    fn scale_values(&self, _typical: f64, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_throughputs(&self, _typical: f64, _throughput: &Throughput, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+}
+
+/// 프레임 `i`: 어두운 원이 왼쪽에서 오른쪽으로 지나간다
+fn synthetic_frames() -> Vec<u8> {
+    (0..FRAMES)
+        .flat_map(|i| {
+            let r = HEIGHT as f32 / 3.0;
+            let cx = WIDTH as f32 * i as f32 / FRAMES as f32;
+            let cy = HEIGHT as f32 / 2.0;
+            (0..WIDTH * HEIGHT).map(move |k| {
+                let (x, y) = ((k % WIDTH) as f32, (k / WIDTH) as f32);
+                let d = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt() - r;
+                (128.0 + d * 16.0).clamp(16.0, 240.0) as u8
+            })
+        })
+        .collect()
+}
+
+fn blob_alloc(c: &mut Criterion<Allocations>) {
+    let frames = synthetic_frames();
+    let opts = EncodeOptions { width: WIDTH as u16, height: HEIGHT as u16, max_frames: Some(FRAMES as u32), parallel: false, ..EncodeOptions::default() };
+    let capacity = max_blob_len(&opts).unwrap() as usize;
+
+    let mut group = c.benchmark_group("blob_buffer");
+    for (name, presize) in [("grow", false), ("presized", true)] {
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                let buf = if presize { Vec::with_capacity(capacity) } else { Vec::new() };
+                let mut out = Cursor::new(buf);
+                encode_blob_to(&mut RawSource::new(frames.as_slice(), true), &opts, &mut out).unwrap();
+                black_box(out.into_inner())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_measurement(Allocations).sample_size(10);
+    targets = blob_alloc
+}
+criterion_main!(benches);
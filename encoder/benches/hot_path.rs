@@ -0,0 +1,162 @@
+//! 인코딩 hot path 벤치마크 (`cargo bench`)
+//!
+//! ffmpeg 없이 메모리에서 만든 gray 프레임(밝은 배경 위를 지나가는 어두운 원)을 threshold, pack, XOR 단계에
+//! 차례로 넣는다. 외부 프로그램이나 fixture 파일이 필요 없다.
+//! 단계마다 criterion `BenchmarkGroup`을 하나 두고 해상도별로 `Throughput::Elements(FRAMES)`를 걸어
+//! 반복 한 번에 `FRAMES`장을 처리하므로 결과가 frames/s로 나온다.
+//! 이름으로 골라 돌릴 수 있다 (`cargo bench -- xor`, `cargo bench -- 640x480`).
+//! `black_segment`는 검은 정지 화면 구간을 `encode_blob`으로 끝까지 인코딩해서 `--drop-duplicates`와
+//! `--repeat-records`가 같은 프레임을 건너뛰는 경로를 잰다.
+
+use badapple_encoder::{binarize_frame, binarize_pack_into, encode_blob, pack_bits, xor_bytes_inplace, DitherMode, EncodeOptions, RawSource};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+/// 해상도별 프레임 수 (반복 한 번에 이만큼 처리한다)
+const FRAMES: usize = 32;
+const RESOLUTIONS: [(usize, usize); 3] = [(80, 60), (160, 120), (640, 480)];
+const THRESHOLD: u8 = 128;
+/// `black_segment`: 160x120 검은 화면 프레임 수
+const BLACK_FRAMES: usize = 300;
+
+/// 프레임 `i`: 가장자리가 흐린 원이 왼쪽에서 오른쪽으로 지나간다 (Bad Apple처럼 흑백 면적이 크게 바뀐다)
+fn synthetic_frames(width: usize, height: usize) -> Vec<Vec<u8>> {
+    (0..FRAMES)
+        .map(|i| {
+            let r = height as f32 / 3.0;
+            let cx = width as f32 * i as f32 / FRAMES as f32;
+            let cy = height as f32 / 2.0;
+            (0..width * height)
+                .map(|k| {
+                    let (x, y) = ((k % width) as f32, (k / width) as f32);
+                    let d = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt() - r;
+                    (128.0 + d * 16.0).clamp(16.0, 240.0) as u8
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// 한 해상도의 입력: gray 프레임, 0/1 프레임, packed 프레임
+struct Fixture {
+    width: usize,
+    height: usize,
+    frames: Vec<Vec<u8>>,
+    bits01: Vec<Vec<u8>>,
+    packed: Vec<Vec<u8>>,
+}
+
+impl Fixture {
+    fn new(width: usize, height: usize) -> Self {
+        let frames = synthetic_frames(width, height);
+        let bits01: Vec<Vec<u8>> = frames.iter().map(|g| binarize_frame(g, width, THRESHOLD, DitherMode::None)).collect();
+        let packed = bits01.iter().map(|b| pack_bits(b)).collect();
+        Self { width, height, frames, bits01, packed }
+    }
+
+    fn id(&self) -> BenchmarkId {
+        BenchmarkId::from_parameter(format!("{}x{}", self.width, self.height))
+    }
+}
+
+fn hot_path(c: &mut Criterion) {
+    let fixtures: Vec<Fixture> = RESOLUTIONS.iter().map(|&(w, h)| Fixture::new(w, h)).collect();
+
+    let mut group = c.benchmark_group("threshold");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for f in &fixtures {
+        group.bench_with_input(f.id(), f, |b, f| {
+            b.iter(|| {
+                for g in &f.frames {
+                    black_box(binarize_frame(black_box(g), f.width, THRESHOLD, DitherMode::None));
+                }
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("pack_bits");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for f in &fixtures {
+        group.bench_with_input(f.id(), f, |b, f| {
+            b.iter(|| {
+                for bits in &f.bits01 {
+                    black_box(pack_bits(black_box(bits)));
+                }
+            })
+        });
+    }
+    group.finish();
+
+    // 인코더가 실제로 쓰는 한 번에 하는 경로 (bits01 중간 버퍼 없음)
+    let mut group = c.benchmark_group("threshold+pack");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for f in &fixtures {
+        let mut out = Vec::new();
+        group.bench_with_input(f.id(), f, |b, f| {
+            b.iter(|| {
+                for g in &f.frames {
+                    binarize_pack_into(black_box(g), f.width, THRESHOLD, DitherMode::None, false, &mut out);
+                    black_box(&out);
+                }
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("xor");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for f in &fixtures {
+        let mut diff = vec![0u8; f.packed[0].len()];
+        group.bench_with_input(f.id(), f, |b, f| {
+            b.iter(|| {
+                for i in 0..FRAMES {
+                    diff.copy_from_slice(&f.packed[i]);
+                    xor_bytes_inplace(&mut diff, black_box(&f.packed[(i + FRAMES - 1) % FRAMES]));
+                    black_box(&diff);
+                }
+            })
+        });
+    }
+    group.finish();
+
+    // threshold+pack → 앞 프레임과 XOR (DeltaWriter의 delta 한 장)
+    let mut group = c.benchmark_group("full");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for f in &fixtures {
+        let (mut out, mut diff) = (Vec::new(), vec![0u8; f.packed[0].len()]);
+        let mut prev = vec![0u8; f.packed[0].len()];
+        group.bench_with_input(f.id(), f, |b, f| {
+            b.iter(|| {
+                for g in &f.frames {
+                    binarize_pack_into(black_box(g), f.width, THRESHOLD, DitherMode::None, false, &mut out);
+                    diff.copy_from_slice(&out);
+                    xor_bytes_inplace(&mut diff, &prev);
+                    prev.copy_from_slice(&out);
+                    black_box(&diff);
+                }
+            })
+        });
+    }
+    group.finish();
+
+    // 검은 화면만 이어지는 구간: 매 프레임 diff를 쓰는 기본값과 같은 프레임을 접는 두 방식
+    let black = vec![0u8; 160 * 120 * BLACK_FRAMES];
+    let base = EncodeOptions { width: 160, height: 120, ..EncodeOptions::default() };
+    let modes = [
+        ("plain", base.clone()),
+        ("drop-duplicates", EncodeOptions { drop_duplicates: true, ..base.clone() }),
+        ("repeat-records", EncodeOptions { repeat_records: true, ..base.clone() }),
+    ];
+    let mut group = c.benchmark_group("black_segment");
+    group.throughput(Throughput::Elements(BLACK_FRAMES as u64));
+    for (name, opts) in &modes {
+        group.bench_with_input(BenchmarkId::from_parameter(name), opts, |b, opts| {
+            b.iter(|| black_box(encode_blob(&mut RawSource::new(black.as_slice(), true), opts).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, hot_path);
+criterion_main!(benches);
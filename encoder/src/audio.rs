@@ -0,0 +1,406 @@
+//! 오디오 입력 → AU 첨부 (필요하면 ffmpeg로 트랜스코딩)
+
+use crate::{
+    pdf::Attachment,
+    probe::has_audio_stream,
+    progress::quiet,
+    source::{echo_stderr, stderr_excerpt, ConcatList, Trim},
+};
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// PDF에 넣을 오디오 포맷
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioFormat {
+    /// Ogg Vorbis
+    Ogg,
+    /// Ogg Opus (`-b:a 64k`)
+    #[default]
+    Opus,
+    /// MP3
+    Mp3,
+    /// 입력 파일을 그대로 넣는다.
+    Passthrough,
+}
+
+impl AudioFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            AudioFormat::Ogg => "ogg",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Passthrough => "passthrough",
+        }
+    }
+}
+
+impl std::str::FromStr for AudioFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "ogg" => AudioFormat::Ogg,
+            "opus" => AudioFormat::Opus,
+            "mp3" => AudioFormat::Mp3,
+            "passthrough" => AudioFormat::Passthrough,
+            _ => bail!("unknown audio format '{}' (expected ogg, opus, mp3, passthrough)", s),
+        })
+    }
+}
+
+/// AU 첨부 옵션
+#[derive(Debug, Clone, Default)]
+pub struct AudioOptions {
+    pub format: AudioFormat,
+    /// 입력이 이미 같은 컨테이너(Ogg / MP3)면 트랜스코딩하지 않고 그대로 넣는다
+    pub copy: bool,
+    /// ffmpeg `-b:a` 값 (예: `96k`). 없으면 코덱 기본값 (vorbis/mp3는 `-q:a 4`, opus는 64k)
+    pub bitrate: Option<String>,
+    /// 트랜스코딩할 때 2-pass loudnorm으로 음량을 맞춘다 (`--normalize-audio`, ffmpeg를 한 번 더 돌린다)
+    pub normalize: bool,
+    /// `--start` / `--duration` (비디오와 같은 구간만 넣는다)
+    pub trim: Trim,
+}
+
+/// loudnorm 목표값 (integrated -16 LUFS, LRA 11, true peak -1.5 dBTP)
+const LOUDNORM_TARGET: &str = "I=-16:LRA=11:TP=-1.5";
+
+/// loudnorm 1st pass (`print_format=json`) 측정값
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnormStats {
+    pub input_i: f64,
+    pub input_tp: f64,
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+}
+
+impl LoudnormStats {
+    /// 2nd pass용 loudnorm 필터 (측정값을 `measured_*`로 넘긴다)
+    pub fn filter(&self) -> String {
+        format!(
+            "loudnorm={}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            LOUDNORM_TARGET, self.input_i, self.input_tp, self.input_lra, self.input_thresh, self.target_offset
+        )
+    }
+}
+
+/// ffmpeg stderr 끝에 찍힌 loudnorm JSON (`{ "input_i" : "-27.61", ... }`)을 읽는다.
+/// 값이 전부 문자열인 평평한 객체라서 key별로 찾아서 숫자로 바꾼다.
+pub fn parse_loudnorm_json(stderr: &str) -> Result<LoudnormStats> {
+    let start = stderr.rfind('{').context("no loudnorm JSON in ffmpeg output")?;
+    let end = start + stderr[start..].find('}').context("unterminated loudnorm JSON in ffmpeg output")?;
+    let json = &stderr[start + 1..end];
+    let field = |key: &str| -> Result<f64> {
+        let value = json
+            .split(',')
+            .filter_map(|kv| kv.split_once(':'))
+            .find(|(k, _)| k.trim().trim_matches('"') == key)
+            .map(|(_, v)| v.trim().trim_matches('"'))
+            .with_context(|| format!("loudnorm JSON has no \"{}\"", key))?;
+        value.parse().with_context(|| format!("invalid loudnorm {} '{}'", key, value))
+    };
+    Ok(LoudnormStats {
+        input_i: field("input_i")?,
+        input_tp: field("input_tp")?,
+        input_lra: field("input_lra")?,
+        input_thresh: field("input_thresh")?,
+        target_offset: field("target_offset")?,
+    })
+}
+
+/// loudnorm 1st pass: `-f null -`로 디코딩만 하면서 음량을 잰다.
+/// 무음 입력처럼 측정값이 유한하지 않으면 `None` (정규화를 건너뛴다).
+fn measure_loudness(path: &Path, concat: bool, trim: &Trim) -> Result<Option<LoudnormStats>> {
+    let af = format!("loudnorm={}:print_format=json", LOUDNORM_TARGET);
+    // loudnorm JSON은 info 레벨로 찍히므로 -loglevel error를 주지 않는다
+    let out = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats"])
+        .args(trim.input_args())
+        .args(concat_args(concat))
+        .args(["-i", path.to_string_lossy().as_ref(), "-vn", "-af", &af, "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .output()
+        .context("failed to spawn ffmpeg for loudnorm (is it installed?)")?;
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    echo_stderr("ffmpeg", &stderr);
+    if !out.status.success() {
+        bail!("ffmpeg loudnorm pass exited with non-zero status ({})\nffmpeg stderr:\n{}", out.status, stderr_excerpt(&stderr));
+    }
+    let stats = parse_loudnorm_json(&stderr)?;
+    let finite = [stats.input_i, stats.input_tp, stats.input_lra, stats.input_thresh, stats.target_offset]
+        .iter()
+        .all(|v| v.is_finite());
+    Ok(finite.then_some(stats))
+}
+
+/// `--audio-bitrate` 값 검사: 숫자 + 선택적으로 k/M (ffmpeg `-b:a` 형식)
+pub fn parse_bitrate(s: &str) -> Result<String> {
+    let digits = s.strip_suffix(['k', 'K', 'M']).unwrap_or(s);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) || digits.bytes().all(|b| b == b'0') {
+        bail!("invalid audio bitrate '{}' (expected e.g. 64k, 128000)", s);
+    }
+    Ok(s.to_string())
+}
+
+/// 앞부분 magic bytes로 오디오 MIME을 정한다. 모르는 포맷은 `application/octet-stream`.
+/// MP3는 ID3 태그 없이 frame sync(11비트 1, layer != 0)로 시작할 수도 있다 (layer 0은 ADTS AAC).
+pub fn detect_audio_mime(data: &[u8]) -> &'static str {
+    let mp3_sync = data.len() >= 2 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0 && data[1] & 0x06 != 0;
+    if data.starts_with(b"OggS") {
+        "audio/ogg"
+    } else if data.starts_with(b"ID3") || mp3_sync {
+        "audio/mpeg"
+    } else if data.starts_with(b"fLaC") {
+        "audio/flac"
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WAVE" {
+        "audio/wav"
+    } else if data.len() >= 8 && &data[4..8] == b"ftyp" {
+        "audio/mp4"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// `detect_audio_mime` 결과에 맞는 첨부 확장자 (`AU.<ext>`)
+pub fn audio_extension(mime: &str) -> &'static str {
+    match mime {
+        "audio/ogg" => "ogg",
+        "audio/mpeg" => "mp3",
+        "audio/flac" => "flac",
+        "audio/wav" => "wav",
+        "audio/mp4" => "m4a",
+        _ => "bin",
+    }
+}
+
+/// `--audio-mime` 값 검사: `type/subtype` (RFC 6838 token 문자만)
+pub fn parse_mime(s: &str) -> Result<String> {
+    let token = |t: &str| !t.is_empty() && t.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$&-^_.+".contains(&b));
+    match s.split_once('/') {
+        Some((ty, sub)) if token(ty) && token(sub) => Ok(s.to_ascii_lowercase()),
+        _ => bail!("invalid MIME type '{}' (expected type/subtype, e.g. audio/mpeg)", s),
+    }
+}
+
+/// 파일 앞부분 (포맷 판별용)
+fn read_head(path: &Path) -> Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(16);
+    File::open(path)
+        .with_context(|| format!("failed to open audio file {}", path.display()))?
+        .take(16)
+        .read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// `path`가 `ConcatList` 목록 파일이면 `-i` 앞에 붙일 concat demuxer 옵션
+fn concat_args(concat: bool) -> &'static [&'static str] {
+    if concat {
+        &["-f", "concat", "-safe", "0"]
+    } else {
+        &[]
+    }
+}
+
+/// `-c:a <codec>`로 트랜스코딩해서 stdout으로 받는다.
+fn transcode(path: &Path, concat: bool, trim: &Trim, codec_args: &[&str], container: &str) -> Result<Vec<u8>> {
+    let out = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error"])
+        .args(trim.input_args())
+        .args(concat_args(concat))
+        .args(["-i", path.to_string_lossy().as_ref(), "-vn"])
+        .args(codec_args)
+        .args(["-f", container, "pipe:1"])
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to spawn ffmpeg for audio (is it installed?)")?;
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    echo_stderr("ffmpeg", &stderr);
+    if !out.status.success() {
+        let stderr = stderr_excerpt(&stderr);
+        if stderr.is_empty() {
+            bail!("ffmpeg audio transcode exited with non-zero status ({})", out.status);
+        }
+        bail!("ffmpeg audio transcode exited with non-zero status ({})\nffmpeg stderr:\n{}", out.status, stderr);
+    }
+    if out.stdout.is_empty() {
+        bail!("ffmpeg audio transcode produced no output");
+    }
+    Ok(out.stdout)
+}
+
+/// 오디오 입력 하나 (위치 인자 또는 `--audio`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioTrack {
+    pub path: PathBuf,
+    /// 트랙 제목 (manifest.json, 플레이어 트랙 선택). 기본은 입력 파일 이름
+    pub name: String,
+    /// `--audio-lang` (BCP 47 태그, 예: `en`, `ja`, `pt-BR`). 첨부 이름과 filespec `/Lang`에 쓴다
+    pub language: Option<String>,
+}
+
+impl AudioTrack {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let name = path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        Self { path, name, language: None }
+    }
+}
+
+/// `--audio-lang` 값: 영숫자 subtag를 `-`로 이은 BCP 47 모양 (첫 subtag는 2~8글자 영문)
+pub fn parse_language(s: &str) -> Result<String> {
+    let mut subtags = s.split('-');
+    let valid = subtags.next().is_some_and(|p| (2..=8).contains(&p.len()) && p.bytes().all(|b| b.is_ascii_alphabetic()))
+        && subtags.all(|p| (1..=8).contains(&p.len()) && p.bytes().all(|b| b.is_ascii_alphanumeric()));
+    if !valid {
+        bail!("invalid audio language '{}' (expected a BCP 47 tag such as en, ja or pt-BR)", s);
+    }
+    Ok(s.to_string())
+}
+
+/// `index`번째 오디오 트랙의 첨부 이름. 첫 트랙은 플레이어가 찾는 이름 그대로(`AU.ogg`),
+/// 그 뒤는 언어가 있으면 `AU_ja.ogg`, 없으면 `AU2.ogg`처럼 번호를 붙인다.
+pub fn track_name(name: &str, index: usize, language: Option<&str>) -> String {
+    if index == 0 {
+        return name.to_string();
+    }
+    let (stem, ext) = name.rsplit_once('.').map_or((name, None), |(s, e)| (s, Some(e)));
+    let stem = match language {
+        Some(lang) => format!("{}_{}", stem, lang),
+        None => format!("{}{}", stem, index),
+    };
+    match ext {
+        Some(ext) => format!("{}.{}", stem, ext),
+        None => stem,
+    }
+}
+
+/// 설정에 맞는 AU 첨부를 만든다.
+/// - passthrough거나, `copy`이고 입력이 이미 같은 컨테이너면 파일 그대로 (PDF를 저장할 때 파일에서 복사)
+/// - 아니면 ffmpeg로 트랜스코딩한 결과를 메모리로
+/// - 트랜스코딩할 입력에 오디오 스트림이 없으면 `None` (AU 첨부 없이 만든다)
+///
+/// MIME은 입력 확장자가 아니라 실제로 넣는 데이터의 magic bytes(`detect_audio_mime`) 기준이다.
+/// passthrough면 첨부 이름의 확장자도 그 MIME을 따른다 (`AU.mp3`, `AU.flac`, ...).
+pub fn prepare_audio(path: &Path, opts: &AudioOptions) -> Result<Option<Attachment>> {
+    let mime = detect_audio_mime(&read_head(path)?);
+    // 정규화하거나 구간을 자르려면 트랜스코딩해야 하므로 --audio-copy는 건너뛴다
+    let copy = opts.copy && !opts.normalize && !opts.trim.is_set();
+    match opts.format {
+        AudioFormat::Passthrough if opts.normalize => bail!("--normalize-audio cannot be used with --audio-format passthrough"),
+        AudioFormat::Passthrough if opts.trim.is_set() => {
+            bail!("--start/--duration cannot be used with --audio-format passthrough (the audio would not be trimmed)")
+        }
+        AudioFormat::Passthrough => {
+            return Ok(Some(Attachment::file(&format!("AU.{}", audio_extension(mime)), mime, path)))
+        }
+        AudioFormat::Ogg | AudioFormat::Opus if copy && mime == "audio/ogg" => {
+            return Ok(Some(Attachment::file("AU.ogg", mime, path)))
+        }
+        AudioFormat::Mp3 if copy && mime == "audio/mpeg" => {
+            return Ok(Some(Attachment::file("AU.mp3", mime, path)))
+        }
+        AudioFormat::Ogg | AudioFormat::Opus | AudioFormat::Mp3 => {}
+    }
+
+    if has_audio_stream(path) == Some(false) {
+        return Ok(None);
+    }
+    transcode_track(path, false, opts).map(Some)
+}
+
+/// `--concat-audio`: 입력 비디오들의 오디오를 ffmpeg concat demuxer로 이어서 한 트랙으로 트랜스코딩한다.
+/// 파일을 그대로 넣을 수 없으므로 passthrough는 안 되고 `--audio-copy`는 무시한다.
+/// 오디오 스트림이 없는 파일이 하나라도 있으면 concat demuxer가 스트림을 맞출 수 없어서 `None`.
+pub fn prepare_concat_audio(paths: &[PathBuf], opts: &AudioOptions) -> Result<Option<Attachment>> {
+    if opts.format == AudioFormat::Passthrough {
+        bail!("--concat-audio cannot be used with --audio-format passthrough (the inputs must be transcoded)");
+    }
+    if paths.iter().any(|p| has_audio_stream(p) == Some(false)) {
+        return Ok(None);
+    }
+    let list = ConcatList::new(paths)?;
+    transcode_track(list.path(), true, opts).map(Some)
+}
+
+/// ogg/opus/mp3로 트랜스코딩한 AU 첨부 (`--normalize-audio`면 loudnorm을 먼저 잰다)
+fn transcode_track(path: &Path, concat: bool, opts: &AudioOptions) -> Result<Attachment> {
+    let (name, codec, container) = match opts.format {
+        AudioFormat::Ogg | AudioFormat::Passthrough => ("AU.ogg", "libvorbis", "ogg"),
+        AudioFormat::Opus => ("AU.ogg", "libopus", "ogg"),
+        AudioFormat::Mp3 => ("AU.mp3", "libmp3lame", "mp3"),
+    };
+    let bitrate = match (&opts.bitrate, opts.format) {
+        (Some(b), _) => Some(b.as_str()),
+        (None, AudioFormat::Opus) => Some("64k"),
+        (None, _) => None,
+    };
+    let loudnorm = if opts.normalize {
+        let stats = measure_loudness(path, concat, &opts.trim).context("failed to measure audio loudness")?;
+        if stats.is_none() && !quiet() {
+            eprintln!("warning: could not measure loudness (silent audio?), skipping --normalize-audio");
+        }
+        stats.map(|s| s.filter())
+    } else {
+        None
+    };
+    let mut args = vec!["-c:a", codec];
+    if let Some(af) = &loudnorm {
+        args.extend(["-af", af.as_str()]);
+    }
+    match bitrate {
+        Some(b) => args.extend(["-b:a", b]),
+        None => args.extend(["-q:a", "4"]),
+    }
+    let data = transcode(path, concat, &opts.trim, &args, container)
+        .with_context(|| format!("failed to transcode audio with {}", codec))?;
+    Ok(Attachment::bytes(name, detect_audio_mime(&data), data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_audio_by_magic_bytes() {
+        for (head, mime) in [
+            (&b"OggS\x00\x02\x00\x00"[..], "audio/ogg"),
+            (b"ID3\x04\x00\x00\x00\x00\x00\x00", "audio/mpeg"),
+            // MPEG-1 layer III frame sync (ID3 없음)
+            (b"\xff\xfb\x90\x64", "audio/mpeg"),
+            // MPEG-2 layer II
+            (b"\xff\xf4\x90\x64", "audio/mpeg"),
+            (b"fLaC\x00\x00\x00\x22", "audio/flac"),
+            (b"RIFF\x24\x08\x00\x00WAVEfmt ", "audio/wav"),
+            (b"\x00\x00\x00\x20ftypM4A ", "audio/mp4"),
+        ] {
+            assert_eq!(detect_audio_mime(head), mime, "{:02x?}", head);
+            assert_ne!(audio_extension(mime), "bin");
+        }
+    }
+
+    #[test]
+    fn unknown_or_short_input_is_octet_stream() {
+        for head in [
+            &b""[..],
+            b"\xff",
+            b"Ogg",
+            // ADTS AAC: sync는 같지만 layer가 0
+            b"\xff\xf1\x50\x80",
+            // RIFF지만 WAVE가 아니다 (AVI), 또는 잘렸다
+            b"RIFF\x24\x08\x00\x00AVI LIST",
+            b"RIFF\x24\x08\x00\x00WAV",
+            b"BAAU\x02\x00\x00\x00",
+            b"plain text file",
+        ] {
+            assert_eq!(detect_audio_mime(head), "application/octet-stream", "{:02x?}", head);
+        }
+        assert_eq!(audio_extension("application/octet-stream"), "bin");
+    }
+}
@@ -0,0 +1,300 @@
+//! 프레임 비트 패킹 / XOR delta 헬퍼
+
+use anyhow::{bail, Result};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// 8픽셀(출력 1바이트)을 MSB-first로 묶는다. 8개보다 짧으면 남는 비트는 0.
+#[cfg(feature = "parallel")]
+#[inline]
+fn pack_byte(chunk: &[u8]) -> u8 {
+    chunk.iter().enumerate().fold(0, |acc, (i, &b)| acc | (((b != 0) as u8) << (7 - i)))
+}
+
+/// 픽셀 하나에 쓰는 비트 수. blob 헤더에 기록된다 (`Gray2`/`Gray4`는 v2 헤더).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitDepth {
+    /// 1-bit bitset (1=black, 0=white)
+    #[default]
+    Binary,
+    /// 4단계 gray, 한 바이트에 네 픽셀 (MSB-first, 앞 픽셀이 bit7-6, 0=black, 3=white)
+    Gray2,
+    /// 16단계 gray, 한 바이트에 두 픽셀 (앞 픽셀이 high nibble, 0=black, 15=white)
+    Gray4,
+}
+
+impl BitDepth {
+    pub fn bits(self) -> usize {
+        match self {
+            BitDepth::Binary => 1,
+            BitDepth::Gray2 => 2,
+            BitDepth::Gray4 => 4,
+        }
+    }
+
+    pub fn from_bits(bits: u8) -> Result<Self> {
+        Ok(match bits {
+            1 => BitDepth::Binary,
+            2 => BitDepth::Gray2,
+            4 => BitDepth::Gray4,
+            _ => bail!("unsupported bit depth {} (expected 1, 2 or 4)", bits),
+        })
+    }
+}
+
+impl std::str::FromStr for BitDepth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.parse::<u8>() {
+            Ok(bits) => Self::from_bits(bits),
+            Err(_) => bail!("invalid bit depth '{}' (expected 1, 2 or 4)", s),
+        }
+    }
+}
+
+/// 1-bit 프레임에서 한 바이트 안의 픽셀 순서 (`--bit-order`). blob 헤더(v2_flags2)에 기록된다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitOrder {
+    /// 앞 픽셀이 bit7 (기본, player.js와 PDF 1-bit 이미지 규약)
+    #[default]
+    Msb,
+    /// 앞 픽셀이 bit0 (canvas/WebGL 쪽 포트용)
+    Lsb,
+}
+
+impl std::str::FromStr for BitOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "msb" => BitOrder::Msb,
+            "lsb" => BitOrder::Lsb,
+            _ => bail!("unknown bit order '{}' (expected msb or lsb)", s),
+        })
+    }
+}
+
+/// 바이트마다 비트 순서를 뒤집는다 (MSB-first ↔ LSB-first, 같은 연산이라 양쪽 변환에 쓴다)
+pub fn reverse_bit_order(packed: &mut [u8]) {
+    packed.iter_mut().for_each(|b| *b = b.reverse_bits());
+}
+
+/// 픽셀 → packed 프레임. `Binary`는 bits01(0/1), `Gray2`/`Gray4`는 gray8을 받아서 위쪽 2/4비트를 MSB-first로 묶는다.
+/// 픽셀 수가 한 바이트에 딱 맞지 않으면 마지막 바이트의 남는 하위 비트는 0 (`Gray2` 5픽셀 → 2 bytes).
+pub fn pack_pixels(pixels: &[u8], depth: BitDepth) -> Vec<u8> {
+    match depth {
+        BitDepth::Binary => pack_bits(pixels),
+        BitDepth::Gray2 => pixels
+            .chunks(4)
+            .map(|p| p.iter().enumerate().fold(0, |acc, (i, &px)| acc | ((px >> 6) << (6 - 2 * i))))
+            .collect(),
+        BitDepth::Gray4 => pixels
+            .chunks(2)
+            .map(|p| (p[0] & 0xf0) | p.get(1).map_or(0, |&px| px >> 4))
+            .collect(),
+    }
+}
+
+/// `pack_pixels`를 행 단위로 (stride = ceil(width * bits / 8), 행 끝은 0으로 채움)
+pub fn pack_pixels_rows(pixels: &[u8], width: usize, depth: BitDepth) -> Vec<u8> {
+    match depth {
+        BitDepth::Binary => pack_bits_rows(pixels, width),
+        _ if width == 0 => Vec::new(),
+        BitDepth::Gray2 | BitDepth::Gray4 => pixels.chunks(width).flat_map(|row| pack_pixels(row, depth)).collect(),
+    }
+}
+
+/// MSB-first bit packing (player.js getBit()와 동일 규약): 픽셀 `i`는 `out[i / 8]`의 bit `7 - i % 8`.
+/// 길이가 8의 배수가 아니면 마지막 바이트의 남는 하위 비트는 0이다 (12 bits → 2 bytes).
+pub fn pack_bits(bits01: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; bits01.len().div_ceil(8)];
+    for (i, &b) in bits01.iter().enumerate() {
+        if b != 0 {
+            out[i / 8] |= 1 << (7 - (i % 8));
+        }
+    }
+    out
+}
+
+/// `pack_bits`와 같지만 행마다 바이트 경계까지 0으로 채운다 (stride = ceil(width/8)).
+/// `width`가 8의 배수면 `pack_bits`와 결과가 같다.
+pub fn pack_bits_rows(bits01: &[u8], width: usize) -> Vec<u8> {
+    if width == 0 {
+        return Vec::new();
+    }
+    let stride = width.div_ceil(8);
+    let mut out = vec![0u8; stride * bits01.len().div_ceil(width)];
+    for (y, row) in bits01.chunks(width).enumerate() {
+        let dst = &mut out[y * stride..(y + 1) * stride];
+        for (x, &b) in row.iter().enumerate() {
+            if b != 0 {
+                dst[x / 8] |= 1 << (7 - (x % 8));
+            }
+        }
+    }
+    out
+}
+
+/// `pack_bits`의 rayon 버전. 출력 바이트마다 8픽셀씩 독립적으로 묶는다.
+#[cfg(feature = "parallel")]
+pub fn pack_bits_par(bits01: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; bits01.len().div_ceil(8)];
+    out.par_iter_mut()
+        .zip(bits01.par_chunks(8))
+        .with_min_len(PAR_MIN_BYTES)
+        .for_each(|(d, chunk)| *d = pack_byte(chunk));
+    out
+}
+
+/// `pack_bits_rows`의 rayon 버전 (행 안에서는 8픽셀씩)
+#[cfg(feature = "parallel")]
+pub fn pack_bits_rows_par(bits01: &[u8], width: usize) -> Vec<u8> {
+    if width == 0 {
+        return Vec::new();
+    }
+    let stride = width.div_ceil(8);
+    let mut out = vec![0u8; stride * bits01.len().div_ceil(width)];
+    out.par_chunks_mut(stride)
+        .zip(bits01.par_chunks(width))
+        .with_min_len((PAR_MIN_BYTES / stride).max(1))
+        .for_each(|(dst, row)| {
+            for (d, chunk) in dst.iter_mut().zip(row.chunks(8)) {
+                *d = pack_byte(chunk);
+            }
+        });
+    out
+}
+
+/// 워커 하나가 최소한 맡는 출력 바이트 수
+#[cfg(feature = "parallel")]
+const PAR_MIN_BYTES: usize = 256;
+
+/// 연속 packing된 프레임 (`pack_bits`) → 행 정렬 packing (`pack_bits_rows`와 같은 배치).
+/// `Gray2`/`Gray4` 프레임은 `width`에 비트 단위 폭 (`w * 2`, `w * 4`)을 넘기면 된다.
+pub fn align_rows(packed: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let stride = width.div_ceil(8);
+    let mut out = vec![0u8; stride * height];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if (packed[i / 8] >> (7 - (i % 8))) & 1 != 0 {
+                out[y * stride + x / 8] |= 1 << (7 - (x % 8));
+            }
+        }
+    }
+    out
+}
+
+/// packed 1-bit 프레임의 픽셀 비트만 뒤집는다 (`--polarity white`). 행 끝/마지막 바이트의 남는 비트는 0으로 둔다.
+pub fn invert_pixel_bits(packed: &mut [u8], width: usize, height: usize, row_align: bool) {
+    let (row_bits, rows) = if row_align { (width.div_ceil(8) * 8, height) } else { (width * height, 1) };
+    let stride = row_bits.div_ceil(8);
+    let used = if row_align { width } else { width * height };
+    for row in packed.chunks_mut(stride).take(rows) {
+        row.iter_mut().for_each(|b| *b = !*b);
+        if used % 8 != 0 {
+            row[used / 8] &= 0xff << (8 - used % 8);
+        }
+    }
+}
+
+/// `dst ^= src`. 길이가 다르면 짧은 쪽까지만 XOR하고 `dst`의 나머지는 그대로 둔다 (zip).
+pub fn xor_bytes_inplace(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src.iter()) {
+        *d ^= *s;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_bits_12_bits_is_msb_first_and_zero_padded() {
+        let bits = [1, 0, 1, 1, 0, 0, 0, 1, 1, 1, 0, 1];
+        // 두 번째 바이트: 픽셀 8..12가 bit7..4, 하위 4비트는 0
+        assert_eq!(pack_bits(&bits), [0b1011_0001, 0b1101_0000]);
+        assert_eq!(pack_bits(&[1; 12]), [0xff, 0xf0]);
+        assert_eq!(pack_bits(&[]), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn pack_bits_treats_any_nonzero_as_set() {
+        assert_eq!(pack_bits(&[0, 2, 0, 0, 0, 0, 0, 255, 9]), [0b0100_0001, 0b1000_0000]);
+    }
+
+    /// w=10, h=3: 연속 packing은 행이 바이트 경계를 넘고, `--row-align`은 행마다 2 bytes
+    const ROWS_10X3: [u8; 30] = [
+        1, 0, 0, 0, 0, 0, 0, 0, 0, 1, //
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, //
+        0, 1, 0, 1, 0, 1, 0, 1, 0, 1,
+    ];
+
+    #[test]
+    fn pack_bits_10x3_contiguous_layout() {
+        assert_eq!(pack_bits(&ROWS_10X3), [0x80, 0x7f, 0xf5, 0x54]);
+    }
+
+    #[test]
+    fn pack_bits_rows_10x3_layout() {
+        let rows = [0x80, 0x40, 0xff, 0xc0, 0x55, 0x40];
+        assert_eq!(pack_bits_rows(&ROWS_10X3, 10), rows);
+        assert_eq!(align_rows(&pack_bits(&ROWS_10X3), 10, 3), rows);
+        #[cfg(feature = "parallel")]
+        assert_eq!(pack_bits_rows_par(&ROWS_10X3, 10), rows);
+    }
+
+    #[test]
+    fn pack_bits_rows_matches_pack_bits_for_width_multiple_of_8() {
+        let bits: Vec<u8> = (0..48).map(|i| (i * 7 % 3 == 0) as u8).collect();
+        assert_eq!(pack_bits_rows(&bits, 16), pack_bits(&bits));
+    }
+
+    #[test]
+    fn gray2_packs_four_pixels_per_byte_msb_first() {
+        // 위쪽 2비트만 쓴다: 0, 85, 170, 255 → 0..3
+        assert_eq!(pack_pixels(&[0, 85, 170, 255, 255], BitDepth::Gray2), [0b00_01_10_11, 0b11_00_00_00]);
+        assert_eq!(pack_pixels(&[0x3f, 0x40, 0xbf], BitDepth::Gray2), [0b00_01_10_00]);
+        // 3x2, 행 정렬: 행마다 1 byte
+        assert_eq!(pack_pixels_rows(&[255, 0, 255, 0, 255, 0], 3, BitDepth::Gray2), [0b11_00_11_00, 0b00_11_00_00]);
+    }
+
+    /// 홀수 w*h에서 packed 길이: 연속이면 ceil(w*h*bits/8), 행 정렬이면 h*ceil(w*bits/8). 헤더의 `packed_len`도 같다.
+    #[test]
+    fn packed_len_for_odd_sizes() {
+        use crate::blob::BlobHeader;
+        for (w, h) in [(1, 1), (3, 5), (5, 3), (7, 7), (13, 9), (17, 1)] {
+            for depth in [BitDepth::Binary, BitDepth::Gray2, BitDepth::Gray4] {
+                let pixels = vec![255u8; w * h];
+                let bits = depth.bits();
+                let mut header = BlobHeader::new(w as u16, h as u16, 30.0);
+                header.set_bit_depth(depth);
+
+                let packed = pack_pixels(&pixels, depth);
+                assert_eq!(packed.len(), (w * h * bits).div_ceil(8), "{}x{} {:?}", w, h, depth);
+                assert_eq!(header.packed_len(), packed.len(), "{}x{} {:?}", w, h, depth);
+
+                let rows = pack_pixels_rows(&pixels, w, depth);
+                assert_eq!(rows.len(), h * (w * bits).div_ceil(8), "{}x{} {:?} row-align", w, h, depth);
+                header.set_row_align(true);
+                assert_eq!(header.packed_len(), rows.len(), "{}x{} {:?} row-align", w, h, depth);
+                assert_eq!(align_rows(&packed, w * bits, h), rows, "{}x{} {:?}", w, h, depth);
+            }
+        }
+    }
+
+    #[test]
+    fn xor_with_shorter_dst_ignores_rest_of_src() {
+        let mut dst = [0x0f, 0xf0];
+        xor_bytes_inplace(&mut dst, &[0xff, 0xff, 0xaa, 0x55]);
+        assert_eq!(dst, [0xf0, 0x0f]);
+    }
+
+    #[test]
+    fn xor_with_shorter_src_keeps_tail_of_dst() {
+        let mut dst = [0x0f, 0xf0, 0xaa, 0x55];
+        xor_bytes_inplace(&mut dst, &[0xff]);
+        assert_eq!(dst, [0xf0, 0xf0, 0xaa, 0x55]);
+    }
+}
@@ -0,0 +1,705 @@
+//! BA blob 포맷 (헤더 레이아웃, CRC)
+//!
+//! v1 헤더 (16 bytes, LE, v2는 20 bytes):
+//! ```text
+//! 0   [u8; 4]  magic "BAPF"
+//! 4   u8       version (= 1)
+//! 5   u8       flags   (bit0: 끝에 CRC32 있음, bit1-3: 프레임 코덱 id,
+//!                       bit4: 행마다 바이트 정렬 (stride = ceil(w/8)),
+//!                       bit5: timing table 있음, bit6: 16x16 타일 레코드 (tiles.rs),
+//!                       bit7: 프레임별 CRC32 table 있음)
+//! 6   u16      width
+//! 8   u16      height
+//! 10  u16      fps_x100
+//! 12  u32      frame_count (저장된 프레임 수, v2_flags2 bit1이면 repeat record까지 센 재생 프레임 수)
+//! 16  u8       bit_depth (v2만, 1, 2 또는 4)
+//! 17  u8       v2_flags (v2만, bit0: 표시할 때 흑백 반전 `--invert`,
+//!                       bit1-2: payload 암호화 id (crypt.rs, 0=평문),
+//!                       bit3: ms duration table 있음 `--frame-durations`,
+//!                       bit4-5: 이진화 방식 (참고용, 0=fixed, 1=otsu, 2=mean, 3=adaptive),
+//!                       bit6: ms timestamp table 있음 `--vfr`,
+//!                       bit7: 켠 비트가 white `--polarity white`)
+//! 18  u8       v2_flags2 (v2만, bit0: 1-bit 프레임의 바이트 안 픽셀 순서가 LSB-first `--bit-order lsb`,
+//!                        bit1: 레코드마다 tag byte, repeat record 있음 `--repeat-records`, 나머지 0)
+//! 19  u8       reserved (v2만, 0)
+//! ..  u32      timing_offset (flags bit5일 때만, blob 시작 기준)
+//! ..  u32      frame_crc_offset (flags bit7일 때만, timing_offset 다음, blob 시작 기준)
+//! ..  u32      ms_timing_offset (v2_flags bit3일 때만, frame_crc_offset 다음, blob 시작 기준)
+//! ..  u32      timestamps_offset (v2_flags bit6일 때만, ms_timing_offset 다음, blob 시작 기준)
+//! ..  [u8; 16] IV (v2_flags 암호화가 aes-ctr일 때만)
+//! ..  ...      frame0 bitset, frame1.. XOR diff bitset
+//!              (bit_depth 2/4면 bitset 대신 픽셀당 2/4비트 gray, MSB-first로 앞 픽셀이 상위 비트,
+//!               v2_flags2 bit0이면 1-bit bitset의 앞 픽셀이 bit0)
+//!              (코덱이 none이 아니거나 bit6이면 프레임마다 `u32 len` + 압축 데이터,
+//!               bit6이면 frame1..은 바뀐 타일만 담은 타일 레코드)
+//!              (v2_flags2 bit1이면 레코드마다 앞에 tag byte: 0x00 + 위의 프레임 레코드, 또는 0xFF + u16 repeat count)
+//! ..  u32 * frame_count  timing table (flags bit5일 때, 저장된 프레임마다 1/fps 단위 duration)
+//! ..  u16 * frame_count  ms duration table (v2_flags bit3일 때, 저장된 프레임마다 보여줄 시간 ms, 1..=65535)
+//! ..  u32 * (frame_count + 1)  ms timestamp table (v2_flags bit6일 때, 저장된 프레임마다 시작 시각 ms + 끝 시각)
+//! ..  u32 * frame_count  frame CRC table (flags bit7일 때, 복원한 packed 프레임마다 CRC32, repeat된 프레임도 한 칸씩)
+//! end u32      CRC32 (flags bit0일 때, blob 앞부분 전체 [0, end-4), 암호화했으면 복호화한 payload 기준)
+//! ```
+//!
+//! legacy(v0) 헤더 (10 bytes): magic/version/flags/CRC 없이 `w, h, fps_x100, frame_count`.
+//! magic이 없으면 legacy로 읽되, 크기가 `10 + packed_len * frame_count`와 정확히 같아야
+//! BA blob으로 인정한다 (`BlobDecoder::new`, player의 `loadPdfFile`).
+//! 새 헤더 필드는 version을 올려서 추가한다. v1 flags의 8비트는 모두 쓰였으므로
+//! 레이아웃을 바꾸는 새 옵션은 version 2 헤더에 넣는다 (v1 디코더는 version 2를 거부한다).
+//! v2 헤더 = v1 헤더 + `bit_depth`, `v2_flags`, `v2_flags2`, reserved 4 bytes. 1-bit이고 반전/암호화/ms duration table/
+//! repeat record가 없고 고정 threshold로 이진화한 blob은 지금도 v1로 쓴다.
+//!
+//! 반전(`V2_FLAG_INVERT`)은 payload를 바꾸지 않는다. bit는 여전히 1=threshold 이하(어두운 픽셀)이고,
+//! 디코더/플레이어가 그릴 때만 흑백을 뒤집는다 (플래그를 무시하면 원래 극성으로 다시 그릴 수 있다).
+//!
+//! 극성(`V2_FLAG_WHITE_INK`, `--polarity white/auto`)은 반대로 payload를 바꾸고 화면은 그대로 둔다.
+//! 1-bit 프레임의 켠 비트가 threshold보다 밝은 픽셀이라, 흰 선이 적은 영상이면 켠 비트와 XOR delta가 줄어든다.
+//! 디코더는 그릴 때 비트를 되돌린다 (`--invert`와 같이 있으면 둘이 상쇄된다). 1-bit blob에만 쓴다.
+//!
+//! LSB-first(`V2_FLAG2_LSB_FIRST`, `--bit-order lsb`)는 저장한 바이트마다 비트 순서만 뒤집는다.
+//! 비트 순서 뒤집기는 XOR과 바뀌지 않으므로 delta와 frame CRC도 저장한 바이트 그대로 계산한다.
+//! 타일 레코드는 MSB-first로 타일을 잘라내므로 같이 쓸 수 없고, 1-bit blob에만 쓴다.
+//!
+//! 이진화 방식(`V2_FLAG_THRESHOLD_MASK`)은 `inspect`에 보여주려고 남기는 값이라 디코딩에는 쓰지 않는다.
+//! 0은 고정 threshold이거나 이진화하지 않은 gray blob이다 (v1 blob도 0으로 읽는다).
+//!
+//! 암호화(`--encrypt`)는 헤더 뒤부터 CRC trailer 앞까지 (프레임 데이터, timing/frame CRC table)만 바꾼다.
+//! 헤더는 평문이라 키 없이도 크기/fps를 읽을 수 있다.
+//!
+//! ms duration table이 있으면 플레이어는 fps 대신 이 table의 누적 시간으로 저장된 프레임을 넘긴다
+//! (가변 frame rate, 일부러 느리거나 빠른 구간). `fps_x100`과 1/fps 단위 timing table은 프레임 번호
+//! (`decode`, 썸네일)에만 쓰인다. CFR이면 table을 쓰지 않는다.
+//!
+//! ms timestamp table(`--vfr`)은 같은 일을 소스의 PTS로 한다. 저장된 프레임 `i`가 처음 보이는 시각(첫 프레임 0)을
+//! 차례로 적고 마지막 칸에 끝 시각(전체 길이)을 적는다. 간격 대신 절대 시각이라 반올림 오차가 쌓이지 않고
+//! 한 프레임이 65.5초보다 길어도 된다. 시각은 줄어들지 않아야 하고, ms duration table과 같이 쓸 수 없다.
+//!
+//! repeat record(`V2_FLAG2_REPEAT`, `--repeat-records`)는 바로 앞 프레임과 같은 프레임이 이어지는 구간을
+//! 0인 XOR diff 대신 레코드 하나로 적는다. 이때 모든 레코드 앞에 tag byte가 붙는다:
+//! ```text
+//! 0x00  frame record   keyframe 또는 XOR diff (tag 없는 blob의 레코드와 같다, 코덱/타일이면 `u32 len` 포함)
+//! 0xFF  repeat record  u16 count (1..=65535): 앞 프레임을 count 프레임 더 보여준다
+//! ```
+//! 첫 레코드는 frame record여야 하고, 65535 프레임보다 긴 구간은 repeat record 여러 개로 나뉜다.
+//! `--drop-duplicates`의 timing table과 달리 frame_count는 재생 프레임 수 그대로라, 프레임 `i`를 찾으려면
+//! 레코드를 처음부터 읽으며 `1`(frame record)과 `count`(repeat record)를 더해 간다.
+//! 저장된 프레임마다 쓰는 timing table, ms duration/timestamp table과는 같이 쓸 수 없다 (둘 다 같은 일을 한다).
+
+use crate::{
+    bits::{BitDepth, BitOrder},
+    codec::CompressionMode,
+    crypt::{Cipher, IV_LEN},
+    tiles::TileGrid,
+};
+use anyhow::{bail, Result};
+
+pub const MAGIC: [u8; 4] = *b"BAPF";
+pub const VERSION: u8 = 1;
+/// bit_depth 필드가 있는 헤더 (`--bit-depth 4`)
+pub const VERSION_2: u8 = 2;
+pub const HEADER_LEN: usize = 16;
+pub const LEGACY_HEADER_LEN: usize = 10;
+pub const CRC_LEN: usize = 4;
+
+/// flags bit0: blob 끝에 CRC32 trailer가 있다
+pub const FLAG_HAS_CRC: u8 = 1 << 0;
+/// flags bit1-3: 프레임 코덱 id (`CompressionMode::id`)
+pub const FLAG_CODEC_SHIFT: u8 = 1;
+pub const FLAG_CODEC_MASK: u8 = 0b111 << FLAG_CODEC_SHIFT;
+/// flags bit4: 프레임 bitset의 각 행이 바이트 경계에서 시작한다
+pub const FLAG_ROW_ALIGN: u8 = 1 << 4;
+/// flags bit5: 같은 프레임을 합치고 timing table로 재생 길이를 기록했다 (`--drop-duplicates`)
+pub const FLAG_TIMING: u8 = 1 << 5;
+/// flags bit6: frame1..이 전체 diff 대신 타일 레코드다 (`--tiles`)
+pub const FLAG_TILES: u8 = 1 << 6;
+/// flags bit7: 프레임 데이터 뒤에 복원한 프레임별 CRC32 table이 있다 (`--checksums`)
+pub const FLAG_FRAME_CRC: u8 = 1 << 7;
+/// timing_offset 필드 크기 (FLAG_TIMING일 때 헤더 뒤에 붙는다)
+pub const TIMING_OFFSET_LEN: usize = 4;
+/// frame_crc_offset 필드 크기 (FLAG_FRAME_CRC일 때 timing_offset 뒤에 붙는다)
+pub const FRAME_CRC_OFFSET_LEN: usize = 4;
+/// v2 헤더에서 frame_count 뒤에 붙는 bit_depth + v2_flags + v2_flags2 + reserved 크기
+pub const V2_EXT_LEN: usize = 4;
+/// v2_flags bit0: 표시할 때 흑백을 뒤집는다 (`--invert`)
+pub const V2_FLAG_INVERT: u8 = 1 << 0;
+/// v2_flags bit1-2: payload 암호화 id (`Cipher::id`, `--encrypt`)
+pub const V2_FLAG_CIPHER_SHIFT: u8 = 1;
+pub const V2_FLAG_CIPHER_MASK: u8 = 0b11 << V2_FLAG_CIPHER_SHIFT;
+/// v2_flags bit3: 저장된 프레임마다 ms duration table이 있다 (`--frame-durations`)
+pub const V2_FLAG_MS_TIMING: u8 = 1 << 3;
+/// v2_flags bit4-5: 이진화 방식 id (`ThresholdKind::id`, 참고용)
+pub const V2_FLAG_THRESHOLD_SHIFT: u8 = 4;
+pub const V2_FLAG_THRESHOLD_MASK: u8 = 0b11 << V2_FLAG_THRESHOLD_SHIFT;
+/// ms_timing_offset 필드 크기 (V2_FLAG_MS_TIMING일 때 frame_crc_offset 뒤에 붙는다)
+pub const MS_TIMING_OFFSET_LEN: usize = 4;
+/// ms duration table 항목 크기 (u16)
+pub const MS_TIMING_ENTRY_LEN: usize = 2;
+/// ms duration table 항목 하나가 담을 수 있는 최대 시간
+pub const MAX_FRAME_MS: u32 = u16::MAX as u32;
+/// v2_flags bit6: 저장된 프레임마다 ms timestamp table이 있다 (`--vfr`)
+pub const V2_FLAG_TIMESTAMPS: u8 = 1 << 6;
+/// v2_flags bit7: 켠 비트가 white (`--polarity white`, 1-bit만)
+pub const V2_FLAG_WHITE_INK: u8 = 1 << 7;
+/// v2_flags2 bit0: 1-bit 프레임의 앞 픽셀이 bit0 (`--bit-order lsb`)
+pub const V2_FLAG2_LSB_FIRST: u8 = 1 << 0;
+/// v2_flags2 bit1: 레코드마다 tag byte가 붙고 repeat record가 있다 (`--repeat-records`)
+pub const V2_FLAG2_REPEAT: u8 = 1 << 1;
+/// repeat record blob의 tag: 뒤에 프레임 레코드가 온다
+pub const RECORD_FRAME: u8 = 0x00;
+/// repeat record blob의 tag: 뒤에 u16 repeat count가 온다
+pub const RECORD_REPEAT: u8 = 0xFF;
+/// repeat record 하나의 크기 (tag + u16 count)
+pub const REPEAT_RECORD_LEN: usize = 3;
+/// timestamps_offset 필드 크기 (V2_FLAG_TIMESTAMPS일 때 ms_timing_offset 뒤에 붙는다)
+pub const TIMESTAMPS_OFFSET_LEN: usize = 4;
+/// ms timestamp table 항목 크기 (u32, 항목 수는 frame_count + 1)
+pub const TIMESTAMP_ENTRY_LEN: usize = 4;
+
+/// 헤더에 기록하는 이진화 방식. threshold 값 자체는 남기지 않는다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThresholdKind {
+    /// 고정 threshold, 또는 이진화하지 않은 gray (`--bit-depth 2/4`)
+    #[default]
+    Fixed,
+    /// 프레임마다 Otsu (`--threshold auto`)
+    Otsu,
+    /// 프레임마다 픽셀 평균 (`--threshold mean`)
+    Mean,
+    /// 픽셀마다 주변 평균 (`--adaptive`)
+    Adaptive,
+}
+
+impl ThresholdKind {
+    pub fn id(self) -> u8 {
+        match self {
+            ThresholdKind::Fixed => 0,
+            ThresholdKind::Otsu => 1,
+            ThresholdKind::Mean => 2,
+            ThresholdKind::Adaptive => 3,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Self {
+        match id & 0b11 {
+            0 => ThresholdKind::Fixed,
+            1 => ThresholdKind::Otsu,
+            2 => ThresholdKind::Mean,
+            _ => ThresholdKind::Adaptive,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ThresholdKind::Fixed => "fixed",
+            ThresholdKind::Otsu => "otsu",
+            ThresholdKind::Mean => "mean",
+            ThresholdKind::Adaptive => "adaptive",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlobHeader {
+    /// 0 = legacy 10-byte 헤더
+    pub version: u8,
+    pub flags: u8,
+    pub width: u16,
+    pub height: u16,
+    pub fps_x100: u16,
+    pub frame_count: u32,
+    /// 픽셀당 비트 수 (v2 헤더에만 기록된다, v0/v1은 항상 `Binary`)
+    pub bit_depth: BitDepth,
+    /// 그릴 때 흑백 반전 (v2 헤더에만 기록된다)
+    pub invert: bool,
+    /// 1-bit payload의 켠 비트가 white (v2 헤더에만 기록된다)
+    pub white_ink: bool,
+    /// 1-bit 프레임의 바이트 안 픽셀 순서 (v2 헤더에만 기록된다)
+    pub bit_order: BitOrder,
+    /// 레코드마다 tag byte, 같은 프레임이 이어지면 repeat record (v2 헤더에만 기록된다)
+    pub repeat_records: bool,
+    /// timing table 위치 (FLAG_TIMING일 때만 의미 있음)
+    pub timing_offset: u32,
+    /// frame CRC table 위치 (FLAG_FRAME_CRC일 때만 의미 있음)
+    pub frame_crc_offset: u32,
+    /// ms duration table이 있다 (v2 헤더에만 기록된다)
+    pub ms_timing: bool,
+    /// ms duration table 위치 (`ms_timing`일 때만 의미 있음)
+    pub ms_timing_offset: u32,
+    /// ms timestamp table이 있다 (v2 헤더에만 기록된다)
+    pub timestamps: bool,
+    /// ms timestamp table 위치 (`timestamps`일 때만 의미 있음)
+    pub timestamps_offset: u32,
+    /// 이진화 방식 (참고용, v2 헤더에만 기록된다)
+    pub threshold: ThresholdKind,
+    /// payload 암호화 방식 (v2 헤더에만 기록된다)
+    pub cipher: Cipher,
+    /// aes-ctr IV (`cipher.has_iv()`일 때만 의미 있음)
+    pub iv: [u8; IV_LEN],
+}
+
+impl BlobHeader {
+    pub fn new(width: u16, height: u16, fps: f32) -> Self {
+        Self {
+            version: VERSION,
+            flags: FLAG_HAS_CRC,
+            width,
+            height,
+            fps_x100: (fps * 100.0).round().clamp(1.0, 65535.0) as u16,
+            frame_count: 0,
+            bit_depth: BitDepth::Binary,
+            invert: false,
+            white_ink: false,
+            bit_order: BitOrder::Msb,
+            repeat_records: false,
+            timing_offset: 0,
+            frame_crc_offset: 0,
+            ms_timing: false,
+            ms_timing_offset: 0,
+            timestamps: false,
+            timestamps_offset: 0,
+            threshold: ThresholdKind::Fixed,
+            cipher: Cipher::None,
+            iv: [0; IV_LEN],
+        }
+    }
+
+    pub fn set_crc(&mut self, on: bool) {
+        if on {
+            self.flags |= FLAG_HAS_CRC;
+        } else {
+            self.flags &= !FLAG_HAS_CRC;
+        }
+    }
+
+    /// legacy 10-byte 헤더 (아직 업데이트되지 않은 플레이어용)
+    pub fn legacy(width: u16, height: u16, fps: f32) -> Self {
+        Self { version: 0, flags: 0, ..Self::new(width, height, fps) }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version == 0
+    }
+
+    /// `Gray2`/`Gray4`면 v2 헤더로, `Binary`면 v1 헤더로 바꾼다 (legacy 헤더에는 쓰지 않는다)
+    pub fn set_bit_depth(&mut self, depth: BitDepth) {
+        self.bit_depth = depth;
+        self.update_version();
+    }
+
+    /// 반전하면 v2 헤더가 필요하다 (legacy 헤더에는 쓰지 않는다)
+    pub fn set_invert(&mut self, on: bool) {
+        self.invert = on;
+        self.update_version();
+    }
+
+    /// 켠 비트를 white로 저장하면 v2 헤더가 필요하다 (legacy 헤더에는 쓰지 않는다)
+    pub fn set_white_ink(&mut self, on: bool) {
+        self.white_ink = on;
+        self.update_version();
+    }
+
+    /// LSB-first로 저장하면 v2 헤더가 필요하다 (legacy 헤더에는 쓰지 않는다)
+    pub fn set_bit_order(&mut self, order: BitOrder) {
+        self.bit_order = order;
+        self.update_version();
+    }
+
+    /// 그릴 때 비트를 뒤집어야 하는지 (1=black이 아닌 쪽으로). 반전과 white 극성은 서로 상쇄된다.
+    pub fn flips_pixels(&self) -> bool {
+        self.invert != self.white_ink
+    }
+
+    /// repeat record를 쓰면 v2 헤더가 필요하다 (legacy 헤더에는 쓰지 않는다)
+    pub fn set_repeat_records(&mut self, on: bool) {
+        self.repeat_records = on;
+        self.update_version();
+    }
+
+    /// 암호화하면 v2 헤더가 필요하다. `iv`는 aes-ctr일 때만 헤더에 기록된다.
+    pub fn set_cipher(&mut self, cipher: Cipher, iv: [u8; IV_LEN]) {
+        self.cipher = cipher;
+        self.iv = if cipher.has_iv() { iv } else { [0; IV_LEN] };
+        self.update_version();
+    }
+
+    /// ms duration table을 쓰면 v2 헤더가 필요하다 (legacy 헤더에는 쓰지 않는다)
+    pub fn set_ms_timing(&mut self, on: bool) {
+        self.ms_timing = on;
+        self.update_version();
+    }
+
+    /// ms timestamp table을 쓰면 v2 헤더가 필요하다 (legacy 헤더에는 쓰지 않는다)
+    pub fn set_timestamps(&mut self, on: bool) {
+        self.timestamps = on;
+        self.update_version();
+    }
+
+    /// 고정 threshold가 아니면 v2 헤더가 필요하다 (legacy 헤더에는 쓰지 않는다)
+    pub fn set_threshold(&mut self, kind: ThresholdKind) {
+        self.threshold = kind;
+        self.update_version();
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher != Cipher::None
+    }
+
+    fn update_version(&mut self) {
+        let v2 = self.bit_depth != BitDepth::Binary || self.invert || self.white_ink || self.bit_order != BitOrder::Msb || self.is_encrypted() || self.ms_timing || self.repeat_records
+            || self.timestamps || self.threshold != ThresholdKind::Fixed;
+        self.version = if v2 { VERSION_2 } else { VERSION };
+    }
+
+    pub fn has_crc(&self) -> bool {
+        self.flags & FLAG_HAS_CRC != 0
+    }
+
+    pub fn is_row_aligned(&self) -> bool {
+        self.flags & FLAG_ROW_ALIGN != 0
+    }
+
+    pub fn set_row_align(&mut self, on: bool) {
+        if on {
+            self.flags |= FLAG_ROW_ALIGN;
+        } else {
+            self.flags &= !FLAG_ROW_ALIGN;
+        }
+    }
+
+    pub fn has_timing(&self) -> bool {
+        self.flags & FLAG_TIMING != 0
+    }
+
+    pub fn set_timing(&mut self, on: bool) {
+        if on {
+            self.flags |= FLAG_TIMING;
+        } else {
+            self.flags &= !FLAG_TIMING;
+        }
+    }
+
+    pub fn is_tiled(&self) -> bool {
+        self.flags & FLAG_TILES != 0
+    }
+
+    pub fn set_tiles(&mut self, on: bool) {
+        if on {
+            self.flags |= FLAG_TILES;
+        } else {
+            self.flags &= !FLAG_TILES;
+        }
+    }
+
+    pub fn has_frame_crc(&self) -> bool {
+        self.flags & FLAG_FRAME_CRC != 0
+    }
+
+    pub fn set_frame_crc(&mut self, on: bool) {
+        if on {
+            self.flags |= FLAG_FRAME_CRC;
+        } else {
+            self.flags &= !FLAG_FRAME_CRC;
+        }
+    }
+
+    /// 프레임 레코드 앞에 `u32 len`이 붙는지 (코덱이 있거나 타일 레코드)
+    pub fn has_record_len(&self) -> bool {
+        self.flags & (FLAG_CODEC_MASK | FLAG_TILES) != 0
+    }
+
+    pub fn tile_grid(&self) -> TileGrid {
+        TileGrid { width: self.width as usize, height: self.height as usize, row_bits: self.row_bits() }
+    }
+
+    pub fn compression(&self) -> Result<CompressionMode> {
+        CompressionMode::from_id((self.flags & FLAG_CODEC_MASK) >> FLAG_CODEC_SHIFT)
+    }
+
+    pub fn set_compression(&mut self, mode: CompressionMode) {
+        self.flags = (self.flags & !FLAG_CODEC_MASK) | (mode.id() << FLAG_CODEC_SHIFT);
+    }
+
+    pub fn header_len(&self) -> usize {
+        if self.is_legacy() {
+            return LEGACY_HEADER_LEN;
+        }
+        let mut len = HEADER_LEN;
+        if self.version >= VERSION_2 {
+            len += V2_EXT_LEN;
+        }
+        if self.has_timing() {
+            len += TIMING_OFFSET_LEN;
+        }
+        if self.has_frame_crc() {
+            len += FRAME_CRC_OFFSET_LEN;
+        }
+        if self.ms_timing {
+            len += MS_TIMING_OFFSET_LEN;
+        }
+        if self.timestamps {
+            len += TIMESTAMPS_OFFSET_LEN;
+        }
+        if self.cipher.has_iv() {
+            len += IV_LEN;
+        }
+        len
+    }
+
+    pub fn fps(&self) -> f32 {
+        self.fps_x100 as f32 / 100.0
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// packed 프레임에서 한 행이 차지하는 비트 수 (`width * bit_depth`, 행 정렬이면 8의 배수로 올림)
+    pub fn row_bits(&self) -> usize {
+        let bits = self.width as usize * self.bit_depth.bits();
+        if self.is_row_aligned() {
+            bits.div_ceil(8) * 8
+        } else {
+            bits
+        }
+    }
+
+    /// 프레임 하나의 packed 크기 (1-bit면 `ceil(w*h/8)`, 4-bit면 `ceil(w*h/2)`)
+    pub fn packed_len(&self) -> usize {
+        (self.row_bits() * self.height as usize).div_ceil(8)
+    }
+}
+
+pub fn write_blob_header(header: &BlobHeader) -> Vec<u8> {
+    let mut out = Vec::with_capacity(header.header_len());
+    if !header.is_legacy() {
+        out.extend_from_slice(&MAGIC);
+        out.push(header.version);
+        out.push(header.flags);
+    }
+    out.extend_from_slice(&header.width.to_le_bytes());
+    out.extend_from_slice(&header.height.to_le_bytes());
+    out.extend_from_slice(&header.fps_x100.to_le_bytes());
+    out.extend_from_slice(&header.frame_count.to_le_bytes());
+    if header.version >= VERSION_2 {
+        out.push(header.bit_depth.bits() as u8);
+        let invert = if header.invert { V2_FLAG_INVERT } else { 0 };
+        let ms_timing = if header.ms_timing { V2_FLAG_MS_TIMING } else { 0 };
+        let threshold = header.threshold.id() << V2_FLAG_THRESHOLD_SHIFT;
+        let timestamps = if header.timestamps { V2_FLAG_TIMESTAMPS } else { 0 };
+        let white_ink = if header.white_ink { V2_FLAG_WHITE_INK } else { 0 };
+        out.push(invert | header.cipher.id() << V2_FLAG_CIPHER_SHIFT | ms_timing | threshold | timestamps | white_ink);
+        let lsb_first = if header.bit_order == BitOrder::Lsb { V2_FLAG2_LSB_FIRST } else { 0 };
+        out.push(lsb_first | if header.repeat_records { V2_FLAG2_REPEAT } else { 0 });
+        out.push(0);
+    }
+    if !header.is_legacy() && header.has_timing() {
+        out.extend_from_slice(&header.timing_offset.to_le_bytes());
+    }
+    if !header.is_legacy() && header.has_frame_crc() {
+        out.extend_from_slice(&header.frame_crc_offset.to_le_bytes());
+    }
+    if header.ms_timing {
+        out.extend_from_slice(&header.ms_timing_offset.to_le_bytes());
+    }
+    if header.timestamps {
+        out.extend_from_slice(&header.timestamps_offset.to_le_bytes());
+    }
+    if header.cipher.has_iv() {
+        out.extend_from_slice(&header.iv);
+    }
+    out
+}
+
+/// magic으로 v1/v2 / legacy 헤더를 구분해서 읽는다.
+pub fn parse_blob_header(data: &[u8]) -> Result<BlobHeader> {
+    let u16_at = |o: usize| u16::from_le_bytes([data[o], data[o + 1]]);
+    let u32_at = |o: usize| u32::from_le_bytes([data[o], data[o + 1], data[o + 2], data[o + 3]]);
+
+    let header = if data.starts_with(&MAGIC) {
+        if data.len() < HEADER_LEN {
+            bail!("BA blob too small for header ({} bytes)", data.len());
+        }
+        let version = data[4];
+        if version != VERSION && version != VERSION_2 {
+            bail!("unsupported BA blob version {} (this build reads up to {})", version, VERSION_2);
+        }
+        let flags = data[5];
+        let mut header = BlobHeader {
+            version,
+            flags,
+            width: u16_at(6),
+            height: u16_at(8),
+            fps_x100: u16_at(10),
+            frame_count: u32_at(12),
+            bit_depth: BitDepth::Binary,
+            invert: false,
+            white_ink: false,
+            bit_order: BitOrder::Msb,
+            repeat_records: false,
+            timing_offset: 0,
+            frame_crc_offset: 0,
+            ms_timing: false,
+            ms_timing_offset: 0,
+            timestamps: false,
+            timestamps_offset: 0,
+            threshold: ThresholdKind::Fixed,
+            cipher: Cipher::None,
+            iv: [0; IV_LEN],
+        };
+        // 선택 필드: (v2) bit_depth, timing_offset, frame_crc_offset, ms_timing_offset, timestamps_offset, (aes-ctr) IV 순서
+        if data.len() < header.header_len() {
+            bail!("BA blob too small for header ({} bytes)", data.len());
+        }
+        let mut off = HEADER_LEN;
+        if version >= VERSION_2 {
+            header.bit_depth = BitDepth::from_bits(data[off])?;
+            let v2_flags = data[off + 1];
+            let known = V2_FLAG_INVERT | V2_FLAG_CIPHER_MASK | V2_FLAG_MS_TIMING | V2_FLAG_THRESHOLD_MASK | V2_FLAG_TIMESTAMPS
+                | V2_FLAG_WHITE_INK;
+            if v2_flags & !known != 0 {
+                bail!("invalid BA v2 header: unknown v2 flags {:#04x}", v2_flags);
+            }
+            header.invert = v2_flags & V2_FLAG_INVERT != 0;
+            header.cipher = Cipher::from_id((v2_flags & V2_FLAG_CIPHER_MASK) >> V2_FLAG_CIPHER_SHIFT)?;
+            header.ms_timing = v2_flags & V2_FLAG_MS_TIMING != 0;
+            header.threshold = ThresholdKind::from_id((v2_flags & V2_FLAG_THRESHOLD_MASK) >> V2_FLAG_THRESHOLD_SHIFT);
+            header.timestamps = v2_flags & V2_FLAG_TIMESTAMPS != 0;
+            header.white_ink = v2_flags & V2_FLAG_WHITE_INK != 0;
+            if header.ms_timing && header.timestamps {
+                bail!("invalid BA v2 header: both an ms duration table and an ms timestamp table");
+            }
+            if header.white_ink && header.bit_depth != BitDepth::Binary {
+                bail!("invalid BA v2 header: white ink polarity on a {}-bit blob", header.bit_depth.bits());
+            }
+            if header.is_encrypted() && !header.has_crc() {
+                bail!("invalid BA header: an encrypted blob needs a CRC trailer");
+            }
+            let v2_flags2 = data[off + 2];
+            if v2_flags2 & !(V2_FLAG2_LSB_FIRST | V2_FLAG2_REPEAT) != 0 {
+                bail!("invalid BA v2 header: unknown v2 flags2 {:#04x}", v2_flags2);
+            }
+            if data[off + 3] != 0 {
+                bail!("invalid BA v2 header: reserved byte is not zero");
+            }
+            if v2_flags2 & V2_FLAG2_LSB_FIRST != 0 {
+                if header.bit_depth != BitDepth::Binary || header.is_tiled() {
+                    bail!("invalid BA v2 header: LSB-first bit order needs an untiled 1-bit blob");
+                }
+                header.bit_order = BitOrder::Lsb;
+            }
+            header.repeat_records = v2_flags2 & V2_FLAG2_REPEAT != 0;
+            if header.repeat_records && (header.has_timing() || header.ms_timing || header.timestamps) {
+                bail!("invalid BA v2 header: repeat records cannot be combined with a per-frame timing table");
+            }
+            if header.bit_depth != BitDepth::Binary && header.is_tiled() {
+                bail!("invalid BA header: tile records need a bit depth of 1");
+            }
+            off += V2_EXT_LEN;
+        }
+        if header.has_timing() {
+            header.timing_offset = u32_at(off);
+            off += TIMING_OFFSET_LEN;
+        }
+        // ms_timing_offset, timestamps_offset과 IV가 붙는지는 v2_flags를 읽어야 알 수 있다
+        if data.len() < header.header_len() {
+            bail!("BA blob too small for header ({} bytes)", data.len());
+        }
+        if header.has_frame_crc() {
+            header.frame_crc_offset = u32_at(off);
+            off += FRAME_CRC_OFFSET_LEN;
+        }
+        if header.ms_timing {
+            header.ms_timing_offset = u32_at(off);
+            off += MS_TIMING_OFFSET_LEN;
+        }
+        if header.timestamps {
+            header.timestamps_offset = u32_at(off);
+            off += TIMESTAMPS_OFFSET_LEN;
+        }
+        if header.cipher.has_iv() {
+            header.iv.copy_from_slice(&data[off..off + IV_LEN]);
+        }
+        header
+    } else {
+        if data.len() < LEGACY_HEADER_LEN {
+            bail!("not a BA blob: missing \"BAPF\" magic and too small for a legacy header ({} bytes)", data.len());
+        }
+        BlobHeader {
+            version: 0,
+            flags: 0,
+            width: u16_at(0),
+            height: u16_at(2),
+            fps_x100: u16_at(4),
+            frame_count: u32_at(6),
+            bit_depth: BitDepth::Binary,
+            invert: false,
+            white_ink: false,
+            bit_order: BitOrder::Msb,
+            repeat_records: false,
+            timing_offset: 0,
+            frame_crc_offset: 0,
+            ms_timing: false,
+            ms_timing_offset: 0,
+            timestamps: false,
+            timestamps_offset: 0,
+            threshold: ThresholdKind::Fixed,
+            cipher: Cipher::None,
+            iv: [0; IV_LEN],
+        }
+    };
+    if header.width == 0 || header.height == 0 {
+        bail!("invalid BA header: {}x{}", header.width, header.height);
+    }
+    header.compression()?;
+    Ok(header)
+}
+
+/// CRC trailer를 검사한다. CRC가 없는 blob은 그대로 통과.
+pub fn check_crc(data: &[u8], header: &BlobHeader) -> Result<()> {
+    if !header.has_crc() {
+        return Ok(());
+    }
+    if data.len() < header.header_len() + CRC_LEN {
+        bail!("BA blob too small for CRC trailer ({} bytes)", data.len());
+    }
+    let (body, trailer) = data.split_at(data.len() - CRC_LEN);
+    let expected = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let computed = crc32fast::hash(body);
+    if expected != computed {
+        bail!("BA blob CRC mismatch: expected {:08x}, computed {:08x}", expected, computed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_header_byte_offsets() {
+        let mut header = BlobHeader::new(0x0140, 0x00f0, 29.97);
+        header.frame_count = 0x0102_0304;
+        let bytes = write_blob_header(&header);
+        assert_eq!(bytes.len(), HEADER_LEN);
+        assert_eq!(&bytes[0..4], b"BAPF");
+        assert_eq!(bytes[4], VERSION);
+        assert_eq!(bytes[5], FLAG_HAS_CRC);
+        assert_eq!(&bytes[6..8], &[0x40, 0x01]);
+        assert_eq!(&bytes[8..10], &[0xf0, 0x00]);
+        assert_eq!(&bytes[10..12], &2997u16.to_le_bytes());
+        assert_eq!(&bytes[12..16], &[0x04, 0x03, 0x02, 0x01]);
+        assert_eq!(parse_blob_header(&bytes).unwrap(), header);
+    }
+
+    #[test]
+    fn legacy_header_byte_offsets() {
+        let mut header = BlobHeader::legacy(160, 120, 30.0);
+        header.frame_count = 6572;
+        let bytes = write_blob_header(&header);
+        assert_eq!(bytes.len(), LEGACY_HEADER_LEN);
+        assert_eq!(&bytes[0..2], &160u16.to_le_bytes());
+        assert_eq!(&bytes[2..4], &120u16.to_le_bytes());
+        assert_eq!(&bytes[4..6], &3000u16.to_le_bytes());
+        assert_eq!(&bytes[6..10], &6572u32.to_le_bytes());
+        assert_eq!(parse_blob_header(&bytes).unwrap(), header);
+    }
+}
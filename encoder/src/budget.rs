@@ -0,0 +1,326 @@
+//! `--max-size`: 샘플 프레임으로 blob 크기를 추정해서 해상도/fps를 고른다
+//!
+//! 1. `FrameSample::collect`가 요청한 설정(w x h @ fps)으로 입력을 한 번 끝까지 읽으면서
+//!    짧은 연속 구간(burst)을 고르게 모은다. 전체 프레임 수도 이때 센다.
+//! 2. 후보 설정마다 샘플을 줄인 크기/fps로 다시 만들어서 실제로 인코딩해 보고,
+//!    프레임당 바이트 수로 전체 blob 크기를 추정한다.
+//! 3. fps를 먼저 (30→24→15→12→10) 내리고, 그래도 안 되면 해상도를 비율 그대로 줄인다.
+
+use crate::{
+    encode::{encode_blob_to, EncodeOptions},
+    source::FrameSource,
+};
+use anyhow::{bail, Context, Result};
+use std::io::Cursor;
+
+/// fps를 내릴 때 쓰는 단계 (요청한 fps보다 낮은 것만)
+pub const FPS_STEPS: [f32; 5] = [30.0, 24.0, 15.0, 12.0, 10.0];
+/// burst 하나의 연속 프레임 수 (XOR diff 크기가 실제와 비슷하게 나오도록)
+pub const SAMPLE_BURST_FRAMES: usize = 12;
+/// 모아둘 최대 burst 수. 넘으면 하나 걸러 버리고 간격을 두 배로 늘린다.
+pub const MAX_SAMPLE_BURSTS: usize = 16;
+/// `--min-width` 기본값
+pub const DEFAULT_MIN_WIDTH: u16 = 32;
+/// `--min-fps` 기본값
+pub const DEFAULT_MIN_FPS: f32 = 10.0;
+/// 해상도를 한 단계 줄일 때 곱하는 비율
+const SCALE_STEP: f64 = 0.8;
+
+/// `--max-size` 값: 숫자 + 선택적으로 K/M/G (1024 단위)
+pub fn parse_size(s: &str) -> Result<u64> {
+    let (digits, unit) = match s.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((i, _)) => s.split_at(i),
+        None => (s, ""),
+    };
+    let mult: u64 = match unit.to_ascii_uppercase().trim_end_matches(['B', 'I']) {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => bail!("invalid size '{}' (expected e.g. 25M, 800K, 1048576)", s),
+    };
+    let n: u64 = digits.parse().with_context(|| format!("invalid size '{}' (expected e.g. 25M, 800K, 1048576)", s))?;
+    if n == 0 {
+        bail!("size must be greater than 0");
+    }
+    n.checked_mul(mult).with_context(|| format!("size '{}' is too large", s))
+}
+
+/// 출력 크기/fps 조합 하나
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    pub width: u16,
+    pub height: u16,
+    pub fps: f32,
+}
+
+/// 입력 전체에서 고르게 모은 gray 프레임 burst
+#[derive(Debug, Clone)]
+pub struct FrameSample {
+    /// 샘플을 뽑은 설정 (후보는 이보다 작거나 같다)
+    pub settings: Settings,
+    /// 입력 전체 프레임 수 (`settings.fps` 기준)
+    pub total_frames: u64,
+    /// (burst 번호, 연속 프레임들)
+    bursts: Vec<(u64, Vec<Vec<u8>>)>,
+}
+
+impl FrameSample {
+    /// `source`를 끝까지 (또는 `max_frames`까지) 읽으면서 burst를 모은다.
+    pub fn collect<S: FrameSource + ?Sized>(source: &mut S, settings: Settings, max_frames: Option<u32>) -> Result<Self> {
+        let frame_len = settings.width as usize * settings.height as usize;
+        let mut bursts: Vec<(u64, Vec<Vec<u8>>)> = Vec::new();
+        let mut stride = 1u64;
+        let mut total = 0u64;
+        let mut buf = vec![0u8; frame_len];
+        while max_frames.is_none_or(|m| total < m as u64) {
+            if !source.read_frame(&mut buf)? {
+                break;
+            }
+            let burst = total / SAMPLE_BURST_FRAMES as u64;
+            total += 1;
+            if !burst.is_multiple_of(stride) {
+                continue;
+            }
+            match bursts.last_mut() {
+                Some((b, frames)) if *b == burst => frames.push(buf.clone()),
+                _ => {
+                    if bursts.len() == MAX_SAMPLE_BURSTS {
+                        // 하나 걸러 버리고 간격을 두 배로
+                        stride *= 2;
+                        bursts.retain(|(b, _)| b.is_multiple_of(stride));
+                        if !burst.is_multiple_of(stride) {
+                            continue;
+                        }
+                    }
+                    bursts.push((burst, vec![buf.clone()]));
+                }
+            }
+        }
+        source.finish()?;
+        if total == 0 {
+            bail!("no frames to sample for --max-size");
+        }
+        Ok(Self { settings, total_frames: total, bursts })
+    }
+
+    /// 샘플에 들어 있는 프레임 수
+    pub fn frame_count(&self) -> usize {
+        self.bursts.iter().map(|(_, f)| f.len()).sum()
+    }
+
+    /// 입력 길이 (초)
+    pub fn seconds(&self) -> f64 {
+        self.total_frames as f64 / self.settings.fps as f64
+    }
+
+    /// burst마다 `to`의 fps로 프레임을 솎고 크기를 area 평균으로 줄인다.
+    fn resampled(&self, to: Settings) -> Vec<Vec<u8>> {
+        let from = self.settings;
+        let step = from.fps as f64 / to.fps as f64;
+        let mut out = Vec::new();
+        for (_, frames) in &self.bursts {
+            let mut t = 0.0;
+            while (t as usize) < frames.len() {
+                out.push(downscale(&frames[t as usize], from, to));
+                t += step;
+            }
+        }
+        out
+    }
+}
+
+/// gray 프레임 area 평균 축소 (`to`가 `from`보다 크면 nearest로 늘린다)
+fn downscale(gray: &[u8], from: Settings, to: Settings) -> Vec<u8> {
+    let (fw, fh) = (from.width as usize, from.height as usize);
+    let (tw, th) = (to.width as usize, to.height as usize);
+    if (fw, fh) == (tw, th) {
+        return gray.to_vec();
+    }
+    let mut out = Vec::with_capacity(tw * th);
+    for y in 0..th {
+        let (y0, y1) = (y * fh / th, ((y + 1) * fh / th).max(y * fh / th + 1));
+        for x in 0..tw {
+            let (x0, x1) = (x * fw / tw, ((x + 1) * fw / tw).max(x * fw / tw + 1));
+            let (mut sum, mut n) = (0u32, 0u32);
+            for row in gray[y0 * fw..y1 * fw].chunks(fw) {
+                sum += row[x0..x1].iter().map(|&p| p as u32).sum::<u32>();
+                n += (x1 - x0) as u32;
+            }
+            out.push((sum / n) as u8);
+        }
+    }
+    out
+}
+
+/// 메모리에 있는 프레임을 순서대로 넘겨주는 소스
+struct VecSource {
+    frames: std::vec::IntoIter<Vec<u8>>,
+}
+
+impl FrameSource for VecSource {
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+        match self.frames.next() {
+            Some(f) => {
+                buf.copy_from_slice(&f);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/// `settings`로 인코딩했을 때 blob 크기 추정. 샘플을 실제로 인코딩해서
+/// 헤더/CRC를 뺀 프레임당 바이트 수에 전체 프레임 수를 곱한다.
+pub fn estimate_blob_len(sample: &FrameSample, settings: Settings, opts: &EncodeOptions) -> Result<u64> {
+    let frames = sample.resampled(settings);
+    let n = frames.len() as u64;
+    let opts = EncodeOptions {
+        width: settings.width,
+        height: settings.height,
+        fps: settings.fps,
+        max_frames: None,
+        jobs: 1,
+        poster_frame: 0,
+        preview_frame: None,
+        flipbook_every: 0,
+        ..opts.clone()
+    };
+    let mut out = Cursor::new(Vec::new());
+    let stats = encode_blob_to(&mut VecSource { frames: frames.into_iter() }, &opts, &mut out)?;
+    let fixed = stats.bytes - stats.frame_bytes.iter().map(|&b| b as u64).sum::<u64>()
+        - if opts.drop_duplicates { 4 * stats.stored_frames as u64 } else { 0 }
+        - if opts.frame_checksums { 4 * if opts.repeat_records { stats.frames } else { stats.stored_frames } as u64 } else { 0 };
+    let per_frame = (stats.bytes - fixed) as f64 / n.max(1) as f64;
+    let total = (sample.seconds() * settings.fps as f64).ceil();
+    Ok(fixed + (per_frame * total).ceil() as u64)
+}
+
+/// 큰 것부터 시도할 후보: 해상도마다 fps 단계를 먼저 내린다.
+/// 해상도는 `SCALE_STEP`씩 줄이고, 마지막은 `min_width`에 맞춘다.
+pub fn candidates(base: Settings, min_width: u16, min_fps: f32) -> Vec<Settings> {
+    let mut fps_list = vec![base.fps];
+    fps_list.extend(FPS_STEPS.iter().copied().filter(|&f| f < base.fps && f >= min_fps));
+    if min_fps < base.fps && !fps_list.contains(&min_fps) {
+        fps_list.push(min_fps);
+    }
+
+    let aspect = base.height as f64 / base.width as f64;
+    let size = |w: u16| (w, ((w as f64 * aspect).round() as u16).max(1));
+    let min_width = min_width.clamp(1, base.width);
+    let mut sizes = vec![size(base.width)];
+    let mut scale = SCALE_STEP;
+    loop {
+        let w = (base.width as f64 * scale).round() as u16;
+        if w <= min_width {
+            if sizes.last().is_some_and(|&(last, _)| last > min_width) {
+                sizes.push(size(min_width));
+            }
+            break;
+        }
+        sizes.push(size(w));
+        scale *= SCALE_STEP;
+    }
+
+    sizes
+        .into_iter()
+        .flat_map(|(width, height)| fps_list.iter().map(move |&fps| Settings { width, height, fps }))
+        .collect()
+}
+
+/// 고른 설정과 추정 크기
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BudgetChoice {
+    pub settings: Settings,
+    /// 추정 blob 크기 + `overhead`
+    pub projected_bytes: u64,
+}
+
+/// `max_bytes` 안에 들어가는 첫 후보를 고른다. `overhead`는 blob 말고 PDF에 더 들어갈 크기
+/// (오디오, 플레이어 첨부, PDF 구조). 가장 작은 후보도 넘으면 그 추정 크기와 함께 에러.
+pub fn choose_settings(
+    sample: &FrameSample,
+    opts: &EncodeOptions,
+    max_bytes: u64,
+    overhead: u64,
+    min_width: u16,
+    min_fps: f32,
+) -> Result<BudgetChoice> {
+    let mut last = None;
+    for settings in candidates(sample.settings, min_width, min_fps) {
+        let projected_bytes = estimate_blob_len(sample, settings, opts)? + overhead;
+        let choice = BudgetChoice { settings, projected_bytes };
+        if projected_bytes <= max_bytes {
+            return Ok(choice);
+        }
+        last = Some(choice);
+    }
+    let last = last.context("no candidate settings for --max-size")?;
+    bail!(
+        "cannot fit the PDF in {} bytes: the smallest settings ({}x{} @ {} fps, --min-width {}, --min-fps {}) project to {} bytes",
+        max_bytes,
+        last.settings.width,
+        last.settings.height,
+        last.settings.fps,
+        min_width,
+        min_fps,
+        last.projected_bytes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::RawSource;
+
+    const BASE: Settings = Settings { width: 64, height: 48, fps: 30.0 };
+
+    /// 64x48 @ 30fps 4초 (120 프레임): 가로 그라데이션이 한 칸씩 밀린다. 코덱이 없으면 크기가 내용과 상관없다.
+    fn sample() -> FrameSample {
+        let frames: Vec<u8> = (0..120usize).flat_map(|f| (0..64 * 48).map(move |i| ((i % 64 + f) * 4 % 256) as u8)).collect();
+        FrameSample::collect(&mut RawSource::new(frames.as_slice(), true), BASE, None).unwrap()
+    }
+
+    /// 헤더 16 + CRC 4 + 프레임마다 packed bytes
+    fn blob_len(width: u64, height: u64, frames: u64) -> u64 {
+        20 + frames * (width * height).div_ceil(8)
+    }
+
+    #[test]
+    fn fits_at_full_size() {
+        let sample = sample();
+        assert_eq!(sample.total_frames, 120);
+        let opts = EncodeOptions::default();
+        let full = blob_len(64, 48, 120);
+        assert_eq!(estimate_blob_len(&sample, BASE, &opts).unwrap(), full);
+        let choice = choose_settings(&sample, &opts, full + 500, 500, DEFAULT_MIN_WIDTH, DEFAULT_MIN_FPS).unwrap();
+        assert_eq!(choice, BudgetChoice { settings: BASE, projected_bytes: full + 500 });
+    }
+
+    #[test]
+    fn lowers_fps_first_then_resolution() {
+        let sample = sample();
+        let opts = EncodeOptions::default();
+        // 한 바이트 모자라면 같은 해상도에서 fps를 한 단계 내린다
+        let choice = choose_settings(&sample, &opts, blob_len(64, 48, 120) - 1, 0, DEFAULT_MIN_WIDTH, DEFAULT_MIN_FPS).unwrap();
+        assert_eq!(choice.settings, Settings { fps: 24.0, ..BASE });
+        assert_eq!(choice.projected_bytes, blob_len(64, 48, 96));
+        // 가장 낮은 fps로도 안 되면 해상도를 0.8배 (51x38) 줄이고 fps를 다시 30부터 내린다
+        let max = blob_len(64, 48, 40) - 1;
+        let choice = choose_settings(&sample, &opts, max, 0, DEFAULT_MIN_WIDTH, DEFAULT_MIN_FPS).unwrap();
+        assert_eq!(choice.settings, Settings { width: 51, height: 38, fps: 15.0 });
+        assert_eq!(choice.projected_bytes, blob_len(51, 38, 60));
+        assert!(choice.projected_bytes <= max);
+    }
+
+    #[test]
+    fn too_small_even_at_the_minimums() {
+        let sample = sample();
+        let err = choose_settings(&sample, &EncodeOptions::default(), 1000, 0, DEFAULT_MIN_WIDTH, DEFAULT_MIN_FPS).unwrap_err();
+        let msg = format!("{:#}", err);
+        assert!(msg.contains("cannot fit the PDF in 1000 bytes"), "{}", msg);
+        assert!(msg.contains(&format!("32x24 @ 10 fps, --min-width 32, --min-fps 10) project to {} bytes", blob_len(32, 24, 40))), "{}", msg);
+        // overhead만으로 넘쳐도 같다
+        assert!(choose_settings(&sample, &EncodeOptions::default(), 1 << 20, 1 << 20, DEFAULT_MIN_WIDTH, DEFAULT_MIN_FPS).is_err());
+    }
+}
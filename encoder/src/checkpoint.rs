@@ -0,0 +1,263 @@
+//! `--checkpoint` / `--resume`: 긴 인코딩을 끊긴 자리부터 이어서 하기
+//!
+//! 인코딩 중 N 입력 프레임마다 blob 파일(`<checkpoint>.blob`)을 flush하고, 그 길이와 인코더 상태
+//! (`prev_packed`, 프레임 수, payload CRC, timing/CRC table, 포스터/flipbook)를 `<checkpoint>`에 쓴다.
+//! 이어서 할 때는 blob 파일을 그 길이로 자르고 입력의 앞 `frames_in` 프레임을 건너뛴 뒤 계속 쓴다.
+//! 끝까지 이어서 만든 blob은 한 번에 만든 blob과 바이트 단위로 같다 (같은 입력, 같은 옵션이면).
+//!
+//! 상태 파일 레이아웃 (little-endian): magic `BACP`, u32 version, 본문, 본문까지의 CRC32.
+//! 임시 파일에 쓰고 rename하므로 쓰다가 끊겨도 앞 checkpoint가 남는다.
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const CHECKPOINT_MAGIC: [u8; 4] = *b"BACP";
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// `--checkpoint-every` 기본값 (입력 프레임 수)
+pub const DEFAULT_CHECKPOINT_EVERY: u32 = 500;
+
+/// 인코딩 중에 checkpoint를 쓸 곳과 간격
+#[derive(Debug, Clone)]
+pub struct CheckpointOptions {
+    /// 상태 파일 (blob은 `Checkpoint::blob_path`)
+    pub path: PathBuf,
+    /// 입력 프레임 몇 개마다 쓸지 (0이면 쓰지 않는다)
+    pub every: u32,
+    /// 입력과 인코딩 옵션의 SHA-256 (`Checkpoint::fingerprint`). 다른 설정으로 이어서 하는 것을 막는다.
+    pub fingerprint: [u8; 32],
+}
+
+/// checkpoint 한 번의 인코더 상태. 필드는 `encode.rs`의 `DeltaWriter`와 `PayloadWriter`를 따른다.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Checkpoint {
+    pub fingerprint: [u8; 32],
+    /// 처음에 쓴 blob 헤더 (frame_count = 0 placeholder, cipher/IV 포함)
+    pub header: Vec<u8>,
+    /// blob 파일에서 유효한 길이 (헤더 + 여기까지의 레코드)
+    pub blob_len: u64,
+    /// 여기까지의 payload(평문) CRC32
+    pub payload_crc: u32,
+    /// 읽은 입력 프레임 수 (이어서 할 때 건너뛸 프레임 수)
+    pub frames_in: u32,
+    /// 저장한 프레임 수
+    pub frame_count: u32,
+    /// 마지막으로 저장한 프레임 (다음 XOR delta의 기준, blob에 기록한 비트 순서)
+    pub prev_packed: Vec<u8>,
+    pub durations: Option<Vec<u32>>,
+    pub durations_ms: Option<Vec<u16>>,
+    pub frame_crcs: Option<Vec<u32>>,
+    pub frame_bytes: Vec<u32>,
+    pub zero_diff_frames: u32,
+    /// `--repeat-records`: 아직 쓰지 않은 repeat run (`blob_len`에 들어 있지 않다)
+    pub repeat_run: u32,
+    pub delta_bits: u64,
+    pub poster: Option<Vec<u8>>,
+    pub preview: Option<Vec<u8>>,
+    pub flipbook: Vec<(u32, Vec<u8>)>,
+    pub flipbook_skipped: u32,
+}
+
+impl Checkpoint {
+    /// 인코딩 설정을 설명하는 문자열 → fingerprint
+    pub fn fingerprint(settings: &str) -> [u8; 32] {
+        Sha256::digest(settings.as_bytes()).into()
+    }
+
+    /// 상태 파일 옆의 blob 파일 (`<path>.blob`)
+    pub fn blob_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".blob");
+        PathBuf::from(name)
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("failed to read checkpoint {}", path.display()))?;
+        Self::from_bytes(&data).with_context(|| format!("invalid checkpoint {}", path.display()))
+    }
+
+    /// `path`에 원자적으로 쓴다 (임시 파일 + rename)
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        fs::write(&tmp, self.to_bytes()).with_context(|| format!("failed to write checkpoint {}", tmp.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("failed to write checkpoint {}", path.display()))
+    }
+
+    /// 인코딩이 끝난 뒤 상태 파일과 blob 파일을 지운다 (없으면 그냥 넘어간다)
+    pub fn remove(path: &Path) -> Result<()> {
+        for p in [path.to_path_buf(), Self::blob_path(path)] {
+            match fs::remove_file(&p) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("failed to remove checkpoint {}", p.display()));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Vec::with_capacity(64 + self.prev_packed.len() + 4 * self.frame_bytes.len());
+        w.extend_from_slice(&CHECKPOINT_MAGIC);
+        put_u32(&mut w, CHECKPOINT_VERSION);
+        w.extend_from_slice(&self.fingerprint);
+        put_bytes(&mut w, &self.header);
+        w.extend_from_slice(&self.blob_len.to_le_bytes());
+        put_u32(&mut w, self.payload_crc);
+        put_u32(&mut w, self.frames_in);
+        put_u32(&mut w, self.frame_count);
+        put_u32(&mut w, self.zero_diff_frames);
+        put_u32(&mut w, self.repeat_run);
+        w.extend_from_slice(&self.delta_bits.to_le_bytes());
+        put_u32(&mut w, self.flipbook_skipped);
+        put_bytes(&mut w, &self.prev_packed);
+        put_opt(&mut w, self.durations.as_deref(), put_u32s);
+        put_opt(&mut w, self.durations_ms.as_deref(), |w, d| {
+            put_u32(w, d.len() as u32);
+            d.iter().for_each(|v| w.extend_from_slice(&v.to_le_bytes()));
+        });
+        put_opt(&mut w, self.frame_crcs.as_deref(), put_u32s);
+        put_u32s(&mut w, &self.frame_bytes);
+        put_opt(&mut w, self.poster.as_deref(), put_bytes);
+        put_opt(&mut w, self.preview.as_deref(), put_bytes);
+        put_u32(&mut w, self.flipbook.len() as u32);
+        for (index, packed) in &self.flipbook {
+            put_u32(&mut w, *index);
+            put_bytes(&mut w, packed);
+        }
+        let crc = crc32fast::hash(&w);
+        put_u32(&mut w, crc);
+        w
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if !data.starts_with(&CHECKPOINT_MAGIC) {
+            bail!("not a checkpoint file (no BACP magic)");
+        }
+        if data.len() < 12 {
+            bail!("checkpoint is truncated ({} bytes)", data.len());
+        }
+        let (body, trailer) = data.split_at(data.len() - 4);
+        let expected = u32::from_le_bytes(trailer.try_into().unwrap());
+        let computed = crc32fast::hash(body);
+        if expected != computed {
+            bail!("checkpoint CRC mismatch: expected {:08x}, computed {:08x}", expected, computed);
+        }
+        let mut r = Reader { data: body, pos: 4 };
+        let version = r.u32()?;
+        if version != CHECKPOINT_VERSION {
+            bail!("unsupported checkpoint version {} (this encoder writes {})", version, CHECKPOINT_VERSION);
+        }
+        let fingerprint = r.take(32)?.try_into().unwrap();
+        let header = r.bytes()?;
+        let blob_len = r.u64()?;
+        let payload_crc = r.u32()?;
+        let frames_in = r.u32()?;
+        let frame_count = r.u32()?;
+        let zero_diff_frames = r.u32()?;
+        let repeat_run = r.u32()?;
+        let delta_bits = r.u64()?;
+        let flipbook_skipped = r.u32()?;
+        let prev_packed = r.bytes()?;
+        let durations = r.opt(Reader::u32s)?;
+        let durations_ms = r.opt(|r| {
+            let n = r.u32()? as usize;
+            (0..n).map(|_| Ok(u16::from_le_bytes(r.take(2)?.try_into().unwrap()))).collect()
+        })?;
+        let frame_crcs = r.opt(Reader::u32s)?;
+        let frame_bytes = r.u32s()?;
+        let poster = r.opt(Reader::bytes)?;
+        let preview = r.opt(Reader::bytes)?;
+        let flipbook = (0..r.u32()?).map(|_| Ok((r.u32()?, r.bytes()?))).collect::<Result<_>>()?;
+        if r.pos != body.len() {
+            bail!("checkpoint has {} trailing bytes", body.len() - r.pos);
+        }
+        Ok(Self {
+            fingerprint,
+            header,
+            blob_len,
+            payload_crc,
+            frames_in,
+            frame_count,
+            prev_packed,
+            durations,
+            durations_ms,
+            frame_crcs,
+            frame_bytes,
+            zero_diff_frames,
+            repeat_run,
+            delta_bits,
+            poster,
+            preview,
+            flipbook,
+            flipbook_skipped,
+        })
+    }
+}
+
+fn put_u32(w: &mut Vec<u8>, v: u32) {
+    w.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u32s(w: &mut Vec<u8>, v: &[u32]) {
+    put_u32(w, v.len() as u32);
+    v.iter().for_each(|&x| put_u32(w, x));
+}
+
+fn put_bytes(w: &mut Vec<u8>, b: &[u8]) {
+    put_u32(w, b.len() as u32);
+    w.extend_from_slice(b);
+}
+
+/// `u8` 있음/없음 표시 + 값
+fn put_opt<T: ?Sized>(w: &mut Vec<u8>, v: Option<&T>, put: impl FnOnce(&mut Vec<u8>, &T)) {
+    w.push(v.is_some() as u8);
+    if let Some(v) = v {
+        put(w, v);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.data.len()).context("checkpoint is truncated")?;
+        let s = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(s)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn u32s(&mut self) -> Result<Vec<u32>> {
+        (0..self.u32()?).map(|_| self.u32()).collect()
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let n = self.u32()? as usize;
+        Ok(self.take(n)?.to_vec())
+    }
+
+    fn opt<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T>) -> Result<Option<T>> {
+        match self.take(1)?[0] {
+            0 => Ok(None),
+            1 => read(self).map(Some),
+            v => bail!("invalid option tag {} in checkpoint", v),
+        }
+    }
+}
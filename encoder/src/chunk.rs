@@ -0,0 +1,245 @@
+//! `--chunk-size`: 큰 BA.bin을 `BA.000.bin`, `BA.001.bin`, ... 첨부로 나눈다
+//!
+//! 뷰어나 플레이어의 첨부 읽기가 큰 stream 하나에서 막히는 경우를 피한다.
+//! 조각은 blob을 순서대로 자른 것뿐이라 이어 붙이면 원래 BA.bin이 되고,
+//! 가장 작은 조각 크기(`MIN_CHUNK_SIZE`)가 헤더보다 커서 헤더는 항상 첫 조각 안에 있다.
+//! 이름은 번호가 0으로 채워져 있어서 EmbeddedFiles name tree 정렬 순서가 곧 조각 순서다.
+
+use crate::pdf::{Attachment, AttachmentData};
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+/// `--chunk-size` 최솟값 (v2 헤더 + timing/frame CRC offset보다 충분히 크다)
+pub const MIN_CHUNK_SIZE: u64 = 1024;
+
+/// 조각 번호 자릿수 (`BA.000.bin`)
+const CHUNK_DIGITS: usize = 3;
+
+/// `BA.bin`, 2 → `BA.002.bin`. 확장자가 없으면 끝에 번호를 붙인다.
+pub fn chunk_name(name: &str, index: usize) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}.{:0w$}.{}", stem, index, ext, w = CHUNK_DIGITS),
+        None => format!("{}.{:0w$}", name, index, w = CHUNK_DIGITS),
+    }
+}
+
+/// `chunk_name`의 반대: `BA.002.bin` → (`BA.bin`, 2)
+pub fn parse_chunk_name(chunk: &str) -> Option<(String, usize)> {
+    let is_index = |s: &str| s.len() == CHUNK_DIGITS && s.bytes().all(|b| b.is_ascii_digit());
+    let (rest, last) = chunk.rsplit_once('.')?;
+    if is_index(last) {
+        return Some((rest.to_string(), last.parse().ok()?));
+    }
+    let (stem, index) = rest.rsplit_once('.')?;
+    if !is_index(index) {
+        return None;
+    }
+    Some((format!("{}.{}", stem, last), index.parse().ok()?))
+}
+
+/// `att`를 `chunk_size` bytes씩 나눈다. 한 조각에 다 들어가면 그대로 하나만 돌려준다.
+/// 조각은 `att`의 메타데이터를 물려받고, 설명 끝에 몇 번째 조각인지 붙인다.
+pub fn split_attachment(att: &Attachment, chunk_size: u64) -> Result<Vec<Attachment>> {
+    if chunk_size < MIN_CHUNK_SIZE {
+        bail!("chunk size must be at least {} bytes", MIN_CHUNK_SIZE);
+    }
+    let len = att.data_len()?;
+    if len <= chunk_size {
+        return Ok(vec![att.clone()]);
+    }
+    let count = len.div_ceil(chunk_size) as usize;
+    if count > 10usize.pow(CHUNK_DIGITS as u32) {
+        bail!("{} ({} bytes) would need {} chunks of {} bytes; use a larger --chunk-size", att.name, len, count, chunk_size);
+    }
+    let mut chunks = Vec::with_capacity(count);
+    match &att.data {
+        AttachmentData::Bytes(b) => {
+            for (i, part) in b.chunks(chunk_size as usize).enumerate() {
+                chunks.push(Attachment::bytes(&chunk_name(&att.name, i), &att.mime, part.to_vec()));
+            }
+        }
+        AttachmentData::File(path) => {
+            let mut f = File::open(path).with_context(|| format!("failed to read {}", path.display()))?;
+            for i in 0..count {
+                let offset = i as u64 * chunk_size;
+                let mut part = Vec::with_capacity(chunk_size.min(len - offset) as usize);
+                f.seek(SeekFrom::Start(offset))
+                    .and_then(|_| (&mut f).take(chunk_size).read_to_end(&mut part))
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                chunks.push(Attachment::bytes(&chunk_name(&att.name, i), &att.mime, part));
+            }
+        }
+    }
+    for (i, chunk) in chunks.iter_mut().enumerate() {
+        chunk.options = att.options.clone();
+        if let Some(desc) = &mut chunk.options.description {
+            desc.push_str(&format!(" (part {} of {})", i + 1, count));
+        }
+    }
+    Ok(chunks)
+}
+
+/// 조각 첨부(`BA.000.bin`, ...)를 원래 이름의 첨부 하나로 다시 이어 붙인다. 다른 첨부는 그대로 둔다.
+/// 조각 순서는 이름의 번호로 정하므로 목록 순서는 상관없다. 번호가 0부터 빠짐없이 이어지지 않거나
+/// 같은 번호가 두 번 나오면 에러.
+pub fn join_chunks(atts: Vec<Attachment>) -> Result<Vec<Attachment>> {
+    let mut out: Vec<Attachment> = Vec::new();
+    // (out에서 이은 첨부가 들어갈 자리, 조각들)
+    let mut groups: Vec<(usize, Vec<(usize, Attachment)>)> = Vec::new();
+    for att in atts {
+        let Some((name, index)) = parse_chunk_name(&att.name) else {
+            out.push(att);
+            continue;
+        };
+        match groups.iter_mut().find(|(slot, _)| out[*slot].name == name) {
+            Some((_, parts)) => parts.push((index, att)),
+            None => {
+                // 자리만 잡아두고 나중에 채운다
+                out.push(Attachment::bytes(&name, &att.mime, Vec::new()));
+                groups.push((out.len() - 1, vec![(index, att)]));
+            }
+        }
+    }
+    for (slot, mut parts) in groups {
+        parts.sort_by_key(|(i, _)| *i);
+        let mut data = Vec::new();
+        for (expect, (index, part)) in parts.iter().enumerate() {
+            if *index < expect {
+                bail!("chunks of {} are not contiguous: {} appears twice", out[slot].name, chunk_name(&out[slot].name, *index));
+            }
+            if *index != expect {
+                bail!("chunks of {} are not contiguous: missing {}", out[slot].name, chunk_name(&out[slot].name, expect));
+            }
+            match &part.data {
+                AttachmentData::Bytes(b) => data.extend_from_slice(b),
+                AttachmentData::File(path) => {
+                    File::open(path)
+                        .and_then(|mut f| f.read_to_end(&mut data))
+                        .with_context(|| format!("failed to read {}", path.display()))?;
+                }
+            }
+        }
+        out[slot].data = AttachmentData::Bytes(data);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        blob::parse_blob_header,
+        encode::{encode_blob, EncodeOptions},
+        source::RawSource,
+    };
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len as u32).map(|i| (i * 7 % 256) as u8).collect()
+    }
+
+    fn bytes(att: &Attachment) -> &[u8] {
+        match &att.data {
+            AttachmentData::Bytes(b) => b,
+            AttachmentData::File(_) => panic!("{} is not in memory", att.name),
+        }
+    }
+
+    fn names(atts: &[Attachment]) -> Vec<&str> {
+        atts.iter().map(|a| a.name.as_str()).collect()
+    }
+
+    #[test]
+    fn round_trip_at_and_past_a_chunk_boundary() {
+        let size = MIN_CHUNK_SIZE as usize;
+        // 딱 한 조각이면 나누지 않는다
+        let one = split_attachment(&Attachment::bytes("BA.bin", "application/octet-stream", data(size)), size as u64).unwrap();
+        assert_eq!(names(&one), ["BA.bin"]);
+        for (len, parts) in [(3 * size, 3), (3 * size + 1, 4), (size + 1, 2)] {
+            let att = Attachment::bytes("BA.bin", "application/octet-stream", data(len)).with_description("BA blob");
+            let chunks = split_attachment(&att, size as u64).unwrap();
+            assert_eq!(chunks.len(), parts, "{} bytes", len);
+            assert_eq!(chunks[0].name, "BA.000.bin");
+            assert!(chunks[..parts - 1].iter().all(|c| bytes(c).len() == size));
+            assert_eq!(bytes(&chunks[parts - 1]).len(), len - (parts - 1) * size);
+            assert_eq!(chunks[1].options.description.as_deref(), Some(format!("BA blob (part 2 of {})", parts).as_str()));
+
+            let joined = join_chunks(chunks).unwrap();
+            assert_eq!(names(&joined), ["BA.bin"]);
+            assert_eq!(bytes(&joined[0]), data(len), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn file_attachment_splits_like_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("BA.bin");
+        std::fs::write(&path, data(2500)).unwrap();
+        let from_file = split_attachment(&Attachment::file("BA.bin", "application/octet-stream", &path), 1024).unwrap();
+        let from_bytes = split_attachment(&Attachment::bytes("BA.bin", "application/octet-stream", data(2500)), 1024).unwrap();
+        assert_eq!(from_file.iter().map(bytes).collect::<Vec<_>>(), from_bytes.iter().map(bytes).collect::<Vec<_>>());
+    }
+
+    /// 가장 큰 헤더(v2 + 모든 optional offset + IV)도 가장 작은 조각 안에 들어간다
+    #[test]
+    fn header_stays_in_the_first_chunk() {
+        let frames: Vec<u8> = (0..40 * 64 * 48).map(|i| ((i * 13 + i / 3072 * 7) % 256) as u8).collect();
+        let opts = EncodeOptions {
+            width: 64,
+            height: 48,
+            drop_duplicates: true,
+            frame_checksums: true,
+            frame_durations_ms: Some(vec![40; 40]),
+            encrypt: Some("aes-ctr:000102030405060708090a0b0c0d0e0f".parse().unwrap()),
+            ..EncodeOptions::default()
+        };
+        let blob = encode_blob(&mut RawSource::new(frames.as_slice(), true), &opts).unwrap();
+        let header = parse_blob_header(&blob).unwrap();
+        assert!((header.header_len() as u64) < MIN_CHUNK_SIZE);
+        let chunks = split_attachment(&Attachment::bytes("BA.bin", "application/octet-stream", blob.clone()), MIN_CHUNK_SIZE).unwrap();
+        assert!(chunks.len() > 1);
+        let first = parse_blob_header(bytes(&chunks[0])).unwrap();
+        assert_eq!((first.width, first.height, first.frame_count, first.iv), (64, 48, header.frame_count, header.iv));
+        assert_eq!(bytes(&join_chunks(chunks).unwrap()[0]), blob);
+    }
+
+    #[test]
+    fn join_rejects_missing_or_repeated_chunks() {
+        let part = |i: usize| Attachment::bytes(&chunk_name("BA.bin", i), "application/octet-stream", vec![i as u8; 4]);
+        let err = |atts: Vec<Attachment>| format!("{:#}", join_chunks(atts).unwrap_err());
+        assert!(err(vec![part(0), part(2)]).contains("missing BA.001.bin"));
+        assert!(err(vec![part(1), part(2)]).contains("missing BA.000.bin"));
+        assert!(err(vec![part(0), part(1), part(1)]).contains("BA.001.bin appears twice"));
+        // 번호로 정렬하므로 목록 순서는 상관없고, 조각이 아닌 첨부는 그대로 남는다
+        let au = Attachment::bytes("AU.ogg", "audio/ogg", b"OggS".to_vec());
+        let joined = join_chunks(vec![part(2), au, part(0), part(1)]).unwrap();
+        assert_eq!(names(&joined), ["BA.bin", "AU.ogg"]);
+        assert_eq!(bytes(&joined[0]), [[0u8; 4], [1; 4], [2; 4]].concat());
+    }
+
+    #[test]
+    fn chunk_size_below_minimum_is_rejected() {
+        let att = Attachment::bytes("BA.bin", "application/octet-stream", data(10));
+        for size in [0, 1, MIN_CHUNK_SIZE - 1] {
+            let err = format!("{:#}", split_attachment(&att, size).unwrap_err());
+            assert!(err.contains("at least 1024 bytes"), "{}", err);
+        }
+        assert_eq!(split_attachment(&att, MIN_CHUNK_SIZE).unwrap().len(), 1);
+        // 번호가 세 자리를 넘으면 더 큰 조각을 쓰라고 한다
+        let big = Attachment::bytes("BA.bin", "application/octet-stream", vec![0; 1001 * 1024]);
+        assert!(format!("{:#}", split_attachment(&big, 1024).unwrap_err()).contains("larger --chunk-size"));
+    }
+
+    #[test]
+    fn chunk_names_round_trip() {
+        for (name, index, chunk) in [("BA.bin", 0, "BA.000.bin"), ("BA.bin", 42, "BA.042.bin"), ("blob", 7, "blob.007"), ("my.video.bin", 1, "my.video.001.bin")] {
+            assert_eq!(chunk_name(name, index), chunk);
+            assert_eq!(parse_chunk_name(chunk), Some((name.to_string(), index)));
+        }
+        for plain in ["BA.bin", "AU.ogg", "BA.00.bin", "BA.0001.bin", "BA.x00.bin", "noext", "BA.12a"] {
+            assert_eq!(parse_chunk_name(plain), None, "{}", plain);
+        }
+    }
+}
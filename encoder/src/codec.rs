@@ -0,0 +1,293 @@
+//! 프레임 데이터(packed bitset / XOR diff) 단위 압축 코덱
+
+use anyhow::{bail, Context, Result};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::io::{Read, Write};
+
+/// 프레임 하나씩 적용하는 코덱. blob 헤더 flags에 기록된다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionMode {
+    /// packed bytes 그대로 (고정 길이, 레코드 길이 prefix 없음)
+    #[default]
+    None,
+    /// `(run_length: u8, byte_value: u8)` 쌍
+    Rle,
+    /// zlib(deflate)
+    Deflate,
+    /// zstd. 값은 압축 레벨 (1..=22). 헤더에는 코덱 id만 남는다.
+    Zstd(i32),
+    /// brotli. 값은 quality (0..=11). 헤더에는 코덱 id만 남는다.
+    Brotli(u32),
+}
+
+/// `--compress zstd`만 주었을 때 쓰는 레벨
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+/// `--compress brotli`만 주었을 때 쓰는 quality
+pub const DEFAULT_BROTLI_QUALITY: u32 = 6;
+/// brotli 창 크기 (log2). 프레임 레코드는 이보다 훨씬 작다.
+const BROTLI_LGWIN: u32 = 22;
+/// brotli reader/writer 내부 버퍼 크기
+const BROTLI_BUFFER: usize = 4096;
+
+impl CompressionMode {
+    /// flags에 들어가는 코덱 id
+    pub fn id(self) -> u8 {
+        match self {
+            CompressionMode::None => 0,
+            CompressionMode::Rle => 1,
+            CompressionMode::Deflate => 2,
+            CompressionMode::Zstd(_) => 3,
+            CompressionMode::Brotli(_) => 4,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Result<Self> {
+        Ok(match id {
+            0 => CompressionMode::None,
+            1 => CompressionMode::Rle,
+            2 => CompressionMode::Deflate,
+            3 => CompressionMode::Zstd(DEFAULT_ZSTD_LEVEL),
+            4 => CompressionMode::Brotli(DEFAULT_BROTLI_QUALITY),
+            _ => bail!("unknown frame codec id {}", id),
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CompressionMode::None => "none",
+            CompressionMode::Rle => "rle",
+            CompressionMode::Deflate => "deflate",
+            CompressionMode::Zstd(_) => "zstd",
+            CompressionMode::Brotli(_) => "brotli",
+        }
+    }
+}
+
+impl std::str::FromStr for CompressionMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "none" => CompressionMode::None,
+            "rle" => CompressionMode::Rle,
+            "deflate" => CompressionMode::Deflate,
+            "zstd" => CompressionMode::Zstd(DEFAULT_ZSTD_LEVEL),
+            "brotli" => CompressionMode::Brotli(DEFAULT_BROTLI_QUALITY),
+            _ => bail!("unknown compression '{}' (expected none, rle, deflate, zstd, brotli)", s),
+        })
+    }
+}
+
+/// 같은 바이트가 이어지는 구간을 `(run, value)`로 줄인다. run은 1..=255.
+pub fn rle_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let v = data[i];
+        let mut run = 1;
+        while run < 255 && i + run < data.len() && data[i + run] == v {
+            run += 1;
+        }
+        out.push(run as u8);
+        out.push(v);
+        i += run;
+    }
+    out
+}
+
+pub fn rle_decode(data: &[u8], expected_len: usize) -> Result<Vec<u8>> {
+    let out = rle_decode_bounded(data, expected_len)?;
+    if out.len() != expected_len {
+        bail!("RLE data expands to {} bytes, expected {}", out.len(), expected_len);
+    }
+    Ok(out)
+}
+
+/// `max_len`까지만 풀어준다 (길이가 정해지지 않은 레코드용)
+fn rle_decode_bounded(data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    if !data.len().is_multiple_of(2) {
+        bail!("RLE data has odd length {}", data.len());
+    }
+    let mut out = Vec::with_capacity(max_len);
+    for pair in data.chunks_exact(2) {
+        let (run, v) = (pair[0] as usize, pair[1]);
+        if run == 0 {
+            bail!("RLE run of length 0");
+        }
+        if out.len() + run > max_len {
+            bail!("RLE data expands past {} bytes", max_len);
+        }
+        out.resize(out.len() + run, v);
+    }
+    Ok(out)
+}
+
+/// 프레임/레코드 하나를 `mode`로 압축한다. 모든 코덱이 이 함수 하나를 거친다.
+pub fn compress_blob(data: &[u8], mode: CompressionMode) -> Result<Vec<u8>> {
+    Ok(match mode {
+        CompressionMode::None => data.to_vec(),
+        CompressionMode::Rle => rle_encode(data),
+        CompressionMode::Deflate => {
+            let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+            z.write_all(data)?;
+            z.finish()?
+        }
+        #[cfg(feature = "native")]
+        CompressionMode::Zstd(level) => zstd::bulk::compress(data, level).context("zstd compression failed")?,
+        #[cfg(not(feature = "native"))]
+        CompressionMode::Zstd(_) => bail!("zstd support is not built in (enable the `native` feature)"),
+        CompressionMode::Brotli(quality) => {
+            let mut w = brotli::CompressorWriter::new(Vec::new(), BROTLI_BUFFER, quality, BROTLI_LGWIN);
+            w.write_all(data).context("brotli compression failed")?;
+            w.into_inner()
+        }
+    })
+}
+
+/// `len` bytes를 `mode`로 압축했을 때 최악의 크기 (`max_blob_len`용). 작은 레코드는 코덱 헤더 때문에 원본보다 커진다.
+/// - rle: run이 모두 1이면 두 배
+/// - deflate: zlib 헤더/adler32 6 bytes + stored block마다 5 bytes
+/// - zstd: `ZSTD_COMPRESSBOUND`
+/// - brotli: `BrotliEncoderMaxCompressedSize` (16 KiB마다 4 bytes + 6)
+pub fn max_compressed_len(len: usize, mode: CompressionMode) -> usize {
+    match mode {
+        CompressionMode::None => len,
+        CompressionMode::Rle => 2 * len,
+        CompressionMode::Deflate => len + 5 * (len / 16_000 + 1) + 6,
+        CompressionMode::Zstd(_) => len + (len >> 8) + if len < 128 << 10 { ((128 << 10) - len) >> 11 } else { 0 },
+        CompressionMode::Brotli(_) => len + 4 * (len >> 14) + 6,
+    }
+}
+
+pub fn decompress_frame(data: &[u8], mode: CompressionMode, expected_len: usize) -> Result<Vec<u8>> {
+    let out = decompress_record(data, mode, expected_len)?;
+    if out.len() != expected_len {
+        bail!("frame decodes to {} bytes, expected {}", out.len(), expected_len);
+    }
+    Ok(out)
+}
+
+/// 길이가 가변인 레코드 (타일 레코드 등)를 최대 `max_len` bytes까지 푼다.
+pub fn decompress_record(data: &[u8], mode: CompressionMode, max_len: usize) -> Result<Vec<u8>> {
+    let out = match mode {
+        CompressionMode::None => data.to_vec(),
+        CompressionMode::Rle => rle_decode_bounded(data, max_len)?,
+        CompressionMode::Deflate => {
+            let mut out = Vec::with_capacity(max_len);
+            ZlibDecoder::new(data)
+                .take(max_len as u64 + 1)
+                .read_to_end(&mut out)
+                .context("invalid deflate frame data")?;
+            out
+        }
+        #[cfg(feature = "native")]
+        CompressionMode::Zstd(_) => {
+            let mut out = Vec::with_capacity(max_len);
+            zstd::stream::read::Decoder::new(data)
+                .context("invalid zstd frame data")?
+                .take(max_len as u64 + 1)
+                .read_to_end(&mut out)
+                .context("invalid zstd frame data")?;
+            out
+        }
+        #[cfg(not(feature = "native"))]
+        CompressionMode::Zstd(_) => bail!("zstd-compressed blobs need the `native` feature (zstd is a C library)"),
+        CompressionMode::Brotli(_) => {
+            let mut out = Vec::with_capacity(max_len);
+            brotli::Decompressor::new(data, BROTLI_BUFFER)
+                .take(max_len as u64 + 1)
+                .read_to_end(&mut out)
+                .context("invalid brotli frame data")?;
+            out
+        }
+    };
+    if out.len() > max_len {
+        bail!("record decodes past {} bytes", max_len);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) -> Vec<u8> {
+        let encoded = rle_encode(data);
+        assert_eq!(rle_decode(&encoded, data.len()).unwrap(), data);
+        encoded
+    }
+
+    #[test]
+    fn rle_all_zeros() {
+        // 255 bytes씩 한 쌍
+        assert_eq!(round_trip(&[0; 600]), [255, 0, 255, 0, 90, 0]);
+        assert_eq!(round_trip(&[0; 255]), [255, 0]);
+    }
+
+    #[test]
+    fn rle_all_identical() {
+        assert_eq!(round_trip(&[0xa5; 7]), [7, 0xa5]);
+        assert_eq!(round_trip(&[0xff; 256]), [255, 0xff, 1, 0xff]);
+    }
+
+    /// 압축이 안 되는 데이터(xorshift 난수)와 아주 작은 입력에서도 상한을 넘지 않는다
+    #[test]
+    fn max_compressed_len_bounds_incompressible_input() {
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..70_000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect();
+        let modes = [CompressionMode::None, CompressionMode::Rle, CompressionMode::Deflate, CompressionMode::Zstd(DEFAULT_ZSTD_LEVEL), CompressionMode::Brotli(DEFAULT_BROTLI_QUALITY)];
+        for len in [0, 1, 2, 17, 300, 4096, 70_000] {
+            // zstd는 native feature에서만 압축할 수 있다
+            for mode in modes.into_iter().filter(|m| cfg!(feature = "native") || !matches!(m, CompressionMode::Zstd(_))) {
+                let out = compress_blob(&noise[..len], mode).unwrap();
+                assert!(out.len() <= max_compressed_len(len, mode), "{:?} {} bytes -> {}", mode, len, out.len());
+            }
+        }
+    }
+
+    #[test]
+    fn rle_alternating() {
+        let data: Vec<u8> = (0..10).map(|i| if i % 2 == 0 { 0x00 } else { 0xff }).collect();
+        // run이 모두 1이라 두 배로 늘어난다
+        assert_eq!(round_trip(&data).len(), 2 * data.len());
+    }
+
+    #[test]
+    fn rle_empty() {
+        assert_eq!(round_trip(&[]), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn rle_decode_rejects_bad_input() {
+        assert!(rle_decode(&[3, 0], 2).is_err());
+        assert!(rle_decode(&[3, 0], 4).is_err());
+        assert!(rle_decode(&[0, 7], 0).is_err());
+        assert!(rle_decode(&[1], 1).is_err());
+    }
+
+    #[test]
+    fn every_codec_round_trips_a_frame() {
+        let frame: Vec<u8> = (0..1200u32).map(|i| if i % 97 < 40 { 0 } else { (i * 31) as u8 }).collect();
+        for mode in [
+            CompressionMode::None,
+            CompressionMode::Rle,
+            CompressionMode::Deflate,
+            CompressionMode::Zstd(DEFAULT_ZSTD_LEVEL),
+            CompressionMode::Brotli(DEFAULT_BROTLI_QUALITY),
+        ] {
+            #[cfg(not(feature = "native"))]
+            if matches!(mode, CompressionMode::Zstd(_)) {
+                continue;
+            }
+            let packed = compress_blob(&frame, mode).unwrap();
+            assert_eq!(decompress_frame(&packed, mode, frame.len()).unwrap(), frame, "{}", mode.name());
+        }
+    }
+}
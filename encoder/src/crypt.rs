@@ -0,0 +1,361 @@
+//! `--encrypt`: blob payload 암호화 (xor / aes-ctr)
+//!
+//! 헤더는 평문 그대로 두고 그 뒤 payload(프레임 레코드, timing/frame CRC table)만 암호화한다.
+//! 어떤 방식인지는 v2 헤더의 `v2_flags` bit1-2에, aes-ctr의 IV는 헤더 끝에 기록한다 (blob.rs 참고).
+//! CRC trailer는 평문 기준 `crc(header || payload)`라서 키가 틀리면 복호화한 payload의 CRC가 맞지 않는다.
+//! 키 자체는 blob에 들어가지 않는다 (`--embed-key-in-url`이면 START 링크의 `#k=<hex>`에만).
+//! xor는 키를 반복해서 XOR할 뿐이라 가리기(obfuscation) 용도다.
+//! 어느 쪽도 암호문을 인증하지 않는다 (MAC/AEAD 없음). CRC32는 틀린 키를 알아내는 용도일 뿐이라, 키가 없어도
+//! 암호문 비트를 뒤집고 CRC를 다시 맞추면 복호화한 payload가 바뀐다. 기밀성만 필요할 때 쓴다.
+//!
+//! `--encrypt-audio`면 AU 첨부도 같은 키로 암호화한다. 오디오에는 헤더가 없으므로 작은 헤더를 붙인다:
+//! `b"BAAU"` | `u8` cipher id | `[u8; 3]` 0 | (aes-ctr) `[u8; 16]` IV | 암호문 | `u32` LE crc32(평문)
+
+use aes::{Aes128, Aes256};
+use anyhow::{bail, Context, Result};
+use ctr::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+/// aes-ctr IV (초기 counter block) 크기
+pub const IV_LEN: usize = 16;
+/// `--encrypt-audio` 첨부 맨 앞의 magic
+pub const AUDIO_MAGIC: [u8; 4] = *b"BAAU";
+
+/// payload 암호화 방식. v2 헤더 `v2_flags`에 기록된다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cipher {
+    /// 평문
+    #[default]
+    None,
+    /// payload offset `i`의 바이트에 `key[i % key.len()]`을 XOR
+    Xor,
+    /// AES-128/256 CTR (키 길이로 정해진다), 헤더의 IV에서 시작하는 big-endian counter
+    AesCtr,
+}
+
+impl Cipher {
+    /// `v2_flags`에 들어가는 id
+    pub fn id(self) -> u8 {
+        match self {
+            Cipher::None => 0,
+            Cipher::Xor => 1,
+            Cipher::AesCtr => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Result<Self> {
+        Ok(match id {
+            0 => Cipher::None,
+            1 => Cipher::Xor,
+            2 => Cipher::AesCtr,
+            _ => bail!("unknown payload cipher id {}", id),
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Cipher::None => "none",
+            Cipher::Xor => "xor",
+            Cipher::AesCtr => "aes-ctr",
+        }
+    }
+
+    /// 헤더에 IV가 붙는지
+    pub fn has_iv(self) -> bool {
+        self == Cipher::AesCtr
+    }
+
+    /// 이 방식에 쓸 수 있는 키인지
+    pub fn check_key(self, key: &[u8]) -> Result<()> {
+        match self {
+            Cipher::None => Ok(()),
+            Cipher::Xor if key.is_empty() => bail!("xor key must not be empty"),
+            Cipher::Xor => Ok(()),
+            Cipher::AesCtr if key.len() == 16 || key.len() == 32 => Ok(()),
+            Cipher::AesCtr => bail!("aes-ctr key must be 16 or 32 bytes (32 or 64 hex digits), got {} bytes", key.len()),
+        }
+    }
+}
+
+/// `--encrypt <xor|aes-ctr>:<hexkey>` 값
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobKey {
+    pub cipher: Cipher,
+    pub key: Vec<u8>,
+}
+
+impl std::str::FromStr for BlobKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (scheme, hex) = s
+            .split_once(':')
+            .with_context(|| format!("invalid --encrypt '{}' (expected xor:<hexkey> or aes-ctr:<hexkey>)", s))?;
+        let cipher = match scheme {
+            "xor" => Cipher::Xor,
+            "aes-ctr" => Cipher::AesCtr,
+            _ => bail!("unknown cipher '{}' (expected xor, aes-ctr)", scheme),
+        };
+        let key = parse_hex(hex)?;
+        cipher.check_key(&key)?;
+        Ok(Self { cipher, key })
+    }
+}
+
+impl BlobKey {
+    /// 키를 소문자 hex로 (`#k=<hex>` URL fragment용)
+    pub fn key_hex(&self) -> String {
+        self.key.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// 짝수 길이 hex 문자열 → bytes (대소문자 모두 허용)
+pub fn parse_hex(s: &str) -> Result<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!("invalid hex key '{}' (expected an even number of hex digits)", s);
+    }
+    Ok((0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect())
+}
+
+/// 운영체제 난수로 새 IV를 만든다 (blob마다 하나)
+#[cfg(feature = "native")]
+pub fn random_iv() -> Result<[u8; IV_LEN]> {
+    let mut iv = [0u8; IV_LEN];
+    getrandom::fill(&mut iv).map_err(|e| anyhow::anyhow!("failed to generate an IV: {}", e))?;
+    Ok(iv)
+}
+
+/// payload 앞에서부터 순서대로 keystream을 XOR한다 (암호화/복호화가 같은 연산).
+pub enum Keystream {
+    Xor { key: Vec<u8>, pos: usize },
+    Aes128(Box<Aes128Ctr>),
+    Aes256(Box<Aes256Ctr>),
+}
+
+impl Keystream {
+    /// `cipher`가 `None`이면 에러 (평문 blob에는 keystream이 없다)
+    pub fn new(cipher: Cipher, key: &[u8], iv: &[u8; IV_LEN]) -> Result<Self> {
+        cipher.check_key(key)?;
+        Ok(match cipher {
+            Cipher::None => bail!("blob payload is not encrypted"),
+            Cipher::Xor => Keystream::Xor { key: key.to_vec(), pos: 0 },
+            Cipher::AesCtr if key.len() == 16 => Keystream::Aes128(Box::new(Aes128Ctr::new(key.into(), iv.into()))),
+            Cipher::AesCtr => Keystream::Aes256(Box::new(Aes256Ctr::new(key.into(), iv.into()))),
+        })
+    }
+
+    pub fn apply(&mut self, data: &mut [u8]) {
+        match self {
+            Keystream::Xor { key, pos } => {
+                for b in data {
+                    *b ^= key[*pos];
+                    *pos = (*pos + 1) % key.len();
+                }
+            }
+            Keystream::Aes128(c) => c.apply_keystream(data),
+            Keystream::Aes256(c) => c.apply_keystream(data),
+        }
+    }
+
+    /// payload offset `pos`부터 이어서 암호화한다 (`--resume`)
+    pub fn seek(&mut self, pos: u64) {
+        match self {
+            Keystream::Xor { key, pos: at } => *at = (pos % key.len() as u64) as usize,
+            Keystream::Aes128(c) => c.seek(pos),
+            Keystream::Aes256(c) => c.seek(pos),
+        }
+    }
+}
+
+/// `--encrypt-audio`: 오디오 데이터를 `BAAU` 헤더 + 암호문 + CRC32로 감싼다 (IV는 호출마다 새로 뽑는다)
+#[cfg(feature = "native")]
+pub fn encrypt_audio(key: &BlobKey, plain: &[u8]) -> Result<Vec<u8>> {
+    let iv = if key.cipher.has_iv() { random_iv()? } else { [0; IV_LEN] };
+    let mut out = Vec::with_capacity(8 + IV_LEN + plain.len() + 4);
+    out.extend_from_slice(&AUDIO_MAGIC);
+    out.extend_from_slice(&[key.cipher.id(), 0, 0, 0]);
+    if key.cipher.has_iv() {
+        out.extend_from_slice(&iv);
+    }
+    let start = out.len();
+    out.extend_from_slice(plain);
+    Keystream::new(key.cipher, &key.key, &iv)?.apply(&mut out[start..]);
+    out.extend_from_slice(&crc32fast::hash(plain).to_le_bytes());
+    Ok(out)
+}
+
+/// `encrypt_audio`로 만든 데이터인지 (magic만 본다)
+pub fn is_encrypted_audio(data: &[u8]) -> bool {
+    data.starts_with(&AUDIO_MAGIC)
+}
+
+/// `encrypt_audio`의 역. 키가 틀리면 CRC 검사에서 걸린다.
+pub fn decrypt_audio(data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    if !is_encrypted_audio(data) {
+        bail!("not an encrypted audio attachment (no BAAU magic)");
+    }
+    let cipher = Cipher::from_id(*data.get(4).context("encrypted audio header is truncated")?)?;
+    if cipher == Cipher::None {
+        bail!("encrypted audio header has no cipher");
+    }
+    let header_len = 8 + if cipher.has_iv() { IV_LEN } else { 0 };
+    if data.len() < header_len + 4 {
+        bail!("encrypted audio attachment is truncated ({} bytes)", data.len());
+    }
+    let mut iv = [0u8; IV_LEN];
+    if cipher.has_iv() {
+        iv.copy_from_slice(&data[8..8 + IV_LEN]);
+    }
+    let (body, trailer) = data[header_len..].split_at(data.len() - header_len - 4);
+    let mut plain = body.to_vec();
+    Keystream::new(cipher, key, &iv)?.apply(&mut plain);
+    let expected = u32::from_le_bytes(trailer.try_into().unwrap());
+    let computed = crc32fast::hash(&plain);
+    if expected != computed {
+        bail!("encrypted audio CRC mismatch: expected {:08x}, computed {:08x} (wrong key?)", expected, computed);
+    }
+    Ok(plain)
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+    use crate::{
+        decode::{decode_frame, verify_blob},
+        encode::{encode_blob, EncodeOptions},
+        source::RawSource,
+    };
+
+    const AES128: &str = "aes-ctr:000102030405060708090a0b0c0d0e0f";
+    const AES256: &str = "aes-ctr:000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+    const IV: [u8; IV_LEN] = [7; IV_LEN];
+
+    fn plain() -> Vec<u8> {
+        (0..1000u32).map(|i| (i * 37 % 251) as u8).collect()
+    }
+
+    fn encrypted(key: &BlobKey, iv: &[u8; IV_LEN]) -> Vec<u8> {
+        let mut data = plain();
+        Keystream::new(key.cipher, &key.key, iv).unwrap().apply(&mut data);
+        data
+    }
+
+    #[test]
+    fn aes_ctr_round_trip() {
+        for key in [AES128, AES256] {
+            let key: BlobKey = key.parse().unwrap();
+            let cipher = encrypted(&key, &IV);
+            assert_ne!(cipher, plain());
+            // 조각으로 나눠 써도 같은 keystream이 이어진다
+            let mut back = cipher.clone();
+            let mut ks = Keystream::new(key.cipher, &key.key, &IV).unwrap();
+            for part in back.chunks_mut(7) {
+                ks.apply(part);
+            }
+            assert_eq!(back, plain(), "{}", key.key.len());
+            // IV가 다르면 keystream도 다르다
+            assert_ne!(encrypted(&key, &[8; IV_LEN]), cipher);
+        }
+    }
+
+    /// xor: offset `i`의 바이트에 `key[i % len]`. 조각으로 나눠 쓰거나 seek해도 key 위치가 이어진다.
+    #[test]
+    fn xor_round_trip() {
+        let key: BlobKey = "xor:a55a0f".parse().unwrap();
+        assert_eq!((key.cipher, key.key.as_slice()), (Cipher::Xor, &[0xa5, 0x5a, 0x0f][..]));
+        let cipher = encrypted(&key, &[0; IV_LEN]);
+        let expected: Vec<u8> = plain().iter().enumerate().map(|(i, b)| b ^ key.key[i % 3]).collect();
+        assert_eq!(cipher, expected);
+        let mut back = cipher.clone();
+        let mut ks = Keystream::new(Cipher::Xor, &key.key, &[0; IV_LEN]).unwrap();
+        for part in back.chunks_mut(5) {
+            ks.apply(part);
+        }
+        assert_eq!(back, plain());
+        let mut tail = plain()[301..].to_vec();
+        let mut ks = Keystream::new(Cipher::Xor, &key.key, &[0; IV_LEN]).unwrap();
+        ks.seek(301);
+        ks.apply(&mut tail);
+        assert_eq!(tail, cipher[301..]);
+        assert!(Keystream::new(Cipher::Xor, &[], &[0; IV_LEN]).is_err());
+    }
+
+    #[test]
+    fn parse_hex_rejects_odd_length_and_non_hex() {
+        assert_eq!(parse_hex("00ffA5").unwrap(), [0x00, 0xff, 0xa5]);
+        assert_eq!(parse_hex("").unwrap(), Vec::<u8>::new());
+        for bad in ["abc", "0", "zz", "0g", "00 11", "+1", "é0"] {
+            let err = format!("{:#}", parse_hex(bad).unwrap_err());
+            assert!(err.contains("invalid hex key"), "{:?}: {}", bad, err);
+        }
+        // --encrypt 형식: 방식 이름과 키 길이도 본다
+        assert!("xor:abc".parse::<BlobKey>().is_err());
+        assert!("xor:".parse::<BlobKey>().is_err());
+        assert!("aes-ctr:0011".parse::<BlobKey>().is_err());
+        assert!("rot13:00".parse::<BlobKey>().is_err());
+        assert!("00ff".parse::<BlobKey>().is_err());
+    }
+
+    /// `--resume`: 블록(16 bytes) 경계가 아닌 offset에서 이어서 암호화해도 한 번에 한 것과 같다
+    #[test]
+    fn seek_to_an_unaligned_offset() {
+        for key in [AES128, AES256] {
+            let key: BlobKey = key.parse().unwrap();
+            let whole = encrypted(&key, &IV);
+            for at in [1, 15, 17, 37, 999] {
+                let mut tail = plain()[at..].to_vec();
+                let mut ks = Keystream::new(key.cipher, &key.key, &IV).unwrap();
+                ks.seek(at as u64);
+                ks.apply(&mut tail);
+                assert_eq!(tail, whole[at..], "offset {}", at);
+            }
+        }
+    }
+
+    #[test]
+    fn wrong_key_is_refused_by_the_crc() {
+        let frames: Vec<u8> = (0..4 * 16).map(|i| if i % 5 == 0 { 0 } else { 255 }).collect();
+        let opts = EncodeOptions { width: 8, height: 2, encrypt: Some(AES128.parse().unwrap()), ..EncodeOptions::default() };
+        let blob = encode_blob(&mut RawSource::new(frames.as_slice(), true), &opts).unwrap();
+        let key = &opts.encrypt.as_ref().unwrap().key;
+        verify_blob(&blob, Some(key)).unwrap();
+        let mut wrong = key.clone();
+        wrong[0] ^= 1;
+        let err = format!("{:#}", verify_blob(&blob, Some(&wrong)).unwrap_err());
+        assert!(err.contains("CRC mismatch"), "{}", err);
+        assert!(decode_frame(&blob, 0, Some(&wrong)).is_err());
+
+        // --encrypt-audio도 평문 CRC로 틀린 키를 알아낸다
+        let audio = b"OggS audio bytes".to_vec();
+        for key in [AES128, AES256, "xor:5a"] {
+            let key: BlobKey = key.parse().unwrap();
+            let sealed = encrypt_audio(&key, &audio).unwrap();
+            assert!(is_encrypted_audio(&sealed));
+            assert_eq!(decrypt_audio(&sealed, &key.key).unwrap(), audio);
+            let mut wrong = key.key.clone();
+            wrong[0] ^= 0x80;
+            let err = format!("{:#}", decrypt_audio(&sealed, &wrong).unwrap_err());
+            assert!(err.contains("CRC mismatch"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn decrypt_audio_rejects_truncated_and_foreign_input() {
+        let key: BlobKey = AES128.parse().unwrap();
+        let sealed = encrypt_audio(&key, b"audio").unwrap();
+        let err = format!("{:#}", decrypt_audio(b"OggS\x00\x02 not encrypted", &key.key).unwrap_err());
+        assert!(err.contains("no BAAU magic"), "{}", err);
+        // magic만 있거나, IV/CRC 자리가 모자라다
+        for len in [4, 5, 8, 8 + IV_LEN, 8 + IV_LEN + 3] {
+            let err = format!("{:#}", decrypt_audio(&sealed[..len], &key.key).unwrap_err());
+            assert!(err.contains("truncated"), "{} bytes: {}", len, err);
+        }
+        // 본문이 한 바이트 빠지면 CRC가 맞지 않는다
+        assert!(decrypt_audio(&sealed[..sealed.len() - 1], &key.key).is_err());
+        let mut unknown = sealed.clone();
+        unknown[4] = 9;
+        assert!(format!("{:#}", decrypt_audio(&unknown, &key.key).unwrap_err()).contains("unknown payload cipher"));
+    }
+}
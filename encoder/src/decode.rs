@@ -0,0 +1,783 @@
+//! BA blob 디코더 (player.js와 같은 규약으로 프레임 복원)
+
+use crate::{
+    bits::{reverse_bit_order, xor_bytes_inplace, BitDepth, BitOrder},
+    blob::{
+        check_crc, parse_blob_header, BlobHeader, CRC_LEN, MS_TIMING_ENTRY_LEN, RECORD_FRAME, RECORD_REPEAT, REPEAT_RECORD_LEN, TIMESTAMP_ENTRY_LEN,
+    },
+    codec::{decompress_frame, decompress_record, CompressionMode},
+    crypt::Keystream,
+    tiles::apply_tile_diff,
+};
+#[cfg(feature = "native")]
+use crate::{
+    bits::align_rows,
+    encode::{EncodeOptions, EncodeStats},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use std::borrow::Cow;
+
+/// keyframe + XOR chain을 순서대로 재생한다.
+pub struct BlobDecoder<'a> {
+    header: BlobHeader,
+    codec: CompressionMode,
+    /// 헤더, timing table, CRC trailer를 뺀 프레임 데이터 (암호화된 blob이면 복호화한 사본)
+    payload: Cow<'a, [u8]>,
+    off: usize,
+    /// 돌려준 프레임 수 (repeat record blob이면 재생 프레임, 아니면 저장된 프레임 기준)
+    index: u32,
+    /// 읽은 frame record 수
+    stored: u32,
+    cur: Vec<u8>,
+    /// timing table (저장된 프레임마다 재생 길이), 없으면 모두 1
+    durations: Option<Vec<u32>>,
+    /// 현재 프레임을 앞으로 더 돌려줄 횟수 (timing table 또는 repeat record)
+    hold: u32,
+    /// frame CRC table (있으면 복원한 프레임마다 검사한다)
+    frame_crcs: Option<Vec<u32>>,
+    /// ms duration table (저장된 프레임마다 보여줄 시간)
+    durations_ms: Option<Vec<u16>>,
+    /// ms timestamp table (저장된 프레임마다 시작 시각 + 끝 시각, `--vfr`)
+    timestamps: Option<Vec<u32>>,
+}
+
+/// blob 끝쪽 u32 table 하나 (timing / frame CRC)
+fn read_u32_table(blob: &[u8], offset: usize, count: usize) -> Vec<u32> {
+    blob[offset..offset + 4 * count]
+        .chunks_exact(4)
+        .map(|d| u32::from_le_bytes([d[0], d[1], d[2], d[3]]))
+        .collect()
+}
+
+impl<'a> BlobDecoder<'a> {
+    /// 헤더를 읽고 길이와 CRC(있으면)를 검사한다. 암호화된 blob은 `with_key`로 연다.
+    pub fn new(blob: &'a [u8]) -> Result<Self> {
+        Self::open(blob, true, None)
+    }
+
+    /// `--encrypt`로 만든 blob을 `key`로 복호화해서 연다. 키가 틀리면 CRC 검사에서 걸린다.
+    /// 평문 blob이면 `key`는 쓰지 않는다.
+    pub fn with_key(blob: &'a [u8], key: &[u8]) -> Result<Self> {
+        Self::open(blob, true, Some(key))
+    }
+
+    /// `check_blob_crc`가 false면 CRC trailer 검사를 건너뛴다
+    /// (frame CRC table로 어디가 깨졌는지 찾을 때).
+    fn open(blob: &'a [u8], check_blob_crc: bool, key: Option<&[u8]>) -> Result<Self> {
+        let header = parse_blob_header(blob)?;
+        let codec = header.compression()?;
+        let crc_len = if header.has_crc() { CRC_LEN } else { 0 };
+        // repeat record blob은 레코드 수를 미리 알 수 없다
+        let frames_len = if !header.has_record_len() && !header.repeat_records {
+            header.packed_len() * header.frame_count as usize
+        } else {
+            0
+        };
+        let timing_len = if header.has_timing() { 4 * header.frame_count as usize } else { 0 };
+        let frame_crc_len = if header.has_frame_crc() { 4 * header.frame_count as usize } else { 0 };
+        // ms duration table과 ms timestamp table은 같은 자리를 쓴다 (둘 다 있는 헤더는 거부된다)
+        let ms_len = if header.ms_timing {
+            MS_TIMING_ENTRY_LEN * header.frame_count as usize
+        } else if header.timestamps {
+            TIMESTAMP_ENTRY_LEN * (header.frame_count as usize + 1)
+        } else {
+            0
+        };
+        let min_len = header.header_len() + frames_len + timing_len + ms_len + frame_crc_len + crc_len;
+        // legacy 헤더에는 magic이 없어서 아무 파일이나 헤더처럼 읽힌다.
+        // legacy blob은 크기가 정확히 정해져 있으니 그걸로 걸러낸다.
+        if header.is_legacy() && blob.len() != min_len {
+            bail!(
+                "not a BA blob: missing \"BAPF\" magic, and {} bytes does not match a legacy {}x{} blob of {} frames ({} bytes)",
+                blob.len(),
+                header.width,
+                header.height,
+                header.frame_count,
+                min_len
+            );
+        }
+        if blob.len() < min_len {
+            bail!("BA blob truncated: expected {} bytes, got {}", min_len, blob.len());
+        }
+        // 헤더 뒤부터 CRC trailer 앞까지 복호화한 사본 (아래 table/payload는 모두 여기서 읽는다)
+        let plain = if header.is_encrypted() {
+            let key = key.with_context(|| format!("BA blob is encrypted ({}); pass --key", header.cipher.name()))?;
+            let mut plain = blob.to_vec();
+            let body = header.header_len()..plain.len() - CRC_LEN;
+            Keystream::new(header.cipher, key, &header.iv)?.apply(&mut plain[body]);
+            Some(plain)
+        } else {
+            None
+        };
+        let raw = blob;
+        let blob = match &plain {
+            Some(p) => p.as_slice(),
+            None => raw,
+        };
+        if check_blob_crc {
+            check_crc(blob, &header).map_err(|e| match plain {
+                Some(_) => anyhow!("{:#} (wrong --key?)", e),
+                None => e,
+            })?;
+        }
+        let end = blob.len() - crc_len;
+
+        // frame CRC table은 맨 뒤(CRC trailer 바로 앞), ms duration table과 timing table은 차례로 그 앞에 있어야 한다.
+        let (tables_end, frame_crcs) = if header.has_frame_crc() {
+            let table = header.frame_crc_offset as usize;
+            if table < header.header_len() + frames_len + timing_len + ms_len || table + frame_crc_len != end {
+                bail!(
+                    "invalid BA frame CRC table offset {} ({} frames, frame data ends at {})",
+                    table,
+                    header.frame_count,
+                    end
+                );
+            }
+            (table, Some(read_u32_table(blob, table, header.frame_count as usize)))
+        } else {
+            (end, None)
+        };
+        let end = tables_end;
+        let (end, durations_ms) = if header.ms_timing {
+            let table = header.ms_timing_offset as usize;
+            if table < header.header_len() + frames_len + timing_len || table + ms_len != end {
+                bail!(
+                    "invalid BA ms duration table offset {} ({} frames, frame data ends at {})",
+                    table,
+                    header.frame_count,
+                    end
+                );
+            }
+            let durations: Vec<u16> =
+                blob[table..table + ms_len].chunks_exact(2).map(|d| u16::from_le_bytes([d[0], d[1]])).collect();
+            if let Some(i) = durations.iter().position(|&d| d == 0) {
+                bail!("BA ms duration table: frame {} has zero duration", i);
+            }
+            (table, Some(durations))
+        } else {
+            (end, None)
+        };
+        let (end, timestamps) = if header.timestamps {
+            let table = header.timestamps_offset as usize;
+            if table < header.header_len() + frames_len + timing_len || table + ms_len != end {
+                bail!(
+                    "invalid BA ms timestamp table offset {} ({} frames, frame data ends at {})",
+                    table,
+                    header.frame_count,
+                    end
+                );
+            }
+            let timestamps = read_u32_table(blob, table, header.frame_count as usize + 1);
+            if timestamps[0] != 0 {
+                bail!("BA ms timestamp table: frame 0 starts at {} ms (expected 0)", timestamps[0]);
+            }
+            if let Some(i) = timestamps.windows(2).position(|w| w[1] < w[0]) {
+                bail!("BA ms timestamp table: frame {} starts before frame {}", i + 1, i);
+            }
+            (table, Some(timestamps))
+        } else {
+            (end, None)
+        };
+        let (payload_end, durations) = if header.has_timing() {
+            let table = header.timing_offset as usize;
+            if table < header.header_len() + frames_len || table + timing_len != end {
+                bail!(
+                    "invalid BA timing table offset {} ({} frames, frame data ends at {})",
+                    table,
+                    header.frame_count,
+                    end
+                );
+            }
+            let durations = read_u32_table(blob, table, header.frame_count as usize);
+            if let Some(i) = durations.iter().position(|&d| d == 0) {
+                bail!("BA timing table: frame {} has zero duration", i);
+            }
+            if durations.iter().try_fold(0u32, |acc, &d| acc.checked_add(d)).is_none() {
+                bail!("BA timing table: total frame count overflows u32");
+            }
+            (table, Some(durations))
+        } else {
+            (end, None)
+        };
+        let payload = match &plain {
+            Some(p) => Cow::Owned(p[header.header_len()..payload_end].to_vec()),
+            None => Cow::Borrowed(&raw[header.header_len()..payload_end]),
+        };
+        Ok(Self {
+            header,
+            codec,
+            payload,
+            off: 0,
+            index: 0,
+            stored: 0,
+            cur: vec![0u8; header.packed_len()],
+            durations,
+            hold: 0,
+            frame_crcs,
+            durations_ms,
+            timestamps,
+        })
+    }
+
+    pub fn header(&self) -> &BlobHeader {
+        &self.header
+    }
+
+    /// 재생되는 프레임 수 (timing table이 있으면 duration 합, 아니면 frame_count)
+    pub fn total_frames(&self) -> u32 {
+        match &self.durations {
+            Some(d) => d.iter().sum(),
+            None => self.header.frame_count,
+        }
+    }
+
+    /// 지금까지 읽은 frame record 수 (끝까지 읽으면 저장된 프레임 수)
+    pub fn stored_frames(&self) -> u32 {
+        self.stored
+    }
+
+    /// ms duration table (저장된 프레임마다 보여줄 시간). 없으면 `None` (fps대로 재생).
+    pub fn durations_ms(&self) -> Option<&[u16]> {
+        self.durations_ms.as_deref()
+    }
+
+    /// 저장된 프레임마다 처음 보이는 시각 (ms, 첫 프레임은 0)과 끝나는 시각.
+    /// ms timestamp table이 있으면 그대로, ms duration table이 있으면 누적해서,
+    /// 둘 다 없으면 timing table과 fps로 계산한다.
+    pub fn timestamps_ms(&self) -> (Vec<u64>, u64) {
+        let count = self.header.frame_count as usize;
+        if let Some(table) = &self.timestamps {
+            return (table[..count].iter().map(|&t| t as u64).collect(), table[count] as u64);
+        }
+        let tick_ms = 1000.0 / self.header.fps() as f64;
+        let mut starts = Vec::with_capacity(count);
+        let mut t = 0u64;
+        let mut ticks = 0u64;
+        for i in 0..count {
+            match &self.durations_ms {
+                Some(ms) => {
+                    starts.push(t);
+                    t += ms[i] as u64;
+                }
+                None => {
+                    // 누적 tick에서 바로 ms로 바꿔서 반올림 오차가 쌓이지 않게 한다
+                    starts.push((ticks as f64 * tick_ms).round() as u64);
+                    ticks += self.durations.as_ref().map_or(1, |d| d[i] as u64);
+                    t = (ticks as f64 * tick_ms).round() as u64;
+                }
+            }
+        }
+        (starts, t)
+    }
+
+    /// `ms` 시각에 보이는 재생 프레임 번호 (`decode_frame`에 넘기는 번호, 끝을 넘으면 마지막 프레임).
+    /// 플레이어처럼 `timestamps_ms`로 저장된 프레임을 고르고, 합쳐진 프레임이면 그 첫 재생 프레임을 돌려준다.
+    pub fn frame_at_ms(&self, ms: u64) -> u32 {
+        let (starts, _) = self.timestamps_ms();
+        let stored = starts.partition_point(|&t| t <= ms).saturating_sub(1);
+        match &self.durations {
+            Some(d) => d[..stored].iter().sum(),
+            None => stored as u32,
+        }
+    }
+
+    /// 다음 프레임 레코드를 packed 크기로 풀어서 돌려준다.
+    /// 타일 레코드(frame1..)는 길이가 가변이라 최대 크기까지만 허용한다.
+    fn next_record(&mut self) -> Result<Vec<u8>> {
+        let n = self.cur.len();
+        let rest = &self.payload[self.off..];
+        if !self.header.has_record_len() {
+            if rest.len() < n {
+                bail!("frame {}: record needs {} bytes, only {} left", self.index, n, rest.len());
+            }
+            self.off += n;
+            return Ok(rest[..n].to_vec());
+        }
+        if rest.len() < 4 {
+            bail!("frame {}: record length truncated", self.index);
+        }
+        let len = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if rest.len() < 4 + len {
+            bail!("frame {}: record needs {} bytes, only {} left", self.index, len, rest.len() - 4);
+        }
+        self.off += 4 + len;
+        let data = &rest[4..4 + len];
+        let out = if self.header.is_tiled() && self.index > 0 {
+            decompress_record(data, self.codec, self.header.tile_grid().max_record_len())
+        } else {
+            decompress_frame(data, self.codec, n)
+        };
+        out.with_context(|| format!("frame {}: failed to decode {} record", self.index, self.codec.name()))
+    }
+
+    /// repeat record blob: 다음 레코드의 tag를 읽고, repeat record면 그 count를 돌려준다 (frame record면 `None`)
+    fn next_repeat(&mut self) -> Result<Option<u32>> {
+        let rest = &self.payload[self.off..];
+        match rest.first() {
+            None => bail!("frame {}: record tag truncated", self.index),
+            Some(&RECORD_FRAME) => {
+                self.off += 1;
+                Ok(None)
+            }
+            Some(&RECORD_REPEAT) => {
+                if rest.len() < REPEAT_RECORD_LEN {
+                    bail!("frame {}: repeat record truncated", self.index);
+                }
+                let count = u16::from_le_bytes([rest[1], rest[2]]) as u32;
+                if self.index == 0 {
+                    bail!("frame 0: repeat record before the first frame record");
+                }
+                if count == 0 || count > self.header.frame_count - self.index {
+                    bail!("frame {}: repeat record of {} frames does not fit frame_count {}", self.index, count, self.header.frame_count);
+                }
+                self.off += REPEAT_RECORD_LEN;
+                Ok(Some(count))
+            }
+            Some(&tag) => bail!("frame {}: unknown record tag {:#04x}", self.index, tag),
+        }
+    }
+
+    /// 다음 프레임의 packed bitset (MSB-first, 1=black). 끝이면 `None`.
+    /// timing table이나 repeat record가 있으면 합쳐진 프레임도 원래 frame rate대로 반복해서 돌려준다.
+    pub fn next_frame(&mut self) -> Result<Option<&[u8]>> {
+        if self.hold > 0 {
+            self.hold -= 1;
+            if self.header.repeat_records {
+                self.index += 1;
+            }
+            return Ok(Some(&self.cur));
+        }
+        if self.index >= self.header.frame_count {
+            return Ok(None);
+        }
+        if self.header.repeat_records {
+            if let Some(count) = self.next_repeat()? {
+                self.hold = count - 1;
+                self.index += 1;
+                return Ok(Some(&self.cur));
+            }
+        }
+        let chunk = self.next_record()?;
+        if self.index == 0 {
+            self.cur.copy_from_slice(&chunk);
+        } else if self.header.is_tiled() {
+            apply_tile_diff(&mut self.cur, &chunk, &self.header.tile_grid())
+                .with_context(|| format!("frame {}: bad tile record", self.index))?;
+        } else {
+            xor_bytes_inplace(&mut self.cur, &chunk);
+        }
+        if let Some(crcs) = &self.frame_crcs {
+            let (expected, computed) = (crcs[self.index as usize], crc32fast::hash(&self.cur));
+            if expected != computed {
+                bail!(
+                    "frame {}: CRC mismatch (expected {:08x}, computed {:08x}); this is the first corrupt frame",
+                    self.index,
+                    expected,
+                    computed
+                );
+            }
+        }
+        if let Some(d) = &self.durations {
+            self.hold = d[self.index as usize] - 1;
+        }
+        self.index += 1;
+        self.stored += 1;
+        Ok(Some(&self.cur))
+    }
+}
+
+/// blob 무결성 검사: 헤더, CRC trailer(있으면), 그리고 모든 프레임 레코드가 끝까지 풀리는지.
+/// CRC trailer가 틀렸는데 frame CRC table이 있으면 처음 깨진 프레임을 같이 알려준다.
+///
+/// magic이 CRC보다 먼저다: `BAPF`로 시작하지 않으면 legacy(v0, 10 bytes 헤더) blob으로 읽되 크기가 정확히 맞을 때만
+/// 받아들이므로, AU.ogg 같은 엉뚱한 파일은 CRC를 계산하기 전에 "not a BA blob"으로 끝난다.
+/// 암호화된 blob은 `key`가 있어야 한다. 키가 틀리면 payload 전체가 깨지므로 frame CRC table로 찾지 않는다.
+pub fn verify_blob(data: &[u8], key: Option<&[u8]>) -> Result<()> {
+    let mut dec = match BlobDecoder::open(data, true, key) {
+        Ok(dec) => dec,
+        Err(e) if parse_blob_header(data).is_ok_and(|h| h.has_frame_crc() && h.has_crc() && !h.is_encrypted()) => {
+            let mut dec = BlobDecoder::open(data, false, key)?;
+            loop {
+                match dec.next_frame() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => return Err(e.context("all frames match the frame CRC table")),
+                    Err(frame_err) => return Err(e.context(format!("{:#}", frame_err))),
+                }
+            }
+        }
+        Err(e) => return Err(e),
+    };
+    while dec.next_frame()?.is_some() {}
+    Ok(())
+}
+
+/// 이미 만든 blob (`--blob-in`)을 끝까지 재생해서 `encode_blob_to`가 돌려줬을 통계를 다시 만든다.
+/// 포스터/flipbook은 `opts`의 `poster_frame`, `flipbook_every`, `flipbook_max_pages`를 따른다.
+/// 재생하면서 CRC와 프레임 레코드도 전부 검사된다.
+#[cfg(feature = "native")]
+pub fn blob_stats(blob: &[u8], key: Option<&[u8]>, opts: &EncodeOptions) -> Result<EncodeStats> {
+    let mut dec = BlobDecoder::open(blob, true, key)?;
+    let header = dec.header;
+    // 썸네일/flipbook 이미지는 MSB-first (`encode_blob_to`와 같게)
+    let to_rows = |packed: &[u8]| {
+        let mut msb = packed.to_vec();
+        if header.bit_order == BitOrder::Lsb {
+            reverse_bit_order(&mut msb);
+        }
+        if header.is_row_aligned() {
+            msb
+        } else {
+            align_rows(&msb, header.width as usize * header.bit_depth.bits(), header.height as usize)
+        }
+    };
+    let mut stats = EncodeStats {
+        bytes: blob.len() as u64,
+        white_ink: header.white_ink,
+        ..Default::default()
+    };
+    let mut prev: Option<Vec<u8>> = None;
+    loop {
+        let (stored, off) = (dec.stored, dec.off);
+        let Some(frame) = dec.next_frame()? else {
+            break;
+        };
+        let i = stats.frames;
+        if i == opts.poster_frame {
+            stats.poster = Some(to_rows(frame));
+        }
+        if opts.flipbook_every > 0 && i.is_multiple_of(opts.flipbook_every) {
+            if stats.flipbook.len() < opts.flipbook_max_pages as usize {
+                stats.flipbook.push((i, to_rows(frame)));
+            } else {
+                stats.flipbook_skipped += 1;
+            }
+        }
+        if prev.as_deref() == Some(frame) {
+            stats.zero_diff_frames += 1;
+        }
+        prev = Some(frame.to_vec());
+        // repeat record는 `encode_blob_to`처럼 그 앞에 저장한 프레임의 크기에 더한다
+        if dec.stored != stored {
+            stats.frame_bytes.push((dec.off - off) as u32);
+        } else if dec.off != off {
+            if let Some(last) = stats.frame_bytes.last_mut() {
+                *last += (dec.off - off) as u32;
+            }
+        }
+        stats.frames += 1;
+    }
+    stats.stored_frames = dec.stored;
+    stats.raw_bytes = stats.frames as u64 * header.packed_len() as u64;
+    stats.duration_ms = dec.durations_ms().map(|d| d.iter().map(|&ms| ms as u64).sum());
+    if header.timestamps {
+        stats.duration_ms = Some(dec.timestamps_ms().1);
+    }
+    Ok(stats)
+}
+
+/// `index`번 프레임의 packed bitset. 암호화된 blob은 `key`가 있어야 한다.
+pub fn decode_frame(blob: &[u8], index: u32, key: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut dec = BlobDecoder::open(blob, true, key)?;
+    let frame_count = dec.total_frames();
+    if index >= frame_count {
+        bail!("frame index {} out of range (frame_count = {})", index, frame_count);
+    }
+    for _ in 0..index {
+        dec.next_frame()?;
+    }
+    Ok(dec.next_frame()?.map(|f| f.to_vec()).unwrap_or_default())
+}
+
+/// `index`번 프레임을 gray8 (w*h, 행 우선)로 복원한다. 반전/극성 플래그도 적용된다 (`decode`, wasm `decodeFrame`).
+pub fn decode_frame_gray(blob: &[u8], index: u32, key: Option<&[u8]>) -> Result<Vec<u8>> {
+    let header = parse_blob_header(blob)?;
+    let packed = decode_frame(blob, index, key)?;
+    Ok(unpack_frame_to_gray(&header, &packed))
+}
+
+/// packed bitset → gray8 (1=black → 0, 0=white → 255)
+pub fn unpack_to_gray(packed: &[u8], pixel_count: usize) -> Vec<u8> {
+    (0..pixel_count)
+        .map(|i| if (packed[i / 8] >> (7 - (i % 8))) & 1 != 0 { 0 } else { 255 })
+        .collect()
+}
+
+/// 헤더의 행 정렬, bit depth, 비트 순서, 반전, 극성까지 고려해서 packed 프레임 → gray8 (w*h)
+pub fn unpack_frame_to_gray(header: &BlobHeader, packed: &[u8]) -> Vec<u8> {
+    let mut gray = unpack_frame_raw(header, packed);
+    if header.flips_pixels() {
+        gray.iter_mut().for_each(|v| *v = 255 - *v);
+    }
+    gray
+}
+
+fn unpack_frame_raw(header: &BlobHeader, packed: &[u8]) -> Vec<u8> {
+    if header.bit_order == BitOrder::Lsb {
+        // 1-bit만 LSB-first일 수 있다 (parse_blob_header가 검사)
+        let mut msb = packed.to_vec();
+        reverse_bit_order(&mut msb);
+        return unpack_frame_raw(&BlobHeader { bit_order: BitOrder::Msb, ..*header }, &msb);
+    }
+    if header.bit_depth == BitDepth::Gray2 {
+        // MSB-first 2비트씩, 0..3 → 0..255
+        let (w, stride) = (header.width as usize, header.row_bits() / 2);
+        return (0..header.pixel_count())
+            .map(|p| {
+                let i = p / w * stride + p % w;
+                ((packed[i / 4] >> (6 - 2 * (i % 4))) & 0b11) * 85
+            })
+            .collect();
+    }
+    if header.bit_depth == BitDepth::Gray4 {
+        // nibble 0..15 → 0..255
+        let (w, stride) = (header.width as usize, header.row_bits() / 4);
+        return (0..header.pixel_count())
+            .map(|p| {
+                let i = p / w * stride + p % w;
+                let nibble = if i % 2 == 0 { packed[i / 2] >> 4 } else { packed[i / 2] & 0x0f };
+                nibble * 17
+            })
+            .collect();
+    }
+    if !header.is_row_aligned() {
+        return unpack_to_gray(packed, header.pixel_count());
+    }
+    let (w, row_bits) = (header.width as usize, header.row_bits());
+    let mut gray = Vec::with_capacity(header.pixel_count());
+    for y in 0..header.height as usize {
+        for x in 0..w {
+            let i = y * row_bits + x;
+            gray.push(if (packed[i / 8] >> (7 - (i % 8))) & 1 != 0 { 0 } else { 255 });
+        }
+    }
+    gray
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+    use crate::{
+        bits::{pack_bits, pack_bits_rows},
+        blob::{write_blob_header, FLAG_ROW_ALIGN, HEADER_LEN, LEGACY_HEADER_LEN, MAGIC, V2_FLAG2_LSB_FIRST, VERSION, VERSION_2},
+        encode::encode_blob,
+        source::RawSource,
+    };
+
+    /// 12x4, 4 프레임: 3x2 black 사각형이 오른쪽으로 한 칸씩 움직인다 (0 = black, 255 = white)
+    fn moving_square() -> Vec<Vec<u8>> {
+        (0..4)
+            .map(|f| {
+                let mut gray = vec![255u8; 12 * 4];
+                for y in 1..3 {
+                    for x in f + 2..f + 5 {
+                        gray[y * 12 + x] = 0;
+                    }
+                }
+                gray
+            })
+            .collect()
+    }
+
+    fn encode_tiny(frames: &[Vec<u8>], opts: &EncodeOptions) -> Vec<u8> {
+        encode_blob(&mut RawSource::new(frames.concat().as_slice(), true), opts).unwrap()
+    }
+
+    #[test]
+    fn encode_then_decode_frame_round_trips() {
+        let frames = moving_square();
+        let opts = EncodeOptions { width: 12, height: 4, fps: 12.5, ..EncodeOptions::default() };
+        let blob = encode_tiny(&frames, &opts);
+        let header = parse_blob_header(&blob).unwrap();
+        assert_eq!((header.width, header.height, header.fps_x100, header.frame_count), (12, 4, 1250, 4));
+        for (i, gray) in frames.iter().enumerate() {
+            let bits01: Vec<u8> = gray.iter().map(|&px| (px == 0) as u8).collect();
+            assert_eq!(decode_frame(&blob, i as u32, None).unwrap(), pack_bits(&bits01), "frame {}", i);
+            assert_eq!(&decode_frame_gray(&blob, i as u32, None).unwrap(), gray, "frame {}", i);
+        }
+        assert!(decode_frame(&blob, 4, None).is_err());
+    }
+
+    #[test]
+    fn keyframe_is_packed_frame_and_rest_are_xor_diffs() {
+        let frames = moving_square();
+        let opts = EncodeOptions { width: 12, height: 4, ..EncodeOptions::default() };
+        let blob = encode_tiny(&frames, &opts);
+        // 48 px = 6 bytes/프레임, 압축 없음: 헤더 뒤에 frame0 bitset, 그 다음 XOR diff
+        let packed: Vec<Vec<u8>> =
+            frames.iter().map(|g| pack_bits(&g.iter().map(|&px| (px == 0) as u8).collect::<Vec<_>>())).collect();
+        let body = &blob[HEADER_LEN..blob.len() - CRC_LEN];
+        assert_eq!(body.len(), 4 * 6);
+        assert_eq!(&body[..6], packed[0].as_slice());
+        let mut diff = packed[1].clone();
+        xor_bytes_inplace(&mut diff, &packed[0]);
+        assert_eq!(&body[6..12], diff.as_slice());
+    }
+
+    #[test]
+    fn invert_flag_shows_the_complement_of_the_keyframe() {
+        let frames = moving_square();
+        let opts = EncodeOptions { width: 12, height: 4, ..EncodeOptions::default() };
+        let plain = encode_tiny(&frames, &opts);
+        let inverted = encode_tiny(&frames, &EncodeOptions { invert: true, ..opts.clone() });
+        let header = parse_blob_header(&inverted).unwrap();
+        assert!(header.invert && header.flips_pixels());
+        // payload는 그대로, 헤더 플래그만 다르다
+        assert_eq!(decode_frame(&inverted, 0, None).unwrap(), decode_frame(&plain, 0, None).unwrap());
+
+        // 48 px = 6 bytes라 padding 비트가 없다
+        let shown = decode_frame_gray(&inverted, 0, None).unwrap();
+        let shown_bits = pack_bits(&shown.iter().map(|&px| (px == 0) as u8).collect::<Vec<_>>());
+        let complement: Vec<u8> = decode_frame(&plain, 0, None).unwrap().iter().map(|b| !b).collect();
+        assert_eq!(shown_bits, complement);
+        let plain_gray = decode_frame_gray(&plain, 0, None).unwrap();
+        assert!(shown.iter().zip(&plain_gray).all(|(a, b)| *a == 255 - *b));
+    }
+
+    #[test]
+    fn gray2_frames_decode_to_four_levels() {
+        // 5x3 (w*h 홀수), 레벨 0..3 → 0, 85, 170, 255
+        let frames: Vec<Vec<u8>> = (0..3).map(|f| (0..15).map(|i| ((i + f) % 4 * 85) as u8).collect()).collect();
+        for row_align in [false, true] {
+            let opts = EncodeOptions { width: 5, height: 3, bit_depth: BitDepth::Gray2, row_align, ..EncodeOptions::default() };
+            let blob = encode_tiny(&frames, &opts);
+            let header = parse_blob_header(&blob).unwrap();
+            assert_eq!((header.version, header.bit_depth), (VERSION_2, BitDepth::Gray2));
+            assert_eq!(header.packed_len(), if row_align { 6 } else { 4 });
+            for (i, gray) in frames.iter().enumerate() {
+                assert_eq!(&decode_frame_gray(&blob, i as u32, None).unwrap(), gray, "frame {} row_align {}", i, row_align);
+            }
+        }
+        // 1-bit는 기본값이고 v1 헤더 그대로다
+        let binary = encode_tiny(&moving_square(), &EncodeOptions { width: 12, height: 4, ..EncodeOptions::default() });
+        assert_eq!(parse_blob_header(&binary).unwrap().version, VERSION);
+    }
+
+    /// `--bit-order`와 `--row-align` 네 조합: 10x3 (폭이 8의 배수가 아님) keyframe의 바이트와 헤더 플래그
+    #[test]
+    fn bit_order_and_row_align_combinations() {
+        let frames: Vec<Vec<u8>> = [[0b10_0000_0001u16, 0b11_1111_1111, 0b01_0101_0101], [0b11_0000_0000, 0, 0b00_0000_0011]]
+            .iter()
+            .map(|rows| rows.iter().flat_map(|row| (0..10).rev().map(move |x| if row >> x & 1 != 0 { 0 } else { 255 })).collect())
+            .collect();
+        let bits01: Vec<u8> = frames[0].iter().map(|&px| (px == 0) as u8).collect();
+        for bit_order in [BitOrder::Msb, BitOrder::Lsb] {
+            for row_align in [false, true] {
+                let case = format!("{:?} row_align={}", bit_order, row_align);
+                let opts = EncodeOptions { width: 10, height: 3, bit_order, row_align, ..EncodeOptions::default() };
+                let blob = encode_tiny(&frames, &opts);
+                let header = parse_blob_header(&blob).unwrap();
+                assert_eq!((header.bit_order, header.is_row_aligned()), (bit_order, row_align), "{}", case);
+                assert_eq!(blob[5] & FLAG_ROW_ALIGN != 0, row_align, "{}", case);
+                // MSB-first는 v1 헤더 그대로, LSB-first만 v2 헤더의 v2_flags2 bit0
+                if bit_order == BitOrder::Lsb {
+                    assert_eq!((header.version, blob[18] & V2_FLAG2_LSB_FIRST), (VERSION_2, V2_FLAG2_LSB_FIRST), "{}", case);
+                } else {
+                    assert_eq!(header.version, VERSION, "{}", case);
+                }
+
+                let mut keyframe = if row_align { pack_bits_rows(&bits01, 10) } else { pack_bits(&bits01) };
+                assert_eq!(keyframe.len(), if row_align { 6 } else { 4 }, "{}", case);
+                if bit_order == BitOrder::Lsb {
+                    keyframe.iter_mut().for_each(|b| *b = b.reverse_bits());
+                }
+                let body = &blob[header.header_len()..];
+                assert_eq!(&body[..keyframe.len()], keyframe.as_slice(), "{}", case);
+                assert_eq!(decode_frame(&blob, 0, None).unwrap(), keyframe, "{}", case);
+                for (i, gray) in frames.iter().enumerate() {
+                    assert_eq!(&decode_frame_gray(&blob, i as u32, None).unwrap(), gray, "{} frame {}", case, i);
+                }
+            }
+        }
+        // repeat record로 반복한 프레임의 CRC도 저장한 바이트(LSB-first) 기준이다
+        let held = [frames[0].clone(), frames[0].clone(), frames[1].clone()];
+        let opts = EncodeOptions { width: 10, height: 3, bit_order: BitOrder::Lsb, repeat_records: true, frame_checksums: true, ..EncodeOptions::default() };
+        let blob = encode_tiny(&held, &opts);
+        verify_blob(&blob, None).unwrap();
+        let table = parse_blob_header(&blob).unwrap().frame_crc_offset as usize;
+        assert_eq!(blob[table..table + 4], blob[table + 4..table + 8]);
+    }
+
+    /// 8x2, 3 프레임: 프레임마다 black 픽셀이 4개씩 는다
+    fn encode_steps(opts: &EncodeOptions) -> Vec<u8> {
+        let frames: Vec<u8> = (0..3).flat_map(|f| (0..16).map(move |i| if i < f * 4 { 0 } else { 255 })).collect();
+        encode_blob(&mut RawSource::new(frames.as_slice(), true), &EncodeOptions { width: 8, height: 2, ..opts.clone() }).unwrap()
+    }
+
+    #[test]
+    fn ogg_file_is_rejected_by_magic_before_crc() {
+        // Ogg page 헤더 + 아무 데이터. legacy 헤더로 읽으면 크기가 맞지 않는다.
+        let mut ogg = b"OggS\x00\x02".to_vec();
+        ogg.extend((0..4090u32).map(|i| (i * 31 % 256) as u8));
+        for data in [&ogg[..], &ogg[..4]] {
+            let err = format!("{:#}", verify_blob(data, None).unwrap_err());
+            assert!(err.contains("not a BA blob") && err.contains("magic"), "{}", err);
+            assert!(!err.contains("CRC"), "{}", err);
+        }
+        // magic이 맞는 blob이면 그 다음에 CRC를 본다
+        let mut blob = encode_steps(&EncodeOptions::default());
+        assert!(blob.starts_with(&MAGIC));
+        let last = blob.len() - 1;
+        blob[last] ^= 1;
+        assert!(format!("{:#}", verify_blob(&blob, None).unwrap_err()).contains("CRC mismatch"));
+    }
+
+    #[test]
+    fn legacy_v0_blob_is_detected_without_magic() {
+        let legacy = encode_steps(&EncodeOptions { legacy_header: true, ..EncodeOptions::default() });
+        assert!(!legacy.starts_with(&MAGIC));
+        verify_blob(&legacy, None).unwrap();
+        let header = parse_blob_header(&legacy).unwrap();
+        assert!(header.is_legacy());
+        assert_eq!((header.width, header.height, header.frame_count), (8, 2, 3));
+
+        let v1 = encode_steps(&EncodeOptions::default());
+        for i in 0..3 {
+            assert_eq!(decode_frame(&legacy, i, None).unwrap(), decode_frame(&v1, i, None).unwrap(), "frame {}", i);
+        }
+        // 한 바이트라도 크기가 다르면 legacy blob이 아니다
+        let err = format!("{:#}", verify_blob(&legacy[..legacy.len() - 1], None).unwrap_err());
+        assert!(err.contains("not a BA blob"), "{}", err);
+    }
+
+    /// 8x2 1-bit blob: keyframe + XOR delta 하나, 끝에 CRC32
+    fn blob() -> Vec<u8> {
+        let mut header = BlobHeader::new(8, 2, 30.0);
+        header.frame_count = 2;
+        let mut blob = write_blob_header(&header);
+        blob.extend_from_slice(&[0xf0, 0x0f, 0x01, 0x80]);
+        let crc = crc32fast::hash(&blob);
+        blob.extend_from_slice(&crc.to_le_bytes());
+        blob
+    }
+
+    #[test]
+    fn crc_trailer_catches_a_flipped_byte() {
+        let mut blob = blob();
+        verify_blob(&blob, None).unwrap();
+        blob[17] ^= 0x10;
+        let err = verify_blob(&blob, None).unwrap_err();
+        assert!(format!("{:#}", err).contains("CRC mismatch"), "{:#}", err);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let mut blob = blob();
+        blob[4] = VERSION_2 + 1;
+        let err = BlobDecoder::new(&blob).err().unwrap();
+        assert!(format!("{:#}", err).contains("unsupported BA blob version"), "{:#}", err);
+    }
+
+    #[test]
+    fn missing_magic_is_read_as_legacy_only_at_the_exact_size() {
+        let mut header = BlobHeader::legacy(8, 2, 30.0);
+        header.frame_count = 2;
+        let mut legacy = write_blob_header(&header);
+        legacy.extend_from_slice(&[0xf0, 0x0f, 0x01, 0x80]);
+        assert_eq!(legacy.len(), LEGACY_HEADER_LEN + 4);
+        assert_eq!(decode_frame(&legacy, 1, None).unwrap(), [0xf1, 0x8f]);
+
+        legacy.push(0);
+        let err = BlobDecoder::new(&legacy).err().unwrap();
+        assert!(format!("{:#}", err).starts_with("not a BA blob"), "{:#}", err);
+    }
+}
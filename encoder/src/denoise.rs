@@ -0,0 +1,288 @@
+//! `--denoise`: 이진화한 뒤 XOR delta를 만들기 전에 packed 1-bit 프레임을 거른다
+//!
+//! 노이즈가 많은 입력은 threshold 근처 픽셀이 프레임마다 깜빡여서 delta가 커진다.
+//! - `spatial` : 3x3 다수결. 주변과 다른 외딴 픽셀을 지운다 (가장자리는 화면 안의 이웃만 센다).
+//! - `temporal:N` : 픽셀이 새 값을 N 프레임 연속으로 유지해야 바뀐다 (hysteresis).
+//!
+//! 둘 다 켜면 spatial을 먼저 하고 temporal을 건다. 포스터/flipbook도 거른 프레임을 쓴다 (재생되는 화면과 같다).
+
+use anyhow::{bail, Context, Result};
+
+/// `temporal:N`에서 N의 상한 (카운터가 u16)
+pub const MAX_TEMPORAL_FRAMES: u32 = 1000;
+
+/// `--denoise` 설정. 기본은 둘 다 꺼져 있다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Denoise {
+    /// 3x3 다수결 필터
+    pub spatial: bool,
+    /// 새 값을 유지해야 하는 연속 프레임 수 (0이면 끔)
+    pub temporal: u32,
+}
+
+impl Denoise {
+    pub fn is_enabled(&self) -> bool {
+        self.spatial || self.temporal > 0
+    }
+}
+
+/// `spatial`, `temporal:3`, `spatial,temporal:3`
+impl std::str::FromStr for Denoise {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut denoise = Self::default();
+        for mode in s.split(',').map(str::trim) {
+            match mode.split_once(':') {
+                None if mode == "spatial" => denoise.spatial = true,
+                Some(("temporal", n)) => {
+                    let n: u32 = n.trim().parse().with_context(|| format!("invalid --denoise frame count '{}'", n))?;
+                    if !(2..=MAX_TEMPORAL_FRAMES).contains(&n) {
+                        bail!("--denoise temporal:N needs N between 2 and {} (got {})", MAX_TEMPORAL_FRAMES, n);
+                    }
+                    denoise.temporal = n;
+                }
+                _ => bail!("unknown --denoise mode '{}' (expected spatial, temporal:N or both separated by a comma)", mode),
+            }
+        }
+        Ok(denoise)
+    }
+}
+
+/// packed 프레임(1=black, MSB-first, 행 사이 `row_bits`)을 순서대로 거르는 상태
+pub struct Denoiser {
+    denoise: Denoise,
+    width: usize,
+    height: usize,
+    row_bits: usize,
+    /// 지금까지 내보낸 프레임 (temporal 기준)
+    shown: Vec<u8>,
+    /// 픽셀마다 `shown`과 다른 값이 연속으로 나온 프레임 수
+    held: Vec<u16>,
+    /// 거르기 전 바로 앞 프레임 (delta 밀도 비교용)
+    prev_raw: Vec<u8>,
+    /// 3x3 필터용 0/1 픽셀 버퍼
+    pixels: Vec<u8>,
+    out: Vec<u8>,
+    started: bool,
+    /// 거르기 전 프레임끼리 XOR 했을 때 켜진 비트 수 합
+    pub raw_delta_bits: u64,
+}
+
+impl Denoiser {
+    pub fn new(denoise: Denoise, width: usize, height: usize, row_align: bool) -> Self {
+        let row_bits = if row_align { width.div_ceil(8) * 8 } else { width };
+        let packed_len = (row_bits * height).div_ceil(8);
+        Self {
+            denoise,
+            width,
+            height,
+            row_bits,
+            shown: vec![0; packed_len],
+            held: if denoise.temporal > 0 { vec![0; packed_len * 8] } else { Vec::new() },
+            prev_raw: vec![0; packed_len],
+            pixels: Vec::new(),
+            out: Vec::new(),
+            started: false,
+            raw_delta_bits: 0,
+        }
+    }
+
+    /// 프레임 하나를 거른 결과. 프레임 순서대로 불러야 한다.
+    pub fn apply(&mut self, packed: &[u8]) -> &[u8] {
+        if self.started {
+            self.raw_delta_bits += delta_bits(&self.prev_raw, packed);
+        }
+        self.prev_raw.copy_from_slice(packed);
+        self.out.clear();
+        self.out.extend_from_slice(packed);
+        if self.denoise.spatial {
+            self.majority();
+        }
+        if self.denoise.temporal > 0 {
+            if self.started {
+                self.hysteresis();
+            }
+            self.shown.copy_from_slice(&self.out);
+        }
+        self.started = true;
+        &self.out
+    }
+
+    fn bit_index(&self, x: usize, y: usize) -> usize {
+        y * self.row_bits + x
+    }
+
+    /// 3x3 다수결: 이웃(자기 포함) 중 켜진 쪽이 절반을 넘으면 켜고, 딱 절반이면 그대로 둔다
+    fn majority(&mut self) {
+        let (w, h) = (self.width, self.height);
+        self.pixels.clear();
+        for y in 0..h {
+            for x in 0..w {
+                let i = self.bit_index(x, y);
+                self.pixels.push((self.out[i / 8] >> (7 - i % 8)) & 1);
+            }
+        }
+        for y in 0..h {
+            let (y0, y1) = (y.saturating_sub(1), (y + 1).min(h - 1));
+            for x in 0..w {
+                let (x0, x1) = (x.saturating_sub(1), (x + 1).min(w - 1));
+                let set: usize = (y0..=y1)
+                    .map(|yy| self.pixels[yy * w + x0..=yy * w + x1].iter().map(|&p| p as usize).sum::<usize>())
+                    .sum();
+                let n = (y1 - y0 + 1) * (x1 - x0 + 1);
+                let on = match (2 * set).cmp(&n) {
+                    std::cmp::Ordering::Greater => true,
+                    std::cmp::Ordering::Less => false,
+                    std::cmp::Ordering::Equal => self.pixels[y * w + x] == 1,
+                };
+                let i = self.bit_index(x, y);
+                let mask = 0x80 >> (i % 8);
+                if on {
+                    self.out[i / 8] |= mask;
+                } else {
+                    self.out[i / 8] &= !mask;
+                }
+            }
+        }
+    }
+
+    /// `shown`과 다른 픽셀은 `temporal` 프레임 연속으로 달라야 바뀐다. 그 전에는 `shown` 값을 내보낸다.
+    fn hysteresis(&mut self) {
+        let need = self.denoise.temporal as u16;
+        for (byte, (out, shown)) in self.out.iter_mut().zip(&self.shown).enumerate() {
+            let diff = *out ^ *shown;
+            let held = &mut self.held[byte * 8..byte * 8 + 8];
+            if diff == 0 {
+                held.fill(0);
+                continue;
+            }
+            let mut result = *shown;
+            for (bit, count) in held.iter_mut().enumerate() {
+                let mask = 0x80 >> bit;
+                if diff & mask == 0 {
+                    *count = 0;
+                    continue;
+                }
+                *count += 1;
+                if *count >= need {
+                    result ^= mask;
+                    *count = 0;
+                }
+            }
+            *out = result;
+        }
+    }
+}
+
+/// 두 packed 프레임의 XOR에서 켜진 비트 수
+pub fn delta_bits(a: &[u8], b: &[u8]) -> u64 {
+    a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones() as u64).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::CompressionMode,
+        decode::BlobDecoder,
+        encode::{encode_blob_to, EncodeOptions, EncodeStats},
+        source::RawSource,
+    };
+    use std::io::Cursor;
+
+    /// 8x3 1-bit 프레임들을 거른다 (행 정렬, 1 byte가 한 행)
+    fn filter(denoise: Denoise, frames: &[[u8; 3]]) -> Vec<[u8; 3]> {
+        let mut d = Denoiser::new(denoise, 8, 3, true);
+        frames.iter().map(|f| d.apply(f).try_into().unwrap()).collect()
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!("spatial".parse::<Denoise>().unwrap(), Denoise { spatial: true, temporal: 0 });
+        assert_eq!("spatial, temporal:3".parse::<Denoise>().unwrap(), Denoise { spatial: true, temporal: 3 });
+        assert!("temporal:1".parse::<Denoise>().is_err());
+        assert!("temporal".parse::<Denoise>().is_err());
+        assert!("median".parse::<Denoise>().is_err());
+    }
+
+    #[test]
+    fn spatial_removes_isolated_pixels_and_keeps_blocks() {
+        let spatial = Denoise { spatial: true, temporal: 0 };
+        // 외딴 픽셀 하나 → 지워진다, 구멍 하나 → 메워진다
+        assert_eq!(filter(spatial, &[[0, 0b0001_0000, 0]]), [[0, 0, 0]]);
+        assert_eq!(filter(spatial, &[[0xff, 0b1110_1111, 0xff]]), [[0xff, 0xff, 0xff]]);
+        // 위아래 가장자리에 붙은 3x3 덩어리는 남는다 (가장자리는 화면 안 이웃만 센다)
+        let block = [0b0000_1110; 3];
+        assert_eq!(filter(spatial, &[block]), [block]);
+    }
+
+    #[test]
+    fn temporal_changes_a_pixel_after_n_frames() {
+        let temporal = Denoise { spatial: false, temporal: 3 };
+        let on = [0x80, 0, 0];
+        let off = [0, 0, 0];
+        // 두 프레임만 켜졌다 꺼지면 그대로, 세 프레임 유지하면 세 번째에 켜진다
+        assert_eq!(filter(temporal, &[off, on, on, off, on, on, on, on]), [off, off, off, off, off, off, on, on]);
+        // 첫 프레임은 거르지 않는다
+        assert_eq!(filter(temporal, &[on, off, on]), [on, on, on]);
+    }
+
+    /// 32x16, 40 프레임: 움직이는 검은 막대 위에 프레임마다 다른 자리에서 깜빡이는 외딴 픽셀
+    fn noisy() -> Vec<u8> {
+        let mut seed = 0x2545_f491u32;
+        let mut frames = Vec::new();
+        for f in 0..40 {
+            let mut gray: Vec<u8> = (0..32 * 16).map(|i| if (f / 4..f / 4 + 6).contains(&(i % 32)) { 0 } else { 255 }).collect();
+            for _ in 0..12 {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                let i = (seed >> 8) as usize % gray.len();
+                gray[i] = 255 - gray[i];
+            }
+            frames.extend(gray);
+        }
+        frames
+    }
+
+    fn encode(opts: &EncodeOptions) -> (Vec<u8>, EncodeStats) {
+        let mut out = Cursor::new(Vec::new());
+        let stats = encode_blob_to(&mut RawSource::new(noisy().as_slice(), true), opts, &mut out).unwrap();
+        (out.into_inner(), stats)
+    }
+
+    /// 디코딩한 프레임끼리의 XOR 비트 수 합
+    fn decoded_delta_bits(blob: &[u8]) -> u64 {
+        let mut dec = BlobDecoder::new(blob).unwrap();
+        let mut prev: Option<Vec<u8>> = None;
+        let mut bits = 0;
+        while let Some(frame) = dec.next_frame().unwrap() {
+            if let Some(prev) = &prev {
+                bits += delta_bits(prev, frame);
+            }
+            prev = Some(frame.to_vec());
+        }
+        bits
+    }
+
+    #[test]
+    fn noisy_source_has_strictly_fewer_delta_bits() {
+        let base = EncodeOptions { width: 32, height: 16, ..EncodeOptions::default() };
+        let (plain_blob, plain) = encode(&base);
+        assert_eq!(plain.raw_delta_bits, plain.delta_bits);
+        assert_eq!(decoded_delta_bits(&plain_blob), plain.delta_bits);
+        let (plain_packed, _) = encode(&EncodeOptions { compression: CompressionMode::Deflate, ..base.clone() });
+        for mode in ["spatial", "temporal:2", "spatial,temporal:3"] {
+            let opts = EncodeOptions { denoise: mode.parse().unwrap(), ..base.clone() };
+            let (blob, stats) = encode(&opts);
+            assert!(stats.delta_bits < plain.delta_bits, "{}: {} >= {}", mode, stats.delta_bits, plain.delta_bits);
+            // 거르기 전 밀도는 필터가 없을 때와 같고, 보고한 값은 blob에 실제로 들어간 delta다
+            assert_eq!(stats.raw_delta_bits, plain.delta_bits, "{}", mode);
+            assert_eq!(decoded_delta_bits(&blob), stats.delta_bits, "{}", mode);
+            // 압축하지 않으면 레코드 크기가 같으므로 deflate 크기로 비교한다
+            let (packed, _) = encode(&EncodeOptions { compression: CompressionMode::Deflate, ..opts.clone() });
+            assert!(packed.len() < plain_packed.len(), "{}", mode);
+            #[cfg(feature = "parallel")]
+            assert_eq!(encode(&EncodeOptions { jobs: 4, ..opts.clone() }).0, blob, "{} --jobs 4", mode);
+        }
+    }
+}
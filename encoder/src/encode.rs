@@ -0,0 +1,1759 @@
+//! gray 프레임 → BA blob 인코딩
+
+use crate::{
+    bits::{align_rows, invert_pixel_bits, pack_pixels, pack_pixels_rows, reverse_bit_order, xor_bytes_inplace, BitDepth, BitOrder},
+    blob::{
+        parse_blob_header, write_blob_header, BlobHeader, ThresholdKind, CRC_LEN, MAX_FRAME_MS, MS_TIMING_ENTRY_LEN, RECORD_FRAME, RECORD_REPEAT,
+        REPEAT_RECORD_LEN, TIMESTAMP_ENTRY_LEN,
+    },
+    checkpoint::{Checkpoint, CheckpointOptions},
+    codec::{compress_blob, max_compressed_len, CompressionMode},
+    crypt::{random_iv, BlobKey, Keystream, IV_LEN},
+    denoise::{Denoise, Denoiser},
+    tiles::{encode_tile_diff, TileGrid},
+    source::{FrameSource, FrameTime},
+    threshold::{
+        binarize_pack_adaptive_into, binarize_pack_into, dither_levels, AdaptiveThreshold, DitherMode, Polarity, ThresholdMode,
+    },
+};
+#[cfg(feature = "parallel")]
+use crate::threshold::binarize_pack_into_par;
+use anyhow::{anyhow, bail, Context, Result};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::{
+    collections::VecDeque,
+    io::{Cursor, Seek, SeekFrom, Write},
+    time::Duration,
+};
+#[cfg(feature = "parallel")]
+use std::{sync::mpsc, thread};
+
+/// BA blob 인코딩 옵션
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    pub width: u16,
+    pub height: u16,
+    pub fps: f32,
+    pub threshold: ThresholdMode,
+    /// `--adaptive`: 있으면 `threshold` 대신 픽셀마다 주변 평균으로 이진화한다
+    pub adaptive: Option<AdaptiveThreshold>,
+    pub dither: DitherMode,
+    /// 픽셀당 비트 수. `Gray2`/`Gray4`는 threshold 없이 `px >> 6`/`px >> 4`로 양자화하고 v2 헤더로 쓴다
+    /// (`Gray2`는 `dither`도 쓴다).
+    pub bit_depth: BitDepth,
+    /// 흑백 반전 (`--invert`). payload는 그대로 두고 v2 헤더 플래그로만 기록한다.
+    pub invert: bool,
+    /// 켠 비트가 black인지 white인지 (`--polarity`, 1-bit만). `Auto`는 앞 프레임을 보고 인코딩 전에 고른다.
+    pub polarity: Polarity,
+    /// 저장하는 1-bit 프레임의 바이트 안 픽셀 순서 (`--bit-order`). 인코딩 루프는 MSB-first로 돌고 기록할 때만 뒤집는다.
+    pub bit_order: BitOrder,
+    pub max_frames: Option<u32>,
+    /// magic/version/CRC 없는 예전 10-byte 헤더로 쓴다
+    pub legacy_header: bool,
+    /// 프레임 단위 코덱 (legacy 헤더에서는 none만 가능)
+    pub compression: CompressionMode,
+    /// blob 끝에 CRC32 trailer를 붙인다 (legacy 헤더에서는 무시)
+    pub checksum: bool,
+    /// 바로 앞과 같은 프레임은 저장하지 않고 timing table에 재생 길이로 기록한다
+    pub drop_duplicates: bool,
+    /// `--repeat-records`: 바로 앞과 같은 프레임이 이어지면 0인 diff 대신 repeat record 하나로 쓴다.
+    /// frame_count는 재생 프레임 수 그대로다 (v2 헤더, 레이아웃은 blob.rs 참고).
+    pub repeat_records: bool,
+    /// frame1..을 바뀐 16x16 타일만 담은 레코드로 쓴다
+    pub tiles: bool,
+    /// 복원한 프레임마다 CRC32를 frame CRC table에 기록한다 (`--checksums`)
+    pub frame_checksums: bool,
+    /// 행마다 바이트 경계까지 채운다 (stride = ceil(w/8), 기본은 연속으로 packing)
+    pub row_align: bool,
+    /// threshold+pack 워커 수. 1이면 한 스레드에서 순서대로 처리한다.
+    pub jobs: usize,
+    /// PDF 썸네일(포스터)로 쓸 입력 프레임 번호 (0부터, 재생 프레임 기준)
+    pub poster_frame: u32,
+    /// `--preview-frame`: 이 입력 프레임의 이진화 전 gray 화면을 `EncodeStats::preview`로 남긴다
+    pub preview_frame: Option<u32>,
+    /// `--flipbook N`: N 프레임마다 하나씩 PDF 페이지용으로 모아둔다 (0이면 끔)
+    pub flipbook_every: u32,
+    /// flipbook으로 모을 최대 페이지 수 (넘는 프레임은 `EncodeStats::flipbook_skipped`로 센다)
+    pub flipbook_max_pages: u32,
+    /// 프레임 하나의 threshold+pack을 rayon으로 나눠서 처리한다 (`parallel` feature).
+    /// 결과는 같고, 끄면 픽셀 루프가 호출한 스레드에서만 돈다.
+    pub parallel: bool,
+    /// `--encrypt`: 헤더 뒤 payload를 이 키로 암호화한다 (v2 헤더, CRC trailer 필요)
+    pub encrypt: Option<BlobKey>,
+    /// `--denoise`: 이진화한 프레임을 XOR delta 전에 거른다 (1-bit만)
+    pub denoise: Denoise,
+    /// `--frame-durations`: 입력 프레임마다 보여줄 시간 (ms, 1..=65535). 있으면 ms duration table을 쓴다 (v2 헤더).
+    pub frame_durations_ms: Option<Vec<u32>>,
+    /// `--vfr`: 소스가 주는 프레임 시각(`FrameSource::frame_time`)을 ms timestamp table에 쓴다 (v2 헤더).
+    /// `fps`는 프레임 번호와 참고용으로만 남는다.
+    pub vfr: bool,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            height: 60,
+            fps: 30.0,
+            threshold: ThresholdMode::Fixed(128),
+            adaptive: None,
+            dither: DitherMode::None,
+            bit_depth: BitDepth::Binary,
+            invert: false,
+            polarity: Polarity::Black,
+            bit_order: BitOrder::Msb,
+            max_frames: None,
+            legacy_header: false,
+            compression: CompressionMode::None,
+            checksum: true,
+            row_align: false,
+            drop_duplicates: false,
+            repeat_records: false,
+            tiles: false,
+            frame_checksums: false,
+            jobs: 1,
+            poster_frame: 0,
+            preview_frame: None,
+            flipbook_every: 0,
+            flipbook_max_pages: DEFAULT_FLIPBOOK_MAX_PAGES,
+            parallel: cfg!(feature = "parallel"),
+            encrypt: None,
+            denoise: Denoise::default(),
+            frame_durations_ms: None,
+            vfr: false,
+        }
+    }
+}
+
+/// `--flipbook-max-pages` 기본값
+pub const DEFAULT_FLIPBOOK_MAX_PAGES: u32 = 1000;
+
+/// `--frame-durations` 파일: 한 줄에 입력 프레임 하나의 ms (`33`), 또는 `<count>x<ms>`로 같은 길이 여러 프레임
+/// (`120x33`). 빈 줄과 `#` 뒤는 무시한다.
+pub fn parse_frame_durations(text: &str) -> Result<Vec<u32>> {
+    let mut out = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (count, ms) = match line.split_once(['x', '*']) {
+            Some((count, ms)) => (count.trim().parse::<usize>().ok(), ms.trim()),
+            None => (Some(1), line),
+        };
+        let (Some(count), Ok(ms)) = (count, ms.parse::<u32>()) else {
+            bail!("line {}: expected <ms> or <count>x<ms>, got '{}'", n + 1, line);
+        };
+        if !(1..=MAX_FRAME_MS).contains(&ms) {
+            bail!("line {}: frame duration {} ms is out of range (1..={} ms)", n + 1, ms, MAX_FRAME_MS);
+        }
+        out.extend(std::iter::repeat_n(ms, count));
+    }
+    if out.is_empty() {
+        bail!("no frame durations found");
+    }
+    Ok(out)
+}
+
+/// `max_frames`가 정해져 있을 때 blob 크기의 상한 (코덱 최악의 경우 포함).
+/// 프레임 수 제한이 없거나 인코더가 거부할 옵션이면 `None`.
+/// 헤더 크기는 인코더와 같은 `blob_header`로 구하므로 v2 헤더가 되는 조건을 따로 적지 않는다.
+pub fn max_blob_len(opts: &EncodeOptions) -> Option<u64> {
+    let frames = opts.max_frames? as u64;
+    let mut header = blob_header(opts).ok()?;
+    if let Some(key) = &opts.encrypt {
+        header.set_cipher(key.cipher, [0; IV_LEN]);
+    }
+    // `--polarity auto`는 인코딩 전에 정해진다. white가 되면 v2 헤더다.
+    if matches!(opts.polarity, Polarity::Auto(_)) {
+        header.set_white_ink(true);
+    }
+    let bits = opts.width as u64 * opts.bit_depth.bits() as u64;
+    let row_bits = if opts.row_align { bits.div_ceil(8) * 8 } else { bits };
+    let packed = (row_bits * opts.height as u64).div_ceil(8);
+    // 타일 레코드는 모든 타일이 바뀌면 packed보다 조금 크다.
+    let record = if opts.tiles {
+        let grid = TileGrid { width: opts.width as usize, height: opts.height as usize, row_bits: row_bits as usize };
+        packed.max(grid.max_record_len() as u64)
+    } else {
+        packed
+    };
+    // 코덱이 있으면 최악의 압축 크기 (`max_compressed_len`). 레코드마다 u32 len.
+    let mut per_frame = match (opts.compression, opts.tiles) {
+        (CompressionMode::None, false) => packed,
+        (mode, _) => max_compressed_len(record as usize, mode) as u64 + 4,
+    };
+    let mut fixed = header.header_len() as u64;
+    if header.repeat_records {
+        per_frame += 1; // tag byte (repeat record는 frame record보다 작다)
+    }
+    if header.has_timing() {
+        per_frame += 4; // timing table entry
+    }
+    if header.has_frame_crc() {
+        per_frame += 4; // frame CRC table entry
+    }
+    if header.ms_timing {
+        per_frame += MS_TIMING_ENTRY_LEN as u64;
+    }
+    if header.timestamps {
+        per_frame += TIMESTAMP_ENTRY_LEN as u64;
+        fixed += TIMESTAMP_ENTRY_LEN as u64; // 끝 시각 한 칸
+    }
+    if header.has_crc() {
+        fixed += CRC_LEN as u64;
+    }
+    Some(fixed + frames * per_frame)
+}
+
+/// 인코딩 결과 요약
+#[derive(Debug, Clone, Default)]
+pub struct EncodeStats {
+    /// 기록한 blob 바이트 수
+    pub bytes: u64,
+    /// 입력에서 읽은 프레임 수 (재생되는 프레임 수)
+    pub frames: u32,
+    /// blob에 실제로 저장한 프레임 수 (`--drop-duplicates`, `--repeat-records`가 아니면 `frames`와 같다)
+    pub stored_frames: u32,
+    /// `poster_frame` 번째 packed 프레임 (`--row-align`과 상관없이 행 정렬, stride = ceil(w * bit_depth / 8)).
+    /// PDF 썸네일용. 입력 프레임이 그보다 적으면 `None`.
+    pub poster: Option<Vec<u8>>,
+    /// `preview_frame` 번째 입력 gray 프레임 (threshold/양자화 전, `width * height` bytes)
+    pub preview: Option<Vec<u8>>,
+    /// `flipbook_every`마다 모은 (입력 프레임 번호, bitset). bitset 배치는 `poster`와 같다.
+    pub flipbook: Vec<(u32, Vec<u8>)>,
+    /// `flipbook_max_pages`를 넘어서 버린 flipbook 프레임 수
+    pub flipbook_skipped: u32,
+    /// 바로 앞 입력 프레임과 같았던 입력 프레임 수 (diff가 전부 0, `--drop-duplicates`면 버려진 프레임)
+    pub zero_diff_frames: u32,
+    /// 저장한 프레임마다 blob에 쓴 레코드 크기 (`u32 len` prefix, 뒤따르는 repeat record 포함)
+    pub frame_bytes: Vec<u32>,
+    /// 압축/delta 없이 packed 프레임을 그대로 나열했을 때 크기 (`frames * packed_len`)
+    pub raw_bytes: u64,
+    /// 저장한 XOR delta(첫 프레임 뒤)에서 켜진 비트 수 합 (바뀐 픽셀 수)
+    pub delta_bits: u64,
+    /// `--denoise`로 거르기 전 프레임끼리의 delta 비트 수 합 (`--denoise`가 없으면 `delta_bits`와 같다)
+    pub raw_delta_bits: u64,
+    /// `--frame-durations`: ms duration table 합, `--vfr`: timestamp table의 끝 시각 (재생 시간), 없으면 `None` (fps대로)
+    pub duration_ms: Option<u64>,
+    /// 켠 비트가 white다 (`--polarity white`, 또는 auto가 고른 쪽). `poster`/`flipbook` 비트도 이 극성이다.
+    pub white_ink: bool,
+}
+
+impl EncodeStats {
+    pub fn dropped_frames(&self) -> u32 {
+        self.frames - self.stored_frames
+    }
+
+    /// blob 크기 / raw 크기 (작을수록 잘 줄었다)
+    pub fn compression_ratio(&self) -> f64 {
+        if self.raw_bytes == 0 {
+            return 0.0;
+        }
+        self.bytes as f64 / self.raw_bytes as f64
+    }
+
+    /// `--report`용 JSON. 필드 순서가 고정이라 빌드끼리 diff 하기 쉽다.
+    pub fn report_json(&self, encode_time: Duration) -> String {
+        let per_frame = if self.frames == 0 { 0.0 } else { self.bytes as f64 / self.frames as f64 };
+        let frame_bytes: Vec<String> = self.frame_bytes.iter().map(u32::to_string).collect();
+        format!(
+            "{{\n  \"frame_count\": {},\n  \"stored_frames\": {},\n  \"blob_bytes\": {},\n  \"bytes_per_frame\": {:.2},\n  \"zero_diff_frames\": {},\n  \"raw_bytes\": {},\n  \"compression_ratio\": {:.4},\n  \"encode_seconds\": {:.3},\n  \"frame_bytes\": [{}]\n}}\n",
+            self.frames,
+            self.stored_frames,
+            self.bytes,
+            per_frame,
+            self.zero_diff_frames,
+            self.raw_bytes,
+            self.compression_ratio(),
+            encode_time.as_secs_f64(),
+            frame_bytes.join(", ")
+        )
+    }
+}
+
+/// 작은 입력용: blob 전체를 메모리에 만든다.
+pub fn encode_blob<S: FrameSource + ?Sized>(source: &mut S, opts: &EncodeOptions) -> Result<Vec<u8>> {
+    let mut out = Cursor::new(Vec::new());
+    encode_blob_to(source, opts, &mut out)?;
+    Ok(out.into_inner())
+}
+
+/// blob을 `out`에 프레임 단위로 바로 기록하고, 끝나면 frame_count를 seek로 patch 한다.
+pub fn encode_blob_to<S, W>(source: &mut S, opts: &EncodeOptions, out: &mut W) -> Result<EncodeStats>
+where
+    S: FrameSource + ?Sized,
+    W: Write + Seek,
+{
+    encode_blob_with(source, opts, out, None, None)
+}
+
+/// `--checkpoint` / `--resume`: `encode_blob_to`와 같지만 `ckpt.every` 입력 프레임마다 `out`을 flush하고
+/// 인코더 상태를 `ckpt.path`에 쓴다 (`checkpoint` 모듈 참고).
+///
+/// `resume`이 있으면 `out`은 그 checkpoint를 쓴 blob 파일을 `blob_len`으로 잘라서 끝에 둔 것이고,
+/// `source`는 입력의 앞 `frames_in` 프레임을 건너뛴 것이어야 한다. `--polarity auto`는 처음 인코딩이 고른 쪽을 따른다.
+/// `--denoise`와 `--vfr`는 상태(필터 이력, 프레임 시계)를 저장하지 않으므로 받지 않는다.
+pub fn encode_blob_resumable<S, W>(
+    source: &mut S,
+    opts: &EncodeOptions,
+    out: &mut W,
+    ckpt: &CheckpointOptions,
+    resume: Option<&Checkpoint>,
+) -> Result<EncodeStats>
+where
+    S: FrameSource + ?Sized,
+    W: Write + Seek,
+{
+    if opts.denoise.is_enabled() {
+        bail!("--checkpoint cannot be combined with --denoise (the filter state is not saved)");
+    }
+    if opts.vfr {
+        bail!("--checkpoint cannot be combined with --vfr (the frame clock is not saved)");
+    }
+    let Some(cp) = resume else {
+        return encode_blob_with(source, opts, out, Some(ckpt), None);
+    };
+    if cp.fingerprint != ckpt.fingerprint {
+        bail!("checkpoint {} was written for a different input or different encoding options", ckpt.path.display());
+    }
+    let stored = parse_blob_header(&cp.header).context("checkpoint has an invalid blob header")?;
+    let resolved;
+    let opts = match opts.polarity {
+        Polarity::Auto(_) => {
+            resolved = EncodeOptions { polarity: if stored.white_ink { Polarity::White } else { Polarity::Black }, ..opts.clone() };
+            &resolved
+        }
+        _ => opts,
+    };
+    encode_blob_with(source, opts, out, Some(ckpt), Some((cp, &stored)))
+}
+
+/// `resume`은 이어서 할 checkpoint와 거기 기록된 헤더 (IV를 그대로 쓴다)
+fn encode_blob_with<S, W>(
+    source: &mut S,
+    opts: &EncodeOptions,
+    out: &mut W,
+    ckpt: Option<&CheckpointOptions>,
+    resume: Option<(&Checkpoint, &BlobHeader)>,
+) -> Result<EncodeStats>
+where
+    S: FrameSource + ?Sized,
+    W: Write + Seek,
+{
+    let (resolved, mut source) = resolve_polarity(source, opts)?;
+    let opts = resolved.as_ref().unwrap_or(opts);
+    // header (나중에 frame_count patch, 레이아웃은 blob.rs 참고)
+    let mut header = blob_header(opts)?;
+    let mut keystream = match &opts.encrypt {
+        Some(key) => {
+            let iv = match resume {
+                Some((_, stored)) => stored.iv,
+                None if key.cipher.has_iv() => random_iv()?,
+                None => [0; IV_LEN],
+            };
+            header.set_cipher(key.cipher, iv);
+            Some(Keystream::new(key.cipher, &key.key, &header.iv)?)
+        }
+        None => None,
+    };
+    let header_bytes = write_blob_header(&header); // frame_count = 0 placeholder
+    let (start, pos, crc) = match resume {
+        Some((cp, _)) => {
+            if header_bytes != cp.header {
+                bail!("the blob header in the checkpoint does not match the current encoding options");
+            }
+            let start = out
+                .stream_position()?
+                .checked_sub(cp.blob_len)
+                .context("the checkpoint blob file is shorter than the checkpoint")?;
+            let payload_len = cp.blob_len - header_bytes.len() as u64;
+            if let Some(ks) = &mut keystream {
+                ks.seek(payload_len);
+            }
+            (start, cp.blob_len, crc32fast::Hasher::new_with_initial_len(cp.payload_crc, payload_len))
+        }
+        None => {
+            let start = out.stream_position()?;
+            out.write_all(&header_bytes)?;
+            (start, header_bytes.len() as u64, crc32fast::Hasher::new())
+        }
+    };
+
+    let payload = PayloadWriter {
+        out: &mut *out,
+        crc,
+        pos,
+        codec: opts.compression,
+        record_len: header.has_record_len(),
+        tagged: header.repeat_records,
+        keystream,
+        sealed: Vec::new(),
+    };
+    let mut frames = DeltaWriter::new(payload, &header, opts);
+    if let Some(options) = ckpt {
+        frames.checkpoint = Some(Checkpointer { options: options.clone(), header: header_bytes });
+    }
+    if let Some((cp, _)) = resume {
+        frames.restore(cp)?;
+    }
+    let mut frames = run_frames(&mut source, opts, frames)?;
+    let mut stats = frames.take_stats(&header);
+    let DeltaWriter { sink: mut payload, durations, durations_ms, timestamps, frame_crcs, .. } = frames;
+
+    // timing table: 프레임 데이터 바로 뒤 (CRC에 포함)
+    if let Some(durations) = durations {
+        let offset = payload.out.stream_position()? - start;
+        header.timing_offset =
+            u32::try_from(offset).map_err(|_| anyhow!("BA blob too large for a timing table ({} bytes)", offset))?;
+        for d in durations {
+            payload.write(&d.to_le_bytes())?;
+        }
+    }
+    // ms duration table: timing table 뒤
+    if let Some(durations) = durations_ms {
+        let offset = payload.out.stream_position()? - start;
+        header.ms_timing_offset =
+            u32::try_from(offset).map_err(|_| anyhow!("BA blob too large for a duration table ({} bytes)", offset))?;
+        for d in durations {
+            payload.write(&d.to_le_bytes())?;
+        }
+    }
+    // ms timestamp table: ms duration table 자리 (둘은 같이 쓰지 않는다)
+    if let Some(timestamps) = timestamps {
+        let offset = payload.out.stream_position()? - start;
+        header.timestamps_offset =
+            u32::try_from(offset).map_err(|_| anyhow!("BA blob too large for a timestamp table ({} bytes)", offset))?;
+        for t in timestamps {
+            payload.write(&t.to_le_bytes())?;
+        }
+    }
+    // frame CRC table: 맨 뒤 (역시 CRC에 포함)
+    if let Some(crcs) = frame_crcs {
+        let offset = payload.out.stream_position()? - start;
+        header.frame_crc_offset =
+            u32::try_from(offset).map_err(|_| anyhow!("BA blob too large for a frame CRC table ({} bytes)", offset))?;
+        for c in crcs {
+            payload.write(&c.to_le_bytes())?;
+        }
+    }
+    let payload_crc = payload.crc;
+
+    // frame_count patch (repeat record blob은 재생 프레임 수)
+    header.frame_count = if header.repeat_records { stats.frames } else { stats.stored_frames };
+    let header_bytes = write_blob_header(&header);
+    let end = out.stream_position()?;
+    out.seek(SeekFrom::Start(start))?;
+    out.write_all(&header_bytes)?;
+    out.seek(SeekFrom::Start(end))?;
+
+    // CRC32 trailer = crc(header || payload)
+    if header.has_crc() {
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(&header_bytes);
+        crc.combine(&payload_crc);
+        out.write_all(&crc.finalize().to_le_bytes())?;
+    }
+    out.flush()?;
+
+    stats.bytes = out.stream_position()? - start;
+    Ok(stats)
+}
+
+/// blob을 쓰지 않고 인코딩 루프만 돌린다 (`--stats-only`). 레코드는 `sink`로 가고, `bytes`는 `sink`가 돌려준
+/// 레코드 크기에 헤더, timing/frame CRC table, CRC trailer를 더한 blob 크기다.
+pub fn encode_frames<S, K>(source: &mut S, opts: &EncodeOptions, sink: K) -> Result<EncodeStats>
+where
+    S: FrameSource + ?Sized,
+    K: FrameSink,
+{
+    let (resolved, mut source) = resolve_polarity(source, opts)?;
+    let opts = resolved.as_ref().unwrap_or(opts);
+    let mut header = blob_header(opts)?;
+    if let Some(key) = &opts.encrypt {
+        header.set_cipher(key.cipher, [0; IV_LEN]); // 헤더 크기(IV 자리)만 맞춘다
+    }
+    let mut frames = run_frames(&mut source, opts, DeltaWriter::new(sink, &header, opts))?;
+    let mut stats = frames.take_stats(&header);
+    header.frame_count = if header.repeat_records { stats.frames } else { stats.stored_frames };
+    let tables = 4 * header.frame_count as u64 * (opts.drop_duplicates as u64 + opts.frame_checksums as u64)
+        + if header.ms_timing { MS_TIMING_ENTRY_LEN as u64 * header.frame_count as u64 } else { 0 }
+        + if header.timestamps { TIMESTAMP_ENTRY_LEN as u64 * (header.frame_count as u64 + 1) } else { 0 };
+    stats.bytes = write_blob_header(&header).len() as u64
+        + stats.frame_bytes.iter().map(|&b| b as u64).sum::<u64>()
+        + tables
+        + if header.has_crc() { CRC_LEN as u64 } else { 0 };
+    Ok(stats)
+}
+
+/// 옵션을 검사하고 헤더 flags를 채운다 (frame_count, cipher/IV는 호출하는 쪽이 채운다)
+fn blob_header(opts: &EncodeOptions) -> Result<BlobHeader> {
+    let (w, h, fps) = (opts.width, opts.height, opts.fps);
+    if let Some(adaptive) = &opts.adaptive {
+        adaptive.validate()?;
+    }
+    let mut header = if opts.legacy_header {
+        if opts.compression != CompressionMode::None {
+            bail!("--legacy-header cannot record a compression mode (use --compress none)");
+        }
+        if opts.row_align {
+            bail!("--legacy-header cannot record --row-align");
+        }
+        if opts.drop_duplicates {
+            bail!("--legacy-header cannot record --drop-duplicates");
+        }
+        if opts.tiles {
+            bail!("--legacy-header cannot record --tiles");
+        }
+        if opts.frame_checksums {
+            bail!("--legacy-header cannot record --checksums");
+        }
+        if opts.bit_depth != BitDepth::Binary {
+            bail!("--legacy-header cannot record --bit-depth {}", opts.bit_depth.bits());
+        }
+        if opts.invert {
+            bail!("--legacy-header cannot record --invert");
+        }
+        if opts.polarity.is_white() {
+            bail!("--legacy-header cannot record --polarity white");
+        }
+        if opts.bit_order != BitOrder::Msb {
+            bail!("--legacy-header cannot record --bit-order lsb");
+        }
+        if opts.encrypt.is_some() {
+            bail!("--legacy-header cannot record --encrypt");
+        }
+        if opts.frame_durations_ms.is_some() {
+            bail!("--legacy-header cannot record --frame-durations");
+        }
+        if opts.vfr {
+            bail!("--legacy-header cannot record --vfr timestamps");
+        }
+        if opts.repeat_records {
+            bail!("--legacy-header cannot record --repeat-records");
+        }
+        BlobHeader::legacy(w, h, fps)
+    } else {
+        if opts.bit_depth != BitDepth::Binary {
+            if opts.tiles {
+                bail!("--tiles needs --bit-depth 1");
+            }
+            if opts.dither != DitherMode::None && opts.bit_depth == BitDepth::Gray4 {
+                bail!("--dither needs --bit-depth 1 or 2");
+            }
+            if opts.adaptive.is_some() {
+                bail!("--adaptive needs --bit-depth 1");
+            }
+            if opts.denoise.is_enabled() {
+                bail!("--denoise needs --bit-depth 1");
+            }
+            if opts.polarity != Polarity::Black {
+                bail!("--polarity needs --bit-depth 1 (gray levels keep 0=black)");
+            }
+            if opts.bit_order != BitOrder::Msb {
+                bail!("--bit-order lsb needs --bit-depth 1");
+            }
+        }
+        if opts.bit_order != BitOrder::Msb && opts.tiles {
+            bail!("--bit-order lsb cannot be combined with --tiles (tile records are cut MSB-first)");
+        }
+        if opts.encrypt.is_some() && !opts.checksum {
+            bail!("--encrypt needs the CRC trailer to detect a wrong key (drop --no-checksum)");
+        }
+        let mut header = BlobHeader::new(w, h, fps);
+        header.set_crc(opts.checksum);
+        header.set_bit_depth(opts.bit_depth);
+        header.set_invert(opts.invert);
+        header.set_white_ink(opts.polarity.is_white());
+        header.set_bit_order(opts.bit_order);
+        if let Some(durations) = &opts.frame_durations_ms {
+            if let Some(i) = durations.iter().position(|d| !(1..=MAX_FRAME_MS).contains(d)) {
+                bail!("frame duration {} ms for frame {} is out of range (1..={} ms)", durations[i], i, MAX_FRAME_MS);
+            }
+            header.set_ms_timing(true);
+        }
+        if opts.vfr {
+            if opts.frame_durations_ms.is_some() {
+                bail!("--vfr cannot be combined with --frame-durations (both set the frame times)");
+            }
+            header.set_timestamps(true);
+        }
+        if opts.repeat_records {
+            if opts.drop_duplicates {
+                bail!("--repeat-records cannot be combined with --drop-duplicates (both collapse repeated frames)");
+            }
+            if opts.frame_durations_ms.is_some() || opts.vfr {
+                bail!("--repeat-records cannot be combined with --frame-durations or --vfr (their tables have one entry per stored frame)");
+            }
+            header.set_repeat_records(true);
+        }
+        header.set_threshold(threshold_kind(opts));
+        header
+    };
+    header.set_compression(opts.compression);
+    header.set_row_align(opts.row_align);
+    header.set_timing(opts.drop_duplicates);
+    header.set_tiles(opts.tiles);
+    header.set_frame_crc(opts.frame_checksums);
+    Ok(header)
+}
+
+/// 헤더에 남길 이진화 방식 (gray blob은 이진화하지 않으므로 `Fixed`)
+fn threshold_kind(opts: &EncodeOptions) -> ThresholdKind {
+    if opts.bit_depth != BitDepth::Binary {
+        return ThresholdKind::Fixed;
+    }
+    if opts.adaptive.is_some() {
+        return ThresholdKind::Adaptive;
+    }
+    match opts.threshold {
+        ThresholdMode::Fixed(_) => ThresholdKind::Fixed,
+        ThresholdMode::Otsu => ThresholdKind::Otsu,
+        ThresholdMode::AdaptiveMean => ThresholdKind::Mean,
+    }
+}
+
+/// `--polarity auto`: 앞 N 프레임을 black 극성으로 이진화해 보고 `White`/`Black`으로 바꾼 옵션을 돌려준다.
+/// 미리 읽은 프레임은 `Prefetched`가 처음부터 다시 내준다. `Auto`가 아니면 옵션은 `None`(그대로)이다.
+fn resolve_polarity<'a, S>(source: &'a mut S, opts: &EncodeOptions) -> Result<(Option<EncodeOptions>, Prefetched<'a, S>)>
+where
+    S: FrameSource + ?Sized,
+{
+    let mut prefetched = Prefetched { inner: source, frames: VecDeque::new(), time: None };
+    let Polarity::Auto(sample) = opts.polarity else {
+        return Ok((None, prefetched));
+    };
+    if opts.bit_depth != BitDepth::Binary {
+        bail!("--polarity needs --bit-depth 1 (gray levels keep 0=black)");
+    }
+    let sample = opts.max_frames.map_or(sample, |m| m.min(sample));
+    let pixels = opts.width as u64 * opts.height as u64;
+    let (mut set_bits, mut packed) = (0u64, Vec::new());
+    for _ in 0..sample {
+        let mut gray = vec![0u8; pixels as usize];
+        if !prefetched.inner.read_frame(&mut gray)? {
+            break;
+        }
+        pack_frame_into(&gray, opts, &mut packed);
+        set_bits += packed.iter().map(|b| b.count_ones() as u64).sum::<u64>();
+        prefetched.frames.push_back((gray, prefetched.inner.frame_time()));
+    }
+    let polarity = Polarity::from_sample(set_bits, pixels * prefetched.frames.len() as u64);
+    Ok((Some(EncodeOptions { polarity, ..opts.clone() }), prefetched))
+}
+
+/// 극성을 고르려고 미리 읽은 프레임을 먼저 내주고, 그 뒤는 원래 소스를 그대로 읽는다
+struct Prefetched<'a, S: FrameSource + ?Sized> {
+    inner: &'a mut S,
+    frames: VecDeque<(Vec<u8>, Option<FrameTime>)>,
+    /// 미리 읽은 프레임을 내줄 때의 시각 (`None`이면 `inner`에 묻는다)
+    time: Option<Option<FrameTime>>,
+}
+
+impl<S: FrameSource + ?Sized> FrameSource for Prefetched<'_, S> {
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+        if let Some((gray, time)) = self.frames.pop_front() {
+            buf.copy_from_slice(&gray);
+            self.time = Some(time);
+            return Ok(true);
+        }
+        self.time = None;
+        self.inner.read_frame(buf)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.inner.finish()
+    }
+
+    fn frame_time(&self) -> Option<FrameTime> {
+        self.time.unwrap_or_else(|| self.inner.frame_time())
+    }
+}
+
+/// 프레임을 끝까지 (또는 `max_frames`까지) 읽어서 `frames`에 순서대로 넣는다
+fn run_frames<S, K>(source: &mut S, opts: &EncodeOptions, mut frames: DeltaWriter<K>) -> Result<DeltaWriter<K>>
+where
+    S: FrameSource + ?Sized,
+    K: FrameSink,
+{
+    if opts.jobs > 1 {
+        #[cfg(feature = "parallel")]
+        pack_parallel(source, opts, &mut frames)?;
+        #[cfg(not(feature = "parallel"))]
+        bail!("--jobs needs the encoder to be built with the `parallel` feature");
+    } else {
+        pack_serial(source, opts, &mut frames)?;
+    }
+    source.finish()?;
+    frames.flush_repeat()?;
+    Ok(frames)
+}
+
+/// gray 프레임 하나 → packed 프레임을 `out`에 (프레임끼리 독립이라 병렬로 돌려도 된다)
+fn pack_frame_into(gray: &[u8], opts: &EncodeOptions, out: &mut Vec<u8>) {
+    if opts.bit_depth != BitDepth::Binary {
+        let dithered;
+        let gray = if opts.dither != DitherMode::None {
+            dithered = dither_levels(gray, opts.width as usize, opts.dither);
+            &dithered
+        } else {
+            gray
+        };
+        *out = if opts.row_align {
+            pack_pixels_rows(gray, opts.width as usize, opts.bit_depth)
+        } else {
+            pack_pixels(gray, opts.bit_depth)
+        };
+        return;
+    }
+    binarize_into(gray, opts, out);
+    if opts.polarity.is_white() {
+        invert_pixel_bits(out, opts.width as usize, opts.height as usize, opts.row_align);
+    }
+}
+
+/// threshold (+ dither) → packed bitset (1=black, 0=white)
+fn binarize_into(gray: &[u8], opts: &EncodeOptions, out: &mut Vec<u8>) {
+    let width = opts.width as usize;
+    if let Some(adaptive) = &opts.adaptive {
+        return binarize_pack_adaptive_into(gray, width, &adaptive.thresholds(gray, width), opts.dither, opts.row_align, out);
+    }
+    let threshold = opts.threshold.resolve(gray);
+    #[cfg(feature = "parallel")]
+    if opts.parallel {
+        return binarize_pack_into_par(gray, width, threshold, opts.dither, opts.row_align, out);
+    }
+    binarize_pack_into(gray, width, threshold, opts.dither, opts.row_align, out);
+}
+
+fn pack_serial<S, K>(source: &mut S, opts: &EncodeOptions, frames: &mut DeltaWriter<K>) -> Result<()>
+where
+    S: FrameSource + ?Sized,
+    K: FrameSink,
+{
+    // 입력/출력 버퍼는 프레임마다 재사용한다
+    let mut frame_buf = vec![0u8; opts.width as usize * opts.height as usize];
+    let mut packed = Vec::new();
+    loop {
+        if let Some(m) = opts.max_frames {
+            if frames.frames_in >= m {
+                break;
+            }
+        }
+        if !source.read_frame(&mut frame_buf)? {
+            break;
+        }
+        pack_frame_into(&frame_buf, opts, &mut packed);
+        frames.push(&frame_buf, &packed, source.frame_time())?;
+    }
+    Ok(())
+}
+
+/// reader 스레드가 bounded channel로 raw 프레임을 넘기고, rayon pool이 threshold+pack을
+/// 한 묶음씩 병렬로 처리한다. XOR delta는 이전 프레임에 의존하므로 여기(writer 쪽)서 순서대로.
+#[cfg(feature = "parallel")]
+fn pack_parallel<S, K>(source: &mut S, opts: &EncodeOptions, frames: &mut DeltaWriter<K>) -> Result<()>
+where
+    S: FrameSource + ?Sized,
+    K: FrameSink,
+{
+    let jobs = opts.jobs;
+    let frame_sz = opts.width as usize * opts.height as usize;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .context("failed to build worker pool")?;
+
+    // channel에 최대 jobs*2 프레임 + 처리 중인 한 묶음(jobs*2)까지만 메모리에 있다.
+    // 프레임 시각(`--vfr`)은 읽은 직후에만 알 수 있어서 같이 넘긴다.
+    let (tx, rx) = mpsc::sync_channel::<(Vec<u8>, Option<FrameTime>)>(jobs * 2);
+    let (recycle_tx, recycle_rx) = mpsc::channel::<Vec<u8>>();
+    let max_frames = opts.max_frames;
+
+    thread::scope(|s| {
+        let reader = s.spawn(move || -> Result<()> {
+            let mut n: u32 = 0;
+            while max_frames.is_none_or(|m| n < m) {
+                let mut buf = recycle_rx.try_recv().unwrap_or_else(|_| vec![0u8; frame_sz]);
+                if !source.read_frame(&mut buf)? {
+                    break;
+                }
+                if tx.send((buf, source.frame_time())).is_err() {
+                    break; // writer 쪽이 에러로 먼저 끝남
+                }
+                n += 1;
+            }
+            Ok(())
+        });
+
+        let written = (|| -> Result<()> {
+            let mut batch: Vec<(Vec<u8>, Option<FrameTime>)> = Vec::with_capacity(jobs * 2);
+            while let Ok(first) = rx.recv() {
+                batch.push(first);
+                while batch.len() < jobs * 2 {
+                    match rx.try_recv() {
+                        Ok(buf) => batch.push(buf),
+                        Err(_) => break,
+                    }
+                }
+                let packed: Vec<Vec<u8>> = pool.install(|| {
+                    batch
+                        .par_iter()
+                        .map(|(gray, _)| {
+                            let mut out = Vec::new();
+                            pack_frame_into(gray, opts, &mut out);
+                            out
+                        })
+                        .collect()
+                });
+                for ((gray, time), p) in batch.iter().zip(&packed) {
+                    frames.push(gray, p, *time)?;
+                }
+                for (buf, _) in batch.drain(..) {
+                    let _ = recycle_tx.send(buf);
+                }
+            }
+            Ok(())
+        })();
+        drop(rx);
+
+        let read = reader.join().map_err(|_| anyhow!("frame reader thread panicked"))?;
+        written.and(read)
+    })
+}
+
+/// 저장할 레코드 하나 (`FrameSink`에 넘긴다)
+#[derive(Debug, Clone, Copy)]
+pub enum FrameRecord<'a> {
+    /// 저장한 프레임: keyframe 또는 XOR delta
+    Delta {
+        /// 입력 프레임 번호 (재생 시각은 `index / fps`)
+        index: u32,
+        /// 첫 프레임은 packed 프레임, 그 뒤는 XOR delta (`--tiles`면 타일 레코드). 코덱을 걸기 전이다.
+        data: &'a [u8],
+        /// delta에서 켜진 비트 수 (바뀐 픽셀 수, keyframe이면 `None`)
+        changed_bits: Option<u64>,
+    },
+    /// `--repeat-records`: 앞 프레임을 이만큼 더 보여준다 (0인 delta가 이어진 구간)
+    Repeat(u16),
+}
+
+/// keyframe/XOR delta 레코드를 받는 쪽. blob 기록(`encode_blob_to`)과
+/// `--stats-only` 집계(`DiffStats`)가 같은 인코딩 루프(threshold, `--denoise`, 중복 제거, 타일)를 쓴다.
+pub trait FrameSink {
+    /// 레코드 하나를 받고 blob에 차지하는 크기 (코덱, `u32 len` prefix 포함)를 돌려준다
+    fn write_frame(&mut self, record: &FrameRecord) -> Result<usize>;
+
+    /// `--checkpoint`: 지금까지 받은 레코드를 내보내고 (blob 길이, payload CRC32)를 돌려준다.
+    /// blob을 쓰지 않는 sink는 checkpoint를 만들 수 없다.
+    fn checkpoint(&mut self) -> Result<(u64, u32)> {
+        bail!("this frame sink cannot write checkpoints")
+    }
+}
+
+impl<K: FrameSink + ?Sized> FrameSink for &mut K {
+    fn write_frame(&mut self, record: &FrameRecord) -> Result<usize> {
+        (**self).write_frame(record)
+    }
+
+    fn checkpoint(&mut self) -> Result<(u64, u32)> {
+        (**self).checkpoint()
+    }
+}
+
+/// keyframe + XOR diff를 순서대로 만들어서 `sink`에 넘긴다.
+struct DeltaWriter<K: FrameSink> {
+    sink: K,
+    prev_packed: Vec<u8>,
+    diff: Vec<u8>,
+    /// 저장한 프레임 수
+    frame_count: u32,
+    /// 입력 프레임 수
+    frames_in: u32,
+    /// `--drop-duplicates`: 저장한 프레임마다 재생 길이 (1/fps 단위)
+    durations: Option<Vec<u32>>,
+    /// `--frame-durations`: 입력 프레임마다 받은 시간과, 저장한 프레임마다 합친 시간 (ms)
+    input_ms: Option<Vec<u32>>,
+    durations_ms: Option<Vec<u16>>,
+    /// `--vfr`: 입력 프레임 시각을 ms로 바꾸는 시계와, 저장한 프레임마다 시작 시각 (ms, 끝나면 끝 시각을 붙인다)
+    clock: Option<VfrClock>,
+    timestamps: Option<Vec<u32>>,
+    /// `--checksums`: 저장한 프레임마다 복원된 packed 프레임의 CRC32
+    frame_crcs: Option<Vec<u32>>,
+    /// `--tiles`: diff를 타일 레코드로 쓴다
+    tiles: Option<TileGrid>,
+    /// 포스터로 쓸 입력 프레임 번호와 그 프레임 (blob과 같은 packing)
+    poster_index: u32,
+    poster: Option<Vec<u8>>,
+    /// `--preview-frame`: 미리보기로 쓸 입력 프레임 번호와 그 gray 프레임
+    preview_index: Option<u32>,
+    preview: Option<Vec<u8>>,
+    /// `--flipbook`: N 프레임마다 모은 프레임
+    flipbook_every: u32,
+    flipbook_max_pages: u32,
+    flipbook: Vec<(u32, Vec<u8>)>,
+    flipbook_skipped: u32,
+    /// 앞 입력 프레임과 같았던 입력 프레임 수
+    zero_diff_frames: u32,
+    /// `--repeat-records`: 아직 쓰지 않은 repeat run (앞 프레임과 같은 입력 프레임 수)
+    repeat_records: bool,
+    repeat_run: u32,
+    /// 저장한 프레임마다 쓴 레코드 크기 (뒤따르는 repeat record 포함)
+    frame_bytes: Vec<u32>,
+    /// 저장한 delta에서 켜진 비트 수 합
+    delta_bits: u64,
+    /// `--denoise` (프레임이 들어오는 순서대로 거른다)
+    denoiser: Option<Denoiser>,
+    /// `--bit-order lsb`: 저장할 프레임을 비트 순서를 뒤집어서 담아 두는 버퍼 (포스터/flipbook은 MSB-first 그대로)
+    lsb_first: bool,
+    stored: Vec<u8>,
+    /// `--checkpoint`: `every` 입력 프레임마다 상태를 쓴다
+    checkpoint: Option<Checkpointer>,
+}
+
+/// `--checkpoint`: 상태를 쓸 곳과 처음에 쓴 blob 헤더
+struct Checkpointer {
+    options: CheckpointOptions,
+    header: Vec<u8>,
+}
+
+impl<K: FrameSink> DeltaWriter<K> {
+    fn new(sink: K, header: &BlobHeader, opts: &EncodeOptions) -> Self {
+        let packed_len = header.packed_len();
+        Self {
+            sink,
+            prev_packed: vec![0u8; packed_len],
+            diff: vec![0u8; packed_len],
+            frame_count: 0,
+            frames_in: 0,
+            durations: opts.drop_duplicates.then(Vec::new),
+            input_ms: opts.frame_durations_ms.clone(),
+            durations_ms: opts.frame_durations_ms.as_ref().map(|_| Vec::new()),
+            clock: opts.vfr.then(|| VfrClock::new(opts.fps)),
+            timestamps: opts.vfr.then(Vec::new),
+            frame_crcs: opts.frame_checksums.then(Vec::new),
+            tiles: header.is_tiled().then(|| header.tile_grid()),
+            poster_index: opts.poster_frame,
+            poster: None,
+            preview_index: opts.preview_frame,
+            preview: None,
+            flipbook_every: opts.flipbook_every,
+            flipbook_max_pages: opts.flipbook_max_pages,
+            flipbook: Vec::new(),
+            flipbook_skipped: 0,
+            zero_diff_frames: 0,
+            repeat_records: header.repeat_records,
+            repeat_run: 0,
+            frame_bytes: Vec::new(),
+            delta_bits: 0,
+            denoiser: opts
+                .denoise
+                .is_enabled()
+                .then(|| Denoiser::new(opts.denoise, opts.width as usize, opts.height as usize, opts.row_align)),
+            lsb_first: header.bit_order == BitOrder::Lsb,
+            stored: Vec::new(),
+            checkpoint: None,
+        }
+    }
+
+    /// `--resume`: checkpoint에 저장한 상태로 되돌린다 (`prev_packed`는 바이트 그대로)
+    fn restore(&mut self, cp: &Checkpoint) -> Result<()> {
+        if cp.prev_packed.len() != self.prev_packed.len() {
+            bail!("checkpoint frame is {} bytes, expected {}", cp.prev_packed.len(), self.prev_packed.len());
+        }
+        self.prev_packed.copy_from_slice(&cp.prev_packed);
+        self.frame_count = cp.frame_count;
+        self.frames_in = cp.frames_in;
+        self.durations = cp.durations.clone();
+        self.durations_ms = cp.durations_ms.clone();
+        self.frame_crcs = cp.frame_crcs.clone();
+        self.poster = cp.poster.clone();
+        self.preview = cp.preview.clone();
+        self.flipbook = cp.flipbook.clone();
+        self.flipbook_skipped = cp.flipbook_skipped;
+        self.zero_diff_frames = cp.zero_diff_frames;
+        self.repeat_run = cp.repeat_run;
+        self.frame_bytes = cp.frame_bytes.clone();
+        self.delta_bits = cp.delta_bits;
+        Ok(())
+    }
+
+    /// `sink`를 flush하고 지금 상태를 checkpoint 파일에 쓴다
+    fn save_checkpoint(&mut self) -> Result<()> {
+        let (blob_len, payload_crc) = self.sink.checkpoint()?;
+        let Some(ck) = &self.checkpoint else {
+            return Ok(());
+        };
+        Checkpoint {
+            fingerprint: ck.options.fingerprint,
+            header: ck.header.clone(),
+            blob_len,
+            payload_crc,
+            frames_in: self.frames_in,
+            frame_count: self.frame_count,
+            prev_packed: self.prev_packed.clone(),
+            durations: self.durations.clone(),
+            durations_ms: self.durations_ms.clone(),
+            frame_crcs: self.frame_crcs.clone(),
+            frame_bytes: self.frame_bytes.clone(),
+            zero_diff_frames: self.zero_diff_frames,
+            repeat_run: self.repeat_run,
+            delta_bits: self.delta_bits,
+            poster: self.poster.clone(),
+            preview: self.preview.clone(),
+            flipbook: self.flipbook.clone(),
+            flipbook_skipped: self.flipbook_skipped,
+        }
+        .write(&ck.options.path)
+    }
+
+    /// 끝난 뒤의 통계 (`bytes`는 호출하는 쪽이 채운다). 썸네일/flipbook 이미지는 항상 행 정렬이다.
+    fn take_stats(&mut self, header: &BlobHeader) -> EncodeStats {
+        // 마지막 칸: 마지막 프레임이 끝나는 시각 (전체 길이)
+        let end = match (&self.clock, &mut self.timestamps) {
+            (Some(clock), Some(timestamps)) if !timestamps.is_empty() => {
+                let end = clock.end_ms().max(*timestamps.last().unwrap_or(&0));
+                timestamps.push(end);
+                Some(end as u64)
+            }
+            _ => None,
+        };
+        let to_rows = |packed: Vec<u8>| {
+            if header.is_row_aligned() {
+                packed
+            } else {
+                align_rows(&packed, header.width as usize * header.bit_depth.bits(), header.height as usize)
+            }
+        };
+        EncodeStats {
+            bytes: 0,
+            frames: self.frames_in,
+            stored_frames: self.frame_count,
+            poster: self.poster.take().map(to_rows),
+            preview: self.preview.take(),
+            flipbook: std::mem::take(&mut self.flipbook).into_iter().map(|(i, packed)| (i, to_rows(packed))).collect(),
+            flipbook_skipped: self.flipbook_skipped,
+            zero_diff_frames: self.zero_diff_frames,
+            frame_bytes: std::mem::take(&mut self.frame_bytes),
+            raw_bytes: self.frames_in as u64 * header.packed_len() as u64,
+            delta_bits: self.delta_bits,
+            raw_delta_bits: self.denoiser.as_ref().map_or(self.delta_bits, |d| d.raw_delta_bits),
+            duration_ms: self.durations_ms.as_ref().map(|d| d.iter().map(|&ms| ms as u64).sum()).or(end),
+            white_ink: header.white_ink,
+        }
+    }
+
+    /// `gray`는 `packed`를 만든 입력 프레임 (미리보기용), `time`은 소스가 준 프레임 시각 (`--vfr`)
+    fn push(&mut self, gray: &[u8], packed: &[u8], time: Option<FrameTime>) -> Result<()> {
+        let ms = match &mut self.clock {
+            Some(clock) => Some(clock.tick(time.with_context(|| {
+                format!("--vfr needs frame timestamps, but the input gave none for frame {} (use a video file)", self.frames_in)
+            })?)?),
+            None => None,
+        };
+        let mut denoiser = self.denoiser.take();
+        let packed = match &mut denoiser {
+            Some(denoiser) => denoiser.apply(packed),
+            None => packed,
+        };
+        let mut stored = std::mem::take(&mut self.stored);
+        if self.lsb_first {
+            stored.clear();
+            stored.extend_from_slice(packed);
+            reverse_bit_order(&mut stored);
+        }
+        let result = self.push_filtered(gray, packed, if self.lsb_first { &stored } else { packed }, ms);
+        self.denoiser = denoiser;
+        self.stored = stored;
+        result?;
+        if self.checkpoint.as_ref().is_some_and(|ck| ck.options.every > 0 && self.frames_in.is_multiple_of(ck.options.every)) {
+            self.save_checkpoint()?;
+        }
+        Ok(())
+    }
+
+    /// `packed`는 포스터/flipbook용 MSB-first 프레임, `stored`는 blob에 기록할 순서의 같은 프레임
+    /// (`--bit-order lsb`가 아니면 `packed`와 같다). `start_ms`는 `--vfr`에서 이 입력 프레임이 보이기 시작하는 시각
+    fn push_filtered(&mut self, gray: &[u8], packed: &[u8], stored: &[u8], start_ms: Option<u32>) -> Result<()> {
+        if self.frames_in == self.poster_index {
+            self.poster = Some(packed.to_vec());
+        }
+        if self.preview_index == Some(self.frames_in) {
+            self.preview = Some(gray.to_vec());
+        }
+        if self.flipbook_every > 0 && self.frames_in.is_multiple_of(self.flipbook_every) {
+            if self.flipbook.len() < self.flipbook_max_pages as usize {
+                self.flipbook.push((self.frames_in, packed.to_vec()));
+            } else {
+                self.flipbook_skipped += 1;
+            }
+        }
+        // prev_packed는 마지막으로 저장한 프레임 = 바로 앞 입력 프레임 (버려진 프레임도 같은 내용)
+        let same = self.frames_in > 0 && stored == self.prev_packed.as_slice();
+        if same {
+            self.zero_diff_frames += 1;
+        }
+        let index = self.frames_in;
+        let ms = match &self.input_ms {
+            Some(list) => Some(*list.get(index as usize).with_context(|| {
+                format!("--frame-durations lists {} frames but the input has more", list.len())
+            })?),
+            None => None,
+        };
+        self.frames_in += 1;
+        if self.repeat_records && same {
+            // 앞 프레임을 한 칸 더 보여준다 (u16을 채우면 repeat record를 쓰고 새로 센다)
+            self.repeat_run += 1;
+            if self.repeat_run == u16::MAX as u32 {
+                self.flush_repeat()?;
+            }
+            if let Some(crcs) = &mut self.frame_crcs {
+                crcs.push(crc32fast::hash(stored));
+            }
+            return Ok(());
+        }
+        self.flush_repeat()?;
+        if let Some(durations) = &mut self.durations {
+            if let Some(last) = durations.last_mut() {
+                // 합친 ms가 u16을 넘으면 같은 프레임이라도 새로 저장한다 (diff는 0)
+                let last_ms = self.durations_ms.as_mut().and_then(|d| d.last_mut());
+                let fits = match (ms, &last_ms) {
+                    (Some(ms), Some(last_ms)) => **last_ms as u32 + ms <= MAX_FRAME_MS,
+                    _ => true,
+                };
+                if same && fits {
+                    *last += 1; // 앞 프레임을 한 칸 더 보여준다
+                    if let (Some(ms), Some(last_ms)) = (ms, last_ms) {
+                        *last_ms += ms as u16;
+                    }
+                    return Ok(());
+                }
+            }
+            durations.push(1);
+        }
+        if let (Some(ms), Some(durations_ms)) = (ms, &mut self.durations_ms) {
+            durations_ms.push(ms as u16);
+        }
+        // 합친 프레임은 시작 시각만 남기면 되므로 timestamp table에는 따로 할 일이 없다
+        if let (Some(t), Some(timestamps)) = (start_ms, &mut self.timestamps) {
+            timestamps.push(t);
+        }
+        let written = if self.frame_count == 0 {
+            self.sink.write_frame(&FrameRecord::Delta { index, data: stored, changed_bits: None })?
+        } else {
+            self.diff.copy_from_slice(&self.prev_packed);
+            xor_bytes_inplace(&mut self.diff, stored); // diff = prev XOR cur
+            let changed_bits = self.diff.iter().map(|b| b.count_ones() as u64).sum::<u64>();
+            self.delta_bits += changed_bits;
+            let tiled;
+            let data = match &self.tiles {
+                Some(grid) => {
+                    tiled = encode_tile_diff(&self.diff, grid)?;
+                    &tiled
+                }
+                None => &self.diff,
+            };
+            self.sink.write_frame(&FrameRecord::Delta { index, data, changed_bits: Some(changed_bits) })?
+        };
+        self.frame_bytes.push(written as u32);
+        if let Some(crcs) = &mut self.frame_crcs {
+            crcs.push(crc32fast::hash(stored));
+        }
+        self.prev_packed.copy_from_slice(stored);
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    /// `--repeat-records`: 모아 둔 repeat run을 repeat record로 쓴다 (크기는 그 앞에 저장한 프레임에 더한다)
+    fn flush_repeat(&mut self) -> Result<()> {
+        if self.repeat_run == 0 {
+            return Ok(());
+        }
+        let written = self.sink.write_frame(&FrameRecord::Repeat(self.repeat_run as u16))?;
+        if let Some(last) = self.frame_bytes.last_mut() {
+            *last += written as u32;
+        }
+        self.repeat_run = 0;
+        Ok(())
+    }
+}
+
+/// `--vfr`: 입력 프레임 시각 → 첫 프레임 기준 ms. 반올림은 절대 시각에서 하므로 오차가 쌓이지 않는다.
+struct VfrClock {
+    /// 첫 프레임의 PTS
+    origin: Option<f64>,
+    /// 마지막 입력 프레임의 시각과 그 앞 프레임과의 간격 (마지막 프레임 길이를 모를 때)
+    last: Option<FrameTime>,
+    step: Option<f64>,
+    /// 간격도 모르면 (프레임이 하나뿐) 1/fps
+    fallback_ms: f64,
+}
+
+impl VfrClock {
+    fn new(fps: f32) -> Self {
+        Self { origin: None, last: None, step: None, fallback_ms: 1000.0 / fps.max(0.01) as f64 }
+    }
+
+    /// 입력 프레임 하나의 시작 시각 (ms). PTS가 거꾸로 가면 앞 프레임 시각에 붙인다.
+    fn tick(&mut self, time: FrameTime) -> Result<u32> {
+        let origin = *self.origin.get_or_insert(time.pts_ms);
+        let pts_ms = match self.last {
+            Some(last) => {
+                let pts_ms = time.pts_ms.max(last.pts_ms);
+                self.step = Some(pts_ms - last.pts_ms).filter(|&s| s > 0.0).or(self.step);
+                pts_ms
+            }
+            None => time.pts_ms,
+        };
+        self.last = Some(FrameTime { pts_ms, ..time });
+        Self::to_ms(pts_ms - origin)
+    }
+
+    /// 마지막 입력 프레임이 끝나는 시각 (ms)
+    fn end_ms(&self) -> u32 {
+        let (Some(origin), Some(last)) = (self.origin, self.last) else {
+            return 0;
+        };
+        let length = last.duration_ms.or(self.step).unwrap_or(self.fallback_ms);
+        Self::to_ms(last.pts_ms - origin + length).unwrap_or(u32::MAX)
+    }
+
+    fn to_ms(ms: f64) -> Result<u32> {
+        let ms = ms.round();
+        if ms > u32::MAX as f64 {
+            bail!("--vfr timestamp {:.0} ms does not fit in the u32 timestamp table", ms);
+        }
+        Ok(ms as u32)
+    }
+}
+
+/// 프레임 레코드를 코덱에 맞게 쓰고, 쓴 바이트의 CRC를 누적한다.
+/// 헤더는 마지막에 바뀌므로 payload CRC만 따로 누적했다가 나중에 합친다.
+/// `--encrypt`면 CRC는 평문으로 누적하고 암호화한 사본을 쓴다.
+struct PayloadWriter<'a, W: Write> {
+    out: &'a mut W,
+    crc: crc32fast::Hasher,
+    /// blob 시작부터 지금까지 쓴 바이트 수 (헤더 포함, `--checkpoint`)
+    pos: u64,
+    codec: CompressionMode,
+    /// 레코드마다 `u32 len` prefix (코덱이 있거나 타일 레코드)
+    record_len: bool,
+    /// 레코드마다 tag byte (`--repeat-records`)
+    tagged: bool,
+    keystream: Option<Keystream>,
+    /// 암호화한 사본 (쓸 때마다 재사용)
+    sealed: Vec<u8>,
+}
+
+impl<W: Write> PayloadWriter<'_, W> {
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.crc.update(bytes);
+        match &mut self.keystream {
+            Some(ks) => {
+                self.sealed.clear();
+                self.sealed.extend_from_slice(bytes);
+                ks.apply(&mut self.sealed);
+                self.out.write_all(&self.sealed)?;
+            }
+            None => self.out.write_all(bytes)?,
+        }
+        self.pos += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+impl<W: Write> FrameSink for PayloadWriter<'_, W> {
+    /// 레코드 하나를 쓰고 쓴 바이트 수 (tag, len prefix 포함)를 돌려준다
+    fn write_frame(&mut self, record: &FrameRecord) -> Result<usize> {
+        let data = match *record {
+            FrameRecord::Delta { data, .. } => data,
+            FrameRecord::Repeat(count) => {
+                self.write(&[RECORD_REPEAT])?;
+                self.write(&count.to_le_bytes())?;
+                return Ok(REPEAT_RECORD_LEN);
+            }
+        };
+        let tag = if self.tagged {
+            self.write(&[RECORD_FRAME])?;
+            1
+        } else {
+            0
+        };
+        if !self.record_len {
+            self.write(data)?;
+            return Ok(tag + data.len());
+        }
+        let data = compress_blob(data, self.codec)?;
+        self.write(&(data.len() as u32).to_le_bytes())?;
+        self.write(&data)?;
+        Ok(tag + 4 + data.len())
+    }
+
+    fn checkpoint(&mut self) -> Result<(u64, u32)> {
+        self.out.flush()?;
+        Ok((self.pos, self.crc.clone().finalize()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decode::{blob_stats, decode_frame, verify_blob, BlobDecoder},
+        source::RawSource,
+        blob::{HEADER_LEN, V2_EXT_LEN},
+        codec::{DEFAULT_BROTLI_QUALITY, DEFAULT_ZSTD_LEVEL},
+        stats::DiffStats,
+    };
+    use std::fs::{self, OpenOptions};
+
+    /// 8x2 프레임 (0 = black): 앞 `black`개 픽셀이 검다
+    fn frame(black: usize) -> Vec<u8> {
+        (0..16).map(|i| if i < black { 0 } else { 255 }).collect()
+    }
+
+    /// 멈췄다 움직이는 9 프레임 (검은 픽셀 수): 0 0 0 1 2 2 2 2 3
+    fn stutter() -> Vec<Vec<u8>> {
+        [0, 0, 0, 1, 2, 2, 2, 2, 3].iter().map(|&n| frame(n)).collect()
+    }
+
+    fn tiny() -> EncodeOptions {
+        EncodeOptions { width: 8, height: 2, ..EncodeOptions::default() }
+    }
+
+    fn encode(frames: &[Vec<u8>], opts: &EncodeOptions) -> (Vec<u8>, EncodeStats) {
+        let mut out = Cursor::new(Vec::new());
+        let stats = encode_blob_to(&mut RawSource::new(frames.concat().as_slice(), true), opts, &mut out).unwrap();
+        (out.into_inner(), stats)
+    }
+
+    #[test]
+    fn ten_frame_hold_is_one_keyframe_and_one_repeat_record() {
+        let opts = EncodeOptions { repeat_records: true, ..tiny() };
+        let (blob, stats) = encode(&vec![frame(5); 10], &opts);
+        let header = parse_blob_header(&blob).unwrap();
+        assert!(header.repeat_records);
+        assert_eq!(header.frame_count, 10);
+        assert_eq!((stats.frames, stats.stored_frames), (10, 1));
+        let body = &blob[header.header_len()..blob.len() - CRC_LEN];
+        assert_eq!(body, [RECORD_FRAME, 0xf8, 0x00, RECORD_REPEAT, 9, 0]);
+        for i in 0..10 {
+            assert_eq!(decode_frame(&blob, i, None).unwrap(), [0xf8, 0x00], "frame {}", i);
+        }
+        assert!(decode_frame(&blob, 10, None).is_err());
+    }
+
+    #[test]
+    fn ten_frame_hold_with_drop_duplicates_is_one_stored_frame() {
+        let opts = EncodeOptions { drop_duplicates: true, ..tiny() };
+        let (blob, stats) = encode(&vec![frame(5); 10], &opts);
+        let header = parse_blob_header(&blob).unwrap();
+        assert_eq!((header.frame_count, stats.frames, stats.stored_frames), (1, 10, 1));
+        let timing = header.timing_offset as usize;
+        assert_eq!(&blob[timing..timing + 4], &10u32.to_le_bytes());
+        assert_eq!(BlobDecoder::new(&blob).unwrap().total_frames(), 10);
+    }
+
+    #[test]
+    fn repeat_records_decode_like_plain_deltas() {
+        let frames = stutter();
+        let (plain, _) = encode(&frames, &tiny());
+        for (compression, tiles) in
+            [(CompressionMode::None, false), (CompressionMode::Rle, false), (CompressionMode::Deflate, false), (CompressionMode::None, true)]
+        {
+            let opts = EncodeOptions { repeat_records: true, frame_checksums: true, compression, tiles, ..tiny() };
+            let (blob, stats) = encode(&frames, &opts);
+            assert_eq!((stats.frames, stats.stored_frames), (9, 4), "{:?} tiles={}", compression, tiles);
+            verify_blob(&blob, None).unwrap();
+            for i in 0..9 {
+                assert_eq!(decode_frame(&blob, i, None).unwrap(), decode_frame(&plain, i, None).unwrap(), "frame {}", i);
+            }
+            // --stats-only와 --blob-in도 repeat record를 같은 크기로 센다
+            let projected = DiffStats::collect(&mut RawSource::new(frames.concat().as_slice(), true), &opts).unwrap();
+            assert_eq!(projected.encode.bytes, blob.len() as u64);
+            let replay = blob_stats(&blob, None, &opts).unwrap();
+            assert_eq!((replay.frames, replay.stored_frames), (9, 4));
+            assert_eq!(replay.frame_bytes, stats.frame_bytes);
+        }
+    }
+
+    #[test]
+    fn long_hold_splits_into_u16_repeat_records() {
+        let n = u16::MAX as usize + 10;
+        let opts = EncodeOptions { width: 8, height: 1, repeat_records: true, ..EncodeOptions::default() };
+        let (blob, _) = encode(&vec![vec![0u8; 8]; n], &opts);
+        let header = parse_blob_header(&blob).unwrap();
+        assert_eq!(header.frame_count as usize, n);
+        let body = &blob[header.header_len()..blob.len() - CRC_LEN];
+        assert_eq!(body, [RECORD_FRAME, 0xff, RECORD_REPEAT, 0xff, 0xff, RECORD_REPEAT, 9, 0]);
+        let mut dec = BlobDecoder::new(&blob).unwrap();
+        let mut count = 0;
+        while dec.next_frame().unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!((count, dec.stored_frames()), (n, 1));
+    }
+
+    #[test]
+    fn bad_repeat_records_are_rejected() {
+        let opts = EncodeOptions { repeat_records: true, checksum: false, ..tiny() };
+        let (blob, _) = encode(&vec![frame(5); 10], &opts);
+        let at = parse_blob_header(&blob).unwrap().header_len();
+        for (patch_at, bytes) in [(at + 4, [0u8, 0]), (at + 4, [10, 0]), (at, [0x7f, 0xf8]), (at, [RECORD_REPEAT, 9])] {
+            let mut bad = blob.clone();
+            bad[patch_at..patch_at + 2].copy_from_slice(&bytes);
+            assert!(verify_blob(&bad, None).is_err(), "{:02x?} at {}", bytes, patch_at - at);
+        }
+    }
+
+    /// 검은 화면 300 프레임 (160x120): 0인 diff 299개 대신 repeat record 하나
+    #[test]
+    fn black_segment_shrinks_to_one_frame_record() {
+        let frames = vec![vec![0u8; 160 * 120]; 300];
+        let opts = EncodeOptions { width: 160, height: 120, ..EncodeOptions::default() };
+        let (plain, _) = encode(&frames, &opts);
+        let (repeat, _) = encode(&frames, &EncodeOptions { repeat_records: true, ..opts.clone() });
+        assert_eq!(plain.len(), HEADER_LEN + 300 * 2400 + CRC_LEN);
+        assert_eq!(repeat.len(), HEADER_LEN + V2_EXT_LEN + 1 + 2400 + REPEAT_RECORD_LEN + CRC_LEN);
+        assert!(max_blob_len(&EncodeOptions { max_frames: Some(300), repeat_records: true, ..opts }).unwrap() >= repeat.len() as u64);
+    }
+
+    /// `max_blob_len`은 v2 헤더가 되는 조건(threshold 방식, 극성, bit order 등)을 인코더와 같이 센다
+    #[test]
+    fn max_blob_len_covers_every_header_variant() {
+        let frames = wobble();
+        let base = EncodeOptions { max_frames: Some(frames.len() as u32), ..tiny() };
+        let key = |s: &str| Some(s.parse::<BlobKey>().unwrap());
+        let variants = [
+            base.clone(),
+            EncodeOptions { threshold: ThresholdMode::Otsu, ..base.clone() },
+            EncodeOptions { threshold: ThresholdMode::AdaptiveMean, ..base.clone() },
+            EncodeOptions { adaptive: Some(AdaptiveThreshold::default()), ..base.clone() },
+            EncodeOptions { polarity: Polarity::White, ..base.clone() },
+            EncodeOptions { polarity: Polarity::Auto(5), ..base.clone() },
+            EncodeOptions { bit_order: BitOrder::Lsb, ..base.clone() },
+            EncodeOptions { invert: true, bit_depth: BitDepth::Gray2, ..base.clone() },
+            EncodeOptions { repeat_records: true, frame_checksums: true, ..base.clone() },
+            EncodeOptions { drop_duplicates: true, compression: CompressionMode::Rle, row_align: true, ..base.clone() },
+            EncodeOptions { frame_durations_ms: Some(vec![40; frames.len()]), tiles: true, ..base.clone() },
+            EncodeOptions { encrypt: key("aes-ctr:000102030405060708090a0b0c0d0e0f"), threshold: ThresholdMode::Otsu, ..base.clone() },
+            EncodeOptions { encrypt: key("xor:5a"), compression: CompressionMode::Deflate, ..base.clone() },
+            EncodeOptions { compression: CompressionMode::Zstd(DEFAULT_ZSTD_LEVEL), ..base.clone() },
+            EncodeOptions { compression: CompressionMode::Brotli(DEFAULT_BROTLI_QUALITY), tiles: true, ..base.clone() },
+            EncodeOptions { legacy_header: true, ..base.clone() },
+        ];
+        for opts in variants {
+            let (blob, _) = encode(&frames, &opts);
+            let max = max_blob_len(&opts).unwrap();
+            assert!(max >= blob.len() as u64, "{} < {} for {:?}", max, blob.len(), opts);
+        }
+        // 압축이 안 되는 노이즈 프레임 (64x48): 코덱 레코드가 packed보다 커진다
+        let mut x = 0x9e37_79b9_7f4a_7c15u64;
+        let noise: Vec<Vec<u8>> = (0..8)
+            .map(|_| {
+                (0..64 * 48)
+                    .map(|_| {
+                        x ^= x << 13;
+                        x ^= x >> 7;
+                        x ^= x << 17;
+                        x as u8
+                    })
+                    .collect()
+            })
+            .collect();
+        let big = EncodeOptions { width: 64, height: 48, max_frames: Some(8), ..EncodeOptions::default() };
+        for compression in [CompressionMode::Rle, CompressionMode::Deflate, CompressionMode::Zstd(DEFAULT_ZSTD_LEVEL), CompressionMode::Brotli(DEFAULT_BROTLI_QUALITY)] {
+            for tiles in [false, true] {
+                let opts = EncodeOptions { compression, tiles, threshold: ThresholdMode::Otsu, ..big.clone() };
+                let (blob, _) = encode(&noise, &opts);
+                assert!(max_blob_len(&opts).unwrap() >= blob.len() as u64, "{:?} tiles {}", compression, tiles);
+            }
+        }
+        assert_eq!(max_blob_len(&EncodeOptions { max_frames: None, ..base.clone() }), None);
+        // 인코더가 거부할 조합
+        assert_eq!(max_blob_len(&EncodeOptions { repeat_records: true, drop_duplicates: true, ..base }), None);
+    }
+
+    #[test]
+    fn repeat_records_reject_per_frame_tables() {
+        for opts in [
+            EncodeOptions { repeat_records: true, drop_duplicates: true, ..tiny() },
+            EncodeOptions { repeat_records: true, frame_durations_ms: Some(vec![33; 9]), ..tiny() },
+            EncodeOptions { repeat_records: true, vfr: true, ..tiny() },
+            EncodeOptions { repeat_records: true, legacy_header: true, ..tiny() },
+        ] {
+            let mut out = Cursor::new(Vec::new());
+            assert!(encode_blob_to(&mut RawSource::new(stutter().concat().as_slice(), true), &opts, &mut out).is_err());
+        }
+    }
+
+    /// `--frame-durations`: ms duration table을 누적하면 입력 시간표와 같은 시각이 나온다
+    #[test]
+    fn ms_duration_table_reconstructs_cumulative_timestamps() {
+        let ms = vec![10, 20, 30, 40, 50, 60, 70, 80, 90];
+        let (blob, stats) = encode(&stutter(), &EncodeOptions { frame_durations_ms: Some(ms.clone()), ..tiny() });
+        let dec = BlobDecoder::new(&blob).unwrap();
+        assert!(dec.header().ms_timing);
+        assert_eq!(dec.durations_ms().unwrap(), [10, 20, 30, 40, 50, 60, 70, 80, 90]);
+        let (starts, end) = dec.timestamps_ms();
+        assert_eq!(starts, [0, 10, 30, 60, 100, 150, 210, 280, 360]);
+        assert_eq!((end, stats.duration_ms), (450, Some(450)));
+        assert_eq!([0, 9, 10, 359, 360, 449, 10_000].map(|t| dec.frame_at_ms(t)), [0, 0, 1, 7, 8, 8, 8]);
+
+        // --drop-duplicates는 합친 프레임의 시간을 더한다 (0 0 0 | 1 | 2 2 2 2 | 3)
+        let (blob, _) = encode(&stutter(), &EncodeOptions { frame_durations_ms: Some(ms), drop_duplicates: true, ..tiny() });
+        let dec = BlobDecoder::new(&blob).unwrap();
+        assert_eq!(dec.durations_ms().unwrap(), [60, 40, 260, 90]);
+        assert_eq!(dec.timestamps_ms(), (vec![0, 60, 100, 360], 450));
+        // 합친 프레임 안의 시각은 그 첫 재생 프레임
+        assert_eq!([59, 60, 100, 359, 360].map(|t| dec.frame_at_ms(t)), [0, 3, 4, 4, 8]);
+    }
+
+    /// 합친 시간이 u16을 넘으면 같은 프레임이라도 새로 저장하고, 끝 시각은 그대로다
+    #[test]
+    fn ms_durations_of_merged_frames_stay_within_u16() {
+        let opts = EncodeOptions { frame_durations_ms: Some(vec![30_000; 9]), drop_duplicates: true, ..tiny() };
+        let (blob, stats) = encode(&stutter(), &opts);
+        let dec = BlobDecoder::new(&blob).unwrap();
+        assert_eq!(dec.durations_ms().unwrap(), [60_000, 30_000, 30_000, 60_000, 60_000, 30_000]);
+        assert_eq!(stats.stored_frames, 6);
+        assert_eq!(dec.timestamps_ms().1, 270_000);
+        assert_eq!(dec.frame_at_ms(60_000), 2);
+
+        for bad in [vec![0; 9], vec![MAX_FRAME_MS + 1; 9], vec![33; 8]] {
+            let mut out = Cursor::new(Vec::new());
+            let opts = EncodeOptions { frame_durations_ms: Some(bad), ..tiny() };
+            assert!(encode_blob_to(&mut RawSource::new(stutter().concat().as_slice(), true), &opts, &mut out).is_err());
+        }
+    }
+
+    /// `--vfr` 테스트용 소스: 프레임마다 불규칙한 PTS를 준다 (마지막 프레임만 길이가 있다)
+    struct IrregularSource {
+        frames: Vec<Vec<u8>>,
+        pts_ms: Vec<f64>,
+        next: usize,
+    }
+
+    impl FrameSource for IrregularSource {
+        fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+            let Some(frame) = self.frames.get(self.next) else {
+                return Ok(false);
+            };
+            buf.copy_from_slice(frame);
+            self.next += 1;
+            Ok(true)
+        }
+
+        fn frame_time(&self) -> Option<FrameTime> {
+            let i = self.next.checked_sub(1)?;
+            Some(FrameTime { pts_ms: self.pts_ms[i], duration_ms: (i + 1 == self.pts_ms.len()).then_some(20.0) })
+        }
+    }
+
+    /// 600 프레임, 간격 16.68..100.1 ms (소수점 아래가 매번 남는다), PTS는 1234.5 ms에서 시작
+    fn irregular() -> IrregularSource {
+        let gaps = [33.37, 16.68, 100.1, 41.71, 16.68, 66.73, 33.37];
+        let mut t = 1234.5;
+        let pts_ms = (0..600)
+            .map(|i| {
+                let pts = t;
+                t += gaps[i % gaps.len()];
+                pts
+            })
+            .collect();
+        let wobble = wobble();
+        let frames = (0..600).map(|i| wobble[i % wobble.len()].clone()).collect();
+        IrregularSource { frames, pts_ms, next: 0 }
+    }
+
+    /// 재생 시각은 소스 PTS에서 0.5 ms 안이고, 600 프레임 뒤에도 어긋남이 쌓이지 않는다
+    #[test]
+    fn vfr_timestamps_follow_source_pts_without_drift() {
+        let source = irregular();
+        let (pts_ms, origin) = (source.pts_ms.clone(), source.pts_ms[0]);
+        let end = (pts_ms[599] - origin + 20.0).round() as u64;
+        for opts in [EncodeOptions { vfr: true, ..tiny() }, EncodeOptions { vfr: true, drop_duplicates: true, ..tiny() }] {
+            let mut out = Cursor::new(Vec::new());
+            let stats = encode_blob_to(&mut irregular(), &opts, &mut out).unwrap();
+            let blob = out.into_inner();
+            let dec = BlobDecoder::new(&blob).unwrap();
+            assert!(dec.header().timestamps);
+            assert_eq!((dec.total_frames(), stats.duration_ms), (600, Some(end)));
+            assert_eq!(stats.stored_frames < 600, opts.drop_duplicates);
+
+            let (starts, last) = dec.timestamps_ms();
+            assert_eq!(last, end);
+            // 저장된 프레임마다 시작 시각은 그 첫 재생 프레임의 PTS
+            let mut prev = None;
+            for (stored, &start) in starts.iter().enumerate() {
+                let first = dec.frame_at_ms(start);
+                let pts = pts_ms[first as usize] - origin;
+                assert!((start as f64 - pts).abs() <= 0.5, "stored {}: {} vs {:.2}", stored, start, pts);
+                if let Some(prev) = prev {
+                    assert!(first > prev && dec.frame_at_ms(start - 1) == prev, "stored {}", stored);
+                }
+                if !opts.drop_duplicates {
+                    assert_eq!(first, stored as u32);
+                }
+                prev = Some(first);
+            }
+            assert_eq!(dec.frame_at_ms(end + 1000), dec.frame_at_ms(*starts.last().unwrap()));
+
+            #[cfg(feature = "parallel")]
+            {
+                let mut par = Cursor::new(Vec::new());
+                encode_blob_to(&mut irregular(), &EncodeOptions { jobs: 4, ..opts.clone() }, &mut par).unwrap();
+                assert_eq!(par.into_inner(), blob);
+            }
+        }
+
+        // 시각을 모르는 소스는 받지 않는다
+        let mut out = Cursor::new(Vec::new());
+        let opts = EncodeOptions { vfr: true, ..tiny() };
+        assert!(encode_blob_to(&mut RawSource::new(stutter().concat().as_slice(), true), &opts, &mut out).is_err());
+    }
+
+    /// checkpoint 때 아직 쓰지 않은 repeat run이 있어도 이어서 만든 blob은 한 번에 만든 blob과 같다
+    #[test]
+    fn resume_keeps_pending_repeat_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let frames = stutter();
+        let opts = EncodeOptions { repeat_records: true, ..tiny() };
+        let ckpt = CheckpointOptions { path: dir.path().join("ckpt"), every: 2, fingerprint: Checkpoint::fingerprint("stutter") };
+        let blob_path = Checkpoint::blob_path(&ckpt.path);
+
+        // 6 프레임에서 끊긴 인코딩: 마지막 checkpoint(6)에 repeat run 1이 남아 있다
+        let mut out = fs::File::create(&blob_path).unwrap();
+        let first = EncodeOptions { max_frames: Some(6), ..opts.clone() };
+        encode_blob_resumable(&mut RawSource::new(frames.concat().as_slice(), true), &first, &mut out, &ckpt, None).unwrap();
+        let cp = Checkpoint::read(&ckpt.path).unwrap();
+        assert_eq!((cp.frames_in, cp.repeat_run), (6, 1));
+
+        let mut out = OpenOptions::new().read(true).write(true).open(&blob_path).unwrap();
+        out.set_len(cp.blob_len).unwrap();
+        out.seek(SeekFrom::End(0)).unwrap();
+        encode_blob_resumable(&mut RawSource::new(frames[6..].concat().as_slice(), true), &opts, &mut out, &ckpt, Some(&cp)).unwrap();
+        drop(out);
+        assert_eq!(fs::read(&blob_path).unwrap(), encode(&frames, &opts).0);
+    }
+
+    /// 30 프레임: 검은 픽셀 수가 들쭉날쭉 바뀐다 (바로 앞과 같은 프레임도 섞여 있다)
+    fn wobble() -> Vec<Vec<u8>> {
+        [0, 3, 3, 7, 16, 2, 2, 2, 9, 11, 5, 5, 0, 1, 14, 14, 6, 8, 8, 12, 4, 15, 3, 3, 3, 10, 13, 7, 2, 16].iter().map(|&n| frame(n)).collect()
+    }
+
+    #[cfg(feature = "parallel")]
+    mod jobs {
+        use super::*;
+        use std::{
+            io,
+            sync::{
+                atomic::{AtomicU32, Ordering},
+                Arc,
+            },
+        };
+
+        /// 가로줄 하나가 한 칸씩 내려가는 8x8 프레임 `n`개. 읽은 프레임 수를 `read`에 센다.
+        struct Bars {
+            n: u32,
+            read: Arc<AtomicU32>,
+        }
+
+        impl Bars {
+            fn new(n: u32) -> Self {
+                Self { n, read: Arc::new(AtomicU32::new(0)) }
+            }
+        }
+
+        impl FrameSource for Bars {
+            fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+                let i = self.read.load(Ordering::SeqCst);
+                if i == self.n {
+                    return Ok(false);
+                }
+                for (p, v) in buf.iter_mut().enumerate() {
+                    *v = if p / 8 == i as usize % 8 { 0 } else { 255 };
+                }
+                self.read.store(i + 1, Ordering::SeqCst);
+                Ok(true)
+            }
+        }
+
+        /// 프레임 레코드를 쓸 때마다 reader가 몇 프레임 앞서 있는지 잰다
+        struct LagProbe {
+            out: Cursor<Vec<u8>>,
+            read: Arc<AtomicU32>,
+            frame_len: usize,
+            written: u32,
+            max_lag: u32,
+        }
+
+        impl Write for LagProbe {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                // 코덱 없는 blob은 프레임 레코드 하나를 한 번에 쓴다 (헤더와 CRC는 길이가 다르다)
+                if buf.len() == self.frame_len {
+                    self.written += 1;
+                    self.max_lag = self.max_lag.max(self.read.load(Ordering::SeqCst) - self.written);
+                }
+                self.out.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.out.flush()
+            }
+        }
+
+        impl Seek for LagProbe {
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                self.out.seek(pos)
+            }
+        }
+
+        fn opts(jobs: usize) -> EncodeOptions {
+            EncodeOptions { width: 8, height: 8, jobs, ..EncodeOptions::default() }
+        }
+
+        /// `--jobs 1`은 pack_serial을 그대로 타고, 워커 수를 늘려도 바이트 단위로 같아야 한다
+        #[test]
+        fn jobs_output_matches_the_serial_path() {
+            let serial = encode_blob(&mut Bars::new(50), &opts(1)).unwrap();
+            for jobs in [2, 3, 4, 8] {
+                assert_eq!(encode_blob(&mut Bars::new(50), &opts(jobs)).unwrap(), serial, "jobs {}", jobs);
+            }
+        }
+
+        /// `--jobs`: 워커 수와 상관없이 한 스레드로 만든 blob과 바이트 단위로 같다
+        #[test]
+        fn jobs_match_serial_output() {
+            let frames = wobble();
+            let variants = [
+                tiny(),
+                EncodeOptions { compression: CompressionMode::Deflate, drop_duplicates: true, frame_checksums: true, ..tiny() },
+                EncodeOptions { tiles: true, repeat_records: true, poster_frame: 7, flipbook_every: 4, ..tiny() },
+                EncodeOptions { max_frames: Some(17), ..tiny() },
+            ];
+            for base in variants {
+                let (serial, serial_stats) = encode(&frames, &base);
+                for jobs in [2, 4, 8] {
+                    let (blob, stats) = encode(&frames, &EncodeOptions { jobs, ..base.clone() });
+                    assert_eq!(blob, serial, "jobs {} {:?}", jobs, base);
+                    assert_eq!((stats.frames, stats.stored_frames), (serial_stats.frames, serial_stats.stored_frames));
+                    assert_eq!((&stats.poster, &stats.flipbook), (&serial_stats.poster, &serial_stats.flipbook));
+                }
+            }
+        }
+
+        /// reader는 channel(jobs*2) + 처리 중인 묶음(jobs*2)보다 앞서 읽지 못한다
+        #[test]
+        fn reader_stays_within_the_channel_bound() {
+            for jobs in [1, 2, 4] {
+                let source = &mut Bars::new(400);
+                let mut probe = LagProbe { out: Cursor::new(Vec::new()), read: source.read.clone(), frame_len: 8, written: 0, max_lag: 0 };
+                let stats = encode_blob_to(source, &opts(jobs), &mut probe).unwrap();
+                assert_eq!((stats.frames, probe.written), (400, 400));
+                assert!(probe.max_lag <= 4 * jobs as u32, "jobs {}: reader ran {} frames ahead", jobs, probe.max_lag);
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! 디코드한 프레임을 이미지 파일로 쓰기 (PGM / PNG, gray8, PBM)
+
+use anyhow::{Context, Result};
+use flate2::{write::ZlibEncoder, Compression};
+use std::{fs, io::Write, path::Path};
+
+/// 확장자가 `.png`면 PNG, `.pbm`이면 1-bit PBM(P4), 그 외는 binary PGM(P5)으로 저장한다.
+pub fn write_gray_image(path: &Path, w: u16, h: u16, gray: &[u8]) -> Result<()> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let bytes = if ext.eq_ignore_ascii_case("png") {
+        encode_png(w, h, gray)?
+    } else if ext.eq_ignore_ascii_case("pbm") {
+        encode_pbm(w, h, gray)
+    } else {
+        encode_pgm(w, h, gray)
+    };
+    fs::write(path, bytes).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn encode_pgm(w: u16, h: u16, gray: &[u8]) -> Vec<u8> {
+    let mut out = format!("P5\n{} {}\n255\n", w, h).into_bytes();
+    out.extend_from_slice(gray);
+    out
+}
+
+/// binary PBM(P4). 128 미만이 black(1)이고 행마다 바이트 경계로 채운다.
+pub fn encode_pbm(w: u16, h: u16, gray: &[u8]) -> Vec<u8> {
+    let mut out = format!("P4\n{} {}\n", w, h).into_bytes();
+    for row in gray.chunks(w as usize).take(h as usize) {
+        let mut packed = vec![0u8; row.len().div_ceil(8)];
+        for (x, _) in row.iter().enumerate().filter(|(_, &v)| v < 128) {
+            packed[x / 8] |= 0x80 >> (x % 8);
+        }
+        out.extend_from_slice(&packed);
+    }
+    out
+}
+
+pub fn encode_png(w: u16, h: u16, gray: &[u8]) -> Result<Vec<u8>> {
+    fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(kind);
+        crc.update(data);
+        out.extend_from_slice(&crc.finalize().to_be_bytes());
+    }
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+
+    // IHDR: width, height, bit depth 8, color type 0 (gray)
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(w as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(h as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 0, 0, 0, 0]);
+    chunk(&mut out, b"IHDR", &ihdr);
+
+    // IDAT: 각 scanline 앞에 filter type 0
+    let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in gray.chunks(w as usize) {
+        z.write_all(&[0])?;
+        z.write_all(row)?;
+    }
+    chunk(&mut out, b"IDAT", &z.finish()?);
+    chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+}
@@ -0,0 +1,139 @@
+//! Bad Apple PDF 인코더 라이브러리
+//!
+//! - `encode_blob` / `encode_blob_to` : gray 프레임 소스 → BA blob, `encode_frames` : 같은 루프를 `FrameSink`로 (blob 없이)
+//! - `encode_blob_resumable` / `Checkpoint` : `--checkpoint`/`--resume`용 중간 상태 저장과 이어서 인코딩
+//! - `DiffStats` : `--stats-only`용 delta 밀도/코덱별 크기 보고서
+//! - `Denoise` / `Denoiser` : `--denoise`용 1-bit 프레임 필터 (3x3 다수결, temporal hysteresis)
+//! - `BlobDecoder` / `decode_frame` : BA blob → 프레임 복원 (검증용), `blob_stats` : `--blob-in`용 통계 재구성
+//! - `prepare_audio` : 오디오 입력 → AU 첨부 (ogg/opus/mp3 트랜스코딩), `prepare_concat_audio` : 입력 비디오들의 오디오 잇기
+//! - `ImageSequenceSource` : PNG/JPEG 프레임 파일 입력 (`--frames`, ffmpeg 없이)
+//! - `ChainSource` : 여러 비디오를 하나씩 ffmpeg로 디코드해서 이어 붙이기 (`--input`, `--playlist`)
+//! - `FrameTime` : `--vfr`용 프레임 PTS (ffmpeg showinfo에서 읽는다)
+//! - `probe_video` / `TargetSize` : ffprobe로 입력 크기/fps 확인, auto 값 채우기
+//! - `FrameSample` / `choose_settings` : `--max-size`용 blob 크기 추정, 해상도/fps 선택
+//! - `split_attachment` / `join_chunks` : `--chunk-size`용 BA.bin 조각 나누기/잇기
+//! - `BlobKey` / `Keystream` : `--encrypt`용 payload 암호화 (xor / aes-ctr), `encrypt_audio` : `--encrypt-audio`
+//! - `Progress` / `ProgressSource` / `CountingWriter` : 인코딩 진행 표시, `set_quiet` : `--quiet`, `set_verbose` : `--verbose`
+//! - `Manifest` : `manifest.json` 첨부 (blob/오디오 요약, SHA-256)
+//! - `build_pdf` : START 페이지 + EmbeddedFiles(BA.bin, AU.ogg, ...) PDF (`PdfVersion`: 1.4/1.5/1.7/2.0)
+//! - `PdfDocument::save` : `build_pdf` 결과 저장 (파일 첨부는 메모리에 올리지 않고 파일에서 복사)
+//! - `save_linearized` : `build_pdf` 결과를 linearized("fast web view") PDF로 저장 (`--linearize`, 실험적)
+//! - `expand_start_url` : start_url 템플릿(`{w}`, `{frames}`, `{blob_sha256:8}`, ...)을 인코딩 결과로 채우기
+//! - `verify_pdf` : 저장한 PDF를 다시 열어서 첨부/START 링크 확인
+//! - `read_attachments` : PDF에서 첨부 꺼내기 (`extract`)
+//! - `decode_frame_gray` : blob + 프레임 번호 → gray8 픽셀, `wasm` feature면 JS에 `decodeFrame`으로 내보낸다
+//!
+//! CLI(`main.rs`)는 인자 처리뿐 아니라 이 API를 엮는 순서(소스 열기, 체크포인트, 인코딩, 첨부 준비, PDF 저장과 검증)도
+//! 직접 가지고 있다 (`open_video`, `encode_video`). 라이브러리로 쓰면 그 순서는 호출하는 쪽이 정한다.
+//! 인코더, ffmpeg, PDF, 파일 입출력은 `native` feature(기본)에 있고,
+//! 끄면 blob 디코딩(`bits`, `blob`, `codec`, `crypt`, `decode`, `tiles`)만 남아서 wasm32-unknown-unknown으로 빌드된다.
+
+#[cfg(feature = "native")]
+pub mod audio;
+pub mod bits;
+pub mod blob;
+#[cfg(feature = "native")]
+pub mod budget;
+#[cfg(feature = "native")]
+pub mod checkpoint;
+#[cfg(feature = "native")]
+pub mod chunk;
+pub mod codec;
+pub mod crypt;
+pub mod decode;
+#[cfg(feature = "native")]
+pub mod denoise;
+#[cfg(feature = "native")]
+pub mod encode;
+#[cfg(feature = "native")]
+pub mod imageio;
+#[cfg(feature = "native")]
+pub mod linearize;
+#[cfg(feature = "native")]
+pub mod manifest;
+#[cfg(feature = "native")]
+pub mod pdf;
+#[cfg(feature = "native")]
+pub mod probe;
+#[cfg(feature = "native")]
+pub mod progress;
+#[cfg(feature = "native")]
+pub mod sequence;
+#[cfg(feature = "native")]
+pub mod source;
+#[cfg(feature = "native")]
+pub mod stats;
+#[cfg(feature = "native")]
+pub mod threshold;
+pub mod tiles;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "native")]
+pub mod writer;
+
+#[cfg(feature = "native")]
+pub use audio::{
+    audio_extension, detect_audio_mime, parse_bitrate, parse_mime, parse_language, parse_loudnorm_json, prepare_audio, prepare_concat_audio, track_name, AudioFormat, AudioOptions, AudioTrack,
+    LoudnormStats,
+};
+pub use bits::{
+    align_rows, invert_pixel_bits, pack_bits, pack_bits_rows, pack_pixels, pack_pixels_rows, reverse_bit_order, xor_bytes_inplace, BitDepth, BitOrder,
+};
+#[cfg(feature = "parallel")]
+pub use bits::{pack_bits_par, pack_bits_rows_par};
+pub use blob::{parse_blob_header, write_blob_header, BlobHeader, ThresholdKind};
+#[cfg(feature = "native")]
+pub use budget::{
+    choose_settings, estimate_blob_len, parse_size, BudgetChoice, FrameSample, Settings, DEFAULT_MIN_FPS, DEFAULT_MIN_WIDTH,
+};
+#[cfg(feature = "native")]
+pub use checkpoint::{Checkpoint, CheckpointOptions, DEFAULT_CHECKPOINT_EVERY};
+#[cfg(feature = "native")]
+pub use chunk::{chunk_name, join_chunks, parse_chunk_name, split_attachment, MIN_CHUNK_SIZE};
+pub use codec::{rle_decode, rle_encode, CompressionMode, DEFAULT_BROTLI_QUALITY, DEFAULT_ZSTD_LEVEL};
+#[cfg(feature = "native")]
+pub use crypt::encrypt_audio;
+pub use crypt::{decrypt_audio, is_encrypted_audio, parse_hex, BlobKey, Cipher, Keystream, AUDIO_MAGIC, IV_LEN};
+#[cfg(feature = "native")]
+pub use decode::blob_stats;
+pub use decode::{decode_frame, decode_frame_gray, unpack_frame_to_gray, unpack_to_gray, verify_blob, BlobDecoder};
+#[cfg(feature = "native")]
+pub use denoise::{Denoise, Denoiser, MAX_TEMPORAL_FRAMES};
+#[cfg(feature = "native")]
+pub use encode::{
+    encode_blob, encode_blob_resumable, encode_blob_to, encode_frames, max_blob_len, parse_frame_durations, EncodeOptions, EncodeStats, FrameRecord, FrameSink, DEFAULT_FLIPBOOK_MAX_PAGES,
+};
+#[cfg(feature = "native")]
+pub use linearize::save_linearized;
+#[cfg(feature = "native")]
+pub use manifest::{attachment_sha256, AudioManifest, Manifest, MANIFEST_NAME};
+#[cfg(feature = "native")]
+pub use pdf::{
+    build_pdf, expand_start_url, mime_for_name, normalize_start_url, parse_attachment_name, pdf_date, read_attachments, start_url_placeholders, verify_pdf, Anchor, Attachment, AttachmentData, AttachmentOptions, ButtonPlacement,
+    ButtonStyle, Chapter, FlipbookPage, OpenAction, PageSize, PageStyle, PdfDocument, PdfOptions, PdfVersion, Preview, StartUrlVars, Thumbnail, ViewerPreferences, DEFAULT_BUTTON_FILL_GRAY,
+    DEFAULT_BUTTON_FONT_SIZE, DEFAULT_BUTTON_RECT, DEFAULT_BUTTON_TEXT, DEFAULT_THUMBNAIL_RECT, KOREAN_FONTS, STANDARD_FONTS,
+    CHAPTER_DEST_PREFIX, PDF_PRODUCER, START_URL_PLACEHOLDERS,
+};
+#[cfg(feature = "native")]
+pub use probe::{
+    check_ffmpeg, detect_crop, parse_cropdetect, parse_ffmpeg_version, probe_audio, probe_video, AudioInfo, TargetSize, VideoInfo,
+    CROPDETECT_FRAMES, MIN_FFMPEG_VERSION,
+};
+#[cfg(feature = "native")]
+pub use progress::{format_duration, quiet, set_quiet, set_verbose, verbose, CountingWriter, Progress, ProgressSource};
+#[cfg(feature = "native")]
+pub use sequence::{image_dimensions, list_frames, natural_cmp, ImageSequenceSource};
+#[cfg(feature = "native")]
+pub use source::{
+    concat_list_entry, parse_pad_color, parse_timestamp, read_playlist, ChainSource, ConcatList, Crop, FfmpegOptions, FfmpegSource,
+    FrameCounts, FrameSource, FrameTime, RawSource, ScaleMode, Trim,
+};
+#[cfg(feature = "native")]
+pub use stats::{format_timestamp, DiffStats, FrameCost, PdfOverhead, StatsFormat, DENSITY_BUCKETS, STATS_TOP_FRAMES};
+#[cfg(all(feature = "native", feature = "parallel"))]
+pub use threshold::{binarize_frame_par, binarize_pack_into_par};
+#[cfg(feature = "native")]
+pub use threshold::{
+    binarize_frame, binarize_pack_adaptive_into, dither_levels, binarize_pack_into, mean_threshold, otsu_threshold, AdaptiveThreshold, DitherMode, Polarity, ThresholdMode,
+    DEFAULT_ADAPTIVE_BLOCK, DEFAULT_ADAPTIVE_OFFSET, DEFAULT_POLARITY_SAMPLE,
+};
@@ -0,0 +1,475 @@
+//! `--linearize`: "fast web view"용 linearized PDF (PDF 1.7 Annex F, 실험적)
+//!
+//! lopdf에는 linearization이 없어서 `build_pdf`가 만든 `Document`를 여기서 직접 다시 쓴다.
+//! 순서는 Annex F의 part 구분을 따른다:
+//!
+//! ```text
+//! header | linearization dict | first-page xref + trailer (/Prev → main xref)
+//! part 4: catalog, /OpenAction, /ViewerPreferences 등 문서를 여는 데 필요한 객체
+//! part 5: primary hint stream (page offset / shared object hint table)
+//! part 6: 첫 페이지 객체 (page 객체가 맨 앞)
+//! part 7: 나머지 페이지 (페이지마다 page 객체 + 그 페이지만 쓰는 객체)
+//! part 8: 첫 페이지 밖에서 둘 이상의 페이지가 같이 쓰는 객체
+//! part 9: 그 밖 (page tree, EmbeddedFiles 첨부, Info, outline, ...)
+//! main xref + trailer (startxref → first-page xref)
+//! ```
+//!
+//! 첫 페이지 section 객체는 나머지 뒤 번호를 받아서 xref가 두 subsection(`0 n`, `n+1 ...`)으로 나뉜다.
+//! hint table의 offset은 spec대로 hint stream이 없는 것처럼 센다.
+//! linearization dict와 first-page trailer의 숫자는 폭을 고정해서 한 번에 배치한다.
+//! xref는 PDF 버전과 상관없이 예전 table 형식으로 쓴다 (object stream은 쓰지 않는다).
+//! 첫 페이지(START 버튼과 포스터)가 첨부보다 앞에 오므로 URL로 여는 뷰어가
+//! BA/AU를 다 받기 전에 첫 페이지를 그릴 수 있다.
+//! 객체는 `writer::Chunk`로 직렬화하므로 파일 첨부는 여기서도 쓸 때 파일에서 복사한다.
+
+use crate::{
+    pdf::PdfDocument,
+    writer::{indirect_object, object_bytes, xref_entry, Chunk, TRAILER_XREF_KEYS},
+};
+use anyhow::{bail, Context, Result};
+use lopdf::{Dictionary, Object, ObjectId};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// linearization dict / first-page trailer의 고정 폭 숫자 자리
+const NUM_WIDTH: usize = 10;
+
+/// catalog에서 문서를 열 때 바로 필요한 key (part 4)
+const OPEN_DOCUMENT_KEYS: [&[u8]; 5] = [b"ViewerPreferences", b"PageMode", b"Threads", b"OpenAction", b"AcroForm"];
+
+/// `pdf`를 linearized PDF로 `path`에 쓴다. `pdf.doc`의 객체는 옮겨 쓰면서 비운다.
+pub fn save_linearized(pdf: &mut PdfDocument, path: &Path) -> Result<()> {
+    let doc = &mut pdf.doc;
+    let pages: Vec<ObjectId> = doc.get_pages().into_values().collect();
+    if pages.is_empty() {
+        bail!("cannot linearize a PDF without pages");
+    }
+    let root = doc.trailer.get(b"Root").and_then(Object::as_reference).context("trailer has no /Root")?;
+    let objects = std::mem::take(&mut doc.objects);
+    let plan = Plan::new(&objects, root, &pages);
+
+    // 번호: part 7/8/9가 1..=n, 그 뒤 linearization dict, part 4, hint stream, part 6
+    let rest: Vec<ObjectId> = plan.part7.iter().flatten().chain(&plan.part8).chain(&plan.part9).copied().collect();
+    let mut renumber = HashMap::new();
+    for (i, &id) in rest.iter().enumerate() {
+        renumber.insert(id, (i as u32 + 1, 0));
+    }
+    let first_section_start = rest.len() as u32 + 1;
+    let lin_num = first_section_start;
+    for (i, &id) in plan.part4.iter().enumerate() {
+        renumber.insert(id, (lin_num + 1 + i as u32, 0));
+    }
+    let hint_num = lin_num + 1 + plan.part4.len() as u32;
+    for (i, &id) in plan.part6.iter().enumerate() {
+        renumber.insert(id, (hint_num + 1 + i as u32, 0));
+    }
+    let size = hint_num + 1 + plan.part6.len() as u32;
+
+    let serialize = |ids: &[ObjectId]| -> Result<Vec<Chunk>> {
+        ids.iter().map(|id| Chunk::new(renumber[id].0, renumbered(&objects[id], &renumber), pdf.files.get(id))).collect()
+    };
+    let part4 = serialize(&plan.part4)?;
+    let part6 = serialize(&plan.part6)?;
+    let part7 = plan.part7.iter().map(|group| serialize(group)).collect::<Result<Vec<_>>>()?;
+    let part8 = serialize(&plan.part8)?;
+    let part9 = serialize(&plan.part9)?;
+    let contents = plan.content_ranges(&objects, &pages, &part6, &part7);
+    drop(objects);
+
+    // 앞부분(header, linearization dict, first-page xref/trailer)은 고정 폭이라 값 없이 길이를 먼저 안다
+    let mut header = format!("%PDF-{}\n", doc.version).into_bytes();
+    header.extend_from_slice(b"%\xE2\xE3\xCF\xD3\n");
+    let mut trailer = Dictionary::new();
+    for (key, value) in doc.trailer.iter().filter(|(k, _)| !TRAILER_XREF_KEYS.contains(&k.as_slice())) {
+        trailer.set(key.clone(), renumbered(value, &renumber));
+    }
+    // `<< ... >>` 안쪽만 (앞에 /Size, 뒤에 /Prev를 고정 폭으로 붙인다)
+    let trailer = object_bytes(Object::Dictionary(trailer))?;
+    let trailer_entries = &trailer[2..trailer.len() - 2];
+    let first_count = size - first_section_start;
+    let lin_dict = |l: usize, h: [usize; 2], e: usize, t: usize| {
+        format!(
+            "{} 0 obj\n<< /Linearized 1 /L {:>w$} /H [ {:>w$} {:>w$} ] /O {} /E {:>w$} /N {} /T {:>w$} >>\nendobj\n",
+            lin_num,
+            l,
+            h[0],
+            h[1],
+            renumber[&pages[0]].0,
+            e,
+            pages.len(),
+            t,
+            w = NUM_WIDTH
+        )
+        .into_bytes()
+    };
+    let first_xref = |offsets: &[usize], prev: usize| {
+        let mut out = format!("xref\n{} {}\n", first_section_start, first_count).into_bytes();
+        for &off in offsets {
+            out.extend_from_slice(xref_entry(off as u64).as_bytes());
+        }
+        out.extend_from_slice(format!("trailer\n<< /Size {} ", size).as_bytes());
+        out.extend_from_slice(trailer_entries);
+        out.extend_from_slice(format!(" /Prev {:>w$} >>\nstartxref\n0\n%%EOF\n", prev, w = NUM_WIDTH).as_bytes());
+        out
+    };
+    let lin_len = lin_dict(0, [0, 0], 0, 0).len();
+    let first_xref_offset = header.len() + lin_len;
+    let part4_offset = first_xref_offset + first_xref(&vec![0; first_count as usize], 0).len();
+    let part4_offsets = offsets_from(part4_offset, &part4);
+    let hint_offset = part4_offset + total_len(&part4);
+
+    // hint stream이 없다고 치고 센 offset (hint table에 들어가는 값)
+    let part6_offsets = offsets_from(hint_offset, &part6);
+    let mut cursor = hint_offset + total_len(&part6);
+    let part7_offsets: Vec<Vec<usize>> = part7
+        .iter()
+        .map(|group| {
+            let offsets = offsets_from(cursor, group);
+            cursor += total_len(group);
+            offsets
+        })
+        .collect();
+    let part8_offsets = offsets_from(cursor, &part8);
+    cursor += total_len(&part8);
+    let part9_offsets = offsets_from(cursor, &part9);
+    cursor += total_len(&part9);
+
+    let hints = plan.hint_stream(&part6, &part6_offsets, &part7, &part8, &part8_offsets, &contents, &renumber);
+    let hint = indirect_object(hint_num, hints)?;
+    let shift = hint.len();
+    let real = |offsets: &[usize]| offsets.iter().map(|o| o + shift).collect::<Vec<_>>();
+    let first_page_end = hint_offset + shift + total_len(&part6);
+    let main_xref_offset = cursor + shift;
+
+    // main xref: 0..=n (part 7/8/9)
+    let mut main_xref = format!("xref\n0 {}\n", rest.len() + 1).into_bytes();
+    let t = main_xref_offset + main_xref.len() - 1;
+    main_xref.extend_from_slice(b"0000000000 65535 f \n");
+    for off in part7_offsets.iter().flatten().chain(&part8_offsets).chain(&part9_offsets) {
+        main_xref.extend_from_slice(xref_entry((off + shift) as u64).as_bytes());
+    }
+    main_xref.extend_from_slice(
+        format!("trailer\n<< /Size {} >>\nstartxref\n{}\n%%EOF\n", rest.len() + 1, first_xref_offset).as_bytes(),
+    );
+    let file_len = main_xref_offset + main_xref.len();
+
+    let first_offsets: Vec<usize> = std::iter::once(header.len())
+        .chain(part4_offsets)
+        .chain(std::iter::once(hint_offset))
+        .chain(real(&part6_offsets))
+        .collect();
+    let lin = lin_dict(file_len, [hint_offset, shift], first_page_end, t);
+    let first = first_xref(&first_offsets, main_xref_offset);
+    debug_assert_eq!(lin.len(), lin_len);
+
+    let mut out = BufWriter::new(File::create(path).with_context(|| format!("failed to create {}", path.display()))?);
+    for bytes in [&header, &lin, &first] {
+        out.write_all(bytes)?;
+    }
+    for chunk in &part4 {
+        chunk.write_to(&mut out)?;
+    }
+    out.write_all(&hint)?;
+    for chunk in part6.iter().chain(part7.iter().flatten()).chain(&part8).chain(&part9) {
+        chunk.write_to(&mut out)?;
+    }
+    out.write_all(&main_xref)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// 객체를 어느 part에 둘지
+struct Plan {
+    part4: Vec<ObjectId>,
+    /// page 객체가 맨 앞
+    part6: Vec<ObjectId>,
+    /// 페이지 2..마다 page 객체 + 그 페이지만 쓰는 객체
+    part7: Vec<Vec<ObjectId>>,
+    part8: Vec<ObjectId>,
+    part9: Vec<ObjectId>,
+    /// 페이지 2..가 참조하는 shared object (part 6이나 8에 있는 것)
+    shared_refs: Vec<Vec<ObjectId>>,
+}
+
+impl Plan {
+    fn new(objects: &std::collections::BTreeMap<ObjectId, Object>, root: ObjectId, pages: &[ObjectId]) -> Self {
+        let is_page_node = |id: &ObjectId| {
+            objects
+                .get(id)
+                .and_then(|o| o.as_dict().ok())
+                .and_then(|d| d.get(b"Type").and_then(Object::as_name).ok())
+                .is_some_and(|t| t == b"Page" || t == b"Pages")
+        };
+        // page tree를 따라 다른 페이지로 넘어가지 않는 참조 closure (BFS 순서)
+        let closure = |start: Vec<ObjectId>, skip: &HashSet<ObjectId>| {
+            let mut seen: HashSet<ObjectId> = start.iter().copied().collect();
+            let mut queue: VecDeque<ObjectId> = start.into();
+            let mut out = Vec::new();
+            while let Some(id) = queue.pop_front() {
+                let Some(obj) = objects.get(&id) else { continue };
+                out.push(id);
+                let mut refs = Vec::new();
+                collect_refs(obj, &mut refs);
+                for r in refs {
+                    if objects.contains_key(&r) && !skip.contains(&r) && !is_page_node(&r) && seen.insert(r) {
+                        queue.push_back(r);
+                    }
+                }
+            }
+            out
+        };
+
+        let catalog = objects.get(&root).and_then(|o| o.as_dict().ok());
+        let open_refs = OPEN_DOCUMENT_KEYS
+            .iter()
+            .filter_map(|k| catalog.and_then(|c| c.get(k).ok()))
+            .flat_map(|v| {
+                let mut refs = Vec::new();
+                collect_refs(v, &mut refs);
+                refs
+            })
+            .filter(|r| !is_page_node(r));
+        // catalog 자신은 넣되 /Pages, /Names 같은 나머지 key는 따라가지 않는다
+        let part4: Vec<ObjectId> =
+            std::iter::once(root).chain(closure(open_refs.filter(|r| *r != root).collect(), &HashSet::from([root]))).collect();
+        let part4_set: HashSet<ObjectId> = part4.iter().copied().collect();
+
+        let page_objects: Vec<Vec<ObjectId>> = pages.iter().map(|&p| closure(vec![p], &part4_set)).collect();
+        let mut users: HashMap<ObjectId, usize> = HashMap::new();
+        for objs in &page_objects {
+            for id in objs {
+                *users.entry(*id).or_default() += 1;
+            }
+        }
+        let part6 = page_objects[0].clone();
+        let part6_set: HashSet<ObjectId> = part6.iter().copied().collect();
+        let mut part7 = Vec::new();
+        let mut part8 = Vec::new();
+        let mut part8_set = HashSet::new();
+        for objs in &page_objects[1..] {
+            let mut group = Vec::new();
+            for id in objs.iter().filter(|id| !part6_set.contains(id)) {
+                if users[id] == 1 {
+                    group.push(*id);
+                } else if part8_set.insert(*id) {
+                    part8.push(*id);
+                }
+            }
+            part7.push(group);
+        }
+        let shared_refs = page_objects[1..]
+            .iter()
+            .map(|objs| objs.iter().filter(|id| part6_set.contains(id) || part8_set.contains(id)).copied().collect())
+            .collect();
+        let placed: HashSet<ObjectId> =
+            part4.iter().chain(&part6).chain(part7.iter().flatten()).chain(&part8).copied().collect();
+        let part9 = objects.keys().filter(|id| !placed.contains(id)).copied().collect();
+        Self { part4, part6, part7, part8, part9, shared_refs }
+    }
+
+    /// 페이지마다 `/Contents` stream의 (페이지 첫 객체부터의 offset, 길이). 길이는 `N 0 obj`부터 `endobj`까지다.
+    /// content stream이 여러 개면 첫 stream의 offset과 길이 합이다. 페이지 section(part 6/7) 밖에 있는
+    /// content stream(다른 페이지와 같이 쓰는 경우)은 페이지 기준 offset이 없으므로 (0, 0)으로 둔다.
+    fn content_ranges(
+        &self,
+        objects: &std::collections::BTreeMap<ObjectId, Object>,
+        pages: &[ObjectId],
+        part6: &[Chunk],
+        part7: &[Vec<Chunk>],
+    ) -> Vec<(u64, u64)> {
+        let groups = std::iter::once((&self.part6, part6)).chain(self.part7.iter().zip(part7.iter().map(Vec::as_slice)));
+        pages
+            .iter()
+            .zip(groups)
+            .map(|(page, (ids, bytes))| {
+                let mut refs = Vec::new();
+                if let Some(contents) = objects.get(page).and_then(|o| o.as_dict().ok()).and_then(|d| d.get(b"Contents").ok()) {
+                    collect_refs(contents, &mut refs);
+                }
+                let found: Option<Vec<usize>> = refs.iter().map(|r| ids.iter().position(|id| id == r)).collect();
+                match found {
+                    Some(found) if !found.is_empty() => {
+                        let first = *found.iter().min().unwrap();
+                        let offset = total_len(&bytes[..first]) as u64;
+                        (offset, found.iter().map(|&i| bytes[i].len() as u64).sum())
+                    }
+                    _ => (0, 0),
+                }
+            })
+            .collect()
+    }
+
+    /// primary hint stream: page offset hint table (Table F.3/F.4) + shared object hint table (Table F.5/F.6).
+    /// group은 객체 하나씩이다.
+    #[allow(clippy::too_many_arguments)]
+    fn hint_stream(
+        &self,
+        part6: &[Chunk],
+        part6_offsets: &[usize],
+        part7: &[Vec<Chunk>],
+        part8: &[Chunk],
+        part8_offsets: &[usize],
+        contents: &[(u64, u64)],
+        renumber: &HashMap<ObjectId, ObjectId>,
+    ) -> Object {
+        let nobjects: Vec<u64> = std::iter::once(part6.len()).chain(part7.iter().map(Vec::len)).map(|n| n as u64).collect();
+        let lengths: Vec<u64> =
+            std::iter::once(total_len(part6)).chain(part7.iter().map(|g| total_len(g))).map(|n| n as u64).collect();
+        let shared_index: HashMap<ObjectId, u64> = self
+            .part6
+            .iter()
+            .chain(&self.part8)
+            .enumerate()
+            .map(|(i, id)| (*id, i as u64))
+            .collect();
+        let shared: Vec<Vec<u64>> = std::iter::once(Vec::new())
+            .chain(self.shared_refs.iter().map(|refs| refs.iter().map(|id| shared_index[id]).collect()))
+            .collect();
+
+        let (min_objects, max_objects) = min_max(&nobjects);
+        let (min_len, max_len) = min_max(&lengths);
+        let content_offsets: Vec<u64> = contents.iter().map(|c| c.0).collect();
+        let content_lengths: Vec<u64> = contents.iter().map(|c| c.1).collect();
+        let (min_content_offset, max_content_offset) = min_max(&content_offsets);
+        let (min_content_len, max_content_len) = min_max(&content_lengths);
+        let max_shared = shared.iter().map(|s| s.len() as u64).max().unwrap_or(0);
+        let max_identifier = shared.iter().flatten().copied().max().unwrap_or(0);
+        let mut bits = BitWriter::default();
+        bits.write(min_objects, 32);
+        bits.write(part6_offsets[0] as u64, 32);
+        bits.write(nbits(max_objects - min_objects) as u64, 16);
+        bits.write(min_len, 32);
+        bits.write(nbits(max_len - min_len) as u64, 16);
+        bits.write(min_content_offset, 32);
+        bits.write(nbits(max_content_offset - min_content_offset) as u64, 16);
+        bits.write(min_content_len, 32);
+        bits.write(nbits(max_content_len - min_content_len) as u64, 16);
+        bits.write(nbits(max_shared) as u64, 16);
+        bits.write(nbits(max_identifier) as u64, 16);
+        bits.write(0, 16); // fractional position numerator
+        bits.write(1, 16);
+        bits.items(nobjects.iter().map(|n| n - min_objects), nbits(max_objects - min_objects));
+        bits.items(lengths.iter().map(|n| n - min_len), nbits(max_len - min_len));
+        bits.items(shared.iter().map(|s| s.len() as u64), nbits(max_shared));
+        bits.items(shared.iter().flatten().copied(), nbits(max_identifier));
+        bits.items(std::iter::empty(), 0); // fractional position numerator (0 bits)
+        bits.items(
+            content_offsets.iter().map(|n| n - min_content_offset),
+            nbits(max_content_offset - min_content_offset),
+        );
+        bits.items(
+            content_lengths.iter().map(|n| n - min_content_len),
+            nbits(max_content_len - min_content_len),
+        );
+
+        let shared_offset = bits.bytes.len();
+        let group_lengths: Vec<u64> = part6.iter().chain(part8).map(|o| o.len() as u64).collect();
+        let (min_group, max_group) = min_max(&group_lengths);
+        let (first_shared, first_shared_offset) = match (self.part8.first(), part8_offsets.first()) {
+            (Some(id), Some(&off)) => (renumber[id].0 as u64, off as u64),
+            _ => (0, 0),
+        };
+        bits.write(first_shared, 32);
+        bits.write(first_shared_offset, 32);
+        bits.write(part6.len() as u64, 32);
+        bits.write(group_lengths.len() as u64, 32);
+        bits.write(0, 16); // 객체 수 - 1 (group마다 하나)
+        bits.write(min_group, 32);
+        bits.write(nbits(max_group - min_group) as u64, 16);
+        bits.items(group_lengths.iter().map(|n| n - min_group), nbits(max_group - min_group));
+        bits.items(group_lengths.iter().map(|_| 0), 1); // MD5 없음
+
+        let mut stream = lopdf::Stream::new(lopdf::dictionary! { "S" => shared_offset as i64 }, bits.bytes);
+        stream.allows_compression = false;
+        Object::Stream(stream)
+    }
+}
+
+fn min_max(values: &[u64]) -> (u64, u64) {
+    (values.iter().copied().min().unwrap_or(0), values.iter().copied().max().unwrap_or(0))
+}
+
+/// `value`를 나타내는 데 필요한 비트 수 (0이면 0)
+fn nbits(value: u64) -> u32 {
+    u64::BITS - value.leading_zeros()
+}
+
+/// hint table용 big-endian 비트 쓰기
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// 마지막 바이트에 이미 쓴 비트 수 (0이면 새 바이트부터)
+    used: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            if self.used == 0 {
+                self.bytes.push(0);
+            }
+            let bit = ((value >> i) & 1) as u8;
+            *self.bytes.last_mut().unwrap() |= bit << (7 - self.used);
+            self.used = (self.used + 1) % 8;
+        }
+    }
+
+    /// 항목 하나의 값을 페이지(group) 순서로 쓰고 다음 항목은 바이트 경계에서 시작한다
+    fn items(&mut self, values: impl Iterator<Item = u64>, bits: u32) {
+        for v in values {
+            self.write(v, bits);
+        }
+        self.used = 0;
+    }
+}
+
+fn offsets_from(start: usize, objects: &[Chunk]) -> Vec<usize> {
+    objects
+        .iter()
+        .scan(start, |off, o| {
+            let here = *off;
+            *off += o.len();
+            Some(here)
+        })
+        .collect()
+}
+
+fn total_len(objects: &[Chunk]) -> usize {
+    objects.iter().map(Chunk::len).sum()
+}
+
+fn collect_refs(obj: &Object, out: &mut Vec<ObjectId>) {
+    match obj {
+        Object::Reference(id) => out.push(*id),
+        Object::Array(items) => items.iter().for_each(|o| collect_refs(o, out)),
+        Object::Dictionary(dict) => dict.iter().for_each(|(_, o)| collect_refs(o, out)),
+        Object::Stream(stream) => stream.dict.iter().for_each(|(_, o)| collect_refs(o, out)),
+        _ => {}
+    }
+}
+
+/// 참조를 새 번호로 바꾼 사본 (없는 객체를 가리키는 참조는 null)
+fn renumbered(obj: &Object, map: &HashMap<ObjectId, ObjectId>) -> Object {
+    let dict = |d: &Dictionary| {
+        let mut out = Dictionary::new();
+        for (k, v) in d.iter() {
+            out.set(k.clone(), renumbered(v, map));
+        }
+        out
+    };
+    match obj {
+        Object::Reference(id) => map.get(id).map_or(Object::Null, |&new| Object::Reference(new)),
+        Object::Array(items) => Object::Array(items.iter().map(|o| renumbered(o, map)).collect()),
+        Object::Dictionary(d) => Object::Dictionary(dict(d)),
+        Object::Stream(s) => {
+            let mut s = s.clone();
+            s.dict = dict(&s.dict);
+            Object::Stream(s)
+        }
+        other => other.clone(),
+    }
+}
@@ -1,345 +1,2093 @@
 use anyhow::{bail, Context, Result};
-use lopdf::{dictionary, Dictionary, Document, Object, Stream};
+use badapple_encoder::{
+    ChainSource, FrameCounts, Manifest, read_playlist, verify_pdf, read_attachments, AttachmentData,
+    build_pdf, check_ffmpeg, decode_frame_gray, parse_bitrate, prepare_audio, prepare_concat_audio, track_name, parse_language, AudioTrack, AudioFormat, AudioOptions, encode_blob_to, encode_blob_resumable, Checkpoint, CheckpointOptions, DEFAULT_CHECKPOINT_EVERY, max_blob_len, parse_frame_durations, imageio::{encode_pbm, encode_pgm, write_gray_image}, BlobDecoder, parse_blob_header, verify_blob,
+    mime_for_name, parse_attachment_name, parse_mime, parse_chunk_name, parse_size, split_attachment, join_chunks, MIN_CHUNK_SIZE, probe_video, choose_settings, FrameSample, Settings, DEFAULT_MIN_FPS, DEFAULT_MIN_WIDTH, Attachment, EncodeOptions, FfmpegOptions, FfmpegSource, FrameSource, ScaleMode, parse_pad_color, RawSource, ImageSequenceSource, list_frames, image_dimensions, Trim, Crop, detect_crop, VideoInfo, parse_timestamp, FlipbookPage, OpenAction, PdfOptions, PdfVersion, ViewerPreferences, Preview, TargetSize, Thumbnail, DEFAULT_BUTTON_RECT, PageStyle, PageSize, ButtonPlacement, Anchor, Chapter, ThresholdMode, AdaptiveThreshold, DEFAULT_ADAPTIVE_BLOCK, DEFAULT_ADAPTIVE_OFFSET, DitherMode, Polarity, BitOrder, CompressionMode, DiffStats, PdfOverhead, StatsFormat, BlobKey, encrypt_audio, is_encrypted_audio, decrypt_audio, parse_hex, pdf_date, save_linearized, normalize_start_url, expand_start_url, start_url_placeholders, StartUrlVars, attachment_sha256, blob_stats, EncodeStats,
+    BlobHeader, format_duration, quiet, set_quiet, set_verbose, CountingWriter, Progress, ProgressSource,
+};
 use std::{
-    env,
-    fs,
-    io::Read,
-    path::PathBuf,
-    process::{Command, Stdio},
+    env, fs,
+    io::{Cursor, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-// MSB-first bit packing (player.js getBit()와 동일 규약)
-fn pack_bits(bits01: &[u8]) -> Vec<u8> {
-    let mut out = vec![0u8; (bits01.len() + 7) / 8];
-    for (i, &b) in bits01.iter().enumerate() {
-        if b != 0 {
-            out[i / 8] |= 1 << (7 - (i % 8));
-        }
-    }
-    out
-}
-
-fn xor_bytes_inplace(dst: &mut [u8], src: &[u8]) {
-    for (d, s) in dst.iter_mut().zip(src.iter()) {
-        *d ^= *s;
-    }
-}
-
-/// ffmpeg로 raw gray 프레임을 stdout 파이프로 받는다.
-/// - fps, scale, format=gray 고정
-fn encode_video_blob_via_ffmpeg(
-    video_path: &PathBuf,
-    w: u16,
-    h: u16,
-    fps: f32,
-    threshold: u8,
-    max_frames: Option<u32>,
-) -> Result<Vec<u8>> {
-    let fps_str = if fps > 0.0 { fps.to_string() } else { "30".to_string() };
-
-    // ffmpeg filter: fps=...,scale=WxH,format=gray
-    let vf = format!("fps={},scale={}:{},format=gray", fps_str, w, h);
-
-    let mut child = Command::new("ffmpeg")
-        .args([
-            "-hide_banner",
-            "-loglevel",
-            "error",
-            "-i",
-            video_path.to_string_lossy().as_ref(),
-            "-vf",
-            &vf,
-            "-f",
-            "rawvideo",
-            "-pix_fmt",
-            "gray",
-            "pipe:1",
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .context("failed to spawn ffmpeg (is it installed?)")?;
-
-    let mut stdout = child.stdout.take().context("failed to take ffmpeg stdout")?;
-
-    let frame_sz = (w as usize) * (h as usize);
-    let mut frame_buf = vec![0u8; frame_sz];
-
-    // header (나중에 frame_count patch)
-    // u16 w, u16 h, u16 fps_x100, u32 frame_count
-    let mut blob: Vec<u8> = Vec::new();
-    blob.extend_from_slice(&w.to_le_bytes());
-    blob.extend_from_slice(&h.to_le_bytes());
-    let fps_x100: u16 = (fps * 100.0).round().clamp(1.0, 65535.0) as u16;
-    blob.extend_from_slice(&fps_x100.to_le_bytes());
-    blob.extend_from_slice(&0u32.to_le_bytes()); // frame_count placeholder
-
-    let packed_len = (frame_sz + 7) / 8;
-    let mut prev_packed = vec![0u8; packed_len];
-    let mut frame_count: u32 = 0;
-
-    loop {
-        if let Some(m) = max_frames {
-            if frame_count >= m {
-                break;
-            }
+// 상한이 이 크기 이하인 blob은 메모리에서 만들고, 그 외에는 임시 파일로 스트리밍한다.
+const IN_MEMORY_BLOB_MAX: u64 = 16 * 1024 * 1024;
+
+// BA.bin 첨부 설명 (`/Desc`)
+const BA_DESCRIPTION: &str = "Bad Apple video frames in the BA blob format";
+/// `--blob-name`이 없을 때의 BA 첨부 이름 (플레이어가 manifest 없이도 찾는 이름)
+const DEFAULT_BLOB_NAME: &str = "BA.bin";
+
+// --max-size: blob과 첨부 말고 PDF 구조(페이지, 폰트, xref 등)에 드는 크기 (넉넉하게)
+const PDF_OVERHEAD_BYTES: u64 = 16 * 1024;
+
+/// 안내/경고 출력 (`--quiet`면 생략). 에러는 `main`이 돌려줘서 그대로 출력된다.
+macro_rules! note {
+    ($($arg:tt)*) => {
+        if !quiet() {
+            eprintln!($($arg)*);
         }
+    };
+}
+
+/// 인코딩 단계 입력: 어디서 프레임을 읽고 어떻게 크기/fps를 정할지
+#[derive(Debug)]
+struct VideoInput {
+    video: PathBuf,
+    /// w/h/fps 중 `auto`인 것은 ffprobe로 채운다
+    target: TargetSize,
+    scale: ScaleMode,
+    strict: bool,
+    /// `--vf-extra` (빈 문자열이면 None)
+    extra_vf: Option<String>,
+    /// `--max-size`: PDF 크기 상한 (bytes). 있으면 해상도/fps를 여기에 맞춰 낮춘다.
+    max_size: Option<u64>,
+    min_width: u16,
+    min_fps: f32,
+    /// `--start` / `--duration` (비디오와 오디오에 같이 적용)
+    trim: Trim,
+    /// `--crop` (`--autocrop`이면 cropdetect 결과로 채운다)
+    crop: Option<Crop>,
+    autocrop: bool,
+    /// `--frames`: 비디오 대신 읽을 이미지 시퀀스 (디렉터리, `%0Nd` 패턴 또는 `*.png` glob)
+    frames: Option<PathBuf>,
+    strict_size: bool,
+    /// `--input`을 포함한 이어 붙일 비디오 전체 (2개 이상일 때만, `video`는 그 첫 파일)
+    concat_inputs: Vec<PathBuf>,
+    /// `--resume`: 이미 인코딩한 입력 프레임 수 (입력에서 그만큼 건너뛴다)
+    skip_frames: u32,
+}
+
+/// `--checkpoint` / `--resume`: 인코딩 중간 상태를 쓸 파일 (blob은 `<path>.blob`)
+struct CheckpointArgs {
+    path: PathBuf,
+    /// `--checkpoint-every`: 입력 프레임 몇 개마다 쓸지
+    every: u32,
+    /// `--resume`: 이 checkpoint에서 이어서 한다
+    resume: bool,
+}
 
-        // raw gray 한 프레임 읽기
-        let mut read_total = 0usize;
-        while read_total < frame_sz {
-            let n = stdout.read(&mut frame_buf[read_total..])?;
-            if n == 0 {
-                // EOF
-                read_total = 0;
-                break;
+/// 인코딩 단계 결과 (PDF 단계 입력). `--blob-in`이면 기존 blob을 다시 재생해서 만든다.
+struct EncodedBlob {
+    /// `BA.bin` 첨부 (작으면 메모리, 크면 `tmp` 파일)
+    attachment: Attachment,
+    stats: EncodeStats,
+    encode_time: Duration,
+    /// blob을 스트리밍한 임시 파일 (PDF를 저장하고 검증할 때까지 유지)
+    tmp: Option<tempfile::NamedTempFile>,
+    /// `--checkpoint`: 끝까지 성공하면 지울 checkpoint (blob은 그 옆 `.blob` 파일에 있다)
+    checkpoint: Option<PathBuf>,
+}
+
+struct Args {
+    input: VideoInput,
+    /// 오디오 트랙 (위치 인자, 그 뒤에 `--audio`, 각각 `--audio-lang`). 비어 있으면 AU 첨부 없음
+    audio_tracks: Vec<AudioTrack>,
+    out_pdf: PathBuf,
+    /// `{w}`, `{frames}`, `{blob_sha256:8}` 같은 자리 표시자가 있을 수 있는 템플릿 (blob을 만든 뒤 `resolve_start_url`로 채운다)
+    start_url: String,
+    encode: EncodeOptions,
+    audio: AudioOptions,
+    /// 첨부할 플레이어 HTML과 (선택) 추가 JS/CSS 파일
+    player_html: Option<PathBuf>,
+    player_files: Vec<PathBuf>,
+    /// 페이지에 포스터 프레임 썸네일을 그린다 (`--no-thumbnail`이면 false)
+    thumbnail: bool,
+    /// `--poster-rect` (없으면 버튼 위)
+    thumbnail_rect: Option<[f64; 4]>,
+    /// `--open-action`: 문서를 열 때 start_url로 넘어갈지
+    open_action: OpenAction,
+    /// `--viewer` / `--fullscreen`: catalog `/ViewerPreferences`
+    viewer: ViewerPreferences,
+    /// `--pdf-version`: 헤더 버전과 xref 형식
+    pdf_version: PdfVersion,
+    /// `--linearize`: 첫 페이지를 앞에 두는 linearized("fast web view") PDF로 저장한다 (실험적)
+    linearize: bool,
+    /// `--page-size` / `--page-width` / `--page-height`, `--button-label` / `--button-fill-gray` / `--button-font-size` / `--button-font`,
+    /// `--button-appearance` / `--button-border`,
+    /// `--button-rect` 또는 `--button-anchor` / `--button-size`
+    page: PageStyle,
+    /// `manifest.json` 첨부 (`--no-manifest`이면 false)
+    manifest: bool,
+    /// 저장한 PDF를 다시 열어서 첨부/링크 확인 (`--no-verify`이면 false)
+    verify: bool,
+    /// `--report`: 인코딩 통계 JSON을 쓸 경로
+    report: Option<PathBuf>,
+    /// `--dry-run`: 인코딩하고 통계만 출력한다 (오디오 준비, PDF 쓰기 생략)
+    dry_run: bool,
+    /// `--checkpoint` / `--checkpoint-every` / `--resume`
+    checkpoint: Option<CheckpointArgs>,
+    /// `--blob-in`: 인코딩하지 않고 이 blob으로 PDF를 만든다
+    blob_in: Option<PathBuf>,
+    /// `--blob-out`: 인코딩한 blob을 이 파일로도 쓴다
+    blob_out: Option<PathBuf>,
+    /// `--dump-assets`: PDF에 넣는 첨부(BA, AU, manifest, 플레이어 파일)를 이 디렉터리에도 쓴다
+    dump_assets: Option<PathBuf>,
+    /// `--chunk-size`: BA.bin을 이 크기의 `BA.000.bin`, ... 조각으로 나눈다
+    chunk_size: Option<u64>,
+    /// `--chapter <frame>:<title>` (프레임 순서로 정렬)
+    chapters: Vec<Chapter>,
+    /// `--encrypt-audio`: AU 첨부도 `--encrypt` 키로 암호화한다
+    encrypt_audio: bool,
+    /// `--blob-name`: BA 첨부 이름 (기본 `BA.bin`, 나누면 조각 이름도 여기서 나온다)
+    blob_name: String,
+    /// `--audio-name`: 첫 오디오 트랙의 첨부 이름 (나머지 트랙 이름도 여기서 나온다). 없으면 `AU.<ext>`
+    audio_name: Option<String>,
+    /// `--audio-mime`: 오디오 첨부 MIME(/Subtype)을 magic bytes로 정하지 않고 이 값으로
+    audio_mime: Option<String>,
+    /// `--concat-audio`: 첫 오디오 트랙을 이 비디오들의 오디오를 이어서 만든다
+    concat_audio: Option<Vec<PathBuf>>,
+    /// `--stats-only` (`--stats-format`): blob/PDF 대신 delta 밀도/코덱별 크기 보고서를 stdout에 쓴다
+    stats_only: Option<StatsFormat>,
+    /// `--quiet`: 진행 표시와 안내/경고를 끄고 에러만 출력한다
+    quiet: bool,
+    /// `--verbose`: ffmpeg stderr를 `[ffmpeg] ` 접두어로 그대로 보여준다
+    verbose: bool,
+}
+
+/// `--chapter 120:Act 2` → 프레임 120에서 시작하는 장 "Act 2"
+fn parse_chapter(s: &str) -> Result<Chapter> {
+    let (frame, title) = s.split_once(':').with_context(|| format!("invalid chapter '{}' (expected <frame>:<title>)", s))?;
+    let frame_index = frame.trim().parse().with_context(|| format!("invalid chapter frame '{}'", frame))?;
+    let title = title.trim();
+    if title.is_empty() {
+        bail!("chapter at frame {} has an empty title", frame_index);
+    }
+    Ok(Chapter { title: title.to_string(), frame_index })
+}
+
+/// `--poster-rect` / `--button-rect x1,y1,x2,y2`
+fn parse_rect(s: &str) -> Result<[f64; 4]> {
+    let v: Vec<f64> = s
+        .split(',')
+        .map(|p| p.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow::anyhow!("invalid rect '{}' (expected x1,y1,x2,y2)", s))?;
+    match v[..] {
+        [x1, y1, x2, y2] if x2 > x1 && y2 > y1 => Ok([x1, y1, x2, y2]),
+        [_, _, _, _] => bail!("invalid rect '{}' (x2 must be > x1 and y2 > y1)", s),
+        _ => bail!("invalid rect '{}' (expected x1,y1,x2,y2)", s),
+    }
+}
+
+/// `--button-size WxH` (PDF point)
+fn parse_button_size(s: &str) -> Result<(f64, f64)> {
+    let invalid = || anyhow::anyhow!("invalid --button-size '{}' (expected WxH in points, e.g. 300x100)", s);
+    let (w, h) = s.split_once('x').ok_or_else(invalid)?;
+    let (w, h): (f64, f64) = (w.trim().parse().map_err(|_| invalid())?, h.trim().parse().map_err(|_| invalid())?);
+    if !(w > 0.0 && h > 0.0) {
+        bail!("--button-size '{}' must be greater than 0 in both directions", s);
+    }
+    Ok((w, h))
+}
+
+/// 위치 인자: `auto`면 `None`
+fn auto_or<T: std::str::FromStr>(s: &str, what: &str) -> Result<Option<T>> {
+    if s == "auto" {
+        return Ok(None);
+    }
+    s.parse().map(Some).map_err(|_| anyhow::anyhow!("invalid {} '{}' (expected a number or auto)", what, s))
+}
+
+/// `--flag <value>`의 value
+fn opt_value(it: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+    it.next().with_context(|| format!("{} needs a value", flag))
+}
+
+/// 첨부 `/CreationDate`: `SOURCE_DATE_EPOCH`가 있으면 그 시각 (재현 가능한 빌드), 없으면 지금
+fn attachment_date() -> Result<String> {
+    let secs = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(v) => v.trim().parse().with_context(|| format!("invalid SOURCE_DATE_EPOCH '{}'", v))?,
+        Err(_) => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+    };
+    Ok(pdf_date(secs))
+}
+
+fn print_usage(prog: &str) {
+    eprintln!("Usage:");
+    eprintln!("  {} <video.mp4|-|none> <audio|none> <out.pdf> <w|auto> <h|auto> <fps|auto> <threshold|auto|mean> <max_frames_or_0> <start_url> [options]", prog);
+    eprintln!("  {} decode <BA.bin> <frame_index|@time> <out.png|out.pgm|out.pbm> [--key <hexkey>]", prog);
+    eprintln!("  {} verify <BA.bin> [--key <hexkey>]", prog);
+    eprintln!("  {} inspect <BA.bin> [--key <hexkey>] [--dump-frame <N>]", prog);
+    eprintln!("  {} extract <in.pdf> [--out <dir>] [--name <file>] [--key <hexkey>]", prog);
+    eprintln!("  (video '-' reads gray8 rawvideo frames of w x h bytes from stdin; w, h and fps must be given)");
+    eprintln!("  (video 'none' with --frames reads PNG/JPEG frame files instead; fps must be given)");
+    eprintln!("  (video 'none' with --input a.mp4 --input b.mp4 encodes the inputs back to back)");
+    eprintln!("  (video 'none' with --blob-in BA.bin builds the PDF from an existing blob; w, h, fps may be auto)");
+    eprintln!("  (start_url may use {{w}} {{h}} {{fps}} {{frames}} {{bytes}} {{blob_sha256}} {{blob_sha256:N}}, filled in after encoding; {{{{ }}}} for literal braces)");
+    eprintln!("Options:");
+    eprintln!("  --threshold <0-255|auto|mean> override threshold (auto = per-frame Otsu, mean = per-frame pixel mean)");
+    eprintln!("  --adaptive                 local threshold per pixel: mean of the surrounding block minus an offset");
+    eprintln!("  --adaptive-block <N>       --adaptive window size, odd (default 15)");
+    eprintln!("  --adaptive-offset <N>      subtracted from the local mean, may be negative (default 8)");
+    eprintln!("  --dither <none|bayer>      ordered dithering around the threshold (default none)");
+    eprintln!("  --dither-scale <N>         Bayer dither strength (default 64)");
+    eprintln!("  --frame-durations <file>   per-frame display time in ms (one per line or <count>x<ms>); the player follows it instead of fps");
+    eprintln!("  --vfr                      keep every source frame and its timestamp (no fps filter); fps only numbers the frames");
+    eprintln!("  --denoise <MODE>           filter the 1-bit frames before the XOR delta: spatial (3x3 majority), temporal:N");
+    eprintln!("                             (a pixel changes only after N frames in its new value), or spatial,temporal:N");
+    eprintln!("  --invert                   show black as white and vice versa (v2 header flag, payload unchanged)");
+    eprintln!("  --polarity <black|white|auto[:N]>  store dark (default) or bright pixels as set bits; auto picks the minority");
+    eprintln!("                             color of the first N frames (default 30); the picture stays the same");
+    eprintln!("  --bit-depth <1|2|4>        1-bit black/white (default), 4-level gray or 16-level gray (no threshold; --dither works with 2)");
+    eprintln!("  --bits <1|2|4>             same as --bit-depth");
+    eprintln!("  --scale-mode <MODE>        stretch (default), pad/letterbox (letterbox/pillarbox) or crop (fill and center-crop) to w x h; also --scale");
+    eprintln!("  --pad-color <COLOR>        fill for --scale-mode pad: black (default), white or a gray level 0-255");
+    eprintln!("  --keep-aspect              same as --scale-mode pad");
+    eprintln!("  --vf-extra <filter>        extra ffmpeg video filters, inserted after scale and before format=gray");
+    eprintln!("  --legacy-header            write the old 10-byte header (no magic/version/CRC)");
+    eprintln!("  --compress <none|rle|deflate|zstd|brotli>  per-frame codec (default none)");
+    eprintln!("  --compress-level <N>       zstd level 1..=22 (default 3) or brotli quality 0..=11 (default 6)");
+    eprintln!("  --no-checksum              omit the CRC32 trailer");
+    eprintln!("  --checksums                add a CRC32 per reconstructed frame (pinpoints the first corrupt frame)");
+    eprintln!("  --encrypt <xor|aes-ctr>:<hexkey>  encrypt the BA payload (header stays readable; aes-ctr takes a 16 or 32-byte key;");
+    eprintln!("                             unauthenticated: the CRC catches a wrong key, not tampering)");
+    eprintln!("  --embed-key-in-url         append the --encrypt key to start_url as #k=<hexkey>");
+    eprintln!("  --encrypt-audio            also encrypt the AU attachments with the --encrypt key");
+    eprintln!("  --row-align                pad each bitset row to a byte boundary");
+    eprintln!("  --bit-order <msb|lsb>      first pixel of each byte in bit7 (default, player.js) or bit0 (1-bit only, v2 header)");
+    eprintln!("  --tiles                    store only changed 16x16 tiles per frame");
+    eprintln!("  --drop-duplicates          store repeated frames once, with a timing table");
+    eprintln!("  --repeat-records           store runs of repeated frames as repeat records (frame_count stays the playback count, v2 header)");
+    eprintln!("  --jobs <N>                 threshold+pack worker threads (default 1)");
+    eprintln!("  --no-parallel              binarize/pack each frame on a single thread");
+    eprintln!("  --audio <path>             add another audio track (repeatable; named AU_<lang>.ogg, or AU1.ogg, AU2.ogg, ...)");
+    eprintln!("  --audio-lang <code>        language of the preceding audio track (BCP 47, e.g. en, ja); sets /Lang and the name");
+    eprintln!("  --audio-format <opus|ogg|mp3|passthrough>  audio codec to transcode to with ffmpeg (default opus)");
+    eprintln!("  --audio-bitrate <B>        ffmpeg -b:a for transcoded audio (e.g. 96k; opus defaults to 64k)");
+    eprintln!("  --normalize-audio          two-pass ffmpeg loudnorm (-16 LUFS) when transcoding audio (slower)");
+    eprintln!("  --audio-copy               keep the audio as-is if it is already in the target container");
+    eprintln!("  --audio-name <name>        name of the first audio attachment (default AU.<ext>; more tracks get _<lang> or a number)");
+    eprintln!("  --audio-mime <type>        audio attachment MIME (/Subtype) instead of sniffing ogg/mp3/flac/wav/m4a magic bytes");
+    eprintln!("  --blob-name <name>         name of the BA attachment (default BA.bin; the player finds other names via manifest.json)");
+    eprintln!("  --player-html <path>       embed a player HTML and open it from START (start_url stays as fallback)");
+    eprintln!("  --player-file <path>       extra file to embed next to the player (repeatable)");
+    eprintln!("  --no-thumbnail             do not draw the poster frame above the START button");
+    eprintln!("  --poster-frame <N>         frame shown as the page thumbnail (default 0)");
+    eprintln!("  --preview-frame <N>        draw frame N in grayscale (before thresholding) instead of the thumbnail");
+    eprintln!("  --page-size <SIZE>         letter (default), a4, letter-landscape, a4-landscape or WxH in points");
+    eprintln!("  --page-width <PT>          page width in points (overrides one side of --page-size)");
+    eprintln!("  --page-height <PT>         page height in points (overrides one side of --page-size)");
+    eprintln!("  --button-label <TEXT>      START button label (default START; also --button-text, --label). Non-ASCII uses a Korean CID font");
+    eprintln!("  --button-font-size <PT>    button label size (default 36, scaled with the page unless the button is placed by hand)");
+    eprintln!("  --button-fill-gray <G>     button background gray, 0 = black .. 1 = white (default 0.9)");
+    eprintln!("  --button-stroke-gray <G>   button border and label gray (default 0)");
+    eprintln!("  --button-font <NAME>       PDF font for the label (Helvetica, Times-Bold, Courier, ..., HYGoThic-Medium, HYSMyeongJo-Medium)");
+    eprintln!("  --button-appearance        also give the Link annotation an /AP appearance stream drawing the button");
+    eprintln!("  --button-border            let the viewer draw a visible 2pt border around the Link annotation");
+    eprintln!("  --chapter <frame>:<title>  add a bookmark and a named destination (frame-<N>) for this frame (repeatable)");
+    eprintln!("  --button-rect <x1,y1,x2,y2> button (and link) area in points (default 156,360,456,460 on letter)");
+    eprintln!("  --button-anchor <POS>      place the button at center, top, bottom, left, right, top-left, ... of the page");
+    eprintln!("  --button-size <WxH>        button size for --button-anchor in points (default 300x100)");
+    eprintln!("  --open-action <url|js|start|none> go to start_url (or run the START action) when the PDF is opened (default none)");
+    eprintln!("  --viewer <FLAGS>           viewer window prefs, comma-separated: hide-toolbar, hide-menubar, hide-window-ui,");
+    eprintln!("                             fit-window (also opens full screen), center-window, display-title, all");
+    eprintln!("  --fullscreen               same as --viewer all: hide the viewer chrome and open full screen on a single page");
+    eprintln!("  --pdf-version <1.4|1.5|1.7|2.0>  output PDF version (default 1.7; 1.4 writes a classic xref table)");
+    eprintln!("  --linearize                experimental: write a linearized (fast web view) PDF, first page before the attachments");
+    eprintln!("  --autoplay                 same as --open-action start: start the player on open, START stays as a fallback");
+    eprintln!("  --poster-rect <x1,y1,x2,y2>  page area for the thumbnail in points (default: above the button, 106,490,506,740 on letter)");
+    eprintln!("  --flipbook <N>             also add one PDF page per N-th frame (for viewers without JS)");
+    eprintln!("  --flipbook-max-pages <M>   cap the flipbook page count (default 1000)");
+    eprintln!("  --input <video>            append another video after the first one (repeatable, one ffmpeg run each; alias --video)");
+    eprintln!("  --playlist <file>          append the videos listed in a text file (one path per line, '#' comments)");
+    eprintln!("  --concat-audio             use the audio of the video inputs, joined with the ffmpeg concat demuxer (audio arg none)");
+    eprintln!("  --frames <dir|frame_%05d.png|'*.png'> read PNG/JPEG frames (natural sort) instead of a video, no ffmpeg");
+    eprintln!("  --strict-size              with --frames, fail if a frame size differs from the first (default: resize)");
+    eprintln!("  --crop <W:H:X:Y>           crop the source (in source pixels) before scaling");
+    eprintln!("  --autocrop                 detect black bars with ffmpeg cropdetect and crop them");
+    eprintln!("  --start <HH:MM:SS[.ms]>    start encoding here (video and audio, also MM:SS or seconds)");
+    eprintln!("  --duration <HH:MM:SS[.ms]> encode only this long (video and audio); max_frames still caps the frames");
+    eprintln!("  --max-size <N[K|M|G]>      lower fps, then resolution, until the projected PDF fits (samples the video first)");
+    eprintln!("  --min-width <W>            smallest width --max-size may pick (default 32)");
+    eprintln!("  --min-fps <F>              lowest fps --max-size may pick (default 10)");
+    eprintln!("  --chunk-size <N[K|M|G]>    split BA.bin into BA.000.bin, BA.001.bin, ... attachments of at most N bytes");
+    eprintln!("  --no-manifest              do not embed manifest.json (sizes, codecs, SHA-256 of BA/AU)");
+    eprintln!("  --no-verify                do not re-open the written PDF to check attachments and the START link");
+    eprintln!("  --report <report.json>     write encoding stats (sizes, zero-diff frames, wall time) as JSON");
+    eprintln!("  --dry-run                  encode and print the stats (and --report), but skip the audio and do not write the PDF");
+    eprintln!("  --stats-only               run the encoder without writing a blob or PDF; print diff density, the most expensive");
+    eprintln!("                             frames and projected blob/PDF sizes per codec to stdout");
+    eprintln!("  --stats-format <text|json> report format for --stats-only (default text)");
+    eprintln!("  --blob-out <BA.bin>        also write the encoded blob to this file (with --dry-run: only the blob)");
+    eprintln!("  --dump-assets <dir>        also write every embedded file (BA.bin, AU.ogg, ...) byte-for-byte into dir (with --dry-run: BA.bin only)");
+    eprintln!("  --blob-in <BA.bin>         skip encoding and embed this blob (checked first); encoding options are ignored");
+    eprintln!("  --checkpoint <file>        save the encoder state to file (and the blob so far to file.blob) every N frames;");
+    eprintln!("                             both are removed when the run succeeds");
+    eprintln!("  --checkpoint-every <N>     input frames between checkpoints (default {})", DEFAULT_CHECKPOINT_EVERY);
+    eprintln!("  --resume <file>            continue an interrupted --checkpoint run with the same arguments; keeps checkpointing");
+    eprintln!("  --strict                   fail on a truncated final frame instead of warning");
+    eprintln!("  --quiet                    no progress, info or warnings on stderr; only errors");
+    eprintln!("  --verbose                  echo ffmpeg/ffprobe stderr with a [ffmpeg] prefix as it arrives");
+}
+
+fn parse_args(argv: Vec<String>) -> Result<Args> {
+    // 사용법:
+    // cargo run --release -- video.mp4 audio.ogg out.pdf 160 120 30 128 0 https://.../play.html
+    // threshold 자리에 `auto`/`mean`을 쓰거나 `--threshold auto`/`--threshold mean`으로 프레임마다 threshold를 고를 수 있다.
+    let mut a: Vec<String> = Vec::new();
+    let mut encode = EncodeOptions::default();
+    let mut threshold_opt: Option<ThresholdMode> = None;
+    let mut strict = false;
+    let mut scale_mode: Option<ScaleMode> = None;
+    let mut pad_color: Option<u8> = None;
+    let mut extra_vf = None;
+    let mut dither_scale: Option<i32> = None;
+    let mut adaptive = false;
+    let mut adaptive_block = None;
+    let mut adaptive_offset = None;
+    let mut compress_level: Option<i32> = None;
+    let mut audio = AudioOptions::default();
+    let mut player_html = None;
+    let mut player_files = Vec::new();
+    let mut extra_audio: Vec<AudioTrack> = Vec::new();
+    // 어떤 --audio보다 앞에 온 --audio-lang (위치 인자 오디오의 언어)
+    let mut first_audio_lang = None;
+    let mut thumbnail = true;
+    let mut thumbnail_rect = None;
+    let mut open_action = OpenAction::None;
+    let mut viewer = ViewerPreferences::default();
+    let mut pdf_version = PdfVersion::default();
+    let mut linearize = false;
+    let mut fullscreen = false;
+    let mut autoplay = false;
+    let mut page = PageStyle::default();
+    let mut button_rect = None;
+    let mut button_anchor = None;
+    let mut button_size = None;
+    let mut button_font_size = None;
+    let (mut page_width, mut page_height) = (None, None);
+    let mut report = None;
+    let mut dry_run = false;
+    let mut checkpoint = None;
+    let mut checkpoint_every = None;
+    let mut resume = None;
+    let mut stats_only = false;
+    let mut stats_format = None;
+    let mut quiet = false;
+    let mut verbose = false;
+    let mut blob_in = None;
+    let mut blob_out = None;
+    let mut dump_assets = None;
+    let mut max_size = None;
+    let mut chunk_size = None;
+    let mut trim = Trim::default();
+    let mut crop = None;
+    let mut autocrop = false;
+    let mut frames = None;
+    let mut strict_size = false;
+    let mut manifest = true;
+    let mut inputs = Vec::new();
+    let mut chapters = Vec::new();
+    let mut verify = true;
+    let mut embed_key = false;
+    let mut encrypt_audio = false;
+    let mut blob_name = DEFAULT_BLOB_NAME.to_string();
+    let mut audio_name = None;
+    let mut audio_mime = None;
+    let mut concat_audio = false;
+    let mut min_width = None;
+    let mut min_fps = None;
+    let mut it = argv.into_iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--threshold" => threshold_opt = Some(opt_value(&mut it, &arg)?.parse()?),
+            "--legacy-header" => encode.legacy_header = true,
+            "--no-checksum" => encode.checksum = false,
+            "--checksums" => encode.frame_checksums = true,
+            "--encrypt" => encode.encrypt = Some(opt_value(&mut it, &arg)?.parse()?),
+            "--embed-key-in-url" => embed_key = true,
+            "--encrypt-audio" => encrypt_audio = true,
+            "--blob-name" => blob_name = parse_attachment_name(&opt_value(&mut it, &arg)?)?,
+            "--audio-name" => audio_name = Some(parse_attachment_name(&opt_value(&mut it, &arg)?)?),
+            "--audio-mime" => audio_mime = Some(parse_mime(&opt_value(&mut it, &arg)?)?),
+            "--row-align" => encode.row_align = true,
+            "--bit-order" => encode.bit_order = opt_value(&mut it, &arg)?.parse()?,
+            "--drop-duplicates" => encode.drop_duplicates = true,
+            "--repeat-records" => encode.repeat_records = true,
+            "--tiles" => encode.tiles = true,
+            "--no-parallel" => encode.parallel = false,
+            "--vfr" => encode.vfr = true,
+            "--strict" => strict = true,
+            "--keep-aspect" => scale_mode = Some(ScaleMode::Pad { fill: 0 }),
+            "--scale-mode" | "--scale" => scale_mode = Some(opt_value(&mut it, &arg)?.parse()?),
+            "--pad-color" => pad_color = Some(parse_pad_color(&opt_value(&mut it, &arg)?)?),
+            "--vf-extra" => extra_vf = Some(opt_value(&mut it, &arg)?).filter(|s| !s.is_empty()),
+            "--adaptive" => adaptive = true,
+            "--adaptive-block" => adaptive_block = Some(opt_value(&mut it, &arg)?.parse().context("invalid --adaptive-block")?),
+            "--adaptive-offset" => adaptive_offset = Some(opt_value(&mut it, &arg)?.parse().context("invalid --adaptive-offset")?),
+            "--dither" => encode.dither = opt_value(&mut it, &arg)?.parse()?,
+            "--invert" => encode.invert = true,
+            "--polarity" => encode.polarity = opt_value(&mut it, &arg)?.parse()?,
+            "--denoise" => encode.denoise = opt_value(&mut it, &arg)?.parse()?,
+            "--frame-durations" => {
+                let path = PathBuf::from(opt_value(&mut it, &arg)?);
+                let text = fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+                let durations = parse_frame_durations(&text).with_context(|| format!("invalid --frame-durations file {}", path.display()))?;
+                encode.frame_durations_ms = Some(durations);
+            }
+            "--bit-depth" | "--bits" => encode.bit_depth = opt_value(&mut it, &arg)?.parse()?,
+            "--dither-scale" => {
+                dither_scale = Some(opt_value(&mut it, &arg)?.parse().context("invalid --dither-scale")?)
+            }
+            "--audio" => extra_audio.push(AudioTrack::new(opt_value(&mut it, &arg)?)),
+            "--audio-lang" => {
+                // 바로 앞의 --audio (없으면 위치 인자 오디오)에 붙는다
+                let lang = parse_language(&opt_value(&mut it, &arg)?)?;
+                let slot = match extra_audio.last_mut() {
+                    Some(track) => &mut track.language,
+                    None => &mut first_audio_lang,
+                };
+                if let Some(prev) = slot.replace(lang) {
+                    bail!("--audio-lang given twice for the same audio track ({} and {})", prev, slot.as_deref().unwrap_or(""));
+                }
+            }
+            "--audio-format" => audio.format = opt_value(&mut it, &arg)?.parse()?,
+            "--audio-copy" => audio.copy = true,
+            "--normalize-audio" => audio.normalize = true,
+            "--audio-bitrate" => audio.bitrate = Some(parse_bitrate(&opt_value(&mut it, &arg)?)?),
+            "--no-thumbnail" => thumbnail = false,
+            "--no-manifest" => manifest = false,
+            "--no-verify" => verify = false,
+            "--dry-run" => dry_run = true,
+            "--checkpoint" => checkpoint = Some(PathBuf::from(opt_value(&mut it, &arg)?)),
+            "--checkpoint-every" => {
+                checkpoint_every = Some(opt_value(&mut it, &arg)?.parse().context("invalid --checkpoint-every")?);
+                if checkpoint_every == Some(0) {
+                    bail!("--checkpoint-every must be at least 1");
+                }
+            }
+            "--resume" => resume = Some(PathBuf::from(opt_value(&mut it, &arg)?)),
+            "--stats-only" => stats_only = true,
+            "--stats-format" => stats_format = Some(opt_value(&mut it, &arg)?.parse::<StatsFormat>()?),
+            "--quiet" => quiet = true,
+            "--verbose" => verbose = true,
+            "--blob-in" => blob_in = Some(PathBuf::from(opt_value(&mut it, &arg)?)),
+            "--blob-out" => blob_out = Some(PathBuf::from(opt_value(&mut it, &arg)?)),
+            "--dump-assets" => dump_assets = Some(PathBuf::from(opt_value(&mut it, &arg)?)),
+            "--poster-frame" => {
+                encode.poster_frame = opt_value(&mut it, &arg)?.parse().context("invalid --poster-frame")?
+            }
+            "--preview-frame" => {
+                encode.preview_frame = Some(opt_value(&mut it, &arg)?.parse().context("invalid --preview-frame")?)
+            }
+            "--flipbook" => {
+                encode.flipbook_every = opt_value(&mut it, &arg)?.parse().context("invalid --flipbook")?;
+                if encode.flipbook_every == 0 {
+                    bail!("--flipbook must be at least 1");
+                }
+            }
+            "--flipbook-max-pages" => {
+                encode.flipbook_max_pages =
+                    opt_value(&mut it, &arg)?.parse().context("invalid --flipbook-max-pages")?
+            }
+            "--open-action" => open_action = opt_value(&mut it, &arg)?.parse()?,
+            "--viewer" => viewer = opt_value(&mut it, &arg)?.parse()?,
+            "--fullscreen" => fullscreen = true,
+            "--pdf-version" => pdf_version = opt_value(&mut it, &arg)?.parse()?,
+            "--linearize" => linearize = true,
+            "--autoplay" => autoplay = true,
+            "--page-size" => page.size = opt_value(&mut it, &arg)?.parse()?,
+            "--page-width" => page_width = Some(opt_value(&mut it, &arg)?.parse::<f64>().context("invalid --page-width")?),
+            "--page-height" => page_height = Some(opt_value(&mut it, &arg)?.parse::<f64>().context("invalid --page-height")?),
+            "--button-label" | "--button-text" | "--label" => page.button.label = opt_value(&mut it, &arg)?,
+            "--button-stroke-gray" => {
+                page.button.stroke_gray = opt_value(&mut it, &arg)?.parse().context("invalid --button-stroke-gray")?
+            }
+            "--button-fill-gray" => {
+                page.button.fill_gray = opt_value(&mut it, &arg)?.parse().context("invalid --button-fill-gray")?
+            }
+            "--button-font" => page.button.font = opt_value(&mut it, &arg)?,
+            "--button-appearance" => page.button.appearance = true,
+            "--button-border" => page.button.border = true,
+            "--button-font-size" => {
+                button_font_size = Some(opt_value(&mut it, &arg)?.parse().context("invalid --button-font-size")?)
+            }
+            "--button-rect" => button_rect = Some(parse_rect(&opt_value(&mut it, &arg)?)?),
+            "--button-anchor" => button_anchor = Some(opt_value(&mut it, &arg)?.parse()?),
+            "--button-size" => button_size = Some(parse_button_size(&opt_value(&mut it, &arg)?)?),
+            "--poster-rect" => thumbnail_rect = Some(parse_rect(&opt_value(&mut it, &arg)?)?),
+            "--chapter" => chapters.push(parse_chapter(&opt_value(&mut it, &arg)?)?),
+            "--input" | "--video" => inputs.push(PathBuf::from(opt_value(&mut it, &arg)?)),
+            "--playlist" => inputs.extend(read_playlist(Path::new(&opt_value(&mut it, &arg)?))?),
+            "--concat-audio" => concat_audio = true,
+            "--frames" => frames = Some(PathBuf::from(opt_value(&mut it, &arg)?)),
+            "--strict-size" => strict_size = true,
+            "--crop" => crop = Some(opt_value(&mut it, &arg)?.parse()?),
+            "--autocrop" => autocrop = true,
+            "--start" => trim.start = Some(parse_timestamp(&opt_value(&mut it, &arg)?).context("invalid --start")?),
+            "--duration" => trim.duration = Some(parse_timestamp(&opt_value(&mut it, &arg)?).context("invalid --duration")?),
+            "--max-size" => max_size = Some(parse_size(&opt_value(&mut it, &arg)?)?),
+            "--chunk-size" => chunk_size = Some(parse_size(&opt_value(&mut it, &arg)?).context("invalid --chunk-size")?),
+            "--min-width" => min_width = Some(opt_value(&mut it, &arg)?.parse().context("invalid --min-width")?),
+            "--min-fps" => min_fps = Some(opt_value(&mut it, &arg)?.parse().context("invalid --min-fps")?),
+            "--report" => report = Some(PathBuf::from(opt_value(&mut it, &arg)?)),
+            "--player-html" => player_html = Some(PathBuf::from(opt_value(&mut it, &arg)?)),
+            "--player-file" => player_files.push(PathBuf::from(opt_value(&mut it, &arg)?)),
+            "--compress" => encode.compression = opt_value(&mut it, &arg)?.parse()?,
+            "--compress-level" => {
+                compress_level = Some(opt_value(&mut it, &arg)?.parse().context("invalid --compress-level")?)
+            }
+            "--jobs" => {
+                encode.jobs = opt_value(&mut it, &arg)?.parse().context("invalid --jobs")?;
+                if encode.jobs == 0 {
+                    bail!("--jobs must be at least 1");
+                }
+            }
+            _ => a.push(arg),
+        }
+    }
+    if a.len() < 10 {
+        print_usage(&a[0]);
+        bail!("not enough args");
+    }
+    let mut video = PathBuf::from(&a[1]);
+    if blob_in.is_some() {
+        if video.as_os_str() != "none" || frames.is_some() || !inputs.is_empty() {
+            bail!("--blob-in replaces the video input; pass none as the video and drop --frames/--input");
+        }
+        if blob_out.is_some() {
+            bail!("--blob-out and --blob-in cannot be used together (the blob would only be copied)");
+        }
+        if max_size.is_some() {
+            bail!("--max-size needs to encode the video; it cannot be used with --blob-in");
+        }
+        if encode.preview_frame.is_some() {
+            bail!("--preview-frame needs the video frames before thresholding; it cannot be used with --blob-in");
+        }
+        if encode.frame_durations_ms.is_some() {
+            bail!("--frame-durations is recorded while encoding; it cannot be used with --blob-in");
+        }
+        if encode.vfr {
+            bail!("--vfr timestamps are recorded while encoding; it cannot be used with --blob-in");
+        }
+        if encode.polarity != Polarity::Black {
+            bail!("--polarity is recorded while encoding; it cannot be used with --blob-in (the blob header already has it)");
+        }
+        if encode.bit_order != BitOrder::Msb {
+            bail!("--bit-order is recorded while encoding; it cannot be used with --blob-in (the blob header already has it)");
+        }
+    }
+    // --input: video 뒤에 이어 붙일 파일 (video가 none이면 --input 파일만)
+    let mut concat_inputs = Vec::new();
+    if !inputs.is_empty() {
+        if frames.is_some() || video.as_os_str() == "-" {
+            bail!("--input/--playlist need video files; they cannot be combined with --frames or stdin ('-')");
+        }
+        if video.as_os_str() != "none" {
+            inputs.insert(0, video);
+        }
+        video = inputs[0].clone();
+        if inputs.len() > 1 {
+            concat_inputs = inputs;
+        }
+    }
+    // 위치 인자 오디오가 첫 트랙, --audio는 그 뒤에 붙는다
+    let first_audio = (a[2] != "none").then(|| AudioTrack { language: first_audio_lang.clone(), ..AudioTrack::new(&a[2]) });
+    if first_audio.is_none() && !concat_audio {
+        if let Some(lang) = &first_audio_lang {
+            bail!("--audio-lang {} comes before any audio track (put it after the --audio it applies to)", lang);
+        }
+    }
+    // --concat-audio: 위치 인자 오디오 대신 비디오 입력들의 오디오를 이어 붙인 트랙 (제목은 첫 파일 이름)
+    let concat_audio = if concat_audio {
+        if a[2] != "none" {
+            bail!("--concat-audio takes the audio from the video inputs; pass none as the audio");
+        }
+        if blob_in.is_some() || frames.is_some() || video.as_os_str() == "-" || video.as_os_str() == "none" {
+            bail!("--concat-audio needs video files as input (not --blob-in, --frames or stdin)");
+        }
+        Some(if concat_inputs.is_empty() { vec![video.clone()] } else { concat_inputs.clone() })
+    } else {
+        None
+    };
+    let concat_track = concat_audio.as_ref().map(|v| AudioTrack { language: first_audio_lang.clone(), ..AudioTrack::new(&v[0]) });
+    let audio_tracks: Vec<AudioTrack> = first_audio.into_iter().chain(concat_track).chain(extra_audio).collect();
+    let out_pdf = PathBuf::from(&a[3]);
+    let target = TargetSize {
+        width: auto_or(&a[4], "width")?,
+        height: auto_or(&a[5], "height")?,
+        fps: auto_or(&a[6], "fps")?,
+    };
+    if frames.is_some() {
+        if video.as_os_str() != "none" {
+            bail!("--frames replaces the video argument; pass none as the video");
+        }
+        if target.fps.is_none() {
+            bail!("an image sequence (--frames) needs an explicit fps (there is no container to read it from)");
+        }
+    } else if video.as_os_str() == "none" && blob_in.is_none() {
+        bail!("video 'none' needs --frames <dir|pattern|glob> or --blob-in <BA.bin>");
+    } else if strict_size {
+        bail!("--strict-size needs --frames");
+    }
+    if video.as_os_str() == "-" && target.is_auto() {
+        bail!("raw frames from stdin ('-') need explicit w, h and fps (auto needs ffprobe on a file)");
+    }
+    // ffmpeg를 거치지 않는 입력에는 ffmpeg 필터/구간 옵션을 쓸 수 없다
+    let direct_input = match &frames {
+        Some(_) => Some("an image sequence (--frames)"),
+        None if video.as_os_str() == "-" => Some("raw frames from stdin"),
+        None => None,
+    };
+    let scale = match (scale_mode.unwrap_or_default(), pad_color) {
+        (ScaleMode::Pad { .. }, Some(fill)) => ScaleMode::Pad { fill },
+        (_, Some(_)) => bail!("--pad-color needs --scale-mode pad"),
+        (mode, None) => mode,
+    };
+    if let Some(what) = direct_input {
+        if scale_mode.is_some() || extra_vf.is_some() {
+            bail!("--scale-mode/--keep-aspect and --vf-extra need ffmpeg input, not {}", what);
+        }
+        if trim.is_set() {
+            bail!("--start and --duration need ffmpeg input, not {}", what);
+        }
+        if crop.is_some() || autocrop {
+            bail!("--crop and --autocrop need ffmpeg input, not {}", what);
+        }
+    }
+    encode.threshold = match threshold_opt {
+        Some(t) => t,
+        None => a[7].parse()?,
+    };
+    if adaptive {
+        if threshold_opt.is_some() {
+            bail!("--adaptive replaces the global threshold; drop --threshold");
+        }
+        let a = AdaptiveThreshold {
+            block: adaptive_block.unwrap_or(DEFAULT_ADAPTIVE_BLOCK),
+            offset: adaptive_offset.unwrap_or(DEFAULT_ADAPTIVE_OFFSET),
+        };
+        a.validate().context("invalid --adaptive-block")?;
+        encode.adaptive = Some(a);
+    } else if adaptive_block.is_some() || adaptive_offset.is_some() {
+        bail!("--adaptive-block and --adaptive-offset need --adaptive");
+    }
+    if let Some(s) = dither_scale {
+        match &mut encode.dither {
+            DitherMode::Bayer4x4 { scale } => *scale = s,
+            DitherMode::None => bail!("--dither-scale needs --dither bayer"),
+        }
+    }
+    if let Some(l) = compress_level {
+        match &mut encode.compression {
+            CompressionMode::Zstd(level) if (1..=22).contains(&l) => *level = l,
+            CompressionMode::Zstd(_) => bail!("--compress-level must be 1..=22 for zstd"),
+            CompressionMode::Brotli(quality) if (0..=11).contains(&l) => *quality = l as u32,
+            CompressionMode::Brotli(_) => bail!("--compress-level must be 0..=11 for brotli"),
+            _ => bail!("--compress-level needs --compress zstd or brotli"),
+        }
+    }
+    let mf: u32 = a[8].parse()?;
+    encode.max_frames = if mf == 0 { None } else { Some(mf) };
+    if encode.max_frames.is_some_and(|m| encode.poster_frame >= m) {
+        bail!("--poster-frame {} is past max_frames {}", encode.poster_frame, mf);
+    }
+    if let Some(n) = encode.preview_frame {
+        if encode.max_frames.is_some_and(|m| n >= m) {
+            bail!("--preview-frame {} is past max_frames {}", n, mf);
+        }
+        // 포스터 썸네일 자리에 gray 미리보기를 그린다
+        thumbnail = false;
+    }
+    if audio.normalize && audio.format == AudioFormat::Passthrough {
+        bail!("--normalize-audio cannot be used with --audio-format passthrough");
+    }
+    if fullscreen {
+        viewer = ViewerPreferences::all();
+    }
+    if autoplay {
+        if open_action != OpenAction::None {
+            bail!("--autoplay is --open-action start; do not combine it with --open-action");
+        }
+        open_action = OpenAction::Start;
+    }
+    if crop.is_some() && autocrop {
+        bail!("--crop and --autocrop cannot be used together");
+    }
+    if trim.duration == Some(0.0) {
+        bail!("--duration must be greater than 0");
+    }
+    if trim.is_set() && audio.format == AudioFormat::Passthrough && !audio_tracks.is_empty() {
+        bail!("--start/--duration cannot be used with --audio-format passthrough (the audio would not be trimmed)");
+    }
+    audio.trim = trim;
+    chapters.sort_by_key(|c| c.frame_index);
+    // --page-width/--page-height는 --page-size (또는 letter)의 한 변만 바꾼다
+    if page_width.is_some() || page_height.is_some() {
+        page.size = PageSize::new(page_width.unwrap_or(page.size.width), page_height.unwrap_or(page.size.height))?;
+    }
+    // 버튼 위치: --button-rect 그대로, anchor, 아니면 원래 버튼을 페이지 크기에 맞춰 옮기고 줄이거나 키운다
+    page.button_place = match (button_rect, button_anchor, button_size) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+            bail!("--button-rect cannot be combined with --button-anchor/--button-size")
+        }
+        (Some(rect), None, None) => ButtonPlacement::Rect(rect),
+        (None, None, None) => ButtonPlacement::default(),
+        (None, anchor, size) => {
+            let [x1, y1, x2, y2] = DEFAULT_BUTTON_RECT;
+            let (width, height) = size.unwrap_or((x2 - x1, y2 - y1));
+            ButtonPlacement::Anchor { anchor: anchor.unwrap_or(Anchor::Center), width, height }
+        }
+    };
+    // 버튼을 같이 줄였으면 글자도 같은 비율로
+    page.button.font_size = match (button_font_size, page.button_place) {
+        (Some(size), _) => size,
+        (None, ButtonPlacement::Scaled(_)) => page.button.font_size * page.size.letter_scale(),
+        (None, _) => page.button.font_size,
+    };
+    if chunk_size.is_some_and(|c| c < MIN_CHUNK_SIZE) {
+        bail!("--chunk-size must be at least {} bytes", MIN_CHUNK_SIZE);
+    }
+    if max_size.is_none() && (min_width.is_some() || min_fps.is_some()) {
+        bail!("--min-width and --min-fps need --max-size");
+    }
+    if max_size.is_some() && direct_input.is_some() {
+        bail!("--max-size needs a video file to sample (it decodes the input twice through ffmpeg)");
+    }
+    if encode.vfr {
+        if let Some(what) = direct_input {
+            bail!("--vfr reads frame timestamps from ffmpeg; {} has none (use --frame-durations instead)", what);
+        }
+        if max_size.is_some() {
+            bail!("--vfr cannot be combined with --max-size (the size budget picks a constant fps)");
+        }
+        if encode.frame_durations_ms.is_some() {
+            bail!("--vfr cannot be combined with --frame-durations (both set the frame times)");
+        }
+    }
+    let min_width = min_width.unwrap_or(DEFAULT_MIN_WIDTH);
+    let min_fps: f32 = min_fps.unwrap_or(DEFAULT_MIN_FPS);
+    if min_width == 0 || min_fps <= 0.0 {
+        bail!("--min-width and --min-fps must be greater than 0");
+    }
+    if player_html.is_none() && !player_files.is_empty() {
+        bail!("--player-file needs --player-html");
+    }
+    // 인코딩을 시작하기 전에 확인한다 (build_pdf도 같은 값으로 다시 정규화한다).
+    // 자리 표시자는 blob을 만든 뒤에 채우므로 여기서는 이름과, 아무 값이나 넣었을 때의 URL 모양만 본다.
+    let example = StartUrlVars { width: 1, height: 1, fps: 1.0, frames: 1, bytes: 1, blob_sha256: "0".repeat(64) };
+    normalize_start_url(&expand_start_url(&a[9], &example)?)?;
+    let mut start_url = a[9].clone();
+    if embed_key {
+        let key = encode.encrypt.as_ref().context("--embed-key-in-url needs --encrypt")?;
+        // 플레이어가 location.hash에서 읽는다. fragment는 서버로 보내지지 않는다.
+        start_url.push(if start_url.contains('#') { '&' } else { '#' });
+        start_url.push_str(&format!("k={}", key.key_hex()));
+    }
+    if encrypt_audio && encode.encrypt.is_none() {
+        bail!("--encrypt-audio needs --encrypt");
+    }
+    if audio_tracks.is_empty() && (audio_name.is_some() || audio_mime.is_some()) {
+        bail!("--audio-name and --audio-mime need an audio track");
+    }
+    if encrypt_audio && audio_mime.is_some() {
+        bail!("--audio-mime cannot be used with --encrypt-audio (encrypted audio is always application/octet-stream)");
+    }
+    // 조각 이름(BA.000.bin)처럼 생긴 이름은 extract/플레이어가 조각으로 보고 이어 붙이려 한다
+    for name in std::iter::once(&blob_name).chain(&audio_name) {
+        if parse_chunk_name(name).is_some() {
+            bail!("attachment name {} looks like a --chunk-size chunk (<stem>.NNN.<ext>); pick another name", name);
+        }
+    }
+    if audio_name.as_ref() == Some(&blob_name) {
+        bail!("--blob-name and --audio-name are both {}", blob_name);
+    }
+    // --resume은 같은 파일에 계속 checkpoint를 쓴다
+    let every = checkpoint_every.unwrap_or(DEFAULT_CHECKPOINT_EVERY);
+    let checkpoint = match (checkpoint, resume) {
+        (Some(c), Some(r)) if c != r => bail!("--checkpoint {} and --resume {} name different files", c.display(), r.display()),
+        (_, Some(r)) => Some(CheckpointArgs { path: r, every, resume: true }),
+        (Some(c), None) => Some(CheckpointArgs { path: c, every, resume: false }),
+        (None, None) => None,
+    };
+    match &checkpoint {
+        Some(_) => {
+            if blob_in.is_some() || stats_only {
+                bail!("--checkpoint/--resume need to encode a blob; they cannot be used with --blob-in or --stats-only");
+            }
+            if !concat_inputs.is_empty() {
+                bail!("--checkpoint/--resume need a single input (not --input/--playlist with several videos)");
+            }
+            if encode.denoise.is_enabled() {
+                bail!("--checkpoint cannot be combined with --denoise (the filter state is not saved)");
+            }
+            if encode.vfr {
+                bail!("--checkpoint cannot be combined with --vfr (the frame clock is not saved)");
             }
-            read_total += n;
         }
-        if read_total == 0 {
-            break;
+        None if checkpoint_every.is_some() => bail!("--checkpoint-every needs --checkpoint or --resume"),
+        None => {}
+    }
+    if stats_format.is_some() && !stats_only {
+        bail!("--stats-format needs --stats-only");
+    }
+    if stats_only {
+        if blob_in.is_some() {
+            bail!("--stats-only needs to encode the video; it cannot be used with --blob-in");
         }
+        if blob_out.is_some() || report.is_some() || dump_assets.is_some() {
+            bail!("--stats-only does not write a blob, --dump-assets or --report; use --stats-format json for a machine-readable report");
+        }
+    }
+    Ok(Args {
+        input: VideoInput {
+            video,
+            target,
+            scale,
+            strict,
+            extra_vf,
+            max_size,
+            min_width,
+            min_fps,
+            trim,
+            crop,
+            autocrop,
+            frames,
+            strict_size,
+            concat_inputs,
+            skip_frames: 0,
+        },
+        audio_tracks,
+        out_pdf,
+        start_url,
+        encode,
+        audio,
+        player_html,
+        player_files,
+        thumbnail,
+        thumbnail_rect,
+        open_action,
+        viewer,
+        pdf_version,
+        linearize,
+        page,
+        manifest,
+        verify,
+        report,
+        dry_run,
+        checkpoint,
+        blob_in,
+        blob_out,
+        dump_assets,
+        chunk_size,
+        chapters,
+        encrypt_audio,
+        blob_name,
+        audio_name,
+        audio_mime,
+        concat_audio,
+        stats_only: stats_only.then(|| stats_format.unwrap_or_default()),
+        quiet,
+        verbose,
+    })
+}
+
+/// 명시한 fps 상한 (플레이어가 setInterval로 따라갈 수 있는 정도)
+const MAX_FPS: f32 = 240.0;
 
-        // threshold → bits01 (1=black, 0=white)
-        let mut bits01 = vec![0u8; frame_sz];
-        for (i, &px) in frame_buf.iter().enumerate() {
-            bits01[i] = if px <= threshold { 1 } else { 0 };
+/// ffmpeg를 띄우기 전에 값과 경로를 확인한다. 인자 하나하나의 형식은 `parse_args`가 이미 봤고,
+/// 여기서는 값의 범위, 읽을 파일이 있는지, 쓸 디렉터리를 만들고 쓸 수 있는지를 본다
+/// (`start_url`은 `parse_args`에서 `normalize_start_url`로 확인했다).
+fn validate_config(cfg: &Args) -> Result<()> {
+    let target = &cfg.input.target;
+    if target.width == Some(0) || target.height == Some(0) {
+        bail!("width and height must be at least 1 (or auto)");
+    }
+    if let Some(fps) = target.fps.filter(|&f| !(f > 0.0 && f <= MAX_FPS)) {
+        bail!("fps must be greater than 0 and at most {} (or auto), got {}", MAX_FPS, fps);
+    }
+
+    let readable = |what: &str, path: &Path| -> Result<()> {
+        if path.is_dir() {
+            bail!("{} {} is a directory, not a file", what, path.display());
         }
+        fs::File::open(path).map(drop).with_context(|| format!("cannot read {} {}", what, path.display()))
+    };
+    let video = &cfg.input.video;
+    if cfg.blob_in.is_none() && cfg.input.frames.is_none() && video.as_os_str() != "-" && video.as_os_str() != "none" {
+        readable("input video", video)?;
+    }
+    for path in cfg.input.concat_inputs.iter().skip(1) {
+        readable("input video", path)?;
+    }
+    if let Some(path) = &cfg.blob_in {
+        readable("--blob-in", path)?;
+    }
+    for track in cfg.audio_tracks.iter().filter(|_| !cfg.dry_run) {
+        readable("audio", &track.path)?;
+    }
+    // 첫 트랙 뒤는 AU_<lang>.ogg라서 같은 언어가 둘이면 첨부 이름이 겹친다
+    let langs: Vec<&str> = cfg.audio_tracks.iter().skip(1).filter_map(|t| t.language.as_deref()).collect();
+    if let Some((_, lang)) = langs.iter().enumerate().find(|(i, l)| langs[..*i].contains(l)) {
+        bail!("--audio-lang {} is given to more than one audio track; the attachment names would clash", lang);
+    }
+    for path in cfg.player_html.iter().chain(&cfg.player_files) {
+        readable("player file", path)?;
+    }
+
+    // 출력 디렉터리는 없으면 만들고, 임시 파일을 하나 만들어 봐서 쓸 수 있는지 본다
+    let outputs = [(!cfg.dry_run && cfg.stats_only.is_none()).then_some(&cfg.out_pdf), cfg.blob_out.as_ref(), cfg.report.as_ref()];
+    for path in outputs.into_iter().flatten() {
+        if path.is_dir() {
+            bail!("output {} is a directory", path.display());
+        }
+        let dir = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        fs::create_dir_all(dir).with_context(|| format!("cannot create output directory {}", dir.display()))?;
+        tempfile::NamedTempFile::new_in(dir).with_context(|| format!("output directory {} is not writable", dir.display()))?;
+    }
+    if let Some(dir) = &cfg.dump_assets {
+        if dir.is_file() {
+            bail!("--dump-assets {} is a file, not a directory", dir.display());
+        }
+        fs::create_dir_all(dir).with_context(|| format!("cannot create --dump-assets directory {}", dir.display()))?;
+        tempfile::NamedTempFile::new_in(dir).with_context(|| format!("--dump-assets directory {} is not writable", dir.display()))?;
+    }
+    Ok(())
+}
 
-        let packed = pack_bits(&bits01);
+/// `--key <hexkey>` 값. `--encrypt`와 같은 `xor:<hexkey>` 형식도 받는다 (방식은 헤더에서 읽는다).
+fn parse_key(s: &str) -> Result<Vec<u8>> {
+    match s.split_once(':') {
+        Some(_) => Ok(s.parse::<BlobKey>()?.key),
+        None => parse_hex(s).context("invalid --key"),
+    }
+}
 
-        if frame_count == 0 {
-            blob.extend_from_slice(&packed);
-            prev_packed.copy_from_slice(&packed);
-        } else {
-            let mut diff = prev_packed.clone();
-            xor_bytes_inplace(&mut diff, &packed); // diff = prev XOR cur
-            blob.extend_from_slice(&diff);
-            prev_packed.copy_from_slice(&packed);
+/// decode/verify 인자에서 `--key <hexkey>`를 빼고 나머지 위치 인자를 돌려준다
+fn split_key_arg(a: &[String]) -> Result<(Vec<String>, Option<Vec<u8>>)> {
+    let mut rest = Vec::new();
+    let mut key = None;
+    let mut it = a.iter().cloned();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--key" => key = Some(parse_key(&opt_value(&mut it, &arg)?)?),
+            _ => rest.push(arg),
         }
+    }
+    Ok((rest, key))
+}
 
-        frame_count += 1;
+/// `decode <BA.bin> <frame_index|@time> <out.png|out.pgm|out.pbm> [--key <hexkey>]`
+/// blob을 player.js와 같은 방식으로 재생해서 프레임 하나를 이미지로 저장한다.
+/// `@time`(`@12.5`, `@1:02.5`)이면 플레이어가 그 시각에 보여주는 프레임 (ms duration/timestamp table을 따른다).
+fn run_decode(a: &[String]) -> Result<()> {
+    let (a, key) = split_key_arg(a)?;
+    if a.len() < 3 {
+        bail!("usage: decode <BA.bin> <frame_index|@time> <out.png|out.pgm|out.pbm> [--key <hexkey>]");
     }
+    let blob_path = PathBuf::from(&a[0]);
+    let out = PathBuf::from(&a[2]);
 
-    let status = child.wait()?;
-    if !status.success() {
-        bail!("ffmpeg exited with non-zero status");
+    let blob = fs::read(&blob_path).with_context(|| format!("failed to read {}", blob_path.display()))?;
+    let header = parse_blob_header(&blob)?;
+    eprintln!(
+        "BA header: v{}, {}x{}, fps={}, frames={}",
+        header.version,
+        header.width,
+        header.height,
+        header.fps(),
+        header.frame_count
+    );
+
+    let index: u32 = match a[1].strip_prefix('@') {
+        Some(time) => {
+            let ms = (parse_timestamp(time)? * 1000.0).round() as u64;
+            let dec = match &key {
+                Some(key) => BlobDecoder::with_key(&blob, key)?,
+                None => BlobDecoder::new(&blob)?,
+            };
+            let index = dec.frame_at_ms(ms);
+            eprintln!("Frame at {}: {}", format_duration(ms as f64 / 1000.0), index);
+            index
+        }
+        None => a[1].parse().context("invalid frame index (expected a number or @time)")?,
+    };
+    let gray = decode_frame_gray(&blob, index, key.as_deref())?;
+    write_gray_image(&out, header.width, header.height, &gray)?;
+    eprintln!("Wrote frame {}: {}", index, out.display());
+    Ok(())
+}
+
+/// `verify <BA.bin> [--key <hexkey>]`: 헤더, CRC, 프레임 레코드를 검사한다. 실패하면 non-zero로 종료.
+fn run_verify(a: &[String]) -> Result<()> {
+    let (a, key) = split_key_arg(a)?;
+    let blob_path = PathBuf::from(a.first().context("usage: verify <BA.bin> [--key <hexkey>]")?);
+    let blob = fs::read(&blob_path).with_context(|| format!("failed to read {}", blob_path.display()))?;
+    verify_blob(&blob, key.as_deref()).with_context(|| format!("{} failed verification", blob_path.display()))?;
+    let header = parse_blob_header(&blob)?;
+    if header.is_legacy() {
+        eprintln!("OK: {} ({} frames, legacy header without magic, no checksum to compare)", blob_path.display(), header.frame_count);
+    } else if header.has_crc() {
+        eprintln!("OK: {} ({} frames, CRC32 matches)", blob_path.display(), header.frame_count);
+    } else {
+        eprintln!("OK: {} ({} frames, no checksum to compare)", blob_path.display(), header.frame_count);
     }
+    Ok(())
+}
 
-    // frame_count patch
-    let fc_bytes = frame_count.to_le_bytes();
-    blob[6..10].copy_from_slice(&fc_bytes);
+/// `inspect`의 timing 줄: 플레이어가 프레임을 넘기는 기준
+fn timing_summary(header: &BlobHeader) -> &'static str {
+    if header.timestamps {
+        "ms timestamp table (variable frame rate, fps is advisory)"
+    } else if header.ms_timing {
+        "ms duration table"
+    } else {
+        "constant fps"
+    }
+}
 
-    Ok(blob)
+/// `inspect`의 threshold 줄. v0/v1 헤더에는 이진화 방식 칸이 없다.
+fn threshold_summary(header: &BlobHeader) -> String {
+    if header.bit_depth.bits() != 1 {
+        format!("none ({}-bit gray)", header.bit_depth.bits())
+    } else if header.version < 2 {
+        "fixed or not recorded (v1 header)".to_string()
+    } else {
+        header.threshold.name().to_string()
+    }
 }
 
-/// PDF 생성:
-/// - 1페이지 컨텐츠에 START 버튼처럼 보이게 그려놓고
-/// - 같은 영역에 Link annotation (/URI)을 올린다.
-/// - EmbeddedFiles에 BA.bin / AU.ogg를 첨부한다.
-fn add_attachment(doc: &mut Document, name: &str, data: &[u8], mime: &str) -> lopdf::ObjectId {
-    let ef_id = doc.new_object_id();
-    let ef_stream = Stream::new(
-        dictionary! {
-            "Type" => "EmbeddedFile",
-            "Subtype" => mime,
-            "Length" => data.len() as i64,
-        },
-        data.to_vec(),
-    );
-    doc.objects.insert(ef_id, Object::Stream(ef_stream));
-
-    let filespec_id = doc.new_object_id();
-    let filespec = dictionary! {
-        "Type" => "Filespec",
-        "F" => Object::String(name.as_bytes().to_vec(), lopdf::StringFormat::Literal),
-        "UF" => Object::String(name.as_bytes().to_vec(), lopdf::StringFormat::Literal),
-        "EF" => dictionary! {
-            "F" => Object::Reference(ef_id),
-        },
+/// `inspect <BA.bin> [--key <hexkey>] [--dump-frame <N>]`: 헤더 요약과 CRC 검사 결과를 출력한다 (플레이어 디버깅용).
+/// `--dump-frame`이면 N번 프레임을 복원해서 stdout에 PBM(4-bit blob은 PGM)으로 쓰고 요약은 stderr로 보낸다.
+fn run_inspect(a: &[String]) -> Result<()> {
+    const USAGE: &str = "usage: inspect <BA.bin> [--key <hexkey>] [--dump-frame <N>]";
+    let (a, key) = split_key_arg(a)?;
+    let mut blob_path = None;
+    let mut dump = None;
+    let mut it = a.into_iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--dump-frame" => dump = Some(opt_value(&mut it, &arg)?.parse::<u32>().context("invalid --dump-frame index")?),
+            s if s.starts_with("--") => bail!("unknown inspect option {}\n{}", s, USAGE),
+            _ if blob_path.is_none() => blob_path = Some(PathBuf::from(arg)),
+            _ => bail!("{}", USAGE),
+        }
+    }
+    let blob_path = blob_path.context(USAGE)?;
+    let blob = fs::read(&blob_path).with_context(|| format!("failed to read {}", blob_path.display()))?;
+    let header = parse_blob_header(&blob).with_context(|| format!("{} is not a BA blob", blob_path.display()))?;
+
+    let mut flags = Vec::new();
+    for (on, name) in [
+        (header.has_crc(), "crc"),
+        (header.is_row_aligned(), "row-align"),
+        (header.has_timing(), "timing"),
+        (header.is_tiled(), "tiles"),
+        (header.has_frame_crc(), "frame-crc"),
+        (header.invert, "invert"),
+        (header.is_encrypted(), "encrypted"),
+        (header.ms_timing, "ms-durations"),
+        (header.timestamps, "timestamps"),
+        (header.white_ink, "white-ink"),
+        (header.bit_order == BitOrder::Lsb, "lsb-first"),
+        (header.repeat_records, "repeat-records"),
+    ] {
+        if on {
+            flags.push(name);
+        }
+    }
+    // 암호화된 blob은 키가 없으면 timing table을 읽을 수 없다.
+    // repeat record blob은 frame_count가 재생 프레임 수라 저장된 프레임 수는 끝까지 읽어서 센다.
+    let playback = match &key {
+        Some(key) => BlobDecoder::with_key(&blob, key),
+        None => BlobDecoder::new(&blob),
+    }
+    .and_then(|mut d| {
+        let stored = if header.repeat_records {
+            while d.next_frame()?.is_some() {}
+            d.stored_frames()
+        } else {
+            header.frame_count
+        };
+        Ok((d.total_frames(), d.timestamps_ms().1, stored))
+    });
+    let crc = if header.is_encrypted() && key.is_none() {
+        "not checked (encrypted, pass --key)".to_string()
+    } else {
+        match verify_blob(&blob, key.as_deref()) {
+            Ok(()) if header.has_crc() => "OK".to_string(),
+            Ok(()) => "no checksum (all frame records decode)".to_string(),
+            Err(e) => format!("FAILED: {:#}", e),
+        }
+    };
+
+    let mut lines = vec![
+        format!("file:          {}", blob_path.display()),
+        format!("magic:         {}", if header.is_legacy() { "none (legacy 10-byte header)" } else { "BAPF" }),
+        format!("version:       {}", header.version),
+        format!("flags:         0x{:02x} [{}]", header.flags, flags.join(", ")),
+        format!("size:          {}x{}", header.width, header.height),
+        format!("fps:           {}", header.fps()),
+        format!("bit depth:     {}", header.bit_depth.bits()),
+        format!("threshold:     {}", threshold_summary(&header)),
+        format!("stored frames: {}", playback.as_ref().map_or(header.frame_count, |p| p.2)),
+    ];
+    match &playback {
+        Ok((n, ms, _)) => lines.push(format!("frames:        {} ({:.3}s)", n, *ms as f64 / 1000.0)),
+        Err(_) => lines.push("frames:        unknown".to_string()),
+    }
+    lines.extend([
+        format!("blob size:     {} bytes", blob.len()),
+        format!("bytes/frame:   {:.1}", blob.len() as f64 / header.frame_count.max(1) as f64),
+        format!("compression:   {}", header.compression().map(|c| c.name()).unwrap_or("unknown")),
+        format!("cipher:        {}", header.cipher.name()),
+        format!("checksum:      {}", if header.has_crc() { "CRC32 trailer" } else { "none" }),
+        format!("frame CRCs:    {}", if header.has_frame_crc() { "yes" } else { "no" }),
+        format!("timing:        {}", timing_summary(&header)),
+        format!("validation:    {}", crc),
+    ]);
+
+    let Some(index) = dump else {
+        for line in &lines {
+            println!("{}", line);
+        }
+        return Ok(());
+    };
+    // stdout은 이미지 전용
+    for line in &lines {
+        eprintln!("{}", line);
+    }
+    let gray = decode_frame_gray(&blob, index, key.as_deref())?;
+    let image = if header.bit_depth.bits() == 1 {
+        encode_pbm(header.width, header.height, &gray)
+    } else {
+        encode_pgm(header.width, header.height, &gray)
     };
-    doc.objects.insert(filespec_id, Object::Dictionary(filespec));
-    filespec_id
+    std::io::stdout().lock().write_all(&image).context("failed to write frame to stdout")
 }
 
-fn make_pdf(out_pdf: &PathBuf, start_url: &str, ba_raw: &[u8], au_raw: &[u8]) -> Result<()> {
-    let mut doc = Document::with_version("1.7");
+/// `extract <in.pdf> [--out <dir>] [--name <file>] [--key <hexkey>]`: 첨부를 파일로 꺼낸다 (플레이어 디버깅용).
+/// `--key`가 있으면 `--encrypt-audio`로 암호화한 AU 첨부를 복호화해서 쓴다 (BA.bin은 그대로, decode/verify가 `--key`를 받는다).
+fn run_extract(a: &[String]) -> Result<()> {
+    const USAGE: &str = "usage: extract <in.pdf> [--out <dir>] [--name <file>] [--key <hexkey>]";
+    let mut pdf_path = None;
+    let mut out_dir = PathBuf::from(".");
+    let mut only = None;
+    let mut key = None;
+    let mut it = a.iter().cloned();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--out" => out_dir = PathBuf::from(opt_value(&mut it, &arg)?),
+            "--name" => only = Some(opt_value(&mut it, &arg)?),
+            "--key" => key = Some(parse_key(&opt_value(&mut it, &arg)?)?),
+            s if s.starts_with("--") => bail!("unknown extract option {}\n{}", s, USAGE),
+            _ if pdf_path.is_none() => pdf_path = Some(PathBuf::from(arg)),
+            _ => bail!("{}", USAGE),
+        }
+    }
+    let pdf_path = pdf_path.context(USAGE)?;
+    let doc = lopdf::Document::load(&pdf_path).with_context(|| format!("failed to open {}", pdf_path.display()))?;
+    let atts = read_attachments(&doc).with_context(|| format!("failed to read attachments of {}", pdf_path.display()))?;
+    // --chunk-size로 나뉜 BA.000.bin, ...은 BA.bin 하나로 이어서 꺼낸다
+    let mut atts = join_chunks(atts).with_context(|| format!("failed to reassemble chunks in {}", pdf_path.display()))?;
+    if let Some(name) = &only {
+        let names: Vec<String> = atts.iter().map(|a| a.name.clone()).collect();
+        atts.retain(|a| &a.name == name);
+        if atts.is_empty() {
+            bail!("{} has no attachment named {} (found: {})", pdf_path.display(), name, names.join(", "));
+        }
+    }
+    if atts.is_empty() {
+        bail!("{} has no embedded files", pdf_path.display());
+    }
+    fs::create_dir_all(&out_dir).with_context(|| format!("failed to create {}", out_dir.display()))?;
+    for att in &atts {
+        // 첨부 이름에 경로가 들어 있어도 out_dir 밖으로 쓰지 않는다
+        let file_name = std::path::Path::new(&att.name)
+            .file_name()
+            .filter(|n| *n != "." && *n != "..")
+            .with_context(|| format!("attachment name {:?} is not a file name", att.name))?;
+        let path = out_dir.join(file_name);
+        if let (Some(key), AttachmentData::Bytes(data)) = (&key, &att.data) {
+            if is_encrypted_audio(data) {
+                let plain = decrypt_audio(data, key).with_context(|| format!("failed to decrypt {}", att.name))?;
+                fs::write(&path, &plain).with_context(|| format!("failed to write {}", path.display()))?;
+                eprintln!("Extracted {} (decrypted, {} bytes): {}", att.name, plain.len(), path.display());
+                continue;
+            }
+        }
+        match &att.data {
+            AttachmentData::Bytes(data) => fs::write(&path, data),
+            AttachmentData::File(src) => fs::copy(src, &path).map(drop),
+        }
+        .with_context(|| format!("failed to write {}", path.display()))?;
+        eprintln!("Extracted {} ({}, {} bytes): {}", att.name, att.mime, att.data_len()?, path.display());
+    }
+    Ok(())
+}
 
-    // Object IDs
-    let catalog_id = doc.new_object_id();
-    let pages_id = doc.new_object_id();
-    let page_id = doc.new_object_id();
+fn main() -> Result<()> {
+    let argv: Vec<String> = env::args().collect();
+    match argv.get(1).map(String::as_str) {
+        Some("decode") => return run_decode(&argv[2..]),
+        Some("verify") => return run_verify(&argv[2..]),
+        Some("inspect") => return run_inspect(&argv[2..]),
+        Some("extract") => return run_extract(&argv[2..]),
+        _ => {}
+    }
 
-    // Font object (Helvetica)
-    let font_id = doc.new_object_id();
-    doc.objects.insert(
-        font_id,
-        Object::Dictionary(dictionary! {
-            "Type" => "Font",
-            "Subtype" => "Type1",
-            "BaseFont" => "Helvetica"
-        }),
-    );
+    let Args {
+        input,
+        audio_tracks,
+        out_pdf,
+        start_url,
+        encode: mut opts,
+        audio,
+        player_html,
+        player_files,
+        thumbnail,
+        thumbnail_rect,
+        open_action,
+        viewer,
+        pdf_version,
+        linearize,
+        page,
+        manifest,
+        verify,
+        report,
+        dry_run,
+        checkpoint,
+        blob_in,
+        blob_out,
+        dump_assets,
+        chunk_size,
+        chapters,
+        encrypt_audio,
+        blob_name,
+        audio_name,
+        audio_mime,
+        concat_audio,
+        stats_only,
+        quiet: _,
+        verbose: _,
+    } = {
+        let args = parse_args(argv)?;
+        set_quiet(args.quiet);
+        set_verbose(args.verbose);
+        validate_config(&args)?;
+        args
+    };
 
-    // Attachments (EmbeddedFiles)
-    let ba_filespec_id = add_attachment(&mut doc, "BA.bin", ba_raw, "application/octet-stream");
-    let au_filespec_id = add_attachment(&mut doc, "AU.ogg", au_raw, "audio/ogg");
-
-    let names_id = doc.new_object_id();
-    let embedded_files = dictionary! {
-        "Names" => vec![
-            Object::String("AU.ogg".as_bytes().to_vec(), lopdf::StringFormat::Literal),
-            Object::Reference(au_filespec_id),
-            Object::String("BA.bin".as_bytes().to_vec(), lopdf::StringFormat::Literal),
-            Object::Reference(ba_filespec_id),
-        ]
-    };
-    doc.objects.insert(
-        names_id,
-        Object::Dictionary(dictionary! { "EmbeddedFiles" => embedded_files }),
-    );
+    // 1) AU 준비 (기본은 ffmpeg로 Ogg/Opus 트랜스코딩, 오디오 인자가 none이거나 --dry-run이면 생략)
+    // BA.bin이 첫 첨부가 되도록 나머지 첨부는 따로 모아둔다 (--max-size가 크기를 미리 알아야 한다)
+    // 첫 트랙은 플레이어가 찾는 AU.ogg 그대로, 그 뒤는 AU_<lang>.ogg (언어가 없으면 AU1.ogg, ...).
+    // 제목은 입력 파일 이름 (manifest.json에 들어간다), 언어는 filespec /Lang
+    let mut extra_attachments = Vec::new();
+    let mut audio_titles = Vec::new();
+    if dry_run && !audio_tracks.is_empty() {
+        note!("Dry run: skipping audio{}", if input.max_size.is_some() { " (--max-size does not count it)" } else { "" });
+    }
+    for (i, track) in audio_tracks.iter().enumerate().filter(|_| !dry_run) {
+        // --concat-audio 트랙은 위치 인자 자리 (첫 트랙)
+        let au = match &concat_audio {
+            Some(videos) if i == 0 => {
+                note!("Concatenating the audio of {} inputs (ffmpeg concat demuxer)", videos.len());
+                prepare_concat_audio(videos, &audio)?
+            }
+            _ => prepare_audio(&track.path, &audio)?,
+        };
+        match au {
+            Some(mut au) => {
+                if let Some(mime) = &audio_mime {
+                    au.mime.clone_from(mime);
+                }
+                note!("AU bytes ({}): {}", au.mime, au.data_len()?);
+                if encrypt_audio {
+                    let key = opts.encrypt.as_ref().context("--encrypt-audio needs --encrypt")?;
+                    au = encrypt_audio_attachment(au, key)?;
+                    note!("AU encrypted ({}): {} bytes", key.cipher.name(), au.data_len()?);
+                }
+                au.options.language = track.language.clone();
+                extra_attachments.push(au);
+                audio_titles.push(track.name.clone());
+            }
+            None if concat_audio.is_some() && i == 0 => {
+                note!("warning: not every video input has an audio stream, skipping the --concat-audio track")
+            }
+            None => note!("warning: no audio stream in {}, skipping AU attachment", track.path.display()),
+        }
+    }
+    let tracks = audio_titles.len();
+    for (i, (au, title)) in extra_attachments.iter_mut().zip(&audio_titles).enumerate() {
+        let name = audio_name.clone().unwrap_or_else(|| au.name.clone());
+        au.name = track_name(&name, i, au.options.language.as_deref());
+        let lang = au.options.language.as_ref().map_or_else(String::new, |l| format!(" ({})", l));
+        au.options.description = Some(if tracks == 1 {
+            format!("Audio track{}", lang)
+        } else {
+            format!("Audio track {} of {}: {}{}", i + 1, tracks, title, lang)
+        });
+    }
+    if tracks > 1 {
+        note!("Audio tracks: {}", extra_attachments.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", "));
+    }
 
-    // Page Resources: Font only
-    let resources = dictionary! {
-        "Font" => dictionary! {
-            "F1" => Object::Reference(font_id),
-        }
-    };
-
-    // Page content: START 버튼처럼 보이도록 사각형+텍스트 그리기
-    // 좌표: PDF point (612x792)
-    // 버튼 영역 Rect = [x1 y1 x2 y2]
-    let x1 = 156.0;
-    let y1 = 360.0;
-    let x2 = 456.0;
-    let y2 = 460.0;
-
-    let content = format!(
-        "q\n\
-         0.9 g\n\
-         {x1} {y1} {w} {h} re\n\
-         f\n\
-         0 g\n\
-         2 w\n\
-         {x1} {y1} {w} {h} re\n\
-         S\n\
-         BT\n\
-         /F1 36 Tf\n\
-         {tx} {ty} Td\n\
-         (START) Tj\n\
-         ET\n\
-         Q\n",
-        x1 = x1,
-        y1 = y1,
-        w = x2 - x1,
-        h = y2 - y1,
-        tx = x1 + 80.0,
-        ty = y1 + 35.0
-    );
+    // 2) 플레이어 첨부 (--player-html, 첨부 이름은 파일 이름)
+    let mut embedded_player = None;
+    for (i, path) in player_html.iter().chain(&player_files).enumerate() {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .with_context(|| format!("invalid player file name: {}", path.display()))?
+            .to_string();
+        let att = Attachment::file(&name, mime_for_name(&name), path)
+            .with_description(if i == 0 { "Embedded player page" } else { "Embedded player file" });
+        note!("Player file {} ({}): {}", name, att.mime, att.data_len()?);
+        if i == 0 {
+            embedded_player = Some(name);
+        }
+        extra_attachments.push(att);
+    }
 
-    let contents_id = doc.new_object_id();
-    doc.objects.insert(
-        contents_id,
-        Object::Stream(Stream::new(dictionary! { "Length" => content.as_bytes().len() as i64 }, content.into_bytes())),
-    );
+    // --stats-only: 같은 인코딩 루프로 보고서만 쓰고 끝낸다 (AU/플레이어 첨부는 PDF 예상 크기에 들어간다)
+    if let Some(format) = stats_only {
+        return stats_video(input, &mut opts, &extra_attachments, tracks, thumbnail, format);
+    }
 
-    // Link annotation overlay
-    let annot_id = doc.new_object_id();
-    let annot = dictionary! {
-        "Type" => "Annot",
-        "Subtype" => "Link",
-        "Rect" => vec![
-            Object::Real(x1),
-            Object::Real(y1),
-            Object::Real(x2),
-            Object::Real(y2),
-        ],
-        "Border" => vec![0.into(), 0.into(), 0.into()],
-        "A" => dictionary! {
-            "S" => "URI",
-            "URI" => Object::String(start_url.as_bytes().to_vec(), lopdf::StringFormat::Literal),
-        }
-    };
-    doc.objects.insert(annot_id, Object::Dictionary(annot));
-
-    // Page dictionary
-    doc.objects.insert(
-        page_id,
-        Object::Dictionary(dictionary! {
-            "Type" => "Page",
-            "Parent" => Object::Reference(pages_id),
-            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
-            "Resources" => resources,
-            "Contents" => Object::Reference(contents_id),
-            "Annots" => vec![Object::Reference(annot_id)]
-        }),
-    );
+    // 3-4) BA blob: 입력을 인코딩하거나, --blob-in이면 이미 만든 blob을 검사해서 그대로 쓴다
+    let EncodedBlob { attachment: mut ba_attachment, stats, encode_time, tmp: ba_tmp, checkpoint: ba_checkpoint } = match &blob_in {
+        Some(path) => load_blob(path, &input.target, &mut opts)?,
+        None => encode_video(input, &mut opts, &extra_attachments, thumbnail, checkpoint.as_ref())?,
+    };
+    ba_attachment.name = blob_name;
+    if let Some(path) = &blob_out {
+        write_blob_out(&ba_attachment, path)?;
+    }
+    if let Some(path) = &report {
+        fs::write(path, stats.report_json(encode_time))
+            .with_context(|| format!("failed to write report {}", path.display()))?;
+        note!("Wrote report: {}", path.display());
+    }
+    if dry_run {
+        // AU는 건너뛰었으므로 BA.bin만 (나누기 전 blob)
+        if let Some(dir) = &dump_assets {
+            dump_attachments(std::slice::from_ref(&ba_attachment), dir)?;
+        }
+        note!(
+            "Dry run: {} frames, {} bytes ({:.1} bytes/frame, {:.1}% of raw) in {:.2}s; no PDF written",
+            stats.frames,
+            stats.bytes,
+            stats.bytes as f64 / stats.frames as f64,
+            stats.compression_ratio() * 100.0,
+            encode_time.as_secs_f64()
+        );
+        if let Some(path) = &ba_checkpoint {
+            remove_checkpoint(path)?;
+        }
+        return Ok(());
+    }
+    // blob이 확정됐으니 start_url 템플릿을 최종 프레임 수/해시로 채운다 (--blob-in도 같다)
+    let start_url = resolve_start_url(&start_url, &opts, &stats, &ba_attachment)?;
+    // --chunk-size: BA.bin을 조각으로 나눈다 (한 조각에 들어가면 BA.bin 그대로)
+    let ba_chunks = match chunk_size {
+        Some(size) => split_attachment(&ba_attachment, size)?,
+        None => Vec::new(),
+    };
+    // manifest.json은 BA/AU가 확정된 뒤에 나누기 전 blob 기준으로 해시한다 (크기는 PDF_OVERHEAD_BYTES 안에 든다)
+    let manifest = if manifest {
+        let audio: Vec<_> = audio_titles.iter().map(String::as_str).zip(&extra_attachments).collect();
+        let mut manifest = Manifest::new(&ba_attachment, stats.frames, &audio)?;
+        manifest.duration_ms = stats.duration_ms;
+        if ba_chunks.len() > 1 {
+            manifest.video_chunks = ba_chunks.iter().map(|c| c.name.clone()).collect();
+        }
+        Some(manifest.attachment())
+    } else {
+        None
+    };
+    let mut attachments = if ba_chunks.len() > 1 {
+        note!("Split {} into {} chunks ({} .. {})", ba_attachment.name, ba_chunks.len(), ba_chunks[0].name, ba_chunks[ba_chunks.len() - 1].name);
+        ba_chunks
+    } else {
+        vec![ba_attachment]
+    };
+    attachments.extend(extra_attachments);
+    attachments.extend(manifest);
+    let date = attachment_date()?;
+    for att in &mut attachments {
+        att.options.creation_date = Some(date.clone());
+    }
+    // PDF에 넣는 것과 같은 첨부(같은 바이트)를 그대로 파일로 쓴다
+    if let Some(dir) = &dump_assets {
+        dump_attachments(&attachments, dir)?;
+    }
 
-    // Pages + Catalog
-    doc.objects.insert(
-        pages_id,
-        Object::Dictionary(dictionary! {
-            "Type" => "Pages",
-            "Kids" => vec![Object::Reference(page_id)],
-            "Count" => 1
-        }),
-    );
-    doc.objects.insert(
-        catalog_id,
-        Object::Dictionary(dictionary! {
-            "Type" => "Catalog",
-            "Pages" => Object::Reference(pages_id),
-            "Names" => Object::Reference(names_id),
-            "AF" => vec![Object::Reference(ba_filespec_id), Object::Reference(au_filespec_id)],
-        }),
-    );
-    doc.trailer.set("Root", Object::Reference(catalog_id));
+    // 5) PDF 생성 (attachments + 포스터 프레임 썸네일 + flipbook 페이지)
+    if stats.flipbook_skipped > 0 {
+        note!(
+            "warning: flipbook capped at {} pages, {} more frames skipped (raise --flipbook-max-pages)",
+            opts.flipbook_max_pages, stats.flipbook_skipped
+        );
+    }
+    if opts.flipbook_every > 0 {
+        note!("Flipbook pages: {}", stats.flipbook.len());
+    }
+    // 1-bit 이미지는 blob 비트 그대로라 white 극성이면 한 번 더 뒤집어 그린다
+    let invert_bits = opts.invert != stats.white_ink;
+    let flipbook = stats
+        .flipbook
+        .into_iter()
+        .map(|(frame, bits)| FlipbookPage {
+            frame,
+            image: Thumbnail { width: opts.width, height: opts.height, bit_depth: opts.bit_depth, invert: invert_bits, bits },
+        })
+        .collect();
+    let thumbnail = match stats.poster {
+        Some(bits) if thumbnail => {
+            Some(Thumbnail { width: opts.width, height: opts.height, bit_depth: opts.bit_depth, invert: invert_bits, bits })
+        }
+        None if thumbnail && stats.frames > 0 => {
+            bail!("--poster-frame {} is past the end of the video ({} frames)", opts.poster_frame, stats.frames)
+        }
+        _ => None,
+    };
+    let preview = match stats.preview {
+        Some(gray) => Some(Preview { width: opts.width, height: opts.height, gray, invert: opts.invert }),
+        None if stats.frames > 0 && opts.preview_frame.is_some() => {
+            bail!("--preview-frame {} is past the end of the video ({} frames)", opts.preview_frame.unwrap_or(0), stats.frames)
+        }
+        None => None,
+    };
+    if let Some(ch) = chapters.last().filter(|c| c.frame_index >= stats.frames) {
+        bail!("--chapter {:?} at frame {} is past the end of the video ({} frames)", ch.title, ch.frame_index, stats.frames);
+    }
+    let mut doc = build_pdf(
+        &PdfOptions {
+            start_url: start_url.clone(),
+            embedded_player,
+            thumbnail,
+            thumbnail_rect,
+            preview,
+            flipbook,
+            open_action,
+            viewer,
+            version: pdf_version,
+            page,
+            chapters,
+        },
+        &attachments,
+    )?;
+    if linearize {
+        save_linearized(&mut doc, &out_pdf).context("failed to save linearized pdf")?;
+    } else {
+        doc.save(&out_pdf).context("failed to save pdf")?;
+    }
+    drop(doc);
+    note!("Wrote PDF: {}", out_pdf.display());
+    if verify {
+        verify_pdf(&out_pdf, &attachments, &start_url)
+            .with_context(|| format!("{} failed verification after writing", out_pdf.display()))?;
+        note!("Verified PDF: {} attachments round-trip, START link ok", attachments.len());
+    }
+    drop(ba_tmp);
+    if let Some(path) = &ba_checkpoint {
+        remove_checkpoint(path)?;
+    }
 
-    // 저장
-    doc.save(out_pdf).context("failed to save pdf")?;
     Ok(())
 }
 
-fn parse_args() -> Result<(PathBuf, PathBuf, PathBuf, u16, u16, f32, u8, Option<u32>, String)> {
-    // 사용법:
-    // cargo run --release -- video.mp4 audio.ogg out.pdf 160 120 30 128 0 https://.../play.html
-    let a: Vec<String> = env::args().collect();
-    if a.len() < 10 {
-        eprintln!("Usage:");
-        eprintln!("  {} <video.mp4> <audio.ogg> <out.pdf> <w> <h> <fps> <threshold> <max_frames_or_0> <start_url>", a[0]);
-        bail!("not enough args");
+/// start_url 템플릿의 자리 표시자를 인코딩 결과(또는 `--blob-in` blob)로 채우고 정규화한다.
+/// 해시는 템플릿에 `{blob_sha256}`이 있을 때만 계산한다.
+fn resolve_start_url(template: &str, opts: &EncodeOptions, stats: &EncodeStats, blob: &Attachment) -> Result<String> {
+    let names = start_url_placeholders(template)?;
+    let blob_sha256 = if names.iter().any(|n| n == "blob_sha256") { attachment_sha256(blob)?.1 } else { String::new() };
+    let vars = StartUrlVars {
+        width: opts.width,
+        height: opts.height,
+        fps: opts.fps,
+        frames: stats.frames,
+        bytes: blob.data_len()?,
+        blob_sha256,
+    };
+    let url = normalize_start_url(&expand_start_url(template, &vars)?)?;
+    if !names.is_empty() {
+        note!("Start URL: {}", url);
     }
-    let video = PathBuf::from(&a[1]);
-    let audio = PathBuf::from(&a[2]);
-    let out = PathBuf::from(&a[3]);
-    let w: u16 = a[4].parse()?;
-    let h: u16 = a[5].parse()?;
-    let fps: f32 = a[6].parse()?;
-    let threshold: u8 = a[7].parse()?;
-    let mf: u32 = a[8].parse()?;
-    let max_frames = if mf == 0 { None } else { Some(mf) };
-    let start_url = a[9].clone();
-    Ok((video, audio, out, w, h, fps, threshold, max_frames, start_url))
+    Ok(url)
 }
 
-fn main() -> Result<()> {
-    let (video, audio, out_pdf, w, h, fps, threshold, max_frames, start_url) = parse_args()?;
+/// `--checkpoint`: 끝까지 성공했으면 상태 파일과 blob 파일을 지운다
+fn remove_checkpoint(path: &Path) -> Result<()> {
+    Checkpoint::remove(path)?;
+    note!("Removed checkpoint {}", path.display());
+    Ok(())
+}
+
+/// 인코딩 단계: 입력 확인 (ffprobe, crop), `--max-size` 설정 고르기, 프레임 인코딩.
+/// `opts`의 크기/fps는 여기서 정해진다. `attachments`는 `--max-size`가 셈에 넣을 다른 첨부.
+/// 크기/fps를 정하고 (`--max-size`면 샘플링까지) 연 입력. 인코딩과 `--stats-only`가 같이 쓴다.
+struct OpenedVideo {
+    source: Box<dyn FrameSource>,
+    /// 진행 표시와 메모리 할당용 예상 프레임 수 (모르면 `None`)
+    expected_frames: Option<u64>,
+    /// blob 말고 PDF에 더 들어갈 크기 (다른 첨부, 썸네일/flipbook 이미지, PDF 구조)
+    overhead: u64,
+    max_size: Option<u64>,
+    raw_stdin: bool,
+    video: PathBuf,
+    /// --input 여러 개: 입력 파일과 파일마다 읽은 프레임 수 (`ChainSource`가 채운다)
+    chain_counts: Option<(Vec<PathBuf>, FrameCounts)>,
+}
 
-    // 1) BA blob 생성 (raw, uncompressed)
-    let ba_blob = encode_video_blob_via_ffmpeg(&video, w, h, fps, threshold, max_frames)
+impl OpenedVideo {
+    /// 프레임을 하나도 못 읽었으면 에러 (ffmpeg 입력은 `FfmpegSource::finish`가 stderr와 함께 먼저 에러를 낸다)
+    fn check_frames_read(&self, frames: u32, opts: &EncodeOptions, what: &str) -> Result<()> {
+        if frames > 0 {
+            return Ok(());
+        }
+        if self.raw_stdin {
+            bail!(
+                "no frames were read from stdin (need at least {} bytes per frame); {}",
+                opts.width as usize * opts.height as usize,
+                what
+            );
+        }
+        bail!("no frames were read from {}; {}", self.video.display(), what)
+    }
+
+    /// --input 여러 개: 파일마다 몇 프레임을 넣었는지 (max_frames/--duration에서 멈추면 그 뒤 파일은 빠진다)
+    fn report_inputs(&self) {
+        let Some((inputs, counts)) = &self.chain_counts else {
+            return;
+        };
+        let counts = counts.lock().unwrap();
+        for (i, path) in inputs.iter().enumerate() {
+            match counts.get(i) {
+                Some(n) => note!("Input {}/{}: {} frames from {}", i + 1, inputs.len(), n, path.display()),
+                None => note!("Input {}/{}: 0 frames from {} (stopped before this input)", i + 1, inputs.len(), path.display()),
+            }
+        }
+    }
+}
+
+fn open_video(input: VideoInput, opts: &mut EncodeOptions, attachments: &[Attachment], thumbnail: bool) -> Result<OpenedVideo> {
+    let VideoInput {
+        video,
+        target,
+        scale,
+        strict,
+        extra_vf,
+        max_size,
+        min_width,
+        min_fps,
+        trim,
+        crop,
+        autocrop,
+        frames,
+        strict_size,
+        concat_inputs,
+        skip_frames,
+    } = input;
+
+    // video '-': stdin의 gray8 rawvideo를 그대로 읽는다 (ffmpeg/ffprobe 없이)
+    let raw_stdin = video.as_os_str() == "-";
+    // --frames: 이미지 파일 목록 (auto w/h는 첫 프레임 크기에서)
+    let frame_files = match &frames {
+        Some(spec) => {
+            let files = list_frames(spec)?;
+            let (w, h) = image_dimensions(&files[0])?;
+            note!("Image sequence: {} frames from {} (first frame {}x{})", files.len(), spec.display(), w, h);
+            let frame_count = Some(files.len() as u64);
+            Some((files, VideoInfo { width: w, height: h, fps: None, duration: None, frame_count }))
+        }
+        None => None,
+    };
+
+    // --input 여러 개: 파일마다 ffmpeg를 차례로 띄워서 같은 w/h/fps로 이어 붙인다 (`ChainSource`).
+    // ffprobe / cropdetect는 첫 파일(`video`)만 본다.
+    let chained = !concat_inputs.is_empty();
+    if chained {
+        note!("Concatenating {} inputs (one ffmpeg run each)", concat_inputs.len());
+    }
+    let spawn = |ffmpeg: &FfmpegOptions| -> Result<Box<dyn FrameSource>> {
+        Ok(if chained {
+            Box::new(ChainSource::new(concat_inputs.clone(), ffmpeg))
+        } else {
+            Box::new(FfmpegSource::spawn(&video, ffmpeg)?)
+        })
+    };
+
+    // 0) ffmpeg 사전 점검 (없거나 너무 오래됐으면 인코딩 전에 끝낸다)
+    if !raw_stdin && frame_files.is_none() && check_ffmpeg()?.is_none() {
+        note!("warning: could not parse the ffmpeg version, skipping the version check");
+    }
+
+    // auto 크기/fps는 ffprobe로 소스 정보를 읽어서 채운다 (crop 영역 검사에도 쓴다)
+    let info = if let Some((_, info)) = &frame_files {
+        Some(*info)
+    } else if !raw_stdin {
+        // auto/crop에 필요 없으면 (인코딩 전 요약용) ffprobe 실패는 무시하고 ffmpeg 에러에 맡긴다
+        let info = match probe_video(&video) {
+            Err(_) if !(target.is_auto() || crop.is_some() || autocrop) => None,
+            r => r?,
+        };
+        match &info {
+            Some(i) => note!(
+                "Source video: {}x{} @ {} fps",
+                i.width,
+                i.height,
+                i.fps.map_or("unknown".to_string(), |f| format!("{:.2}", f))
+            ),
+            None if target.is_auto() => note!("warning: ffprobe not found, using defaults for auto size/fps"),
+            None => {}
+        }
+        info
+    } else {
+        None
+    };
+
+    // crop: 검은 띠를 scale 전에 잘라낸다. auto 크기는 잘라낸 영역의 비율을 따른다.
+    let crop = if autocrop {
+        let i = info.context("--autocrop needs ffprobe to read the source size")?;
+        match detect_crop(&video, &trim, i.width, i.height).context("failed to detect black bars")? {
+            Some(c) => {
+                note!("Autocrop: detected crop {} (W:H:X:Y)", c);
+                Some(c)
+            }
+            None => {
+                note!("Autocrop: no black bars found, not cropping");
+                None
+            }
+        }
+    } else {
+        crop
+    };
+    if let Some(c) = &crop {
+        match &info {
+            Some(i) => c.check_fits(i.width, i.height)?,
+            None => note!("warning: ffprobe not found, cannot check that --crop {} fits the source", c),
+        }
+    }
+    let info = info.map(|i| match crop {
+        Some(c) => VideoInfo { width: c.width, height: c.height, ..i },
+        None => i,
+    });
+    (opts.width, opts.height, opts.fps) = target.resolve(info.as_ref());
+    if target.is_auto() {
+        note!("Target: {}x{} @ {:.2} fps", opts.width, opts.height, opts.fps);
+    }
+
+    // blob 말고 PDF에 더 들어갈 크기 (--max-size 예산, --stats-only 예상 크기)
+    let mut overhead = 0;
+    for att in attachments {
+        overhead += att.data_len()?;
+    }
+    // 썸네일/flipbook 이미지 (압축 전 크기로 넉넉하게)
+    let packed = (opts.width as u64 * opts.bit_depth.bits() as u64).div_ceil(8) * opts.height as u64;
+    let flipbook_pages = if opts.flipbook_every > 0 { opts.flipbook_max_pages as u64 } else { 0 };
+    overhead += PDF_OVERHEAD_BYTES + packed * (thumbnail as u64 + flipbook_pages);
+    if opts.preview_frame.is_some() {
+        overhead += (opts.width as u64).div_ceil(2) * opts.height as u64; // 4-bit inline image
+    }
+
+    // 3) --max-size: 입력을 한 번 샘플링해서 blob 크기를 추정하고 해상도/fps를 고른다
+    if let Some(max) = max_size {
+        let base = Settings { width: opts.width, height: opts.height, fps: opts.fps };
+        let ffmpeg = FfmpegOptions {
+            width: base.width,
+            height: base.height,
+            fps: base.fps,
+            strict,
+            scale,
+            extra_vf: extra_vf.clone(),
+            trim,
+            crop,
+            concat: false,
+            vfr: opts.vfr,
+        };
+        let mut source = spawn(&ffmpeg).context("failed to sample video frames")?;
+        // 샘플링도 입력을 끝까지 읽으므로 인코딩과 같은 진행 표시를 한다
+        let sample_total = info
+            .filter(|_| !chained)
+            .and_then(|i| i.output_frames(&trim, base.fps))
+            .map(|n| opts.max_frames.map_or(n, |m| n.min(m as u64)));
+        let mut progress = Progress::new(sample_total).with_label("Sampling");
+        let sample = FrameSample::collect(&mut ProgressSource { inner: &mut *source, progress: &mut progress }, base, opts.max_frames)
+            .context("failed to sample video frames")?;
+        drop(progress);
+        note!(
+            "Size budget: sampled {} of {} frames ({:.1}s), other attachments + PDF ~{} bytes",
+            sample.frame_count(),
+            sample.total_frames,
+            sample.seconds(),
+            overhead
+        );
+        let choice = choose_settings(&sample, opts, max, overhead, min_width, min_fps)?;
+        let chosen = choice.settings;
+        note!(
+            "Size budget: chose {}x{} @ {} fps (projected {} of {} bytes)",
+            chosen.width, chosen.height, chosen.fps, choice.projected_bytes, max
+        );
+        // max_frames는 재생 프레임 수라서 fps를 내리면 같은 길이가 되도록 줄인다
+        if let Some(m) = opts.max_frames {
+            opts.max_frames = Some(((m as f64 * chosen.fps as f64 / opts.fps as f64).ceil() as u32).max(1));
+        }
+        (opts.width, opts.height, opts.fps) = (chosen.width, chosen.height, chosen.fps);
+    }
+
+    // 인코딩 전 요약: ffprobe 길이로 프레임 수와 blob 크기를 미리 보여준다 (--input 여러 개면 길이를 몰라서 생략)
+    // --vfr는 fps 필터가 없으므로 소스 fps(평균)로 센다
+    let out_fps = match info {
+        Some(VideoInfo { fps: Some(fps), .. }) if opts.vfr => fps,
+        _ => opts.fps,
+    };
+    if let Some(frames) = info.filter(|_| !chained).and_then(|i| i.output_frames(&trim, out_fps)) {
+        let frames = opts.max_frames.map_or(frames, |m| frames.min(m as u64)).min(u32::MAX as u64) as u32;
+        // 코덱/타일이 있으면 압축 전 (packed 그대로) 크기
+        let raw = EncodeOptions { max_frames: Some(frames), compression: CompressionMode::None, tiles: false, ..opts.clone() };
+        let est = max_blob_len(&raw).unwrap_or(0);
+        note!(
+            "Video: {} frames at {:.2} fps ({}), output ~{:.1} MiB {}",
+            frames,
+            out_fps,
+            format_duration(frames as f64 / out_fps as f64),
+            est as f64 / (1024.0 * 1024.0),
+            if opts.compression == CompressionMode::None && !opts.tiles { "estimated" } else { "before compression" }
+        );
+    }
+
+    // max_frames가 없어도 ffprobe 길이나 이미지 파일 수로 프레임 수를 알면 (--input 여러 개면 모른다)
+    // 최악의 경우(모든 프레임이 전체 프레임) 크기만큼 메모리를 미리 잡는다.
+    // ffprobe 길이는 추정이라 실제 프레임이 조금 더 많으면 그만큼만 Vec이 늘어난다.
+    let probed_frames = info
+        .filter(|_| !chained)
+        .and_then(|i| i.output_frames(&trim, out_fps).or(i.frame_count.filter(|_| frame_files.is_some())));
+    let expected_frames = match (opts.max_frames, probed_frames) {
+        (Some(m), Some(p)) => Some(p.min(m as u64)),
+        (m, p) => m.map(u64::from).or(p),
+    };
+
+    // --resume: 이미 인코딩한 앞 프레임은 건너뛴다. stdin은 읽어서 버리고, 이미지는 목록에서 빼고,
+    // ffmpeg는 그 프레임 시각부터 input seek한다 (`Trim::skip_frames`).
+    let mut chain_counts = None;
+    let source: Box<dyn FrameSource> = if raw_stdin {
+        let mut raw = RawSource::new(std::io::stdin(), strict);
+        let mut buf = vec![0u8; opts.width as usize * opts.height as usize];
+        for i in 0..skip_frames {
+            if !raw.read_frame(&mut buf)? {
+                bail!("stdin ended after {} frames, but the checkpoint has already encoded {}", i, skip_frames);
+            }
+        }
+        Box::new(raw)
+    } else if let Some((mut files, _)) = frame_files {
+        if skip_frames as usize > files.len() {
+            bail!("the image sequence has {} frames, but the checkpoint has already encoded {}", files.len(), skip_frames);
+        }
+        files.drain(..skip_frames as usize);
+        Box::new(ImageSequenceSource::new(files, opts.width, opts.height, strict_size))
+    } else {
+        let trim = if skip_frames > 0 { trim.skip_frames(skip_frames, opts.fps) } else { trim };
+        let ffmpeg = FfmpegOptions {
+            width: opts.width,
+            height: opts.height,
+            fps: opts.fps,
+            strict,
+            scale,
+            extra_vf,
+            trim,
+            crop,
+            concat: false,
+            vfr: opts.vfr,
+        };
+        if chained {
+            let chain = ChainSource::new(concat_inputs.clone(), &ffmpeg);
+            chain_counts = Some((concat_inputs, chain.frame_counts()));
+            Box::new(chain)
+        } else {
+            Box::new(FfmpegSource::spawn(&video, &ffmpeg).context("failed to encode video frames")?)
+        }
+    };
+    Ok(OpenedVideo { source, expected_frames, overhead, max_size, raw_stdin, video, chain_counts })
+}
+
+fn encode_video(
+    mut input: VideoInput,
+    opts: &mut EncodeOptions,
+    attachments: &[Attachment],
+    thumbnail: bool,
+    checkpoint: Option<&CheckpointArgs>,
+) -> Result<EncodedBlob> {
+    // --resume: 같은 입력과 옵션인지는 fingerprint로 본다 (건너뛸 프레임 수를 넣기 전의 입력 설정)
+    let resume = match checkpoint {
+        Some(ck) if ck.resume => Some(Checkpoint::read(&ck.path)?),
+        _ => None,
+    };
+    let settings = format!("{:?}", input);
+    if let Some(cp) = &resume {
+        note!("Resuming from checkpoint {}: {} frames already encoded", checkpoint.map_or(Path::new(""), |c| &c.path).display(), cp.frames_in);
+        input.skip_frames = cp.frames_in;
+    }
+    let skipped = input.skip_frames as u64;
+    let mut opened = open_video(input, opts, attachments, thumbnail)?;
+    let (expected_frames, overhead, max_size) = (opened.expected_frames, opened.overhead, opened.max_size);
+    let expected = EncodeOptions { max_frames: expected_frames.map(|n| n.min(u32::MAX as u64) as u32), ..opts.clone() };
+
+    // 4) BA blob 생성 (raw, 코덱은 --compress)
+    // 크기가 정해지지 않은(긴) 입력은 프레임 단위로 임시 파일에 바로 기록해서
+    // blob 전체를 메모리에 들고 있지 않는다.
+    // 진행 표시: 읽은 프레임 수는 소스에서, blob 크기는 writer에서 센다
+    let mut progress = Progress::new(expected_frames.map(|n| n.saturating_sub(skipped)));
+    let bytes = progress.bytes_counter();
+    let mut source = ProgressSource { inner: &mut *opened.source, progress: &mut progress };
+    let mut tmp = None;
+    let encode_start = Instant::now();
+    let (attachment, stats) = match (checkpoint, max_blob_len(&expected)) {
+        // --checkpoint: blob은 checkpoint 옆 파일에 쓰고 (이어서 할 때는 checkpoint 길이로 잘라서) 그대로 첨부한다
+        (Some(ck), _) => {
+            // --jobs는 출력을 바꾸지 않으므로 다른 값으로 이어서 해도 된다
+            let fingerprint = Checkpoint::fingerprint(&format!("{}\n{:?}", settings, EncodeOptions { jobs: 1, parallel: true, ..opts.clone() }));
+            let options = CheckpointOptions { path: ck.path.clone(), every: ck.every, fingerprint };
+            let blob_path = Checkpoint::blob_path(&ck.path);
+            let mut file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&blob_path)
+                .with_context(|| format!("failed to open checkpoint blob {}", blob_path.display()))?;
+            match &resume {
+                Some(cp) => {
+                    let len = file.metadata()?.len();
+                    if len < cp.blob_len {
+                        bail!("checkpoint blob {} is {} bytes, shorter than the {} bytes in the checkpoint", blob_path.display(), len, cp.blob_len);
+                    }
+                    file.set_len(cp.blob_len)?;
+                    file.seek(SeekFrom::End(0))?;
+                }
+                None => file.set_len(0)?,
+            }
+            let stats = encode_blob_resumable(&mut source, opts, &mut CountingWriter::new(&mut file, bytes), &options, resume.as_ref())
+                .context("failed to encode video frames")?;
+            let att = Attachment::file(DEFAULT_BLOB_NAME, "application/octet-stream", &blob_path).with_description(BA_DESCRIPTION);
+            (att, stats)
+        }
+        (None, Some(n)) if n <= IN_MEMORY_BLOB_MAX => {
+            let mut blob = CountingWriter::new(Cursor::new(Vec::with_capacity(n as usize)), bytes);
+            let stats = encode_blob_to(&mut source, opts, &mut blob).context("failed to encode video frames")?;
+            let blob = blob.into_inner().into_inner();
+            (Attachment::bytes(DEFAULT_BLOB_NAME, "application/octet-stream", blob).with_description(BA_DESCRIPTION), stats)
+        }
+        (None, _) => {
+            let mut file = tempfile::NamedTempFile::new().context("failed to create temp file for BA blob")?;
+            let stats = encode_blob_to(&mut source, opts, &mut CountingWriter::new(file.as_file_mut(), bytes))
+                .context("failed to encode video frames")?;
+            let att = Attachment::file(DEFAULT_BLOB_NAME, "application/octet-stream", file.path()).with_description(BA_DESCRIPTION);
+            tmp = Some(file); // PDF를 저장할 때 여기서 복사한다
+            (att, stats)
+        }
+    };
+    let encode_time = encode_start.elapsed();
+    drop(progress);
+    opened.check_frames_read(stats.frames, opts, "no PDF written")?;
+    opened.report_inputs();
+    note!("BA blob (raw) bytes: {}", stats.bytes);
+    if opts.drop_duplicates {
+        note!(
+            "Dropped {} duplicate frames ({} stored of {})",
+            stats.dropped_frames(),
+            stats.stored_frames,
+            stats.frames
+        );
+    }
+    if opts.repeat_records {
+        note!(
+            "Stored {} repeated frames as repeat records ({} frame records of {})",
+            stats.dropped_frames(),
+            stats.stored_frames,
+            stats.frames
+        );
+    }
+    if let (Some(ms), Some(list)) = (stats.duration_ms, &opts.frame_durations_ms) {
+        note!(
+            "Frame durations: {} ({} at {:.2} fps)",
+            format_duration(ms as f64 / 1000.0),
+            format_duration(stats.frames as f64 / opts.fps as f64),
+            opts.fps
+        );
+        if list.len() > stats.frames as usize {
+            note!("warning: --frame-durations lists {} frames, only the first {} were used", list.len(), stats.frames);
+        }
+    }
+    if let (Some(ms), true) = (stats.duration_ms, opts.vfr) {
+        note!(
+            "Timestamps: {} frames over {} (average {:.2} fps; header fps {:.2} is advisory)",
+            stats.frames,
+            format_duration(ms as f64 / 1000.0),
+            if ms > 0 { stats.frames as f64 * 1000.0 / ms as f64 } else { 0.0 },
+            opts.fps
+        );
+    }
+    if let Polarity::Auto(sample) = opts.polarity {
+        note!(
+            "Polarity: auto picked {} set bits from the first {} frames",
+            if stats.white_ink { "white" } else { "black" },
+            sample.min(stats.frames)
+        );
+    }
+    if opts.denoise.is_enabled() {
+        let (before, after) = (stats.raw_delta_bits, stats.delta_bits);
+        note!(
+            "Denoise: {} changed pixels in the deltas instead of {} ({:.1}% fewer)",
+            after,
+            before,
+            if before > 0 { (before.saturating_sub(after)) as f64 / before as f64 * 100.0 } else { 0.0 }
+        );
+    }
+    note!("{}", encode_summary(&stats, encode_time, opts.drop_duplicates || opts.repeat_records));
+    if let Some(max) = max_size {
+        let total = stats.bytes + overhead;
+        if total > max {
+            bail!(
+                "--max-size {} exceeded: the encoded BA blob is {} bytes (~{} with attachments), the estimate was too low; no PDF written (try a smaller --max-size or --min-width/--min-fps)",
+                max,
+                stats.bytes,
+                total
+            );
+        }
+    }
+    Ok(EncodedBlob { attachment, stats, encode_time, tmp, checkpoint: checkpoint.map(|c| c.path.clone()) })
+}
+
+/// `--stats-only`: blob을 쓰지 않고 인코딩 루프를 돌려서 보고서를 stdout에 쓴다.
+/// `attachments`의 앞 `audio_tracks`개가 AU 첨부다 (나머지는 플레이어 파일).
+fn stats_video(
+    input: VideoInput,
+    opts: &mut EncodeOptions,
+    attachments: &[Attachment],
+    audio_tracks: usize,
+    thumbnail: bool,
+    format: StatsFormat,
+) -> Result<()> {
+    let mut opened = open_video(input, opts, attachments, thumbnail)?;
+    let mut progress = Progress::new(opened.expected_frames).with_label("Analyzing");
+    let stats = DiffStats::collect(&mut ProgressSource { inner: &mut *opened.source, progress: &mut progress }, opts)
         .context("failed to encode video frames")?;
-    eprintln!("BA blob (raw) bytes: {}", ba_blob.len());
+    drop(progress);
+    opened.check_frames_read(stats.encode.frames, opts, "nothing to report")?;
+    opened.report_inputs();
+    let mut audio_bytes = 0;
+    for au in &attachments[..audio_tracks] {
+        audio_bytes += au.data_len()?;
+    }
+    let overhead = PdfOverhead { audio_bytes, audio_tracks, other_bytes: opened.overhead - audio_bytes };
+    match format {
+        StatsFormat::Text => print!("{}", stats.text_report(&overhead)),
+        StatsFormat::Json => print!("{}", stats.json_report(&overhead)),
+    }
+    Ok(())
+}
 
-    // 2) AU bytes 읽기 (raw)
-    let au_raw = fs::read(&audio).context("failed to read audio file")?;
-    eprintln!("AU raw bytes: {}", au_raw.len());
+/// 인코딩 끝 요약 한 줄: 프레임 수 (keyframe은 첫 프레임 하나, 나머지는 XOR delta), 버린 중복, raw 대비 크기, 인코딩 속도.
+/// `drop_duplicates`는 `--drop-duplicates`나 `--repeat-records`로 중복을 저장하지 않았을 때다.
+fn encode_summary(stats: &EncodeStats, encode_time: Duration, drop_duplicates: bool) -> String {
+    let secs = encode_time.as_secs_f64();
+    let delta = stats.stored_frames.saturating_sub(1);
+    format!(
+        "Encoded {} frames (1 keyframe, {} delta{}) in {} ({:.1} fps): {} bytes of {} raw ({:.1}%)",
+        stats.frames,
+        delta,
+        if drop_duplicates { format!(", {} duplicates dropped", stats.dropped_frames()) } else { String::new() },
+        format_duration(secs),
+        if secs > 0.0 { stats.frames as f64 / secs } else { 0.0 },
+        stats.bytes,
+        stats.raw_bytes,
+        stats.compression_ratio() * 100.0
+    )
+}
 
-    // 3) PDF 생성 (attachments)
-    if let Some(parent) = out_pdf.parent() {
-        fs::create_dir_all(parent).ok();
+/// `--blob-in`: 이미 만든 blob을 끝까지 재생해서 검사하고 통계/포스터/flipbook을 다시 만든다.
+/// 크기/fps/bit depth/반전은 헤더를 따른다 (w/h/fps 인자는 auto이거나 헤더와 같아야 한다).
+/// 암호화된 blob은 인코딩할 때와 같은 `--encrypt` 키로 읽는다.
+fn load_blob(path: &Path, target: &TargetSize, opts: &mut EncodeOptions) -> Result<EncodedBlob> {
+    let data = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let header = parse_blob_header(&data).with_context(|| format!("--blob-in {} is not a BA blob", path.display()))?;
+    if target.width.is_some_and(|w| w != header.width)
+        || target.height.is_some_and(|h| h != header.height)
+        || target.fps.is_some_and(|f| (f - header.fps()).abs() > 0.005)
+    {
+        bail!(
+            "--blob-in {} is {}x{} @ {} fps; pass auto or the same w, h and fps",
+            path.display(),
+            header.width,
+            header.height,
+            header.fps()
+        );
     }
-    make_pdf(&out_pdf, &start_url, &ba_blob, &au_raw)?;
-    eprintln!("Wrote PDF: {}", out_pdf.display());
+    let key = match (&opts.encrypt, header.is_encrypted()) {
+        (Some(k), true) if k.cipher == header.cipher => Some(k.key.as_slice()),
+        (Some(_), true) | (None, true) => bail!(
+            "--blob-in {} is encrypted ({}); pass --encrypt {}:<hexkey> with the key it was encoded with",
+            path.display(),
+            header.cipher.name(),
+            header.cipher.name()
+        ),
+        (Some(_), false) => bail!("--encrypt cannot encrypt an existing blob; re-encode the video instead of --blob-in"),
+        (None, false) => None,
+    };
+    (opts.width, opts.height, opts.fps) = (header.width, header.height, header.fps());
+    opts.bit_depth = header.bit_depth;
+    opts.invert = header.invert;
+    let stats = blob_stats(&data, key, opts).with_context(|| format!("--blob-in {} failed verification", path.display()))?;
+    note!(
+        "Loaded BA blob: {} ({}x{} @ {} fps, {} frames, {} bytes)",
+        path.display(),
+        header.width,
+        header.height,
+        header.fps(),
+        stats.frames,
+        stats.bytes
+    );
+    Ok(EncodedBlob {
+        attachment: Attachment::file(DEFAULT_BLOB_NAME, "application/octet-stream", path).with_description(BA_DESCRIPTION),
+        stats,
+        encode_time: Duration::ZERO,
+        tmp: None,
+        checkpoint: None,
+    })
+}
 
+/// 첨부 데이터를 그대로 파일로 쓴다 (메모리면 write, 파일이면 copy)
+fn write_attachment(att: &Attachment, path: &Path) -> Result<()> {
+    match &att.data {
+        AttachmentData::Bytes(b) => fs::write(path, b),
+        AttachmentData::File(src) => fs::copy(src, path).map(|_| ()),
+    }
+    .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// `--blob-out`: 첨부할 BA.bin과 같은 바이트를 파일로 쓴다
+fn write_blob_out(att: &Attachment, path: &Path) -> Result<()> {
+    write_attachment(att, path)?;
+    note!("Wrote BA blob: {}", path.display());
+    Ok(())
+}
+
+/// `--dump-assets`: 첨부마다 `dir/<첨부 이름>`으로 쓴다 (`--chunk-size`면 조각 그대로, 암호화했으면 암호문)
+fn dump_attachments(attachments: &[Attachment], dir: &Path) -> Result<()> {
+    for att in attachments {
+        write_attachment(att, &dir.join(&att.name))?;
+    }
+    let names: Vec<&str> = attachments.iter().map(|a| a.name.as_str()).collect();
+    let plural = if names.len() == 1 { "" } else { "s" };
+    note!("Dumped {} asset{} to {}: {}", names.len(), plural, dir.display(), names.join(", "));
     Ok(())
 }
+
+/// `--encrypt-audio`: AU 첨부를 `BAAU` 헤더를 붙인 암호문으로 바꾼다. 이름과 `/Lang`은 그대로,
+/// MIME은 octet-stream (플레이어가 복호화한 뒤 magic bytes로 다시 판별한다)
+fn encrypt_audio_attachment(au: Attachment, key: &BlobKey) -> Result<Attachment> {
+    let plain = match &au.data {
+        AttachmentData::Bytes(b) => b.clone(),
+        AttachmentData::File(path) => fs::read(path).with_context(|| format!("failed to read {}", path.display()))?,
+    };
+    let mut enc = Attachment::bytes(&au.name, "application/octet-stream", encrypt_audio(key, &plain)?);
+    enc.options = au.options;
+    Ok(enc)
+}
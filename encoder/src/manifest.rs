@@ -0,0 +1,304 @@
+//! `manifest.json` 첨부: blob/오디오 요약과 SHA-256
+//!
+//! 플레이어나 외부 도구가 BA.bin 헤더를 직접 읽지 않고도 크기/fps/코덱을 알 수 있게 한다.
+//! 키 순서와 숫자 형식이 고정이라 같은 입력이면 바이트 단위로 같은 파일이 나온다.
+
+use crate::{
+    blob::{parse_blob_header, BlobHeader},
+    pdf::{js_string, Attachment, AttachmentData},
+    probe::probe_audio,
+};
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::{Read, Write},
+};
+
+/// EmbeddedFiles에 들어가는 이름
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// blob 헤더를 읽을 때 앞에서 가져오는 크기 (v2 + timing/frame CRC offset까지 충분)
+const HEAD_LEN: usize = 64;
+
+/// 첨부 하나의 크기와 SHA-256 (소문자 hex), 앞 `HEAD_LEN` bytes
+struct Digested {
+    bytes: u64,
+    sha256: String,
+    head: Vec<u8>,
+}
+
+fn digest(att: &Attachment) -> Result<Digested> {
+    let mut hasher = Sha256::new();
+    let mut head = Vec::new();
+    let mut bytes = 0u64;
+    let mut feed = |chunk: &[u8]| {
+        if head.len() < HEAD_LEN {
+            head.extend_from_slice(&chunk[..chunk.len().min(HEAD_LEN - head.len())]);
+        }
+        hasher.update(chunk);
+        bytes += chunk.len() as u64;
+    };
+    match &att.data {
+        AttachmentData::Bytes(b) => feed(b),
+        AttachmentData::File(path) => {
+            let mut f = File::open(path).with_context(|| format!("failed to read {}", path.display()))?;
+            let mut buf = vec![0u8; 64 * 1024];
+            loop {
+                let n = f.read(&mut buf).with_context(|| format!("failed to read {}", path.display()))?;
+                if n == 0 {
+                    break;
+                }
+                feed(&buf[..n]);
+            }
+        }
+    }
+    Ok(Digested { bytes, sha256: hex(&hasher.finalize()), head })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 첨부 크기와 SHA-256 (`verify_pdf`가 다시 읽은 첨부와 비교할 때, start_url의 `{blob_sha256}`)
+pub fn attachment_sha256(att: &Attachment) -> Result<(u64, String)> {
+    let d = digest(att)?;
+    Ok((d.bytes, d.sha256))
+}
+
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn json_opt<T>(v: Option<T>, f: impl FnOnce(T) -> String) -> String {
+    v.map_or_else(|| "null".to_string(), f)
+}
+
+/// AU 첨부 요약
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioManifest {
+    pub name: String,
+    /// 트랙 제목 (CLI는 입력 파일 이름)
+    pub title: String,
+    /// `--audio-lang` (첨부의 `/Lang`)
+    pub language: Option<String>,
+    pub mime: String,
+    /// ffprobe `codec_name` (ffprobe가 없으면 `None`)
+    pub codec: Option<String>,
+    /// 초 (ffprobe가 없으면 `None`)
+    pub duration: Option<f64>,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+impl AudioManifest {
+    /// 해시를 구하고 ffprobe로 코덱/길이를 읽는다. 메모리에 있는 첨부는 임시 파일에 써서 넘긴다.
+    pub fn from_attachment(att: &Attachment, title: &str) -> Result<Self> {
+        let d = digest(att)?;
+        let info = match &att.data {
+            AttachmentData::File(path) => probe_audio(path),
+            AttachmentData::Bytes(b) => {
+                let mut tmp = tempfile::NamedTempFile::new().context("failed to create temp file for audio probe")?;
+                tmp.write_all(b).context("failed to write temp file for audio probe")?;
+                probe_audio(tmp.path())
+            }
+        }
+        .unwrap_or_default();
+        Ok(Self {
+            name: att.name.clone(),
+            title: title.to_string(),
+            language: att.options.language.clone(),
+            mime: att.mime.clone(),
+            codec: info.codec,
+            duration: info.duration,
+            bytes: d.bytes,
+            sha256: d.sha256,
+        })
+    }
+}
+
+/// `manifest.json` 내용
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// blob 첨부 이름 (보통 `BA.bin`)
+    pub video_name: String,
+    pub header: BlobHeader,
+    /// 재생 프레임 수 (`--drop-duplicates`면 저장된 프레임 수 `header.frame_count`보다 많다)
+    pub frames: u32,
+    pub video_bytes: u64,
+    pub video_sha256: String,
+    /// `--chunk-size`로 나눈 조각 이름 (순서대로, 나누지 않았으면 비어 있다)
+    pub video_chunks: Vec<String>,
+    /// ms duration table 합 또는 timestamp table 끝 시각 (`EncodeStats::duration_ms`). 있으면 `duration_seconds`는 fps 대신 이 값이다.
+    pub duration_ms: Option<u64>,
+    /// 오디오 트랙 순서대로 (`--audio`로 여러 개면 `AU.ogg`, `AU_ja.ogg` 또는 `AU1.ogg`, ...)
+    pub audio: Vec<AudioManifest>,
+}
+
+impl Manifest {
+    /// `video`는 인코딩이 끝난 BA blob 첨부, `frames`는 `EncodeStats::frames`, `audio`는 (제목, AU 첨부).
+    pub fn new(video: &Attachment, frames: u32, audio: &[(&str, &Attachment)]) -> Result<Self> {
+        let d = digest(video)?;
+        let header = parse_blob_header(&d.head).context("failed to read BA header for manifest")?;
+        Ok(Self {
+            video_name: video.name.clone(),
+            header,
+            frames,
+            video_bytes: d.bytes,
+            video_sha256: d.sha256,
+            video_chunks: Vec::new(),
+            duration_ms: None,
+            audio: audio.iter().map(|(title, att)| AudioManifest::from_attachment(att, title)).collect::<Result<_>>()?,
+        })
+    }
+
+    /// 키 순서 고정 JSON. 이 blob은 keyframe이 첫 프레임 하나뿐이라 `keyframe_interval`은 0이다.
+    /// `chunks`는 나누지 않았으면 `null`, 나눴으면 이어 붙일 순서대로 조각 이름 (`bytes`/`sha256`은 이은 blob 기준).
+    /// `audio`는 첫 트랙 (없으면 `null`), `audio_tracks`는 전체 트랙 배열이다.
+    pub fn to_json(&self) -> String {
+        let h = &self.header;
+        let fps = h.fps_x100 as f64 / 100.0;
+        let compression = h.compression().map_or("unknown", |c| c.name());
+        let chunks = if self.video_chunks.is_empty() {
+            "null".to_string()
+        } else {
+            format!("[{}]", self.video_chunks.iter().map(|c| js_string(c)).collect::<Vec<_>>().join(", "))
+        };
+        let video = format!(
+            "{{\n    \"name\": {},\n    \"width\": {},\n    \"height\": {},\n    \"fps\": {:.2},\n    \"frame_count\": {},\n    \"stored_frames\": {},\n    \"duration_seconds\": {:.3},\n    \"bit_depth\": {},\n    \"compression\": {},\n    \"keyframe_interval\": 0,\n    \"invert\": {},\n    \"bytes\": {},\n    \"sha256\": {},\n    \"chunks\": {}\n  }}",
+            js_string(&self.video_name),
+            h.width,
+            h.height,
+            fps,
+            self.frames,
+            h.frame_count,
+            self.duration_ms.map_or(self.frames as f64 / fps, |ms| ms as f64 / 1000.0),
+            h.bit_depth.bits(),
+            js_string(compression),
+            h.invert,
+            self.video_bytes,
+            js_string(&self.video_sha256),
+            chunks
+        );
+        let audio_json = |a: &AudioManifest, indent: &str| {
+            format!(
+                "{{\n{i}  \"name\": {},\n{i}  \"title\": {},\n{i}  \"language\": {},\n{i}  \"mime\": {},\n{i}  \"codec\": {},\n{i}  \"duration_seconds\": {},\n{i}  \"bytes\": {},\n{i}  \"sha256\": {}\n{i}}}",
+                js_string(&a.name),
+                js_string(&a.title),
+                json_opt(a.language.as_deref(), js_string),
+                js_string(&a.mime),
+                json_opt(a.codec.as_deref(), js_string),
+                json_opt(a.duration, |d| format!("{:.3}", d)),
+                a.bytes,
+                js_string(&a.sha256),
+                i = indent
+            )
+        };
+        let audio = json_opt(self.audio.first(), |a| audio_json(a, "  "));
+        let tracks = if self.audio.is_empty() {
+            "[]".to_string()
+        } else {
+            let items: Vec<String> = self.audio.iter().map(|a| format!("    {}", audio_json(a, "    "))).collect();
+            format!("[\n{}\n  ]", items.join(",\n"))
+        };
+        format!(
+            "{{\n  \"encoder\": \"badapple_encoder\",\n  \"encoder_version\": {},\n  \"format_version\": {},\n  \"video\": {},\n  \"audio\": {},\n  \"audio_tracks\": {}\n}}\n",
+            js_string(env!("CARGO_PKG_VERSION")),
+            h.version,
+            video,
+            audio,
+            tracks
+        )
+    }
+
+    pub fn attachment(&self) -> Attachment {
+        Attachment::bytes(MANIFEST_NAME, "application/json", self.to_json().into_bytes())
+            .with_description("Summary of the embedded video and audio (sizes, codecs, SHA-256)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encode::{encode_blob, EncodeOptions},
+        source::RawSource,
+    };
+
+    /// 8x2 @ 25fps, 3 프레임
+    fn blob() -> Vec<u8> {
+        let frames: Vec<u8> = (0..3 * 16).map(|i| if i % 16 < i / 16 * 4 { 0 } else { 255 }).collect();
+        let opts = EncodeOptions { width: 8, height: 2, fps: 25.0, ..EncodeOptions::default() };
+        encode_blob(&mut RawSource::new(frames.as_slice(), true), &opts).unwrap()
+    }
+
+    fn track(name: &str, language: Option<&str>) -> AudioManifest {
+        AudioManifest {
+            name: name.to_string(),
+            title: "bad apple \"remix\".ogg".to_string(),
+            language: language.map(str::to_string),
+            mime: "audio/ogg".to_string(),
+            codec: Some("vorbis".to_string()),
+            duration: Some(219.0),
+            bytes: 4,
+            sha256: sha256_hex(b"OggS"),
+        }
+    }
+
+    #[test]
+    fn sha256_matches_known_digests() {
+        // FIPS 180-2 예제
+        let abc = Attachment::bytes("a.bin", "application/octet-stream", b"abc".to_vec());
+        assert_eq!(attachment_sha256(&abc).unwrap(), (3, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string()));
+        let empty = Attachment::bytes("e.bin", "application/octet-stream", Vec::new());
+        assert_eq!(attachment_sha256(&empty).unwrap().1, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        // 파일 첨부는 64 KiB씩 읽어도 같은 해시다
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("BA.bin");
+        std::fs::write(&path, &data).unwrap();
+        let from_file = attachment_sha256(&Attachment::file("BA.bin", "application/octet-stream", &path)).unwrap();
+        assert_eq!(from_file, (data.len() as u64, sha256_hex(&data)));
+    }
+
+    #[test]
+    fn json_is_deterministic() {
+        let blob = blob();
+        let video = Attachment::bytes("BA.bin", "application/octet-stream", blob.clone());
+        let mut manifest = Manifest::new(&video, 3, &[]).unwrap();
+        manifest.audio = vec![track("AU.ogg", None), track("AU_ja.ogg", Some("ja"))];
+        let again = Manifest { audio: manifest.audio.clone(), ..Manifest::new(&video, 3, &[]).unwrap() };
+        assert_eq!(manifest.to_json(), again.to_json());
+
+        let sha = sha256_hex(&blob);
+        let audio_sha = sha256_hex(b"OggS");
+        let track_json = |indent: &str, name: &str, language: &str| {
+            format!(
+                "{{\n{i}  \"name\": \"{}\",\n{i}  \"title\": \"bad apple \\\"remix\\\".ogg\",\n{i}  \"language\": {},\n{i}  \"mime\": \"audio/ogg\",\n{i}  \"codec\": \"vorbis\",\n{i}  \"duration_seconds\": 219.000,\n{i}  \"bytes\": 4,\n{i}  \"sha256\": \"{}\"\n{i}}}",
+                name,
+                language,
+                audio_sha,
+                i = indent
+            )
+        };
+        let expected = format!(
+            "{{\n  \"encoder\": \"badapple_encoder\",\n  \"encoder_version\": \"{}\",\n  \"format_version\": 1,\n  \"video\": {{\n    \"name\": \"BA.bin\",\n    \"width\": 8,\n    \"height\": 2,\n    \"fps\": 25.00,\n    \"frame_count\": 3,\n    \"stored_frames\": 3,\n    \"duration_seconds\": 0.120,\n    \"bit_depth\": 1,\n    \"compression\": \"none\",\n    \"keyframe_interval\": 0,\n    \"invert\": false,\n    \"bytes\": {},\n    \"sha256\": \"{}\",\n    \"chunks\": null\n  }},\n  \"audio\": {},\n  \"audio_tracks\": [\n    {},\n    {}\n  ]\n}}\n",
+            env!("CARGO_PKG_VERSION"),
+            blob.len(),
+            sha,
+            track_json("  ", "AU.ogg", "null"),
+            track_json("    ", "AU.ogg", "null"),
+            track_json("    ", "AU_ja.ogg", "\"ja\"")
+        );
+        assert_eq!(manifest.to_json(), expected);
+
+        // 조각 이름과 ms 길이, 오디오 없음
+        manifest.video_chunks = vec!["BA.000.bin".to_string(), "BA.001.bin".to_string()];
+        manifest.duration_ms = Some(1500);
+        manifest.audio.clear();
+        let json = manifest.to_json();
+        assert!(json.contains("\"chunks\": [\"BA.000.bin\", \"BA.001.bin\"]\n"), "{}", json);
+        assert!(json.contains("\"duration_seconds\": 1.500,"), "{}", json);
+        assert!(json.ends_with("\"audio\": null,\n  \"audio_tracks\": []\n}\n"), "{}", json);
+    }
+}
@@ -0,0 +1,1795 @@
+//! START 페이지 + EmbeddedFiles PDF 생성
+
+use crate::{
+    bits::{pack_pixels_rows, BitDepth},
+    progress::quiet,
+    writer::{self, FileStream, FileStreams},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use lopdf::{dictionary, Document, Object, Stream};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// PDF 페이지/링크 옵션
+#[derive(Debug, Clone, Default)]
+pub struct PdfOptions {
+    /// START 버튼이 여는 플레이어 URL
+    pub start_url: String,
+    /// 첨부된 플레이어 HTML의 이름. 있으면 START가 이 첨부를 먼저 열고,
+    /// 지원하지 않는 뷰어에서는 `start_url`로 넘어간다.
+    pub embedded_player: Option<String>,
+    /// 페이지에 그릴 포스터 프레임 (`--no-thumbnail`이면 `None`)
+    pub thumbnail: Option<Thumbnail>,
+    /// 썸네일을 비율 그대로 맞춰 넣을 영역 `[x1, y1, x2, y2]` (PDF point).
+    /// `None`이면 버튼 위 (`PageStyle::default_thumbnail_rect`)
+    pub thumbnail_rect: Option<[f64; 4]>,
+    /// 썸네일 대신 `thumbnail_rect`에 그릴 gray 미리보기 (`--preview-frame`, `thumbnail`과 같이 쓸 수 없다)
+    pub preview: Option<Preview>,
+    /// START 페이지 뒤에 프레임 하나씩 붙일 페이지 (`--flipbook`)
+    pub flipbook: Vec<FlipbookPage>,
+    /// 문서를 열 때 `start_url`로 바로 넘어갈지 (`--open-action`)
+    pub open_action: OpenAction,
+    /// 페이지 크기와 START 버튼 (모양, 위치)
+    pub page: PageStyle,
+    /// 목차(outline)와 named destination으로 넣을 장 (`--chapter`, 프레임 순서대로)
+    pub chapters: Vec<Chapter>,
+    /// 뷰어 창 설정 (`--viewer`, `--fullscreen`)
+    pub viewer: ViewerPreferences,
+    /// 헤더의 PDF 버전과 그에 맞춘 파일 구조 (`--pdf-version`)
+    pub version: PdfVersion,
+}
+
+/// 목차 항목 하나. destination은 그 프레임이 들어 있는 flipbook 페이지 (없으면 START 페이지)이고,
+/// 이름(`frame-<index>`)에 프레임 번호가 들어 있어서 플레이어가 바로 그 프레임으로 갈 수 있다.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    /// 재생 프레임 번호 (0부터)
+    pub frame_index: u32,
+}
+
+/// 장 named destination 이름 앞부분
+pub const CHAPTER_DEST_PREFIX: &str = "frame-";
+
+impl Chapter {
+    /// `/Dests` name tree key
+    pub fn dest_name(&self) -> String {
+        format!("{}{}", CHAPTER_DEST_PREFIX, self.frame_index)
+    }
+}
+
+/// 페이지 크기 (PDF point, `--page-size`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl PageSize {
+    pub const LETTER: PageSize = PageSize { width: 612.0, height: 792.0 };
+    pub const A4: PageSize = PageSize { width: 595.0, height: 842.0 };
+
+    /// 한 변이 0 이하이거나 `MAX_PAGE_SIDE`보다 크면 에러
+    pub fn new(width: f64, height: f64) -> Result<Self> {
+        if !(width > 0.0 && height > 0.0 && width <= MAX_PAGE_SIDE && height <= MAX_PAGE_SIDE) {
+            bail!("page size {}x{} must be between 1 and {} points on each side", width, height, MAX_PAGE_SIDE);
+        }
+        Ok(Self { width, height })
+    }
+
+    /// letter 기준으로 놓은 것을 이 페이지에 맞출 때 곱하는 비율 (가로/세로 비율 중 작은 쪽, letter면 1)
+    pub fn letter_scale(self) -> f64 {
+        (self.width / PageSize::LETTER.width).min(self.height / PageSize::LETTER.height)
+    }
+
+    /// 가로/세로를 바꾼다
+    pub fn landscape(self) -> Self {
+        Self { width: self.height, height: self.width }
+    }
+
+    fn media_box(self) -> Vec<Object> {
+        vec![0.into(), 0.into(), Object::Real(self.width as f32), Object::Real(self.height as f32)]
+    }
+}
+
+impl Default for PageSize {
+    fn default() -> Self {
+        PageSize::LETTER
+    }
+}
+
+/// PDF 페이지 한 변의 최대 크기 (PDF 1.7 implementation limit, 200 inch)
+const MAX_PAGE_SIDE: f64 = 14400.0;
+
+impl std::str::FromStr for PageSize {
+    type Err = anyhow::Error;
+
+    /// `letter`, `a4` (뒤에 `-landscape`를 붙이면 가로), `WxH` (point)
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, landscape) = match s.strip_suffix("-landscape") {
+            Some(name) => (name, true),
+            None => (s, false),
+        };
+        let size = match name {
+            "letter" => PageSize::LETTER,
+            "a4" => PageSize::A4,
+            _ if landscape => bail!("unknown page size '{}' (expected letter, a4, letter-landscape, a4-landscape or WxH)", s),
+            _ => {
+                let invalid = || anyhow!("invalid page size '{}' (expected letter, a4, letter-landscape, a4-landscape or WxH)", s);
+                let (w, h) = s.split_once('x').ok_or_else(invalid)?;
+                PageSize::new(w.trim().parse().map_err(|_| invalid())?, h.trim().parse().map_err(|_| invalid())?)?
+            }
+        };
+        Ok(if landscape { size.landscape() } else { size })
+    }
+}
+
+/// `--button-anchor`: 페이지 안 버튼 위치 (가장자리에서 `ANCHOR_MARGIN`만큼 띄운다)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+// anchor로 놓을 때 페이지 가장자리와 버튼 사이 (PDF point)
+const ANCHOR_MARGIN: f64 = 36.0;
+
+impl std::str::FromStr for Anchor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "center" => Anchor::Center,
+            "top" => Anchor::Top,
+            "bottom" => Anchor::Bottom,
+            "left" => Anchor::Left,
+            "right" => Anchor::Right,
+            "top-left" => Anchor::TopLeft,
+            "top-right" => Anchor::TopRight,
+            "bottom-left" => Anchor::BottomLeft,
+            "bottom-right" => Anchor::BottomRight,
+            _ => bail!(
+                "unknown anchor '{}' (expected center, top, bottom, left, right, top-left, top-right, bottom-left, bottom-right)",
+                s
+            ),
+        })
+    }
+}
+
+/// 버튼 영역을 정하는 방법
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ButtonPlacement {
+    /// `[x1, y1, x2, y2]` 그대로 (`--button-rect`)
+    Rect([f64; 4]),
+    /// letter 기준 `[x1, y1, x2, y2]`를 페이지에 맞춘다: 가운데 점은 페이지 안 같은 비율 자리로 옮기고,
+    /// 크기는 `PageSize::letter_scale`만큼 줄이거나 키운다 (기본값, letter에서는 그대로)
+    Scaled([f64; 4]),
+    /// 페이지 크기에 맞춰 `anchor` 자리에 `width x height` (`--button-anchor`, `--button-size`)
+    Anchor { anchor: Anchor, width: f64, height: f64 },
+}
+
+impl Default for ButtonPlacement {
+    fn default() -> Self {
+        ButtonPlacement::Scaled(DEFAULT_BUTTON_RECT)
+    }
+}
+
+impl ButtonPlacement {
+    /// `page` 위의 `[x1, y1, x2, y2]`
+    pub fn resolve(self, page: PageSize) -> [f64; 4] {
+        let (anchor, w, h) = match self {
+            ButtonPlacement::Rect(rect) => return rect,
+            ButtonPlacement::Scaled([x1, y1, x2, y2]) => {
+                let s = page.letter_scale();
+                let cx = (x1 + x2) / 2.0 * page.width / PageSize::LETTER.width;
+                let cy = (y1 + y2) / 2.0 * page.height / PageSize::LETTER.height;
+                let (hw, hh) = ((x2 - x1) / 2.0 * s, (y2 - y1) / 2.0 * s);
+                return [cx - hw, cy - hh, cx + hw, cy + hh];
+            }
+            ButtonPlacement::Anchor { anchor, width, height } => (anchor, width, height),
+        };
+        let (left, center_x, right) = (ANCHOR_MARGIN, (page.width - w) / 2.0, page.width - ANCHOR_MARGIN - w);
+        let (bottom, center_y, top) = (ANCHOR_MARGIN, (page.height - h) / 2.0, page.height - ANCHOR_MARGIN - h);
+        let (x, y) = match anchor {
+            Anchor::Center => (center_x, center_y),
+            Anchor::Top => (center_x, top),
+            Anchor::Bottom => (center_x, bottom),
+            Anchor::Left => (left, center_y),
+            Anchor::Right => (right, center_y),
+            Anchor::TopLeft => (left, top),
+            Anchor::TopRight => (right, top),
+            Anchor::BottomLeft => (left, bottom),
+            Anchor::BottomRight => (right, bottom),
+        };
+        [x, y, x + w, y + h]
+    }
+}
+
+/// START 페이지 모양: 페이지 크기, 버튼 글자/색, 버튼 위치. `Default`는 letter에 원래 버튼.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PageStyle {
+    pub size: PageSize,
+    pub button: ButtonStyle,
+    /// 버튼 영역. Link annotation도 항상 같은 영역이다.
+    pub button_place: ButtonPlacement,
+}
+
+// 기본 썸네일 영역의 폭, 버튼/페이지 위쪽과의 간격 (letter에서 `DEFAULT_THUMBNAIL_RECT`가 된다)
+const THUMBNAIL_WIDTH: f64 = 400.0;
+const THUMBNAIL_GAP_BELOW: f64 = 30.0;
+const THUMBNAIL_GAP_ABOVE: f64 = 52.0;
+
+impl PageStyle {
+    /// 그려지는 버튼 (= Link annotation) 영역
+    pub fn button_rect(&self) -> [f64; 4] {
+        self.button_place.resolve(self.size)
+    }
+
+    /// `--poster-rect`가 없을 때 썸네일 영역: 버튼 위부터 페이지 위쪽 여백까지, 가운데 400pt 폭
+    /// (폭과 간격은 `letter_scale`을 곱하고, 페이지가 좁으면 양옆 36pt 여백까지). 버튼 위에 자리가 없으면 빈 영역이 된다.
+    pub fn default_thumbnail_rect(&self) -> [f64; 4] {
+        let [_, _, _, button_top] = self.button_rect();
+        let s = self.size.letter_scale();
+        let w = (THUMBNAIL_WIDTH * s).min(self.size.width - 2.0 * ANCHOR_MARGIN);
+        let x = (self.size.width - w) / 2.0;
+        [x, button_top + THUMBNAIL_GAP_BELOW * s, x + w, self.size.height - THUMBNAIL_GAP_ABOVE * s]
+    }
+}
+
+/// START 버튼 모양. `Default`가 원래 모양(회색 바탕, 검은 테두리, Helvetica 36pt "START")이다.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ButtonStyle {
+    /// 버튼 글자 (`--button-label`). 기본 14 폰트는 printable ASCII만 그릴 수 있어서,
+    /// 다른 글자(한글 등)가 있으면 `KOREAN_FONTS[0]`으로 바꿔 그린다.
+    pub label: String,
+    /// 바탕 gray (0 = 검정, 1 = 흰색)
+    pub fill_gray: f64,
+    /// 테두리와 글자 gray
+    pub stroke_gray: f64,
+    /// 글자 크기 (pt)
+    pub font_size: f64,
+    /// PDF 기본 14 폰트 이름 (`STANDARD_FONTS` 중 하나) 또는 `KOREAN_FONTS` 중 하나
+    pub font: String,
+    /// Link annotation에 같은 버튼을 그린 `/AP /N` appearance stream을 붙인다 (`--button-appearance`).
+    /// 페이지 content 대신 annotation 모양을 그리는 뷰어에서도 버튼이 보인다.
+    pub appearance: bool,
+    /// Link annotation에 뷰어가 그리는 테두리(`/BS` 2pt 실선, 색은 `stroke_gray`)를 켠다 (`--button-border`).
+    /// 끄면 `/Border [0 0 0]` (테두리 없음).
+    pub border: bool,
+}
+
+impl Default for ButtonStyle {
+    fn default() -> Self {
+        Self {
+            label: DEFAULT_BUTTON_TEXT.to_string(),
+            fill_gray: DEFAULT_BUTTON_FILL_GRAY,
+            stroke_gray: 0.0,
+            font_size: DEFAULT_BUTTON_FONT_SIZE,
+            font: "Helvetica".to_string(),
+            appearance: false,
+            border: false,
+        }
+    }
+}
+
+/// 문서를 열 때 실행할 catalog `/OpenAction`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenAction {
+    /// 없음 (START를 눌러야 한다)
+    #[default]
+    None,
+    /// `/S /URI` action
+    Url,
+    /// `app.launchURL(...)` JavaScript action (`/Names/JavaScript`에도 등록)
+    Js,
+    /// START 버튼과 같은 action (`--autoplay`: 첨부 플레이어가 있으면 그걸 연다)
+    Start,
+}
+
+impl std::str::FromStr for OpenAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "none" => OpenAction::None,
+            "url" => OpenAction::Url,
+            "js" => OpenAction::Js,
+            "start" => OpenAction::Start,
+            _ => bail!("unknown open action '{}' (expected url, js, start, none)", s),
+        })
+    }
+}
+
+/// catalog `/ViewerPreferences` (`--viewer`, `--fullscreen`). 전부 `false`면 넣지 않는다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ViewerPreferences {
+    /// `/HideToolbar`
+    pub hide_toolbar: bool,
+    /// `/HideMenubar`
+    pub hide_menubar: bool,
+    /// `/HideWindowUI` (스크롤바, 탐색 컨트롤)
+    pub hide_window_ui: bool,
+    /// `/FitWindow`. 켜면 catalog에 `/PageLayout /SinglePage`, `/PageMode /FullScreen`도 넣는다.
+    pub fit_window: bool,
+    /// `/CenterWindow`
+    pub center_window: bool,
+    /// `/DisplayDocTitle` (문서에 Info `/Title`이 없으면 뷰어는 그대로 파일 이름을 보여준다)
+    pub display_title: bool,
+}
+
+impl ViewerPreferences {
+    /// `--fullscreen`: 모든 항목을 켠다
+    pub fn all() -> Self {
+        Self { hide_toolbar: true, hide_menubar: true, hide_window_ui: true, fit_window: true, center_window: true, display_title: true }
+    }
+
+    pub fn is_set(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// 켠 항목만 넣은 `/ViewerPreferences` dictionary
+    fn dictionary(&self) -> lopdf::Dictionary {
+        let mut dict = lopdf::Dictionary::new();
+        for (key, on) in [
+            ("HideToolbar", self.hide_toolbar),
+            ("HideMenubar", self.hide_menubar),
+            ("HideWindowUI", self.hide_window_ui),
+            ("FitWindow", self.fit_window),
+            ("CenterWindow", self.center_window),
+            ("DisplayDocTitle", self.display_title),
+        ] {
+            if on {
+                dict.set(key, true);
+            }
+        }
+        dict
+    }
+}
+
+/// `--viewer hide-toolbar,fit-window,...` (`all`은 `--fullscreen`과 같다)
+impl std::str::FromStr for ViewerPreferences {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut prefs = Self::default();
+        for flag in s.split(',').map(str::trim) {
+            match flag {
+                "hide-toolbar" => prefs.hide_toolbar = true,
+                "hide-menubar" => prefs.hide_menubar = true,
+                "hide-window-ui" => prefs.hide_window_ui = true,
+                "fit-window" => prefs.fit_window = true,
+                "center-window" => prefs.center_window = true,
+                "display-title" => prefs.display_title = true,
+                "all" => prefs = Self::all(),
+                _ => bail!(
+                    "unknown viewer preference '{}' (expected hide-toolbar, hide-menubar, hide-window-ui, fit-window, center-window, display-title or all)",
+                    flag
+                ),
+            }
+        }
+        Ok(prefs)
+    }
+}
+
+/// 출력 PDF 버전 (`--pdf-version`, 기본 1.7)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PdfVersion {
+    /// 1.4: xref stream 대신 예전 xref table + trailer, 1.5 이후 key(`/UF`, `/AF`)는 넣지 않는다
+    V14,
+    V15,
+    #[default]
+    V17,
+    /// 2.0 (ISO 32000-2): deprecated인 Info 대신 XMP metadata에 Producer를 넣는다
+    V20,
+}
+
+/// XMP metadata의 `pdf:Producer` (`PdfVersion::V20`)
+pub const PDF_PRODUCER: &str = concat!("badapple_encoder ", env!("CARGO_PKG_VERSION"));
+
+impl PdfVersion {
+    /// `%PDF-` 헤더에 쓰는 값
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PdfVersion::V14 => "1.4",
+            PdfVersion::V15 => "1.5",
+            PdfVersion::V17 => "1.7",
+            PdfVersion::V20 => "2.0",
+        }
+    }
+
+    /// xref stream(1.5+)으로 저장할지. 아니면 xref table
+    pub fn xref_stream(self) -> bool {
+        self != PdfVersion::V14
+    }
+}
+
+impl std::str::FromStr for PdfVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "1.4" => PdfVersion::V14,
+            "1.5" => PdfVersion::V15,
+            "1.7" => PdfVersion::V17,
+            "2.0" | "2" => PdfVersion::V20,
+            _ => bail!("unknown PDF version '{}' (expected 1.4, 1.5, 1.7 or 2.0)", s),
+        })
+    }
+}
+
+/// `--flipbook` 페이지 하나: 입력 프레임 번호와 그 프레임 이미지
+#[derive(Debug, Clone)]
+pub struct FlipbookPage {
+    pub frame: u32,
+    pub image: Thumbnail,
+}
+
+// flipbook 페이지 여백 (아래쪽은 프레임 번호 자리라 두 배)
+const FLIPBOOK_MARGIN: f64 = 36.0;
+
+/// 썸네일 이미지: 행 정렬 packed 프레임 (stride = ceil(width * bit_depth / 8), MSB-first).
+/// 1-bit는 1=black, 4-bit는 0=black..15=white (`invert`면 반대).
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    pub width: u16,
+    pub height: u16,
+    pub bit_depth: BitDepth,
+    /// blob의 `--invert` 플래그와 같이 흑백을 뒤집어서 그린다
+    pub invert: bool,
+    pub bits: Vec<u8>,
+}
+
+/// `--preview-frame`: 이진화 전 gray 프레임. START 페이지 content stream에
+/// 4-bit inline image(`BI ... ID ... EI`)로 바로 넣어서 XObject 없이 그린다.
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub width: u16,
+    pub height: u16,
+    /// gray8, `width * height` bytes (0 = black)
+    pub gray: Vec<u8>,
+    /// blob의 `--invert` 플래그와 같이 흑백을 뒤집어서 그린다
+    pub invert: bool,
+}
+
+/// 기본 썸네일 영역 (START 버튼 위, PDF point)
+pub const DEFAULT_THUMBNAIL_RECT: [f64; 4] = [106.0, 490.0, 506.0, 740.0];
+/// 기본 START 버튼 영역 (PDF point)
+pub const DEFAULT_BUTTON_RECT: [f64; 4] = [156.0, 360.0, 456.0, 460.0];
+pub const DEFAULT_BUTTON_TEXT: &str = "START";
+pub const DEFAULT_BUTTON_FONT_SIZE: f64 = 36.0;
+pub const DEFAULT_BUTTON_FILL_GRAY: f64 = 0.9;
+
+/// 버튼에 쓸 수 있는 PDF 기본 14 폰트 (글자 폰트만, Symbol/ZapfDingbats 제외)
+pub const STANDARD_FONTS: [&str; 12] = [
+    "Helvetica",
+    "Helvetica-Bold",
+    "Helvetica-Oblique",
+    "Helvetica-BoldOblique",
+    "Times-Roman",
+    "Times-Bold",
+    "Times-Italic",
+    "Times-BoldItalic",
+    "Courier",
+    "Courier-Bold",
+    "Courier-Oblique",
+    "Courier-BoldOblique",
+];
+
+/// 한글 label용 CJK 폰트 (Adobe-Korea1 `UniKS-UCS2-H`). 임베드하지 않아서 뷰어가 가진 한글 글꼴로 그린다.
+pub const KOREAN_FONTS: [&str; 2] = ["HYGoThic-Medium", "HYSMyeongJo-Medium"];
+
+// Korea1 폰트 폭 어림값 (1/1000 em): ASCII는 proportional, 나머지(한글/한자)는 전각
+const KOREAN_ASCII_WIDTH: u32 = 500;
+const KOREAN_WIDE_WIDTH: u32 = 1000;
+
+// Helvetica glyph 폭 (1/1000 em, StandardEncoding 0x20..=0x7e, AFM 기준)
+#[rustfmt::skip]
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 222, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    222, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+// Courier는 고정폭 (1/1000 em)
+const COURIER_WIDTH: u32 = 600;
+
+/// `text`를 `font` `size` pt로 그렸을 때 폭 (기본 14 폰트는 printable ASCII만).
+/// Courier는 정확하고, 나머지는 Helvetica 폭으로, 한글 폰트는 ASCII 반각/나머지 전각으로 어림한다 (가운데 정렬용).
+fn text_width(font: &str, text: &str, size: f64) -> f64 {
+    let units: u32 = if KOREAN_FONTS.contains(&font) {
+        text.chars().map(|c| if c.is_ascii() { KOREAN_ASCII_WIDTH } else { KOREAN_WIDE_WIDTH }).sum()
+    } else if font.starts_with("Courier") {
+        COURIER_WIDTH * text.len() as u32
+    } else {
+        text.bytes().map(|b| HELVETICA_WIDTHS[(b - 0x20) as usize] as u32).sum()
+    };
+    units as f64 * size / 1000.0
+}
+
+/// cap height (1/1000 em, AFM 기준)
+fn cap_height(font: &str) -> f64 {
+    if KOREAN_FONTS.contains(&font) {
+        720.0 // 한글 글자 높이 어림
+    } else if font.starts_with("Courier") {
+        562.0
+    } else if font.starts_with("Times") {
+        662.0
+    } else {
+        718.0
+    }
+}
+
+/// Type1 기본 폰트 object
+fn add_font(doc: &mut Document, base_font: &str) -> lopdf::ObjectId {
+    doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => base_font,
+    })
+}
+
+/// 한글 label용 Type0 폰트 (`KOREAN_FONTS`, 임베드하지 않는 CIDFontType0).
+/// `/W`는 ASCII 자리(CID 1..=95)를 `text_width`와 같은 반각으로 맞춘다.
+fn add_korean_font(doc: &mut Document, base_font: &str) -> lopdf::ObjectId {
+    let descriptor = doc.add_object(dictionary! {
+        "Type" => "FontDescriptor",
+        "FontName" => Object::Name(base_font.as_bytes().to_vec()),
+        "Flags" => 6,
+        "FontBBox" => vec![(-6).into(), (-145).into(), 1003.into(), 880.into()],
+        "ItalicAngle" => 0,
+        "Ascent" => 880,
+        "Descent" => -120,
+        "CapHeight" => 720,
+        "StemV" => 93,
+    });
+    let cid_font = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "CIDFontType0",
+        "BaseFont" => Object::Name(base_font.as_bytes().to_vec()),
+        "CIDSystemInfo" => dictionary! {
+            "Registry" => Object::string_literal("Adobe"),
+            "Ordering" => Object::string_literal("Korea1"),
+            "Supplement" => 1,
+        },
+        "FontDescriptor" => Object::Reference(descriptor),
+        "DW" => KOREAN_WIDE_WIDTH as i64,
+        "W" => vec![1.into(), 95.into(), (KOREAN_ASCII_WIDTH as i64).into()],
+    });
+    doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => Object::Name(base_font.as_bytes().to_vec()),
+        "Encoding" => "UniKS-UCS2-H",
+        "DescendantFonts" => vec![Object::Reference(cid_font)],
+    })
+}
+
+/// 버튼 label을 그릴 폰트: `button.font`가 기본 14 폰트인데 label이 printable ASCII가 아니면 `KOREAN_FONTS[0]`
+fn label_font(button: &ButtonStyle) -> Result<&str> {
+    let font = button.font.as_str();
+    let korean = KOREAN_FONTS.contains(&font);
+    if !korean && !STANDARD_FONTS.contains(&font) {
+        bail!(
+            "unknown button font '{}' (expected one of {}, {})",
+            font,
+            STANDARD_FONTS.join(", "),
+            KOREAN_FONTS.join(", ")
+        );
+    }
+    if let Some(c) = button.label.chars().find(|c| c.is_control() || *c as u32 > 0xffff) {
+        bail!("button label {:?} contains {:?}, which the button cannot draw", button.label, c);
+    }
+    if korean || button.label.bytes().all(|b| (0x20..=0x7e).contains(&b)) {
+        Ok(font)
+    } else {
+        Ok(KOREAN_FONTS[0])
+    }
+}
+
+/// `Tj` 피연산자: 기본 14 폰트는 escape한 literal string, 한글 폰트는 UCS-2 big-endian hex string
+fn label_operand(font: &str, text: &str) -> String {
+    if KOREAN_FONTS.contains(&font) {
+        let hex: String = text.encode_utf16().map(|u| format!("{:04X}", u)).collect();
+        format!("<{}>", hex)
+    } else {
+        format!("({})", escape_pdf_literal(text))
+    }
+}
+
+/// 영역이 비어 있지 않고 페이지 안에 들어가는지
+fn check_rect(what: &str, rect: [f64; 4], page: PageSize) -> Result<()> {
+    let [x1, y1, x2, y2] = rect;
+    if !(x2 > x1 && y2 > y1) {
+        bail!("{} rect [{} {} {} {}] is empty", what, x1, y1, x2, y2);
+    }
+    if x1 < 0.0 || y1 < 0.0 || x2 > page.width || y2 > page.height {
+        bail!("{} rect [{} {} {} {}] is outside the {}x{} page", what, x1, y1, x2, y2, page.width, page.height);
+    }
+    Ok(())
+}
+
+/// PDF literal string 안에 넣을 수 있게 `(`, `)`, `\`를 escape한다.
+fn escape_pdf_literal(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '(' | ')' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// 썸네일 → `/DeviceGray` image XObject (BitsPerComponent = bit depth).
+/// 1-bit는 bit 1이 black이므로 `/Decode [1 0]`으로 뒤집는다 (DeviceGray는 0=black).
+/// `invert`면 그 반대 (`/Decode`를 한 번 더 뒤집는다).
+fn add_thumbnail(doc: &mut Document, thumb: &Thumbnail) -> Result<lopdf::ObjectId> {
+    let bpc = thumb.bit_depth.bits();
+    let stride = (thumb.width as usize * bpc).div_ceil(8);
+    if thumb.width == 0 || thumb.height == 0 || thumb.bits.len() != stride * thumb.height as usize {
+        bail!("thumbnail is {} bytes, expected {} for {}x{}", thumb.bits.len(), stride * thumb.height as usize, thumb.width, thumb.height);
+    }
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => thumb.width as i64,
+        "Height" => thumb.height as i64,
+        "ColorSpace" => "DeviceGray",
+        "BitsPerComponent" => bpc as i64,
+        "Interpolate" => false,
+    };
+    if (thumb.bit_depth == BitDepth::Binary) != thumb.invert {
+        dict.set("Decode", vec![1.into(), 0.into()]);
+    }
+    let mut stream = Stream::new(dict, thumb.bits.clone());
+    stream.compress().context("failed to compress thumbnail")?;
+    let id = doc.new_object_id();
+    doc.objects.insert(id, Object::Stream(stream));
+    Ok(id)
+}
+
+/// `width x height` 이미지를 비율 그대로 `rect` 안 가운데 아래쪽에 맞추는 `cm` 연산자
+fn fit_matrix(width: u16, height: u16, rect: [f64; 4]) -> String {
+    let [bx1, by1, bx2, by2] = rect;
+    let scale = ((bx2 - bx1) / width as f64).min((by2 - by1) / height as f64);
+    let (w, h) = (width as f64 * scale, height as f64 * scale);
+    let x = bx1 + ((bx2 - bx1) - w) / 2.0;
+    format!("{:.3} 0 0 {:.3} {:.3} {:.3} cm", w, h, x, by1)
+}
+
+/// START 버튼을 `rect`에 그리는 content (바탕, 테두리, 가운데 정렬 글자).
+/// 페이지 content와 `/AP` appearance stream(`rect`가 `[0, 0, w, h]`)이 같이 쓴다.
+fn button_content(button: &ButtonStyle, font: &str, label_font: &str, rect: [f64; 4]) -> String {
+    let [x1, y1, x2, y2] = rect;
+    let (text, size) = (&button.label, button.font_size);
+    let text_w = text_width(label_font, text, size);
+    format!(
+        "q\n\
+         {fill} g\n\
+         {x1} {y1} {w} {h} re\n\
+         f\n\
+         {stroke} G\n\
+         {stroke} g\n\
+         2 w\n\
+         {x1} {y1} {w} {h} re\n\
+         S\n\
+         BT\n\
+         /{font} {size} Tf\n\
+         {tx:.2} {ty:.2} Td\n\
+         {text} Tj\n\
+         ET\n\
+         Q\n",
+        x1 = x1,
+        y1 = y1,
+        w = x2 - x1,
+        h = y2 - y1,
+        fill = button.fill_gray,
+        stroke = button.stroke_gray,
+        font = font,
+        size = size,
+        tx = x1 + (x2 - x1 - text_w) / 2.0,
+        ty = y1 + (y2 - y1 - cap_height(label_font) * size / 1000.0) / 2.0,
+        text = label_operand(label_font, text)
+    )
+}
+
+/// 썸네일을 비율 그대로 `rect` 안 가운데 아래쪽에 맞춰 그리는 content 조각
+fn thumbnail_content(thumb: &Thumbnail, rect: [f64; 4]) -> String {
+    format!("q\n{}\n/Im1 Do\nQ\n", fit_matrix(thumb.width, thumb.height, rect))
+}
+
+/// 미리보기를 `rect`에 inline image로 그리는 content 조각. 데이터는 4-bit로 줄여서 행마다 바이트 경계까지 채운다
+/// (DeviceGray라 0=black..15=white 그대로, `invert`면 `/D [1 0]`).
+fn preview_content(preview: &Preview, rect: [f64; 4]) -> Result<Vec<u8>> {
+    let (w, h) = (preview.width as usize, preview.height as usize);
+    if w == 0 || h == 0 || preview.gray.len() != w * h {
+        bail!("preview frame is {} bytes, expected {} for {}x{}", preview.gray.len(), w * h, w, h);
+    }
+    let mut out = format!(
+        "q\n{}\nBI\n/W {} /H {} /CS /DeviceGray /BPC 4{}\nID\n",
+        fit_matrix(preview.width, preview.height, rect),
+        w,
+        h,
+        if preview.invert { " /D [1 0]" } else { "" }
+    )
+    .into_bytes();
+    out.extend(pack_pixels_rows(&preview.gray, w, BitDepth::Gray4));
+    out.extend_from_slice(b"\nEI\nQ\n");
+    Ok(out)
+}
+
+/// flipbook 페이지 하나 (이미지 + 아래쪽에 프레임 번호)
+fn add_flipbook_page(
+    doc: &mut Document,
+    page: &FlipbookPage,
+    pages_id: lopdf::ObjectId,
+    font_id: lopdf::ObjectId,
+    size: PageSize,
+) -> Result<lopdf::ObjectId> {
+    let image_id = add_thumbnail(doc, &page.image)?;
+    let m = FLIPBOOK_MARGIN;
+    let content = format!(
+        "{}BT\n/F1 12 Tf\n{} 40 Td\n(frame {}) Tj\nET\n",
+        thumbnail_content(&page.image, [m, 2.0 * m, size.width - m, size.height - m]),
+        m,
+        page.frame
+    );
+    let mut contents = Stream::new(dictionary! {}, content.into_bytes());
+    contents.compress().context("failed to compress flipbook page")?;
+    let contents_id = doc.new_object_id();
+    doc.objects.insert(contents_id, Object::Stream(contents));
+    let page_id = doc.new_object_id();
+    doc.objects.insert(
+        page_id,
+        Object::Dictionary(dictionary! {
+        "Type" => "Page",
+        "Parent" => Object::Reference(pages_id),
+        "MediaBox" => size.media_box(),
+        "Resources" => dictionary! {
+            "Font" => dictionary! { "F1" => Object::Reference(font_id) },
+            "XObject" => dictionary! { "Im1" => Object::Reference(image_id) },
+        },
+        "Contents" => Object::Reference(contents_id),
+        }),
+    );
+    Ok(page_id)
+}
+
+/// 확장자로 첨부 MIME 타입을 고른다 (플레이어 HTML/JS/CSS용).
+pub fn mime_for_name(name: &str) -> &'static str {
+    let ext = name.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase()).unwrap_or_default();
+    match ext.as_str() {
+        "html" | "htm" => "text/html",
+        "js" | "mjs" => "text/javascript",
+        "css" => "text/css",
+        "json" => "application/json",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// `--blob-name` / `--audio-name` 값 검사: 비어 있지 않고, 경로 구분자나 제어 문자가 없는 파일 이름
+/// (`--dump-assets`/`extract`가 이 이름 그대로 파일을 쓴다)
+pub fn parse_attachment_name(s: &str) -> Result<String> {
+    if s.is_empty() || s == "." || s == ".." {
+        bail!("invalid attachment name '{}'", s);
+    }
+    if s.chars().any(|c| c == '/' || c == '\\' || c.is_control()) {
+        bail!("invalid attachment name '{}' (must be a plain file name without path separators)", s);
+    }
+    Ok(s.to_string())
+}
+
+/// JavaScript 문자열 리터럴 (큰따옴표 포함, ASCII만 쓰므로 JSON 문자열로도 쓴다)
+pub(crate) fn js_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for u in s.encode_utf16() {
+        match u {
+            0x22 => out.push_str("\\\""),
+            0x5c => out.push_str("\\\\"),
+            0x20..=0x7e => out.push(u as u8 as char),
+            _ => out.push_str(&format!("\\u{:04x}", u)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// RFC 3986에서 URI에 그대로 쓸 수 있는 글자 (unreserved + reserved, `%`와 `#`은 따로 본다)
+fn is_uri_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~:/?[]@!$&'()*+,;=".contains(&b)
+}
+
+/// `start_url`을 `/URI` action에 넣을 수 있는 절대 http/https URI로 만든다.
+/// URI에 쓸 수 없는 글자(공백, `\`, `"`, `<>`, 제어 문자, ASCII가 아닌 글자의 UTF-8 bytes 등)는 percent-encode 하고,
+/// 이미 있는 `%XX`는 그대로 둔다. 첫 `#` 뒤는 fragment이고 그 뒤의 `#`은 `%23`이 된다.
+/// 이미 올바른 URI면 그대로 돌려준다. 호스트는 ASCII여야 한다 (IDN은 `xn--` punycode로).
+pub fn normalize_start_url(url: &str) -> Result<String> {
+    let lower = url.to_ascii_lowercase();
+    let Some(scheme_len) = ["http://", "https://"].iter().find(|p| lower.starts_with(*p)).map(|p| p.len()) else {
+        bail!("start_url '{}' must be an absolute http:// or https:// URL", url);
+    };
+    let rest = &url[scheme_len..];
+    let authority = &rest[..rest.find(['/', '?', '#']).unwrap_or(rest.len())];
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    let (host, port) = match host_port.strip_prefix('[') {
+        Some(v6) => match v6.split_once(']') {
+            Some((addr, port)) if !addr.is_empty() && addr.bytes().all(|b| b.is_ascii_hexdigit() || b == b':' || b == b'.') => {
+                (addr, port.strip_prefix(':'))
+            }
+            _ => bail!("start_url '{}' has an invalid IPv6 host", url),
+        },
+        None => match host_port.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        },
+    };
+    if host.is_empty() {
+        bail!("start_url '{}' has no host", url);
+    }
+    if !host_port.starts_with('[') && !host.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.') {
+        bail!("start_url '{}' has an invalid host '{}' (non-ASCII host names must be written as xn-- punycode)", url, host);
+    }
+    if port.is_some_and(|p| !p.bytes().all(|b| b.is_ascii_digit())) {
+        bail!("start_url '{}' has an invalid port", url);
+    }
+
+    let bytes = url.as_bytes();
+    let mut out = String::with_capacity(url.len());
+    let mut in_fragment = false;
+    for (i, &b) in bytes.iter().enumerate() {
+        let escaped_pct = b == b'%' && bytes.get(i + 1..i + 3).is_some_and(|h| h.iter().all(u8::is_ascii_hexdigit));
+        if is_uri_char(b) || escaped_pct || (b == b'#' && !in_fragment) {
+            in_fragment |= b == b'#';
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    Ok(out)
+}
+
+/// `start_url` 템플릿에 쓸 수 있는 자리 표시자 이름 (`{blob_sha256:N}`은 해시 앞 N 글자)
+pub const START_URL_PLACEHOLDERS: &[&str] = &["w", "h", "fps", "frames", "bytes", "blob_sha256"];
+
+/// `start_url` 템플릿 값. blob이 확정된 뒤 (프레임 수와 해시가 최종값일 때) 채운다.
+#[derive(Debug, Clone, Default)]
+pub struct StartUrlVars {
+    pub width: u16,
+    pub height: u16,
+    pub fps: f32,
+    /// 재생 프레임 수
+    pub frames: u32,
+    /// blob 크기 (`--chunk-size`로 나누기 전)
+    pub bytes: u64,
+    /// blob SHA-256 (소문자 hex, 나누기 전, manifest의 `video.sha256`과 같다). 템플릿에 없으면 비워 둬도 된다.
+    pub blob_sha256: String,
+}
+
+/// `start_url` 템플릿에 나오는 자리 표시자 이름 (나온 순서대로). 모르는 이름이나 짝이 안 맞는 중괄호는 에러.
+pub fn start_url_placeholders(template: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    expand_template(template, |name, _| {
+        names.push(name.to_string());
+        Ok(String::new())
+    })?;
+    Ok(names)
+}
+
+/// `start_url` 템플릿의 `{w}`, `{h}`, `{fps}`, `{frames}`, `{bytes}`, `{blob_sha256}` / `{blob_sha256:N}`을 값으로 바꾼다.
+/// `{{`와 `}}`는 글자 그대로의 `{`, `}`다. 결과는 아직 `normalize_start_url`을 거치지 않았다.
+pub fn expand_start_url(template: &str, vars: &StartUrlVars) -> Result<String> {
+    expand_template(template, |name, len| {
+        Ok(match name {
+            "w" => vars.width.to_string(),
+            "h" => vars.height.to_string(),
+            "fps" => vars.fps.to_string(),
+            "frames" => vars.frames.to_string(),
+            "bytes" => vars.bytes.to_string(),
+            _ => {
+                let hash = &vars.blob_sha256;
+                hash[..len.unwrap_or(hash.len()).min(hash.len())].to_string()
+            }
+        })
+    })
+}
+
+/// `{name}` / `{name:N}`마다 `value(name, N)`을 넣는다 (이름과 N은 여기서 검사한다)
+fn expand_template(template: &str, mut value: impl FnMut(&str, Option<usize>) -> Result<String>) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if let Some(t) = tail.strip_prefix("{{").or_else(|| tail.strip_prefix("}}")) {
+            out.push_str(&tail[..1]);
+            rest = t;
+            continue;
+        }
+        if tail.starts_with('}') {
+            bail!("start_url '{}' has an unmatched '}}' (write '}}}}' for a literal brace)", template);
+        }
+        let end = tail
+            .find('}')
+            .with_context(|| format!("start_url '{}' has an unclosed '{{' (write '{{{{' for a literal brace)", template))?;
+        let spec = &tail[1..end];
+        let (name, len) = match spec.split_once(':') {
+            Some((name, len)) => (name, Some(len)),
+            None => (spec, None),
+        };
+        if !START_URL_PLACEHOLDERS.contains(&name) {
+            let supported: Vec<String> = START_URL_PLACEHOLDERS.iter().map(|n| format!("{{{}}}", n)).collect();
+            bail!("unknown placeholder {{{}}} in start_url (supported: {}, {{blob_sha256:N}})", spec, supported.join(", "));
+        }
+        let len = match len {
+            None => None,
+            Some(n) if name == "blob_sha256" => Some(
+                n.parse()
+                    .ok()
+                    .filter(|n| (1..=64).contains(n))
+                    .with_context(|| format!("invalid placeholder {{{}}} in start_url (hash length must be 1..=64)", spec))?,
+            ),
+            Some(_) => bail!("placeholder {{{}}} in start_url takes no ':N' (only blob_sha256 does)", spec),
+        };
+        out.push_str(&value(name, len)?);
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// START 링크 action.
+/// 플레이어가 첨부되어 있으면 JavaScript action으로 첨부를 꺼내 열고
+/// (`exportDataObject`, nLaunch 2 = 임시 파일로 저장 후 기본 앱으로 열기),
+/// 실패하면 URI로 넘어간다. JavaScript를 실행하지 않는 뷰어를 위해
+/// 첨부가 없을 때는 지금처럼 /URI action만 둔다.
+/// `url`은 `normalize_start_url`을 거친 `start_url`.
+fn start_action(opts: &PdfOptions, url: &str) -> lopdf::Dictionary {
+    let Some(player) = &opts.embedded_player else {
+        return uri_action(url);
+    };
+    let js = format!(
+        "try {{ this.exportDataObject({{ cName: {}, nLaunch: 2 }}); }} catch (e) {{ app.launchURL({}, true); }}",
+        js_string(player),
+        js_string(url)
+    );
+    dictionary! {
+        "S" => "JavaScript",
+        "JS" => Object::String(js.into_bytes(), lopdf::StringFormat::Literal),
+    }
+}
+
+/// `url`로 가는 `/S /URI` action (ASCII literal string, `(`/`)`/`\`는 lopdf가 escape한다)
+fn uri_action(url: &str) -> lopdf::Dictionary {
+    dictionary! {
+        "S" => "URI",
+        "URI" => Object::String(url.as_bytes().to_vec(), lopdf::StringFormat::Literal),
+    }
+}
+
+// /Names/JavaScript에 등록하는 문서 수준 스크립트 이름
+const OPEN_SCRIPT_NAME: &str = "OpenPlayer";
+
+/// `--open-action js`: `app.launchURL`을 담은 JavaScript stream과 그 action.
+/// 문서 수준 스크립트(/Names/JavaScript)도 열 때 실행되므로 /OpenAction과 두 번 돌지 않게
+/// 문서 변수로 한 번만 연다.
+fn add_open_script(doc: &mut Document, url: &str) -> lopdf::ObjectId {
+    let js = format!(
+        "if (typeof badappleOpened == \"undefined\") {{ badappleOpened = true; app.launchURL({}, true); }}",
+        js_string(url)
+    );
+    let js_id = doc.add_object(Stream::new(dictionary! {}, js.into_bytes()));
+    doc.add_object(dictionary! {
+        "S" => "JavaScript",
+        "JS" => Object::Reference(js_id),
+    })
+}
+
+/// 첨부 데이터: 작은 것은 메모리, 큰 것은 파일 경로로 넘긴다.
+#[derive(Debug, Clone)]
+pub enum AttachmentData {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+/// EmbeddedFiles 항목 하나
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub name: String,
+    /// RFC 2046 media type. EmbeddedFile stream의 `/Subtype` name으로 쓴다 (`/` → `#2F`).
+    pub mime: String,
+    pub data: AttachmentData,
+    pub options: AttachmentOptions,
+}
+
+/// 첨부 메타데이터: filespec `/Desc` / `/Lang`, EmbeddedFile `/Params`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttachmentOptions {
+    /// 뷰어 첨부 패널에 보이는 설명 (`/Desc`)
+    pub description: Option<String>,
+    /// 내용의 언어 (`/Lang`, 오디오 트랙의 `--audio-lang`)
+    pub language: Option<String>,
+    /// `/Params /CreationDate`와 `/ModDate` (PDF 날짜 문자열, `pdf_date`). 없으면 날짜를 쓰지 않는다.
+    pub creation_date: Option<String>,
+}
+
+impl Attachment {
+    /// 첨부 데이터 크기 (파일이면 metadata 기준)
+    pub fn data_len(&self) -> Result<u64> {
+        Ok(match &self.data {
+            AttachmentData::Bytes(b) => b.len() as u64,
+            AttachmentData::File(path) => fs::metadata(path)
+                .with_context(|| format!("failed to read {}", path.display()))?
+                .len(),
+        })
+    }
+
+    pub fn bytes(name: &str, mime: &str, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            mime: mime.to_string(),
+            data: AttachmentData::Bytes(data),
+            options: AttachmentOptions::default(),
+        }
+    }
+
+    pub fn file(name: &str, mime: &str, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.to_string(),
+            mime: mime.to_string(),
+            data: AttachmentData::File(path.into()),
+            options: AttachmentOptions::default(),
+        }
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.options.description = Some(description.to_string());
+        self
+    }
+}
+
+/// unix 시각(초, UTC) → PDF 날짜 문자열 `D:YYYYMMDDHHmmSSZ`
+pub fn pdf_date(unix_secs: u64) -> String {
+    let (days, secs) = (unix_secs / 86400, unix_secs % 86400);
+    // days since 1970-01-01 → 그레고리력 (Howard Hinnant의 civil_from_days)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "D:{:04}{:02}{:02}{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// `build_pdf` 결과. 파일 첨부(`AttachmentData::File`)의 EmbeddedFile stream은 `doc` 안에서 content가 비어 있고
+/// (`/Length`와 `/Params`는 파일 기준), `save`/`save_to`/`save_linearized`가 쓸 때 파일에서 복사한다.
+/// `doc.save()`로 바로 저장하면 그 첨부가 비므로, lopdf로 더 고쳐서 저장할 때는 `into_document`를 쓴다.
+pub struct PdfDocument {
+    pub doc: Document,
+    pub(crate) files: FileStreams,
+}
+
+impl PdfDocument {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut out = BufWriter::new(File::create(path).with_context(|| format!("failed to create {}", path.display()))?);
+        self.save_to(&mut out)
+    }
+
+    pub fn save_to<W: Write>(&self, out: &mut W) -> Result<()> {
+        writer::write_pdf(self, out)
+    }
+
+    /// 파일 첨부를 메모리로 읽어 넣은 `Document`
+    pub fn into_document(mut self) -> Result<Document> {
+        for (id, file) in &self.files {
+            let data = fs::read(&file.path).with_context(|| format!("failed to read {}", file.path.display()))?;
+            if data.len() as u64 != file.len {
+                bail!("{} changed after the pdf was built ({} bytes, expected {})", file.path.display(), data.len(), file.len);
+            }
+            self.doc.get_object_mut(*id).and_then(Object::as_stream_mut).context("attachment stream missing")?.set_content(data);
+        }
+        Ok(self.doc)
+    }
+}
+
+/// PDF 생성:
+/// - 1페이지 컨텐츠에 START 버튼처럼 보이게 그려놓고
+/// - 같은 영역에 Link annotation (/URI)을 올린다.
+/// - EmbeddedFiles에 BA.bin / AU.ogg (+ 플레이어 파일)를 첨부한다.
+///
+/// 메모리 첨부는 stream content로 넣는다. 파일 첨부는 크기와 MD5만 조금씩 읽어서 구하고 content는 비워 둔 채
+/// `files`에 적는다 (저장할 때 `writer`가 파일에서 복사한다).
+/// `/Params`에는 항상 `/Size`와 `/CheckSum`(압축 전 데이터의 MD5, 16 bytes)을, 날짜가 있으면 `/CreationDate`/`/ModDate`를 넣는다.
+/// EF stream은 지금 압축하지 않지만, 나중에 `/Filter`를 붙여도 CheckSum은 풀린 데이터 기준이라 그대로 맞다.
+fn add_attachment(doc: &mut Document, files: &mut FileStreams, att: &Attachment, version: PdfVersion) -> Result<lopdf::ObjectId> {
+    let (name, mime) = (att.name.as_str(), att.mime.as_str());
+    let (len, checksum, content) = match &att.data {
+        AttachmentData::Bytes(b) => (b.len() as u64, md5::compute(b).0, b.clone()),
+        AttachmentData::File(path) => {
+            let (len, checksum) = file_md5(path)
+                .with_context(|| format!("failed to read attachment data for {} ({})", name, path.display()))?;
+            (len, checksum, Vec::new())
+        }
+    };
+
+    let mut params = dictionary! {
+        "Size" => len as i64,
+        "CheckSum" => Object::String(checksum.to_vec(), lopdf::StringFormat::Hexadecimal),
+    };
+    if let Some(date) = &att.options.creation_date {
+        params.set("CreationDate", Object::string_literal(date.as_str()));
+        params.set("ModDate", Object::string_literal(date.as_str()));
+    }
+    let ef_id = doc.new_object_id();
+    let mut ef_stream = Stream::new(
+        dictionary! {
+            "Type" => "EmbeddedFile",
+            "Subtype" => Object::Name(mime.as_bytes().to_vec()),
+            "Params" => params,
+        },
+        content,
+    );
+    ef_stream.dict.set("Length", len as i64);
+    doc.objects.insert(ef_id, Object::Stream(ef_stream));
+    if let AttachmentData::File(path) = &att.data {
+        files.insert(ef_id, FileStream { path: path.clone(), len });
+    }
+
+    let filespec_id = doc.new_object_id();
+    let mut filespec = dictionary! {
+        "Type" => "Filespec",
+        "F" => Object::String(name.as_bytes().to_vec(), lopdf::StringFormat::Literal),
+    };
+    // `/UF`는 1.7 key (1.4 뷰어는 `/F`만 본다)
+    if version != PdfVersion::V14 {
+        filespec.set("UF", Object::String(name.as_bytes().to_vec(), lopdf::StringFormat::Literal));
+    }
+    filespec.set("EF", dictionary! { "F" => Object::Reference(ef_id) });
+    if let Some(desc) = &att.options.description {
+        filespec.set("Desc", encode_text_string(desc));
+    }
+    if let Some(lang) = &att.options.language {
+        filespec.set("Lang", encode_text_string(lang));
+    }
+    doc.objects.insert(filespec_id, Object::Dictionary(filespec));
+    Ok(filespec_id)
+}
+
+/// 파일을 조금씩 읽으며 (크기, MD5)
+fn file_md5(path: &Path) -> Result<(u64, [u8; 16])> {
+    let mut ctx = md5::Context::new();
+    let len = io::copy(&mut File::open(path)?, &mut ctx)?;
+    Ok((len, ctx.compute().0))
+}
+
+/// 장마다 그 프레임이 들어 있는 마지막 flipbook 페이지(없으면 START 페이지)로 가는 named destination.
+/// `kids`는 START 페이지 + flipbook 페이지 순서의 page reference.
+fn add_chapter_dests(doc: &mut Document, names_id: lopdf::ObjectId, opts: &PdfOptions, kids: &[Object]) -> Result<()> {
+    let mut dests: Vec<(String, Object)> = Vec::new();
+    for ch in &opts.chapters {
+        let page = opts.flipbook.iter().rposition(|p| p.frame <= ch.frame_index).map_or(0, |i| i + 1);
+        let dest = vec![kids[page].clone(), "XYZ".into(), Object::Null, Object::Null, Object::Null];
+        dests.push((ch.dest_name(), Object::Array(dest)));
+    }
+    // name tree는 key가 정렬되어 있어야 한다. 같은 프레임의 장은 destination 하나를 같이 쓴다.
+    dests.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    dests.dedup_by(|a, b| a.0 == b.0);
+    let mut names = Vec::with_capacity(dests.len() * 2);
+    for (name, dest) in dests {
+        names.push(Object::String(name.into_bytes(), lopdf::StringFormat::Literal));
+        names.push(dest);
+    }
+    doc.get_object_mut(names_id)
+        .and_then(Object::as_dict_mut)
+        .context("names dictionary missing")?
+        .set("Dests", dictionary! { "Names" => names });
+    Ok(())
+}
+
+/// 장 제목을 평평한 outline 항목으로 (순서대로, 하위 항목 없음)
+fn add_outlines(doc: &mut Document, chapters: &[Chapter]) -> lopdf::ObjectId {
+    let outlines_id = doc.new_object_id();
+    let ids: Vec<lopdf::ObjectId> = chapters.iter().map(|_| doc.new_object_id()).collect();
+    for (i, ch) in chapters.iter().enumerate() {
+        let mut item = dictionary! {
+            "Title" => encode_text_string(&ch.title),
+            "Parent" => Object::Reference(outlines_id),
+            "Dest" => Object::String(ch.dest_name().into_bytes(), lopdf::StringFormat::Literal),
+        };
+        if i > 0 {
+            item.set("Prev", Object::Reference(ids[i - 1]));
+        }
+        if let Some(next) = ids.get(i + 1) {
+            item.set("Next", Object::Reference(*next));
+        }
+        doc.objects.insert(ids[i], Object::Dictionary(item));
+    }
+    doc.objects.insert(
+        outlines_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Outlines",
+            "First" => Object::Reference(ids[0]),
+            "Last" => Object::Reference(ids[ids.len() - 1]),
+            "Count" => ids.len() as i64,
+        }),
+    );
+    outlines_id
+}
+
+/// PDF text string: ASCII는 그대로, 나머지는 BOM 붙은 UTF-16BE (`decode_text_string`의 반대)
+fn encode_text_string(s: &str) -> Object {
+    if s.is_ascii() {
+        return Object::String(s.as_bytes().to_vec(), lopdf::StringFormat::Literal);
+    }
+    let mut bytes = vec![0xfe, 0xff];
+    bytes.extend(s.encode_utf16().flat_map(u16::to_be_bytes));
+    Object::String(bytes, lopdf::StringFormat::Hexadecimal)
+}
+
+/// PDF 2.0용 XMP metadata stream (`pdf:Producer`, `pdf:PDFVersion`). 뷰어가 바로 읽게 압축하지 않는다.
+fn add_xmp_metadata(doc: &mut Document) -> lopdf::ObjectId {
+    let xmp = format!(
+        concat!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+            "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+            "<rdf:Description rdf:about=\"\" xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\">\n",
+            "<pdf:Producer>{}</pdf:Producer>\n",
+            "<pdf:PDFVersion>{}</pdf:PDFVersion>\n",
+            "</rdf:Description>\n",
+            "</rdf:RDF>\n",
+            "</x:xmpmeta>\n",
+            "<?xpacket end=\"w\"?>"
+        ),
+        PDF_PRODUCER,
+        PdfVersion::V20.as_str()
+    )
+    .into_bytes();
+    doc.add_object(Stream::new(
+        dictionary! { "Type" => "Metadata", "Subtype" => "XML", "Length" => xmp.len() as i64 },
+        xmp,
+    ))
+}
+
+/// 1페이지 START 문서를 만들고 `attachments`를 전부 EmbeddedFiles로 첨부한다.
+/// 저장은 호출하는 쪽에서 `PdfDocument::save` 또는 `save_linearized`로.
+pub fn build_pdf(opts: &PdfOptions, attachments: &[Attachment]) -> Result<PdfDocument> {
+    if let Some(player) = &opts.embedded_player {
+        if !attachments.iter().any(|a| &a.name == player) {
+            bail!("embedded player {} is not among the attachments", player);
+        }
+    }
+    let start_url = normalize_start_url(&opts.start_url)?;
+    let mut doc = Document::with_version(opts.version.as_str());
+    if !opts.version.xref_stream() {
+        doc.reference_table.cross_reference_type = lopdf::xref::XrefType::CrossReferenceTable;
+    }
+
+    // Object IDs
+    let catalog_id = doc.new_object_id();
+    let pages_id = doc.new_object_id();
+    let page_id = doc.new_object_id();
+
+    // Font object (Helvetica, flipbook 프레임 번호와 기본 버튼 글자)
+    let font_id = add_font(&mut doc, "Helvetica");
+
+    // Attachments (EmbeddedFiles)
+    let mut files = FileStreams::new();
+    let mut filespecs = Vec::with_capacity(attachments.len());
+    for att in attachments {
+        filespecs.push((att.name.as_str(), add_attachment(&mut doc, &mut files, att, opts.version)?));
+    }
+
+    // name tree는 key가 정렬되어 있어야 한다 (PDF spec)
+    let mut sorted = filespecs.clone();
+    sorted.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    if let Some(w) = sorted.windows(2).find(|w| w[0].0 == w[1].0) {
+        bail!("duplicate attachment name {}", w[0].0);
+    }
+    let mut names = Vec::with_capacity(sorted.len() * 2);
+    for (name, id) in &sorted {
+        names.push(Object::String(name.as_bytes().to_vec(), lopdf::StringFormat::Literal));
+        names.push(Object::Reference(*id));
+    }
+
+    let names_id = doc.new_object_id();
+    let embedded_files = dictionary! {
+        "Names" => names
+    };
+    let mut names_dict = dictionary! { "EmbeddedFiles" => embedded_files };
+
+    // 열 때 실행할 action (js는 /Names/JavaScript name tree에도 같은 action을 넣는다)
+    let open_action = match opts.open_action {
+        OpenAction::None => None,
+        OpenAction::Url => Some(Object::Dictionary(uri_action(&start_url))),
+        OpenAction::Start => Some(Object::Dictionary(start_action(opts, &start_url))),
+        OpenAction::Js => {
+            let action_id = add_open_script(&mut doc, &start_url);
+            names_dict.set(
+                "JavaScript",
+                dictionary! {
+                    "Names" => vec![
+                        Object::String(OPEN_SCRIPT_NAME.as_bytes().to_vec(), lopdf::StringFormat::Literal),
+                        Object::Reference(action_id),
+                    ],
+                },
+            );
+            Some(Object::Reference(action_id))
+        }
+    };
+    doc.objects.insert(names_id, Object::Dictionary(names_dict));
+
+    // Page Resources: Font (+ 썸네일 image)
+    let button = &opts.page.button;
+    let label_font = label_font(button)?;
+    let mut fonts = dictionary! { "F1" => Object::Reference(font_id) };
+    let button_font = if label_font == "Helvetica" {
+        "F1"
+    } else if KOREAN_FONTS.contains(&label_font) {
+        fonts.set("F2", add_korean_font(&mut doc, label_font));
+        "F2"
+    } else {
+        fonts.set("F2", add_font(&mut doc, label_font));
+        "F2"
+    };
+    let mut resources = dictionary! { "Font" => fonts.clone() };
+    if opts.thumbnail.is_some() && opts.preview.is_some() {
+        bail!("the thumbnail and the preview frame would share the thumbnail rect; pass only one");
+    }
+    let thumbnail_rect = opts.thumbnail_rect.unwrap_or_else(|| opts.page.default_thumbnail_rect());
+    if opts.thumbnail.is_some() || opts.preview.is_some() {
+        let [x1, y1, x2, y2] = thumbnail_rect;
+        if opts.thumbnail_rect.is_none() && !(x2 > x1 && y2 > y1) {
+            bail!(
+                "no room for the thumbnail above the button on a {}x{} page; pass --poster-rect or --no-thumbnail",
+                opts.page.size.width,
+                opts.page.size.height
+            );
+        }
+        check_rect("thumbnail", thumbnail_rect, opts.page.size)?;
+    }
+    if let Some(thumb) = &opts.thumbnail {
+        let image_id = add_thumbnail(&mut doc, thumb)?;
+        resources.set("XObject", dictionary! { "Im1" => Object::Reference(image_id) });
+    }
+
+    // Page content: START 버튼처럼 보이도록 사각형+텍스트 그리기
+    // 좌표: PDF point (`opts.page.size`, 기본 612x792)
+    // 버튼 영역 Rect = [x1 y1 x2 y2], 글자는 가운데 정렬. Link annotation도 같은 Rect.
+    let button_rect = opts.page.button_rect();
+    check_rect("button", button_rect, opts.page.size)?;
+    let [x1, y1, x2, y2] = button_rect;
+    let text = &button.label;
+    let size = button.font_size;
+    if !(size > 0.0 && size.is_finite()) {
+        bail!("button font size must be greater than 0 (got {})", size);
+    }
+    for (what, g) in [("fill", button.fill_gray), ("stroke", button.stroke_gray)] {
+        if !(0.0..=1.0).contains(&g) {
+            bail!("button {} gray must be between 0 and 1 (got {})", what, g);
+        }
+    }
+    let text_w = text_width(label_font, text, size);
+    if text_w > x2 - x1 && !quiet() {
+        eprintln!(
+            "warning: button label {:?} is {:.0}pt wide at {}pt, wider than the {:.0}pt button; it will overflow (lower --button-font-size)",
+            text,
+            text_w,
+            size,
+            x2 - x1
+        );
+    }
+
+    let mut content = match (&opts.thumbnail, &opts.preview) {
+        (Some(t), _) => thumbnail_content(t, thumbnail_rect).into_bytes(),
+        (None, Some(p)) => preview_content(p, thumbnail_rect)?,
+        (None, None) => Vec::new(),
+    };
+    content.extend(button_content(button, button_font, label_font, button_rect).into_bytes());
+
+    let contents_id = doc.new_object_id();
+    doc.objects.insert(
+        contents_id,
+        Object::Stream(Stream::new(dictionary! { "Length" => content.len() as i64 }, content)),
+    );
+
+    // Link annotation overlay
+    let annot_id = doc.new_object_id();
+    let mut annot = dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Link",
+        "Rect" => vec![
+            Object::Real(x1 as f32),
+            Object::Real(y1 as f32),
+            Object::Real(x2 as f32),
+            Object::Real(y2 as f32),
+        ],
+        "Border" => vec![0.into(), 0.into(), 0.into()],
+        "A" => start_action(opts, &start_url),
+    };
+    if button.border {
+        // 뷰어가 annotation 테두리를 그린다 (페이지 content의 2pt 테두리와 같은 두께/색)
+        annot.set("Border", vec![0.into(), 0.into(), 2.into()]);
+        annot.set("BS", dictionary! { "Type" => "Border", "W" => 2, "S" => "S" });
+        annot.set("C", vec![Object::Real(button.stroke_gray as f32); 3]);
+    }
+    if button.appearance {
+        let ap = button_content(button, button_font, label_font, [0.0, 0.0, x2 - x1, y2 - y1]).into_bytes();
+        let ap_id = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), Object::Real((x2 - x1) as f32), Object::Real((y2 - y1) as f32)],
+                "Resources" => dictionary! { "Font" => fonts },
+                "Length" => ap.len() as i64,
+            },
+            ap,
+        ));
+        annot.set("AP", dictionary! { "N" => Object::Reference(ap_id) });
+    }
+    doc.objects.insert(annot_id, Object::Dictionary(annot));
+
+    // Page dictionary
+    doc.objects.insert(
+        page_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Page",
+            "Parent" => Object::Reference(pages_id),
+            "MediaBox" => opts.page.size.media_box(),
+            "Resources" => resources,
+            "Contents" => Object::Reference(contents_id),
+            "Annots" => vec![Object::Reference(annot_id)]
+        }),
+    );
+
+    // flipbook 페이지: font는 START 페이지와 공유하고, 이미지는 페이지마다 하나
+    let mut kids = vec![Object::Reference(page_id)];
+    for page in &opts.flipbook {
+        kids.push(Object::Reference(add_flipbook_page(&mut doc, page, pages_id, font_id, opts.page.size)?));
+    }
+
+    // 장: /Dests name tree + /Outlines
+    let outlines_id = if opts.chapters.is_empty() {
+        None
+    } else {
+        add_chapter_dests(&mut doc, names_id, opts, &kids)?;
+        Some(add_outlines(&mut doc, &opts.chapters))
+    };
+
+    // Pages + Catalog
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Count" => kids.len() as i64,
+            "Kids" => kids,
+        }),
+    );
+    let mut catalog = dictionary! {
+        "Type" => "Catalog",
+        "Pages" => Object::Reference(pages_id),
+        "Names" => Object::Reference(names_id),
+    };
+    // `/AF`(associated files)는 PDF/A-3, 2.0 key. 1.4에서는 name tree만 남긴다
+    if opts.version != PdfVersion::V14 {
+        catalog.set("AF", filespecs.iter().map(|(_, id)| Object::Reference(*id)).collect::<Vec<_>>());
+    }
+    if opts.version == PdfVersion::V20 {
+        catalog.set("Metadata", Object::Reference(add_xmp_metadata(&mut doc)));
+    }
+    if let Some(action) = open_action {
+        catalog.set("OpenAction", action);
+    }
+    if let Some(id) = outlines_id {
+        catalog.set("Outlines", Object::Reference(id));
+        catalog.set("PageMode", "UseOutlines");
+    }
+    if opts.viewer.is_set() {
+        let mut prefs = opts.viewer.dictionary();
+        if opts.viewer.fit_window {
+            catalog.set("PageLayout", "SinglePage");
+            catalog.set("PageMode", "FullScreen");
+            // 전체 화면에서 나오면 목차 패널을 보여준다
+            if outlines_id.is_some() {
+                prefs.set("NonFullScreenPageMode", "UseOutlines");
+            }
+        }
+        catalog.set("ViewerPreferences", prefs);
+    }
+    doc.objects.insert(catalog_id, Object::Dictionary(catalog));
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+
+    Ok(PdfDocument { doc, files })
+}
+
+/// 저장한 PDF를 다시 열어서 첨부와 START 링크가 그대로 들어갔는지 확인한다.
+/// Root → Names → EmbeddedFiles name tree를 따라가서 filespec / EF stream을 풀고,
+/// `expected`와 이름, MIME, 크기, SHA-256이 모두 같아야 한다 (name tree에 다른 항목이 있어도 실패).
+/// 1페이지 Link annotation의 action은 `/URI`가 `start_url`이거나,
+/// 첨부 플레이어를 여는 JavaScript 안에 `start_url`이 들어 있어야 한다 (둘 다 `normalize_start_url`을 거친 값).
+/// Link의 `/Rect`는 MediaBox 안에 있고, 페이지 content에 같은 영역을 그리는 `re`가 있어야 한다 (눌리는 곳 = 버튼).
+pub fn verify_pdf(path: &Path, expected: &[Attachment], start_url: &str) -> Result<()> {
+    let start_url = &normalize_start_url(start_url)?;
+    let doc = Document::load(path).with_context(|| format!("failed to re-open {}", path.display()))?;
+    let catalog = doc.catalog().context("trailer /Root is not a catalog dictionary")?;
+    let names = deref_dict(&doc, catalog, b"Names").context("catalog /Names")?;
+    let tree = deref_dict(&doc, names, b"EmbeddedFiles").context("/Names /EmbeddedFiles")?;
+    let entries = tree
+        .get(b"Names")
+        .and_then(Object::as_array)
+        .context("/EmbeddedFiles has no /Names array")?;
+    if entries.len() % 2 != 0 {
+        bail!("/EmbeddedFiles /Names has an odd number of entries ({})", entries.len());
+    }
+
+    let mut found = Vec::with_capacity(entries.len() / 2);
+    for pair in entries.chunks_exact(2) {
+        let name = pair[0].as_str().context("/EmbeddedFiles key is not a string")?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        if found.last().is_some_and(|last: &String| last.as_bytes() >= name.as_bytes()) {
+            bail!("/EmbeddedFiles name tree is not sorted at {}", name);
+        }
+        let (spec_id, spec) = doc.dereference(&pair[1]).with_context(|| format!("filespec for {}", name))?;
+        let spec = spec.as_dict().with_context(|| format!("filespec for {} is not a dictionary", name))?;
+        let at = || match spec_id {
+            Some(id) => format!("{} (filespec {} {} R)", name, id.0, id.1),
+            None => format!("{} (inline filespec)", name),
+        };
+        match spec.get(b"F").and_then(Object::as_str) {
+            Ok(f) if f == name.as_bytes() => {}
+            _ => bail!("{}: /F does not match the name tree key", at()),
+        }
+        let ef = deref_dict(&doc, spec, b"EF").with_context(|| format!("{}: /EF", at()))?;
+        let (stream_id, stream) = doc
+            .dereference(ef.get(b"F").with_context(|| format!("{}: /EF has no /F", at()))?)
+            .with_context(|| format!("{}: /EF /F", at()))?;
+        let stream = stream.as_stream().with_context(|| format!("{}: /EF /F is not a stream", at()))?;
+        let at = || match stream_id {
+            Some(id) => format!("{} (stream {} {} R)", name, id.0, id.1),
+            None => at(),
+        };
+        let data = if stream.dict.has(b"Filter") {
+            stream.decompressed_content().with_context(|| format!("{}: cannot decode stream", at()))?
+        } else {
+            stream.content.clone()
+        };
+
+        let Some(att) = expected.iter().find(|a| a.name == name) else {
+            bail!("{}: unexpected attachment", at());
+        };
+        let subtype = stream.dict.get(b"Subtype").and_then(Object::as_name_str).unwrap_or("");
+        if subtype != att.mime {
+            bail!("{}: /Subtype is {:?}, expected {:?}", at(), subtype, att.mime);
+        }
+        let (len, sha256) = crate::manifest::attachment_sha256(att)?;
+        if data.len() as u64 != len {
+            bail!("{}: {} bytes in the PDF, expected {}", at(), data.len(), len);
+        }
+        if crate::manifest::sha256_hex(&data) != sha256 {
+            bail!("{}: content differs from what was embedded (SHA-256 mismatch)", at());
+        }
+        let params = deref_dict(&doc, &stream.dict, b"Params").with_context(|| format!("{}: /Params", at()))?;
+        if params.get(b"Size").and_then(Object::as_i64).ok() != Some(data.len() as i64) {
+            bail!("{}: /Params /Size does not match the {} bytes in the stream", at(), data.len());
+        }
+        match params.get(b"CheckSum").and_then(Object::as_str) {
+            Ok(sum) if sum == md5::compute(&data).0 => {}
+            Ok(_) => bail!("{}: /Params /CheckSum does not match the MD5 of the stream data", at()),
+            Err(_) => bail!("{}: /Params has no /CheckSum", at()),
+        }
+        found.push(name);
+    }
+    if let Some(missing) = expected.iter().find(|a| !found.contains(&a.name)) {
+        bail!("attachment {} is missing from /EmbeddedFiles", missing.name);
+    }
+
+    // START 링크
+    let page_id = *doc.get_pages().get(&1).context("PDF has no pages")?;
+    let page = doc.get_dictionary(page_id).context("page 1")?;
+    let annots = match page.get(b"Annots") {
+        Ok(a) => doc.dereference(a)?.1.as_array().context("page 1 /Annots is not an array")?,
+        Err(_) => bail!("page 1 has no /Annots (START link missing)"),
+    };
+    let content = doc.get_page_content(page_id).context("page 1 content")?;
+    let drawn = content_rects(&content);
+    let media_box = page.get(b"MediaBox").and_then(Object::as_array).ok().and_then(|b| pdf_rect(b));
+    let mut links = 0;
+    for annot in annots {
+        let (annot_id, annot) = doc.dereference(annot).context("page 1 /Annots entry")?;
+        let annot = annot.as_dict().context("page 1 annotation is not a dictionary")?;
+        if annot.get(b"Subtype").and_then(Object::as_name_str).ok() != Some("Link") {
+            continue;
+        }
+        links += 1;
+        let at = match annot_id {
+            Some(id) => format!("Link annotation {} {} R", id.0, id.1),
+            None => "inline Link annotation".to_string(),
+        };
+        let rect = annot.get(b"Rect").ok().and_then(|r| r.as_array().ok()).and_then(|r| pdf_rect(r));
+        let Some(rect) = rect else {
+            bail!("{}: /Rect is missing or not 4 numbers", at);
+        };
+        if let Some([bx1, by1, bx2, by2]) = media_box {
+            if rect[0] < bx1 - RECT_EPSILON || rect[1] < by1 - RECT_EPSILON || rect[2] > bx2 + RECT_EPSILON || rect[3] > by2 + RECT_EPSILON {
+                bail!("{}: /Rect {:.2?} is outside the MediaBox {:.2?}", at, rect, [bx1, by1, bx2, by2]);
+            }
+        }
+        if !drawn.iter().any(|d| d.iter().zip(&rect).all(|(a, b)| (a - b).abs() <= RECT_EPSILON)) {
+            bail!("{}: /Rect {:.2?} does not match any rectangle drawn on page 1", at, rect);
+        }
+        let action = deref_dict(&doc, annot, b"A").with_context(|| format!("{}: /A", at))?;
+        let ok = match action.get(b"S").and_then(Object::as_name_str) {
+            Ok("URI") => action.get(b"URI").and_then(Object::as_str).is_ok_and(|u| u == start_url.as_bytes()),
+            Ok("JavaScript") => {
+                let js = action.get(b"JS").with_context(|| format!("{}: /JS missing", at))?;
+                let js = match doc.dereference(js)?.1 {
+                    Object::String(s, _) => s.clone(),
+                    Object::Stream(s) => s.content.clone(),
+                    _ => bail!("{}: /JS is neither a string nor a stream", at),
+                };
+                String::from_utf8_lossy(&js).contains(&js_string(start_url))
+            }
+            _ => bail!("{}: unsupported action type", at),
+        };
+        if !ok {
+            bail!("{}: action does not point at start_url {}", at, start_url);
+        }
+    }
+    if links == 0 {
+        bail!("page 1 has no Link annotation (START link missing)");
+    }
+    Ok(())
+}
+
+// `/Rect`와 그린 `re`를 비교할 때 허용 오차 (PDF point, Real은 f32로 저장된다)
+const RECT_EPSILON: f64 = 0.01;
+
+/// `[x1 y1 x2 y2]` 숫자 배열 → 정규화한 `[x1, y1, x2, y2]` (x1 <= x2, y1 <= y2)
+fn pdf_rect(arr: &[Object]) -> Option<[f64; 4]> {
+    let v: Option<Vec<f64>> = arr.iter().map(|o| o.as_float().ok().map(f64::from)).collect();
+    let &[x1, y1, x2, y2] = v.as_deref()? else {
+        return None;
+    };
+    Some([x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)])
+}
+
+/// content stream에서 `x y w h re` 줄을 찾아 `[x1, y1, x2, y2]`로 (inline image 데이터 같은 다른 줄은 건너뛴다)
+fn content_rects(content: &[u8]) -> Vec<[f64; 4]> {
+    let mut out = Vec::new();
+    for line in content.split(|&b| b == b'\n') {
+        let Ok(line) = std::str::from_utf8(line) else {
+            continue;
+        };
+        let Some(ops) = line.trim().strip_suffix(" re") else {
+            continue;
+        };
+        let v: Option<Vec<f64>> = ops.split_whitespace().map(|t| t.parse().ok()).collect();
+        if let Some(&[x, y, w, h]) = v.as_deref() {
+            out.push([x.min(x + w), y.min(y + h), x.max(x + w), y.max(y + h)]);
+        }
+    }
+    out
+}
+
+/// `dict[key]`가 dictionary거나 dictionary를 가리키는 reference면 그 dictionary
+fn deref_dict<'a>(doc: &'a Document, dict: &'a lopdf::Dictionary, key: &[u8]) -> Result<&'a lopdf::Dictionary> {
+    let obj = dict.get(key).with_context(|| format!("/{} missing", String::from_utf8_lossy(key)))?;
+    let (_, obj) = doc.dereference(obj)?;
+    obj.as_dict().with_context(|| format!("/{} is not a dictionary", String::from_utf8_lossy(key)))
+}
+
+// name tree /Kids 재귀 상한 (순환 참조 방지)
+const NAME_TREE_MAX_DEPTH: usize = 32;
+
+/// PDF 안의 첨부를 전부 꺼낸다 (`extract`). EmbeddedFiles name tree는 평평한 `/Names` 배열과
+/// `/Kids` 트리 둘 다 따라가고, name tree에 없이 catalog `/AF`에만 있는 filespec도 찾는다.
+/// 이름은 `/UF` (없으면 `/F`), MIME은 EF stream의 `/Subtype`이고, stream filter는 풀어서 돌려준다.
+pub fn read_attachments(doc: &Document) -> Result<Vec<Attachment>> {
+    let catalog = doc.catalog().context("trailer /Root is not a catalog dictionary")?;
+    let mut specs = Vec::new();
+    if let Ok(tree) = deref_dict(doc, catalog, b"Names").and_then(|n| deref_dict(doc, n, b"EmbeddedFiles")) {
+        collect_name_tree(doc, tree, 0, &mut specs)?;
+    }
+    if let Ok(af) = catalog.get(b"AF").and_then(|a| doc.dereference(a)).and_then(|(_, a)| a.as_array()) {
+        specs.extend(af.iter());
+    }
+
+    let mut seen_ids = Vec::new();
+    let mut out: Vec<Attachment> = Vec::new();
+    for spec in specs {
+        let (id, spec) = doc.dereference(spec).context("filespec")?;
+        if let Some(id) = id {
+            if seen_ids.contains(&id) {
+                continue;
+            }
+            seen_ids.push(id);
+        }
+        let spec = spec.as_dict().context("filespec is not a dictionary")?;
+        let name = [b"UF".as_slice(), b"F"]
+            .iter()
+            .find_map(|k| spec.get(k).and_then(Object::as_str).ok())
+            .map(decode_text_string)
+            .context("filespec has no /UF or /F name")?;
+        if out.iter().any(|a| a.name == name) {
+            continue;
+        }
+        let ef = deref_dict(doc, spec, b"EF").with_context(|| format!("{}: /EF", name))?;
+        let stream = [b"F".as_slice(), b"UF"]
+            .iter()
+            .find_map(|k| ef.get(k).ok())
+            .with_context(|| format!("{}: /EF has no /F stream", name))?;
+        let stream = doc.dereference(stream)?.1.as_stream().with_context(|| format!("{}: /EF /F is not a stream", name))?;
+        let data = if stream.dict.has(b"Filter") {
+            stream.decompressed_content().with_context(|| format!("{}: cannot decode the stream filter", name))?
+        } else {
+            stream.content.clone()
+        };
+        let mime = stream.dict.get(b"Subtype").and_then(Object::as_name_str).unwrap_or("application/octet-stream");
+        let mut att = Attachment::bytes(&name, mime, data);
+        att.options.description = spec.get(b"Desc").and_then(Object::as_str).ok().map(decode_text_string);
+        att.options.language = spec.get(b"Lang").and_then(Object::as_str).ok().map(decode_text_string);
+        att.options.creation_date = deref_dict(doc, &stream.dict, b"Params")
+            .ok()
+            .and_then(|p| p.get(b"CreationDate").and_then(Object::as_str).ok())
+            .map(|d| String::from_utf8_lossy(d).into_owned());
+        out.push(att);
+    }
+    Ok(out)
+}
+
+/// name tree 노드의 값(`/Names`의 홀수 번째)을 `/Kids`까지 따라가며 모은다.
+fn collect_name_tree<'a>(
+    doc: &'a Document,
+    node: &'a lopdf::Dictionary,
+    depth: usize,
+    out: &mut Vec<&'a Object>,
+) -> Result<()> {
+    if depth > NAME_TREE_MAX_DEPTH {
+        bail!("/EmbeddedFiles name tree is deeper than {} levels (cycle?)", NAME_TREE_MAX_DEPTH);
+    }
+    if let Ok(names) = node.get(b"Names").and_then(|n| doc.dereference(n)).and_then(|(_, n)| n.as_array()) {
+        out.extend(names.iter().skip(1).step_by(2));
+    }
+    if let Ok(kids) = node.get(b"Kids").and_then(|k| doc.dereference(k)).and_then(|(_, k)| k.as_array()) {
+        for kid in kids {
+            let kid = doc.dereference(kid)?.1.as_dict().context("/EmbeddedFiles /Kids entry is not a dictionary")?;
+            collect_name_tree(doc, kid, depth + 1, out)?;
+        }
+    }
+    Ok(())
+}
+
+/// PDF text string: UTF-16BE BOM이 있으면 그걸로, 아니면 (PDFDocEncoding ≈ ASCII) 바이트 그대로
+fn decode_text_string(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xfe, 0xff]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
@@ -0,0 +1,310 @@
+//! ffprobe로 입력 정보 확인 (자동 크기/fps, 오디오 스트림 유무, 검은 띠), ffmpeg 사전 점검
+
+use crate::source::{echo_stderr, stderr_excerpt, Crop, Trim};
+use anyhow::{bail, Context, Result};
+use std::{
+    io::ErrorKind,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// 첫 번째 비디오 스트림 정보
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    /// r_frame_rate (알 수 없으면 `None`)
+    pub fps: Option<f32>,
+    /// 컨테이너 길이 (초, 알 수 없으면 `None`)
+    pub duration: Option<f64>,
+    /// 스트림 `nb_frames` (원본 fps 기준, 컨테이너가 안 알려주면 `None`)
+    pub frame_count: Option<u64>,
+}
+
+impl VideoInfo {
+    /// `trim` 구간을 `fps`로 뽑았을 때 나올 프레임 수 추정. 길이를 모르면 `nb_frames / fps`로 구한다.
+    pub fn output_frames(&self, trim: &Trim, fps: f32) -> Option<u64> {
+        let total = self.duration.or_else(|| Some(self.frame_count? as f64 / self.fps? as f64))?;
+        let mut secs = (total - trim.start.unwrap_or(0.0)).max(0.0);
+        if let Some(d) = trim.duration {
+            secs = secs.min(d);
+        }
+        Some((secs * fps as f64).round() as u64)
+    }
+}
+
+/// 요청한 출력 크기/fps. `None`은 `auto` (소스에서 가져온다).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TargetSize {
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+    pub fps: Option<f32>,
+}
+
+impl TargetSize {
+    pub fn is_auto(&self) -> bool {
+        self.width.is_none() || self.height.is_none() || self.fps.is_none()
+    }
+
+    /// 빈 값을 소스 정보로 채운다. 한 변만 주어지면 소스 비율로 나머지를 계산한다.
+    /// `info`가 없으면 (ffprobe 없음) 80x60, 4:3, 30fps 기준으로 채운다.
+    pub fn resolve(&self, info: Option<&VideoInfo>) -> (u16, u16, f32) {
+        let (sw, sh) = info.map_or((80, 60), |i| (i.width.max(1), i.height.max(1)));
+        let scale = |v: u16, num: u32, den: u32| {
+            ((v as f64 * num as f64 / den as f64).round() as u64).clamp(1, u16::MAX as u64) as u16
+        };
+        let (w, h) = match (self.width, self.height) {
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, scale(w, sh, sw)),
+            (None, Some(h)) => (scale(h, sw, sh), h),
+            (None, None) => (sw.min(u16::MAX as u32) as u16, sh.min(u16::MAX as u32) as u16),
+        };
+        let fps = self.fps.or(info.and_then(|i| i.fps)).unwrap_or(30.0);
+        (w, h, fps)
+    }
+}
+
+/// `"30000/1001"` 또는 `"25"` → fps
+fn parse_rate(s: &str) -> Option<f32> {
+    let (num, den) = match s.split_once('/') {
+        Some((n, d)) => (n.trim().parse::<f64>().ok()?, d.trim().parse::<f64>().ok()?),
+        None => (s.trim().parse::<f64>().ok()?, 1.0),
+    };
+    if num <= 0.0 || den <= 0.0 {
+        return None;
+    }
+    Some((num / den) as f32)
+}
+
+/// ffprobe로 첫 비디오 스트림의 크기, frame rate, 프레임 수와 길이를 읽는다. ffprobe가 없으면 `Ok(None)`.
+pub fn probe_video(path: &Path) -> Result<Option<VideoInfo>> {
+    let out = match Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=width,height,r_frame_rate,nb_frames:format=duration"])
+        .args(["-of", "default=nw=1"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+    {
+        Ok(out) => out,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("failed to run ffprobe"),
+    };
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    echo_stderr("ffprobe", &stderr);
+    if !out.status.success() {
+        let stderr = stderr_excerpt(&stderr);
+        if stderr.is_empty() {
+            bail!("ffprobe failed on {} ({})", path.display(), out.status);
+        }
+        bail!("ffprobe failed on {} ({})\nffprobe stderr:\n{}", path.display(), out.status, stderr);
+    }
+    let (mut width, mut height) = (None, None);
+    let mut info = VideoInfo { width: 0, height: 0, fps: None, duration: None, frame_count: None };
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        // 모르는 값은 "N/A"로 나온다
+        match line.trim().split_once('=') {
+            Some(("width", v)) => width = Some(v.parse::<u32>().with_context(|| format!("ffprobe: bad width '{}'", v))?),
+            Some(("height", v)) => height = Some(v.parse::<u32>().with_context(|| format!("ffprobe: bad height '{}'", v))?),
+            Some(("r_frame_rate", v)) => info.fps = parse_rate(v),
+            Some(("nb_frames", v)) => info.frame_count = v.parse().ok().filter(|&n| n > 0),
+            Some(("duration", v)) => info.duration = v.parse().ok().filter(|d: &f64| d.is_finite() && *d > 0.0),
+            _ => {}
+        }
+    }
+    let (Some(width), Some(height)) = (width, height) else {
+        bail!("no video stream found in {}", path.display());
+    };
+    if width == 0 || height == 0 {
+        bail!("ffprobe reported an empty video stream ({}x{})", width, height);
+    }
+    Ok(Some(VideoInfo { width, height, ..info }))
+}
+
+/// 이보다 오래된 ffmpeg는 거부한다 (major, minor)
+pub const MIN_FFMPEG_VERSION: (u32, u32) = (4, 0);
+
+const FFMPEG_INSTALL_HINT: &str =
+    "install a recent ffmpeg (e.g. `sudo apt install ffmpeg`, `brew install ffmpeg`, or https://ffmpeg.org/download.html) and make sure it is on PATH";
+
+/// `ffmpeg -version` 첫 줄 ("ffmpeg version 6.1.1 ...", "ffmpeg version n5.0-...")에서 (major, minor).
+/// git 빌드("N-112233-g...")처럼 번호가 없으면 `None`.
+pub fn parse_ffmpeg_version(line: &str) -> Option<(u32, u32)> {
+    let v = line.strip_prefix("ffmpeg version ")?.split_whitespace().next()?;
+    let v = v.strip_prefix('n').unwrap_or(v);
+    let mut parts = v.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    Some((major, minor))
+}
+
+fn ffmpeg_list(arg: &str) -> Result<String> {
+    let out = Command::new("ffmpeg")
+        .args(["-hide_banner", arg])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .with_context(|| format!("failed to run ffmpeg {}", arg))?;
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// 인코딩 전에 ffmpeg가 있는지, 너무 오래되지 않았는지, `gray` pixel format과
+/// `rawvideo` muxer를 지원하는지 확인한다. 알아낸 버전을 돌려준다 (git 빌드면 `None`).
+pub fn check_ffmpeg() -> Result<Option<(u32, u32)>> {
+    let out = match Command::new("ffmpeg").arg("-version").stdin(Stdio::null()).stderr(Stdio::null()).output() {
+        Ok(out) => out,
+        Err(e) if e.kind() == ErrorKind::NotFound => bail!("ffmpeg not found: {}", FFMPEG_INSTALL_HINT),
+        Err(e) => return Err(e).context("failed to run ffmpeg -version"),
+    };
+    if !out.status.success() {
+        bail!("ffmpeg -version failed ({}): {}", out.status, FFMPEG_INSTALL_HINT);
+    }
+    let text = String::from_utf8_lossy(&out.stdout);
+    let first = text.lines().next().unwrap_or("").trim();
+    let version = parse_ffmpeg_version(first);
+    if let Some((major, minor)) = version {
+        if (major, minor) < MIN_FFMPEG_VERSION {
+            bail!(
+                "ffmpeg {}.{} is too old (need {}.{} or newer): {}",
+                major,
+                minor,
+                MIN_FFMPEG_VERSION.0,
+                MIN_FFMPEG_VERSION.1,
+                FFMPEG_INSTALL_HINT
+            );
+        }
+    }
+
+    // -pix_fmts: "IO... gray                   1            8      8"
+    let has_gray = ffmpeg_list("-pix_fmts")?
+        .lines()
+        .any(|l| l.split_whitespace().nth(1) == Some("gray"));
+    if !has_gray {
+        bail!("this ffmpeg build does not support the gray pixel format: {}", FFMPEG_INSTALL_HINT);
+    }
+    // -muxers: " E rawvideo        raw video"
+    let has_rawvideo = ffmpeg_list("-muxers")?.lines().any(|l| {
+        let mut w = l.split_whitespace();
+        matches!((w.next(), w.next()), (Some(flags), Some("rawvideo")) if flags.contains('E'))
+    });
+    if !has_rawvideo {
+        bail!("this ffmpeg build has no rawvideo muxer: {}", FFMPEG_INSTALL_HINT);
+    }
+    Ok(version)
+}
+
+/// `--autocrop`에서 cropdetect에 넘기는 프레임 수 (`fps=2`로 솎아서 앞쪽 최대 1분)
+pub const CROPDETECT_FRAMES: u32 = 120;
+
+/// cropdetect stderr의 마지막 `crop=W:H:X:Y`. `reset=0`이라 마지막 값이 지금까지 본
+/// 프레임 전체를 덮는 영역이다. 전부 검은 프레임이면 음수가 나오므로 `None`.
+pub fn parse_cropdetect(stderr: &str) -> Option<Crop> {
+    let last = stderr.lines().rev().find_map(|l| l.rsplit_once("crop=").map(|(_, c)| c))?;
+    last.split_whitespace().next()?.parse().ok()
+}
+
+/// ffmpeg cropdetect로 검은 띠를 찾는다. 띠가 없으면 (`src_w x src_h` 전체면) `None`.
+pub fn detect_crop(path: &Path, trim: &Trim, src_w: u32, src_h: u32) -> Result<Option<Crop>> {
+    // cropdetect 결과는 info 레벨로 찍히므로 -loglevel error를 주지 않는다
+    let out = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats"])
+        .args(trim.input_args())
+        .arg("-i")
+        .arg(path)
+        .args(["-an", "-vf", "fps=2,cropdetect=limit=24:round=2:reset=0", "-frames:v"])
+        .arg(CROPDETECT_FRAMES.to_string())
+        .args(["-f", "null", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .output()
+        .context("failed to spawn ffmpeg for cropdetect (is it installed?)")?;
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    echo_stderr("ffmpeg", &stderr);
+    if !out.status.success() {
+        bail!("ffmpeg cropdetect exited with non-zero status ({})\nffmpeg stderr:\n{}", out.status, stderr_excerpt(&stderr));
+    }
+    let crop = parse_cropdetect(&stderr).filter(|c| (c.width, c.height) != (src_w, src_h));
+    if let Some(c) = &crop {
+        c.check_fits(src_w, src_h).context("cropdetect returned an invalid area")?;
+    }
+    Ok(crop)
+}
+
+/// 첫 번째 오디오 스트림 정보 (ffprobe가 알려준 것만)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioInfo {
+    /// ffprobe `codec_name` (opus, vorbis, mp3, ...)
+    pub codec: Option<String>,
+    /// 컨테이너 길이 (초)
+    pub duration: Option<f64>,
+}
+
+/// 첫 오디오 스트림의 코덱과 길이. ffprobe가 없거나 읽지 못하면 `None`.
+pub fn probe_audio(path: &Path) -> Option<AudioInfo> {
+    let out = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "a:0", "-show_entries", "stream=codec_name:format=duration"])
+        .args(["-of", "default=nw=1"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let mut info = AudioInfo::default();
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        match line.trim().split_once('=') {
+            Some(("codec_name", v)) => info.codec = Some(v.to_string()),
+            Some(("duration", v)) => info.duration = v.parse().ok().filter(|d: &f64| d.is_finite() && *d >= 0.0),
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+/// 입력에 오디오 스트림이 있는지 ffprobe로 확인한다. ffprobe가 없으면 `None`.
+pub fn has_audio_stream(path: &Path) -> Option<bool> {
+    let out = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "a", "-show_entries", "stream=index", "-of", "csv=p=0"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None; // 판단하지 못함: 트랜스코딩에서 에러가 나게 둔다
+    }
+    Some(!out.stdout.iter().all(u8::is_ascii_whitespace))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(crop: &str) -> String {
+        format!("[Parsed_cropdetect_1 @ 0x55d0c8a0] x1:0 x2:639 y1:60 y2:419 w:640 h:352 x:0 y:64 pts:1 t:0.500000 limit:0.094118 crop={}\n", crop)
+    }
+
+    #[test]
+    fn parse_cropdetect_takes_the_last_crop_line() {
+        let stderr = [
+            "Input #0, matroska,webm, from 'in.mkv':\n".to_string(),
+            line("640:352:0:64"),
+            "frame=    1 fps=0.0 q=-0.0 size=N/A time=00:00:00.50\n".to_string(),
+            line("640:360:0:60"),
+            line("624:360:8:60"),
+            "[out#0/null @ 0x55d0c8b0] video:1kB audio:0kB\n".to_string(),
+        ]
+        .concat();
+        assert_eq!(parse_cropdetect(&stderr), Some(Crop { width: 624, height: 360, x: 8, y: 60 }));
+    }
+
+    #[test]
+    fn parse_cropdetect_without_a_usable_crop() {
+        assert_eq!(parse_cropdetect(""), None);
+        assert_eq!(parse_cropdetect("Input #0, mov,mp4, from 'in.mp4':\nframe=  120 fps=0.0\n"), None);
+        // 전부 검은 프레임: 폭과 높이가 음수로 나온다
+        assert_eq!(parse_cropdetect(&[line("640:352:0:64"), line("-640:-480:640:480")].concat()), None);
+    }
+}
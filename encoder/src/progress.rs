@@ -0,0 +1,231 @@
+//! 인코딩 진행 표시와 `--quiet`
+//!
+//! `ProgressSource`가 프레임을 읽을 때마다, `CountingWriter`가 blob에 쓸 때마다 숫자를 올리고
+//! `Progress`가 일정 간격으로 stderr에 한 줄을 그린다. 터미널이면 같은 줄을 `\r`로 덮어쓰고
+//! (10번/초), 파이프나 로그 파일이면 몇 초마다 한 줄씩 남긴다.
+//! `--quiet`면 진행 표시와 안내/경고 출력을 모두 끈다 (에러는 main이 그대로 출력한다).
+//! `--verbose`면 ffmpeg stderr를 `[ffmpeg] ` 접두어를 붙여 그대로 흘려보낸다.
+
+use crate::source::{FrameSource, FrameTime};
+use anyhow::Result;
+use std::{
+    io::{self, IsTerminal, Seek, SeekFrom, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// `--quiet`: 진행 표시, 안내, 경고를 끈다
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// `--verbose`: ffmpeg stderr를 받는 대로 다시 출력한다
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// 터미널에서 진행 줄을 다시 그리는 간격
+const TTY_INTERVAL: Duration = Duration::from_millis(100);
+/// 터미널이 아닐 때 진행 줄을 남기는 간격
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+/// ETA를 보이기 시작하는 경과 시간 (초)
+const ETA_WARMUP_SECS: f64 = 1.0;
+
+/// 요약 출력용 길이: `3m38s`, `1h02m05s`, 1분 미만은 `12.5s`
+pub fn format_duration(secs: f64) -> String {
+    if secs < 60.0 {
+        return format!("{:.1}s", secs);
+    }
+    let s = secs.round() as u64;
+    if s < 3600 {
+        format!("{}m{:02}s", s / 60, s % 60)
+    } else {
+        format!("{}h{:02}m{:02}s", s / 3600, s / 60 % 60, s % 60)
+    }
+}
+
+/// 진행 상태. `total`은 예상 프레임 수 (ffprobe 길이 또는 `max_frames`, 모르면 `None`).
+pub struct Progress {
+    /// 줄 앞에 붙는 단계 이름 (기본 `Encoding`)
+    label: &'static str,
+    total: Option<u64>,
+    frames: u64,
+    bytes: Arc<AtomicU64>,
+    start: Instant,
+    last: Instant,
+    tty: bool,
+    /// 터미널에 지우지 않은 진행 줄이 있다
+    drawn: bool,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(total: Option<u64>) -> Self {
+        let now = Instant::now();
+        Self {
+            label: "Encoding",
+            total: total.filter(|&t| t > 0),
+            frames: 0,
+            bytes: Arc::new(AtomicU64::new(0)),
+            start: now,
+            last: now,
+            tty: io::stderr().is_terminal(),
+            drawn: false,
+            enabled: !quiet(),
+        }
+    }
+
+    /// `--max-size` 샘플링처럼 인코딩이 아닌 단계에 쓸 때
+    pub fn with_label(mut self, label: &'static str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// `CountingWriter`에 넘길 blob 크기 카운터
+    pub fn bytes_counter(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.bytes)
+    }
+
+    /// 한 줄 요약: 프레임 수(/예상, %), 경과, ETA, blob 크기, 프레임당 바이트, 인코딩 속도
+    pub fn line(&self) -> String {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let fps = if elapsed > 0.0 { self.frames as f64 / elapsed } else { 0.0 };
+        let mut line = match self.total {
+            // ffprobe 길이는 추정이라 조금 넘을 수 있다
+            Some(total) => format!(
+                "{}: {}/{} frames ({:.1}%)",
+                self.label,
+                self.frames,
+                total,
+                (self.frames as f64 / total as f64 * 100.0).min(100.0)
+            ),
+            None => format!("{}: {} frames", self.label, self.frames),
+        };
+        line.push_str(&format!(", {} elapsed", format_duration(elapsed)));
+        // 처음 1초는 ffmpeg 시작 시간 때문에 속도가 들쭉날쭉해서 ETA를 보이지 않는다
+        if let Some(total) = self.total.filter(|_| elapsed >= ETA_WARMUP_SECS && fps > 0.0) {
+            line.push_str(&format!(", ETA {}", format_duration(total.saturating_sub(self.frames) as f64 / fps)));
+        }
+        // blob을 쓰지 않는 단계(샘플링)는 크기를 생략한다
+        if bytes > 0 {
+            if bytes < 1 << 20 {
+                line.push_str(&format!(", {:.1} KiB", bytes as f64 / 1024.0));
+            } else {
+                line.push_str(&format!(", {:.1} MiB", bytes as f64 / (1024.0 * 1024.0)));
+            }
+            line.push_str(&format!(", {:.1} B/frame", bytes as f64 / self.frames.max(1) as f64));
+        }
+        line.push_str(&format!(", {:.1} fps", fps));
+        line
+    }
+
+    fn tick(&mut self) {
+        self.frames += 1;
+        if !self.enabled {
+            return;
+        }
+        let interval = if self.tty { TTY_INTERVAL } else { LOG_INTERVAL };
+        if self.last.elapsed() < interval {
+            return;
+        }
+        self.last = Instant::now();
+        if self.tty {
+            eprint!("\r\x1b[2K{}", self.line());
+            self.drawn = true;
+        } else {
+            eprintln!("{}", self.line());
+        }
+    }
+
+    /// 터미널의 진행 줄을 지운다 (그 뒤 출력이 같은 줄에 이어지지 않게)
+    pub fn finish(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[2K");
+            self.drawn = false;
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// 읽은 프레임 수를 `Progress`에 올리는 소스
+pub struct ProgressSource<'a, S: FrameSource + ?Sized> {
+    pub inner: &'a mut S,
+    pub progress: &'a mut Progress,
+}
+
+impl<S: FrameSource + ?Sized> FrameSource for ProgressSource<'_, S> {
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+        let more = self.inner.read_frame(buf)?;
+        if more {
+            self.progress.tick();
+        }
+        Ok(more)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.progress.finish();
+        self.inner.finish()
+    }
+
+    fn frame_time(&self) -> Option<FrameTime> {
+        self.inner.frame_time()
+    }
+}
+
+/// 쓴 위치의 최댓값(= blob 크기)을 카운터에 기록하는 writer. `frame_count` patch처럼 seek로 돌아가도 줄지 않는다.
+pub struct CountingWriter<W> {
+    inner: W,
+    pos: u64,
+    bytes: Arc<AtomicU64>,
+}
+
+impl<W> CountingWriter<W> {
+    pub fn new(inner: W, bytes: Arc<AtomicU64>) -> Self {
+        Self { inner, pos: 0, bytes }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.pos += n as u64;
+        self.bytes.fetch_max(self.pos, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for CountingWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
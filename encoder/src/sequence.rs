@@ -0,0 +1,189 @@
+//! 이미지 시퀀스 입력 (`--frames`): PNG/JPEG 프레임 파일을 ffmpeg 없이 읽는다
+//!
+//! - 디렉터리: 안에 있는 `.png` / `.jpg` / `.jpeg` 파일 전부
+//! - 패턴: `render/frame_%05d.png`처럼 `%d` / `%0Nd` 하나가 들어간 경로.
+//!   같은 디렉터리에서 앞뒤가 같고 그 자리가 숫자인 파일을 모은다 (번호가 비어도 된다).
+//! - glob: `render/*.png`처럼 파일 이름에 `*` / `?`가 들어간 경로 (디렉터리 부분에는 쓸 수 없다).
+//!   디렉터리처럼 이미지 확장자인 파일만 모은다.
+//!
+//! 순서는 파일 이름의 자연 정렬 (`frame_2` < `frame_10`).
+
+use crate::source::FrameSource;
+use anyhow::{bail, Context, Result};
+use image::imageops::{self, FilterType};
+use std::{
+    cmp::Ordering,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const IMAGE_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+/// 디렉터리 안에서 프레임으로 쓸 파일 이름인지
+type NameFilter = Box<dyn Fn(&str) -> bool>;
+
+/// 숫자 구간은 값으로, 나머지는 문자 그대로 비교한다 (`a2.png` < `a10.png`).
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let na = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let nb = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            let (da, db) = (a[..na].trim_start_matches('0'), b[..nb].trim_start_matches('0'));
+            // 앞의 0을 뺀 길이 → 값 → 0 개수 순서
+            let ord = da.len().cmp(&db.len()).then_with(|| da.cmp(db)).then_with(|| na.cmp(&nb));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            (a, b) = (&a[na..], &b[nb..]);
+        } else {
+            if ca != cb {
+                return ca.cmp(&cb);
+            }
+            (a, b) = (&a[ca.len_utf8()..], &b[cb.len_utf8()..]);
+        }
+    }
+}
+
+/// `%d` / `%0Nd` 앞뒤로 나눈다. 패턴이 아니면 `None`.
+fn split_pattern(name: &str) -> Result<Option<(&str, &str)>> {
+    let Some(start) = name.find('%') else {
+        return Ok(None);
+    };
+    let rest = &name[start + 1..];
+    let spec_len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    if !rest[spec_len..].starts_with('d') {
+        bail!("invalid frame pattern '{}' (expected one %d or %0Nd)", name);
+    }
+    let suffix = &rest[spec_len + 1..];
+    if suffix.contains('%') {
+        bail!("invalid frame pattern '{}' (expected one %d or %0Nd)", name);
+    }
+    Ok(Some((&name[..start], suffix)))
+}
+
+/// `*`(0글자 이상), `?`(한 글자)만 있는 glob
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (p, n): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut pi, mut ni) = (0, 0);
+    // 마지막 `*` 위치와 그때 name 위치 (되돌아가서 한 글자 더 먹인다)
+    let mut star = None;
+    while ni < n.len() {
+        match p.get(pi) {
+            Some('*') => {
+                star = Some((pi, ni));
+                pi += 1;
+            }
+            Some(&c) if c == '?' || c == n[ni] => {
+                pi += 1;
+                ni += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    pi = sp + 1;
+                    ni = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// `--frames` 값을 프레임 파일 목록으로 바꾼다 (자연 정렬).
+pub fn list_frames(spec: &Path) -> Result<Vec<PathBuf>> {
+    let is_image = |name: &str| {
+        name.rsplit_once('.').is_some_and(|(_, e)| IMAGE_EXTENSIONS.iter().any(|x| e.eq_ignore_ascii_case(x)))
+    };
+    let (dir, filter): (PathBuf, NameFilter) = if spec.is_dir() {
+        (spec.to_path_buf(), Box::new(is_image))
+    } else {
+        let name = spec.file_name().and_then(|n| n.to_str()).context("invalid frame pattern")?;
+        let dir = spec.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        if dir.to_str().is_some_and(|d| d.contains(['*', '?'])) {
+            bail!("--frames {}: glob wildcards are only supported in the file name", spec.display());
+        }
+        let matches: NameFilter = if name.contains(['*', '?']) {
+            let pattern = name.to_string();
+            Box::new(move |name: &str| is_image(name) && glob_match(&pattern, name))
+        } else {
+            let Some((prefix, suffix)) = split_pattern(name)? else {
+                bail!("--frames {} is neither a directory, a %d pattern nor a glob", spec.display());
+            };
+            let (prefix, suffix) = (prefix.to_string(), suffix.to_string());
+            Box::new(move |name: &str| {
+                name.len() > prefix.len() + suffix.len()
+                    && name.starts_with(&prefix)
+                    && name.ends_with(&suffix)
+                    && name[prefix.len()..name.len() - suffix.len()].bytes().all(|b| b.is_ascii_digit())
+            })
+        };
+        (dir.to_path_buf(), matches)
+    };
+    let mut names = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("failed to read frame directory {}", dir.display()))? {
+        let entry = entry?;
+        if let Some(name) = entry.file_name().to_str() {
+            if filter(name) && entry.file_type()?.is_file() {
+                names.push(name.to_string());
+            }
+        }
+    }
+    if names.is_empty() {
+        bail!("no frame images found for --frames {}", spec.display());
+    }
+    names.sort_by(|a, b| natural_cmp(a, b));
+    Ok(names.into_iter().map(|n| dir.join(n)).collect())
+}
+
+/// 첫 프레임 이미지 크기 (auto w/h용)
+pub fn image_dimensions(path: &Path) -> Result<(u32, u32)> {
+    image::image_dimensions(path).with_context(|| format!("failed to read image {}", path.display()))
+}
+
+/// 이미지 파일을 하나씩 열어서 gray8로 바꾸고 w x h로 맞춘다.
+pub struct ImageSequenceSource {
+    paths: std::vec::IntoIter<PathBuf>,
+    width: u32,
+    height: u32,
+    /// 프레임 크기가 첫 프레임과 다르면 리사이즈 대신 에러 (`--strict-size`)
+    strict_size: bool,
+    first_dims: Option<(u32, u32)>,
+}
+
+impl ImageSequenceSource {
+    pub fn new(paths: Vec<PathBuf>, width: u16, height: u16, strict_size: bool) -> Self {
+        Self { paths: paths.into_iter(), width: width as u32, height: height as u32, strict_size, first_dims: None }
+    }
+}
+
+impl FrameSource for ImageSequenceSource {
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+        let Some(path) = self.paths.next() else {
+            return Ok(false);
+        };
+        let img = image::open(&path).with_context(|| format!("failed to read frame image {}", path.display()))?;
+        let dims = (img.width(), img.height());
+        let first = *self.first_dims.get_or_insert(dims);
+        if dims != first && self.strict_size {
+            bail!(
+                "frame image {} is {}x{}, but the first frame is {}x{} (--strict-size)",
+                path.display(),
+                dims.0,
+                dims.1,
+                first.0,
+                first.1
+            );
+        }
+        let mut gray = img.into_luma8();
+        if dims != (self.width, self.height) {
+            gray = imageops::resize(&gray, self.width, self.height, FilterType::Triangle);
+        }
+        buf.copy_from_slice(gray.as_raw());
+        Ok(true)
+    }
+}
@@ -0,0 +1,773 @@
+//! 프레임 입력 소스 (gray8, w*h 바이트 단위)
+
+use crate::progress::{quiet, verbose};
+use anyhow::{bail, Context, Result};
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+/// gray8 프레임을 하나씩 넘겨주는 입력.
+/// ffmpeg 파이프는 구현체 중 하나일 뿐이고, 테스트나 다른 프로그램은 직접 구현하면 된다.
+/// (`--jobs`에서는 별도 reader 스레드가 읽으므로 `Send`여야 한다.)
+pub trait FrameSource: Send {
+    /// 다음 프레임을 `buf` (정확히 w*h 바이트)에 채운다. 더 이상 프레임이 없으면 `false`.
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool>;
+
+    /// 입력을 다 읽은 뒤 호출된다 (프로세스 종료 확인 등).
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// `read_frame`이 마지막으로 채운 프레임의 표시 시각. PTS를 아는 소스만 준다 (`--vfr`).
+    fn frame_time(&self) -> Option<FrameTime> {
+        None
+    }
+}
+
+/// `--vfr`: 프레임 하나의 표시 시각 (ms). 소스 기준이라 0부터 시작하지 않을 수 있다.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTime {
+    pub pts_ms: f64,
+    /// 보여줄 길이 (모르면 `None`, 다음 프레임 시각으로 정해진다)
+    pub duration_ms: Option<f64>,
+}
+
+impl FrameTime {
+    /// showinfo 로그 한 줄 (`[Parsed_showinfo_3 @ 0x..] n:  12 pts: 6144 pts_time:0.48 duration:512
+    /// duration_time:0.04 ...`)에서 시각을 읽는다. ffmpeg 4.x에는 duration이 없다.
+    pub fn from_showinfo(line: &str) -> Option<Self> {
+        // 같은 필터가 찍는 다른 줄 (config, side data)은 건너뛴다
+        if !line.contains("Parsed_showinfo") || !line.contains(" n:") {
+            return None;
+        }
+        let field = |key: &str| -> Option<f64> {
+            let v: f64 = line.split_whitespace().find_map(|t| t.strip_prefix(key))?.parse().ok()?;
+            v.is_finite().then_some(v * 1000.0)
+        };
+        Some(Self { pts_ms: field("pts_time:")?, duration_ms: field("duration_time:").filter(|&d| d > 0.0) })
+    }
+}
+
+/// `--start` / `--duration`: 입력에서 잘라낼 구간 (초).
+/// 비디오와 오디오 ffmpeg에 같은 `-ss`/`-t`를 넘겨서 BA.bin과 AU가 같은 구간을 덮게 한다.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Trim {
+    pub start: Option<f64>,
+    pub duration: Option<f64>,
+}
+
+impl Trim {
+    pub fn is_set(&self) -> bool {
+        self.start.is_some() || self.duration.is_some()
+    }
+
+    /// `-i` 앞에 붙일 ffmpeg input 옵션 (input seek라서 출력 timestamp는 0부터 시작한다)
+    pub fn input_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(s) = self.start {
+            args.extend(["-ss".to_string(), format!("{:.3}", s)]);
+        }
+        if let Some(d) = self.duration {
+            args.extend(["-t".to_string(), format!("{:.3}", d)]);
+        }
+        args
+    }
+
+    /// `--resume`: 출력 프레임 `frames`개(`fps`)를 건너뛴 구간. 그 다음 프레임 시각보다 반 프레임 앞에서 시작해서
+    /// `-ss`의 ms 반올림에 그 프레임이 잘리지 않게 한다. 소스 fps가 `fps`의 배수가 아니면 fps 필터가 고르는 프레임이
+    /// 처음 인코딩과 한 칸 어긋날 수 있다.
+    pub fn skip_frames(self, frames: u32, fps: f32) -> Trim {
+        let skip = (frames as f64 - 0.5).max(0.0) / fps as f64;
+        Trim { start: Some(self.start.unwrap_or(0.0) + skip), duration: self.duration.map(|d| (d - skip).max(0.0)) }
+    }
+}
+
+/// 여러 파일을 ffmpeg concat demuxer로 이어 붙일 때 쓰는 목록 파일 (`--concat-audio`)
+/// (`file '/abs/segment1.mp4'` 한 줄씩). 임시 파일이라 drop되면 지워진다.
+pub struct ConcatList {
+    file: tempfile::NamedTempFile,
+}
+
+impl ConcatList {
+    /// 경로는 목록 파일 위치와 상관없게 절대 경로로 바꿔서 쓴다 (`-safe 0`).
+    pub fn new(paths: &[PathBuf]) -> Result<Self> {
+        let mut file = tempfile::Builder::new()
+            .prefix("badapple-concat-")
+            .suffix(".txt")
+            .tempfile()
+            .context("failed to create concat list file")?;
+        for path in paths {
+            let abs = fs::canonicalize(path).with_context(|| format!("input not found: {}", path.display()))?;
+            writeln!(file, "{}", concat_list_entry(&abs)).context("failed to write concat list file")?;
+        }
+        file.flush().context("failed to write concat list file")?;
+        Ok(Self { file })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+}
+
+/// concat 목록 한 줄. 작은따옴표는 `'\''`로 닫았다 다시 연다.
+pub fn concat_list_entry(path: &Path) -> String {
+    format!("file '{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+/// `HH:MM:SS[.ms]`, `MM:SS[.ms]` 또는 초(`90.5`) → 초
+pub fn parse_timestamp(s: &str) -> Result<f64> {
+    let invalid = || format!("invalid time '{}' (expected HH:MM:SS[.ms], MM:SS[.ms] or seconds)", s);
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() > 3 {
+        bail!("{}", invalid());
+    }
+    let (last, rest) = parts.split_last().context("empty time")?;
+    let mut secs: f64 = last.parse().ok().filter(|v: &f64| v.is_finite() && *v >= 0.0).with_context(invalid)?;
+    if !rest.is_empty() && secs >= 60.0 {
+        bail!("{}", invalid());
+    }
+    for (i, p) in rest.iter().rev().enumerate() {
+        let v: u32 = p.parse().ok().with_context(invalid)?;
+        // 분은 60 미만, 시는 제한 없음
+        if i == 0 && rest.len() == 2 && v >= 60 {
+            bail!("{}", invalid());
+        }
+        secs += v as f64 * 60f64.powi(i as i32 + 1);
+    }
+    Ok(secs)
+}
+
+/// `--crop W:H:X:Y` / `--autocrop`: scale 전에 잘라낼 소스 영역 (소스 픽셀 단위)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Crop {
+    /// ffmpeg `crop` 필터
+    pub fn filter(&self) -> String {
+        format!("crop={}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+
+    /// `src_w x src_h` 소스 안에 들어가는지 확인한다.
+    pub fn check_fits(&self, src_w: u32, src_h: u32) -> Result<()> {
+        if self.x as u64 + self.width as u64 > src_w as u64 || self.y as u64 + self.height as u64 > src_h as u64 {
+            bail!("crop {} does not fit inside the {}x{} source", self, src_w, src_h);
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Crop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+impl std::str::FromStr for Crop {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let v: Vec<u32> = s
+            .split(':')
+            .map(|p| p.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| anyhow::anyhow!("invalid crop '{}' (expected W:H:X:Y)", s))?;
+        match v[..] {
+            [width, height, x, y] if width > 0 && height > 0 => Ok(Crop { width, height, x, y }),
+            [_, _, _, _] => bail!("invalid crop '{}' (width and height must be at least 1)", s),
+            _ => bail!("invalid crop '{}' (expected W:H:X:Y)", s),
+        }
+    }
+}
+
+/// `--scale-mode`: 소스 비율이 w x h와 다를 때 맞추는 방법
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleMode {
+    /// w x h로 그대로 늘린다 (비율이 깨진다)
+    #[default]
+    Stretch,
+    /// 비율을 유지하고 남는 부분을 `fill` gray(0 = 검정, 255 = 흰색)로 채운다 (letterbox / pillarbox)
+    Pad { fill: u8 },
+    /// 비율을 유지해서 w x h를 다 덮게 키우고 가운데를 잘라낸다
+    Crop,
+}
+
+impl ScaleMode {
+    /// ffmpeg scale(+pad/crop) 필터
+    pub fn filter(self, w: u16, h: u16) -> String {
+        match self {
+            ScaleMode::Stretch => format!("scale={}:{}", w, h),
+            ScaleMode::Pad { fill } => format!(
+                "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2:color=0x{f:02x}{f:02x}{f:02x}",
+                w = w,
+                h = h,
+                f = fill
+            ),
+            ScaleMode::Crop => format!("scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h}", w = w, h = h),
+        }
+    }
+}
+
+impl std::str::FromStr for ScaleMode {
+    type Err = anyhow::Error;
+
+    /// `pad`(= `letterbox`)는 검정으로 채운다 (`--pad-color`로 바꾼다)
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "stretch" => ScaleMode::Stretch,
+            "pad" | "letterbox" => ScaleMode::Pad { fill: 0 },
+            "crop" => ScaleMode::Crop,
+            _ => bail!("unknown scale mode '{}' (expected stretch, pad/letterbox, crop)", s),
+        })
+    }
+}
+
+/// `--pad-color black|white|<0-255>`
+pub fn parse_pad_color(s: &str) -> Result<u8> {
+    match s {
+        "black" => Ok(0),
+        "white" => Ok(255),
+        _ => s.parse().with_context(|| format!("invalid pad color '{}' (expected black, white or a gray level 0-255)", s)),
+    }
+}
+
+/// ffmpeg 디코딩 옵션
+#[derive(Debug, Clone)]
+pub struct FfmpegOptions {
+    pub width: u16,
+    pub height: u16,
+    pub fps: f32,
+    /// 마지막 프레임이 잘려 있으면 경고 대신 에러
+    pub strict: bool,
+    /// 소스 비율이 다를 때 맞추는 방법 (`--scale-mode`, `--keep-aspect`는 검정 pad)
+    pub scale: ScaleMode,
+    /// scale 뒤, format=gray 앞에 끼워 넣을 필터 (`--vf-extra`)
+    pub extra_vf: Option<String>,
+    /// `--start` / `--duration`
+    pub trim: Trim,
+    /// scale 전에 잘라낼 영역 (`--crop`, `--autocrop`)
+    pub crop: Option<Crop>,
+    /// 입력 경로가 `ConcatList` 목록 파일이다 (`-f concat -safe 0 -i <list>`)
+    pub concat: bool,
+    /// `--vfr`: fps 필터 없이 소스 프레임을 그대로 받고, 끝에 붙인 showinfo 로그로 프레임마다 PTS를 읽는다
+    pub vfr: bool,
+}
+
+impl FfmpegOptions {
+    /// `-vf` 필터 체인: [crop →] fps → scale(+pad/crop) [→ `--vf-extra`] → format=gray
+    /// (`--vfr`면 fps 대신 맨 끝에 showinfo)
+    pub fn video_filter(&self) -> String {
+        let (w, h) = (self.width, self.height);
+        let mut chain = Vec::new();
+        if let Some(crop) = &self.crop {
+            chain.push(crop.filter());
+        }
+        if !self.vfr {
+            chain.push(format!("fps={}", if self.fps > 0.0 { self.fps.to_string() } else { "30".to_string() }));
+        }
+        chain.push(self.scale.filter(w, h));
+        if let Some(extra) = self.extra_vf.as_deref().filter(|s| !s.is_empty()) {
+            warn_extra_vf(extra, self.vfr);
+            chain.push(extra.to_string());
+        }
+        chain.push("format=gray".to_string());
+        if self.vfr {
+            chain.push("showinfo".to_string());
+        }
+        chain.join(",")
+    }
+}
+
+/// `--vf-extra`에 인코더가 이미 넣는 필터가 들어 있으면 경고만 한다.
+/// (크기를 바꾸는 필터는 w*h 프레임 크기를 깨뜨린다.)
+fn warn_extra_vf(extra: &str, vfr: bool) {
+    let mut checks = vec![("format=gray", "format=gray"), ("scale=", "scale")];
+    if vfr {
+        // 프레임 시각을 읽는 showinfo 줄이 두 번 찍힌다
+        checks.push(("showinfo", "showinfo (used by --vfr)"));
+    }
+    for (needle, what) in checks {
+        if extra.contains(needle) && !quiet() {
+            eprintln!(
+                "warning: --vf-extra '{}' contains {}, which the encoder already adds (this may duplicate it or change the frame size)",
+                extra, what
+            );
+        }
+    }
+}
+
+/// `buf`가 찰 때까지 읽는다. EOF면 그때까지 읽은 바이트 수를 돌려준다.
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut read_total = 0usize;
+    while read_total < buf.len() {
+        let n = reader.read(&mut buf[read_total..])?;
+        if n == 0 {
+            break;
+        }
+        read_total += n;
+    }
+    Ok(read_total)
+}
+
+/// 이미 gray8 rawvideo인 입력 (stdin 파이프 등)을 w*h 바이트씩 그대로 읽는다.
+/// 크기/fps는 알 수 없으므로 호출하는 쪽이 정한다.
+pub struct RawSource<R: Read + Send> {
+    reader: R,
+    /// 마지막 프레임이 잘려 있으면 경고 대신 에러
+    strict: bool,
+    frames_read: u64,
+}
+
+impl<R: Read + Send> RawSource<R> {
+    pub fn new(reader: R, strict: bool) -> Self {
+        Self { reader, strict, frames_read: 0 }
+    }
+}
+
+impl<R: Read + Send> FrameSource for RawSource<R> {
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+        let read_total = read_up_to(&mut self.reader, buf).context("failed to read raw frames")?;
+        if read_total == buf.len() {
+            self.frames_read += 1;
+            return Ok(true);
+        }
+        if read_total == 0 {
+            return Ok(false);
+        }
+        // 입력 길이가 w*h의 배수가 아니다 (크기를 잘못 줬거나 생산자가 중간에 끝남)
+        let msg = format!("truncated raw frame {}: got {} of {} bytes", self.frames_read, read_total, buf.len());
+        if self.strict {
+            bail!("{}", msg);
+        }
+        if !quiet() {
+            eprintln!("warning: {}, dropping it (use --strict to fail instead)", msg);
+        }
+        Ok(false)
+    }
+}
+
+// stderr는 이 크기까지만 모아둔다 (에러 메시지용)
+const STDERR_KEEP: usize = 64 * 1024;
+/// 에러 메시지에 넣는 ffmpeg stderr 앞부분 크기
+pub(crate) const STDERR_EXCERPT: usize = 2 * 1024;
+
+/// 에러 메시지용 ffmpeg stderr: 앞뒤 공백을 떼고 `STDERR_EXCERPT` bytes까지 (넘으면 남은 크기를 적는다)
+pub(crate) fn stderr_excerpt(stderr: &str) -> String {
+    let stderr = stderr.trim();
+    if stderr.len() <= STDERR_EXCERPT {
+        return stderr.to_string();
+    }
+    let mut end = STDERR_EXCERPT;
+    while !stderr.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n... ({} more bytes)", &stderr[..end], stderr.len() - end)
+}
+
+/// `--verbose`: `output()`으로 다 받은 stderr를 `[program] ` 접두어를 붙여 다시 출력한다
+pub(crate) fn echo_stderr(program: &str, stderr: &str) {
+    if verbose() {
+        for line in stderr.lines() {
+            eprintln!("[{}] {}", program, line);
+        }
+    }
+}
+
+/// ffmpeg로 raw gray 프레임을 stdout 파이프로 받는다.
+/// - [crop,] fps, scale, (`--vf-extra`), format=gray (`FfmpegOptions::video_filter`)
+/// - `--vfr`: `-vsync passthrough`로 프레임을 복제/버리지 않고, stderr의 showinfo 줄을 프레임 순서대로 받는다
+pub struct FfmpegSource {
+    child: Child,
+    stdout: ChildStdout,
+    strict: bool,
+    /// stdout EOF까지 읽었는지 (아니면 finish에서 ffmpeg를 종료시킨다)
+    eof: bool,
+    frames_read: u64,
+    /// 마지막에 잘린 프레임의 bytes (0이면 없음)
+    partial_bytes: usize,
+    /// "no frames" 에러 메시지용
+    input: PathBuf,
+    trim_start: Option<f64>,
+    stderr: Arc<Mutex<String>>,
+    stderr_thread: Option<thread::JoinHandle<()>>,
+    /// `--vfr`: stderr 스레드가 읽은 프레임 시각과, 마지막으로 읽은 프레임의 시각
+    times: Option<mpsc::Receiver<FrameTime>>,
+    last_time: Option<FrameTime>,
+}
+
+impl FfmpegSource {
+    pub fn spawn(video_path: &Path, opts: &FfmpegOptions) -> Result<Self> {
+        let vf = opts.video_filter();
+        // showinfo는 info 레벨로 찍히므로 --vfr면 -loglevel error를 주지 않는다
+        let mut child = Command::new("ffmpeg")
+            .args(if opts.vfr { &["-hide_banner", "-nostats"][..] } else { &["-hide_banner", "-loglevel", "error"] })
+            .args(opts.trim.input_args())
+            .args(if opts.concat { &["-f", "concat", "-safe", "0"][..] } else { &[] })
+            .args([
+                "-i",
+                video_path.to_string_lossy().as_ref(),
+                "-vf",
+                &vf,
+            ])
+            .args(if opts.vfr { &["-vsync", "passthrough"][..] } else { &[] })
+            .args([
+                "-f",
+                "rawvideo",
+                "-pix_fmt",
+                "gray",
+                "pipe:1",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("failed to spawn ffmpeg (is it installed?)")?;
+
+        let stdout = child.stdout.take().context("failed to take ffmpeg stdout")?;
+
+        // stderr는 에러 메시지용으로 모아두고, --verbose면 받는 대로 터미널에도 흘려보낸다.
+        let child_stderr = child.stderr.take().context("failed to take ffmpeg stderr")?;
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let sink = Arc::clone(&stderr);
+        let echo = verbose();
+        let (times_tx, times) = if opts.vfr {
+            let (tx, rx) = mpsc::channel();
+            (Some(tx), Some(rx))
+        } else {
+            (None, None)
+        };
+        let stderr_thread = thread::spawn(move || {
+            for line in BufReader::new(child_stderr).lines().map_while(Result::ok) {
+                // --vfr: showinfo 줄은 시각만 넘기고 모아두지 않는다 (프레임마다 한 줄 이상이라 금방 찬다)
+                if let Some(tx) = &times_tx {
+                    if line.contains("Parsed_showinfo") {
+                        if let Some(time) = FrameTime::from_showinfo(&line) {
+                            let _ = tx.send(time);
+                        }
+                        continue;
+                    }
+                }
+                if echo {
+                    eprintln!("[ffmpeg] {}", line);
+                }
+                let mut s = sink.lock().unwrap();
+                if s.len() < STDERR_KEEP {
+                    s.push_str(&line);
+                    s.push('\n');
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdout,
+            strict: opts.strict,
+            eof: false,
+            frames_read: 0,
+            partial_bytes: 0,
+            input: video_path.to_path_buf(),
+            trim_start: opts.trim.start,
+            stderr,
+            stderr_thread: Some(stderr_thread),
+            times,
+            last_time: None,
+        })
+    }
+
+    /// ffmpeg가 끝날 때까지 기다리고 stderr를 전부 모은다.
+    fn wait(&mut self) -> Result<(std::process::ExitStatus, String)> {
+        let status = self.child.wait().context("failed to wait for ffmpeg")?;
+        if let Some(t) = self.stderr_thread.take() {
+            let _ = t.join();
+        }
+        let stderr = self.stderr.lock().unwrap().clone();
+        Ok((status, stderr))
+    }
+}
+
+impl FrameSource for FfmpegSource {
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+        // raw gray 한 프레임 읽기
+        let read_total = read_up_to(&mut self.stdout, buf)?;
+        if read_total < buf.len() {
+            self.eof = true;
+        }
+        if read_total == buf.len() {
+            // showinfo 줄은 프레임이 파이프로 나가기 전에 찍히므로 여기서 기다려도 막히지 않는다
+            if let Some(times) = &self.times {
+                let time = times.recv().ok().with_context(|| {
+                    format!("ffmpeg did not report a timestamp for frame {} (showinfo output missing?)", self.frames_read)
+                })?;
+                self.last_time = Some(time);
+            }
+            self.frames_read += 1;
+            return Ok(true);
+        }
+        if read_total == 0 {
+            return Ok(false);
+        }
+
+        // 프레임 중간에서 EOF: 크기/포맷이 어긋났거나 ffmpeg가 중간에 죽었다.
+        let msg = format!(
+            "truncated frame {} from ffmpeg: got {} of {} bytes",
+            self.frames_read,
+            read_total,
+            buf.len()
+        );
+        if self.strict {
+            // 여기서 바로 끝나므로 finish 대신 직접 종료 상태와 stderr를 확인한다.
+            let (status, stderr) = self.wait()?;
+            let stderr = stderr_excerpt(&stderr);
+            if stderr.is_empty() {
+                bail!("{} (ffmpeg {})", msg, status);
+            }
+            bail!("{} (ffmpeg {})\nffmpeg stderr:\n{}", msg, status, stderr);
+        }
+        self.partial_bytes = read_total;
+        if !quiet() {
+            eprintln!("warning: {}, dropping it (use --strict to fail instead)", msg);
+        }
+        Ok(false)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if !self.eof {
+            // max_frames로 먼저 멈췄다: 남은 출력은 필요 없으니 ffmpeg를 끝낸다.
+            // (기다리기만 하면 가득 찬 파이프에 막혀 끝나지 않는다)
+            let _ = self.child.kill();
+            self.wait()?;
+            return Ok(());
+        }
+        let (status, stderr) = self.wait()?;
+        if !status.success() {
+            let stderr = stderr_excerpt(&stderr);
+            if stderr.is_empty() {
+                bail!("ffmpeg exited with non-zero status ({})", status);
+            }
+            bail!("ffmpeg exited with non-zero status ({})\nffmpeg stderr:\n{}", status, stderr);
+        }
+        if self.frames_read == 0 {
+            // ffmpeg는 성공했는데 출력이 비었다: 잘못된 스트림, 모든 프레임을 버리는 필터, 끝을 넘은 --start 등
+            let mut msg = match self.trim_start {
+                Some(start) => format!(
+                    "ffmpeg produced no frames from {} (--start {:.3}s is at or past the end?)",
+                    self.input.display(),
+                    start
+                ),
+                None => format!("ffmpeg produced no frames from {}", self.input.display()),
+            };
+            if self.partial_bytes > 0 {
+                msg.push_str(&format!(
+                    "; it wrote only {} bytes, less than one frame (output size mismatch?)",
+                    self.partial_bytes
+                ));
+            }
+            let stderr = stderr_excerpt(&stderr);
+            if stderr.is_empty() {
+                bail!("{}", msg);
+            }
+            bail!("{}\nffmpeg stderr:\n{}", msg, stderr);
+        }
+        Ok(())
+    }
+
+    fn frame_time(&self) -> Option<FrameTime> {
+        self.last_time
+    }
+}
+
+/// `ChainSource`가 입력마다 넘긴 프레임 수 (연 파일까지만)
+pub type FrameCounts = Arc<Mutex<Vec<u64>>>;
+
+/// 여러 비디오(`--input`/`--video`/`--playlist`)를 하나씩 ffmpeg로 디코드해서 이어 붙이는 소스.
+/// 파일마다 같은 crop/fps/scale 필터를 걸기 때문에 해상도, 코덱, 원래 fps가 달라도 된다.
+/// 다음 파일의 첫 프레임은 앞 파일의 마지막 프레임에 대한 XOR delta가 된다 (인코더 입장에서는 한 영상).
+/// `--start`/`--duration`은 이어 붙인 전체 기준이라 ffmpeg에 넘기지 않고 출력 프레임 수로 자른다.
+/// `--vfr`면 파일마다 PTS를 앞 파일이 끝난 시각만큼 밀어서 넘기고, 자르는 것도 그 시각으로 한다.
+pub struct ChainSource {
+    inputs: Vec<PathBuf>,
+    opts: FfmpegOptions,
+    current: Option<FfmpegSource>,
+    next: usize,
+    /// 앞에서 버릴 프레임 수 (`--start`)
+    skip: u64,
+    /// 더 넘길 프레임 수 (`--duration`, 없으면 끝까지)
+    remaining: Option<u64>,
+    counts: FrameCounts,
+    /// `--vfr`: 이어 붙인 시각 (`--start`/`--duration`은 `window`로 자른다)
+    clock: Option<ChainClock>,
+    window: (Option<f64>, Option<f64>),
+}
+
+/// `--vfr` 이어 붙이기: 지금 파일이 시작하는 시각과, 지금 파일에서 본 첫/마지막 프레임 (모두 ms)
+#[derive(Debug, Default)]
+struct ChainClock {
+    offset: f64,
+    origin: Option<f64>,
+    last: Option<FrameTime>,
+    /// 마지막 두 프레임 간격 (마지막 프레임 길이를 모를 때)
+    step: Option<f64>,
+}
+
+impl ChainClock {
+    /// 지금 파일의 프레임 시각 → 이어 붙인 시각
+    fn shift(&mut self, time: FrameTime) -> FrameTime {
+        let origin = *self.origin.get_or_insert(time.pts_ms);
+        if let Some(last) = self.last {
+            self.step = Some(time.pts_ms - last.pts_ms);
+        }
+        self.last = Some(time);
+        FrameTime { pts_ms: self.offset + time.pts_ms - origin, ..time }
+    }
+
+    /// 파일 하나가 끝났다: 다음 파일은 마지막 프레임이 끝나는 시각에서 시작한다
+    fn next_input(&mut self, fps: f32) {
+        if let (Some(origin), Some(last)) = (self.origin, self.last) {
+            let length = last.duration_ms.or(self.step).unwrap_or(1000.0 / fps.max(1.0) as f64);
+            self.offset += last.pts_ms - origin + length;
+        }
+        *self = Self { offset: self.offset, ..Self::default() };
+    }
+}
+
+impl ChainSource {
+    /// ffmpeg는 첫 프레임을 읽을 때 하나씩 띄운다
+    pub fn new(inputs: Vec<PathBuf>, opts: &FfmpegOptions) -> Self {
+        let fps = opts.fps as f64;
+        let by_frames = !opts.vfr;
+        let ms = |s: f64| s * 1000.0;
+        let start = opts.trim.start.unwrap_or(0.0);
+        Self {
+            inputs,
+            opts: FfmpegOptions { trim: Trim::default(), concat: false, ..opts.clone() },
+            current: None,
+            next: 0,
+            skip: opts.trim.start.filter(|_| by_frames).map_or(0, |s| (s * fps).round() as u64),
+            remaining: opts.trim.duration.filter(|_| by_frames).map(|d| (d * fps).round() as u64),
+            counts: Arc::new(Mutex::new(Vec::new())),
+            clock: opts.vfr.then(ChainClock::default),
+            window: (opts.trim.start.map(ms), opts.trim.duration.map(|d| ms(start + d))),
+        }
+    }
+
+    /// 입력별 프레임 수 카운터 (인코딩이 끝난 뒤 요약용)
+    pub fn frame_counts(&self) -> FrameCounts {
+        Arc::clone(&self.counts)
+    }
+}
+
+impl FrameSource for ChainSource {
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<bool> {
+        loop {
+            if self.remaining == Some(0) {
+                return Ok(false);
+            }
+            let Some(source) = &mut self.current else {
+                let Some(path) = self.inputs.get(self.next) else {
+                    return Ok(false);
+                };
+                let source = FfmpegSource::spawn(path, &self.opts)
+                    .with_context(|| format!("failed to decode input {} ({})", self.next + 1, path.display()))?;
+                self.current = Some(source);
+                self.counts.lock().unwrap().push(0);
+                self.next += 1;
+                continue;
+            };
+            if !source.read_frame(buf)? {
+                // 이 파일은 끝났다: 종료 상태를 확인하고 다음 파일로
+                let index = self.next - 1;
+                let mut done = self.current.take().expect("current input");
+                done.finish().with_context(|| format!("failed to decode input {} ({})", index + 1, self.inputs[index].display()))?;
+                if let Some(clock) = &mut self.clock {
+                    clock.next_input(self.opts.fps);
+                }
+                continue;
+            }
+            if let Some(clock) = &mut self.clock {
+                let time = source.frame_time().context("ffmpeg did not report a frame timestamp")?;
+                let time = clock.shift(time);
+                let (start, end) = self.window;
+                if end.is_some_and(|end| time.pts_ms >= end) {
+                    self.remaining = Some(0);
+                    return Ok(false);
+                }
+                if start.is_some_and(|start| time.pts_ms < start) {
+                    continue;
+                }
+            }
+            if self.skip > 0 {
+                self.skip -= 1;
+                continue;
+            }
+            if let Some(n) = &mut self.remaining {
+                *n -= 1;
+            }
+            *self.counts.lock().unwrap().last_mut().expect("open input") += 1;
+            return Ok(true);
+        }
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match &mut self.current {
+            Some(source) => source.finish(),
+            None => Ok(()),
+        }
+    }
+
+    fn frame_time(&self) -> Option<FrameTime> {
+        let clock = self.clock.as_ref()?;
+        let last = clock.last?;
+        Some(FrameTime { pts_ms: clock.offset + last.pts_ms - clock.origin?, ..last })
+    }
+}
+
+/// `--playlist` 파일: 한 줄에 비디오 경로 하나. 빈 줄과 `#`로 시작하는 줄은 건너뛰고,
+/// 상대 경로는 playlist 파일이 있는 디렉터리 기준이다.
+pub fn read_playlist(path: &Path) -> Result<Vec<PathBuf>> {
+    let text = fs::read_to_string(path).with_context(|| format!("failed to read playlist {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    let videos: Vec<PathBuf> = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| base.join(l))
+        .collect();
+    if videos.is_empty() {
+        bail!("playlist {} lists no videos", path.display());
+    }
+    Ok(videos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_timestamp_formats() {
+        for (s, want) in [
+            ("0", 0.0),
+            ("90.5", 90.5),
+            ("1:30", 90.0),
+            ("01:02.25", 62.25),
+            ("1:02:03.456", 3723.456),
+            ("100:00:00", 360000.0),
+        ] {
+            assert!((parse_timestamp(s).unwrap() - want).abs() < 1e-9, "{}", s);
+        }
+    }
+
+    #[test]
+    fn parse_timestamp_rejects_bad_input() {
+        for s in ["", "-1", "-0:10", "1:-10", "abc", "1:2:3:4", "1:60", "1:60:00", "inf", "NaN", "1.5:00", ":30", "1::30"] {
+            assert!(parse_timestamp(s).is_err(), "{}", s);
+        }
+    }
+}
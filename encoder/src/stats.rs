@@ -0,0 +1,317 @@
+//! `--stats-only`: blob/PDF를 쓰지 않고 인코딩 루프만 돌려서 얼마나 줄어들지 보고한다
+//!
+//! `DiffStats`가 `FrameSink`로 레코드마다 바뀐 비트 수와 코덱별(none/rle/deflate/zstd) 레코드 크기를 모은다.
+//! threshold, `--denoise`, `--drop-duplicates`, `--tiles`는 실제 인코딩과 같은 루프를 탄다.
+//! 보고서: delta 밀도 히스토그램, 가장 비싼 delta 프레임 (재생 시각과 함께), 코덱별 blob 크기, PDF 예상 크기.
+
+use crate::{
+    blob::REPEAT_RECORD_LEN,
+    codec::{compress_blob, CompressionMode, DEFAULT_BROTLI_QUALITY, DEFAULT_ZSTD_LEVEL},
+    encode::{encode_frames, EncodeOptions, EncodeStats, FrameRecord, FrameSink},
+    progress::format_duration,
+    source::FrameSource,
+};
+use anyhow::{bail, Result};
+
+/// 보고서에 나열하는 가장 비싼 delta 프레임 수
+pub const STATS_TOP_FRAMES: usize = 10;
+/// 히스토그램 구간 상한 (delta에서 바뀐 비트 / 프레임 비트). 첫 구간은 바뀐 것이 없는 프레임
+pub const DENSITY_BUCKETS: [f64; 8] = [0.0, 0.005, 0.01, 0.02, 0.05, 0.10, 0.25, 1.0];
+/// 텍스트 히스토그램 막대 최대 길이
+const BAR_WIDTH: usize = 40;
+
+/// `--stats-format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatsFormat {
+    /// 사람이 읽는 표
+    #[default]
+    Text,
+    /// 파라미터 sweep 스크립트용 JSON (필드 순서 고정)
+    Json,
+}
+
+impl std::str::FromStr for StatsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "text" => StatsFormat::Text,
+            "json" => StatsFormat::Json,
+            _ => bail!("unknown --stats-format '{}' (expected text, json)", s),
+        })
+    }
+}
+
+/// 저장한 프레임 하나의 비용
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameCost {
+    /// 입력 프레임 번호
+    pub index: u32,
+    /// delta에서 켜진 비트 수 (keyframe이면 `None`)
+    pub changed_bits: Option<u64>,
+    /// `DiffStats::codecs` 순서의 레코드 크기 (tag byte, `u32 len` prefix 포함)
+    pub bytes: [u64; 5],
+}
+
+/// PDF 예상 크기에 더하는 blob 밖의 크기
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PdfOverhead {
+    /// AU 첨부 합 (`--encrypt-audio`면 암호화한 크기)
+    pub audio_bytes: u64,
+    pub audio_tracks: usize,
+    /// 플레이어 첨부, 썸네일/flipbook 이미지, PDF 구조
+    pub other_bytes: u64,
+}
+
+/// 레코드를 모으는 sink 겸 보고서
+#[derive(Debug, Clone)]
+pub struct DiffStats {
+    /// 비교하는 코덱: none, rle, deflate, zstd, brotli (`--compress-level`이 있으면 그 레벨/quality)
+    pub codecs: [CompressionMode; 5],
+    /// `--compress`로 고른 코덱 (`codecs` 안의 번호)
+    pub selected: usize,
+    /// `--tiles`: 코덱이 없어도 레코드마다 `u32 len` prefix가 붙는다
+    tiles: bool,
+    /// `--repeat-records`: 레코드마다 tag byte가 붙는다
+    tagged: bool,
+    pub width: u16,
+    pub height: u16,
+    pub fps: f32,
+    /// 프레임 하나의 비트 수 (`width * height * bit_depth`, 히스토그램 분모)
+    pub frame_bits: u64,
+    pub frames: Vec<FrameCost>,
+    /// 인코딩 루프 통계 (`bytes`는 고른 코덱 기준 blob 크기)
+    pub encode: EncodeStats,
+}
+
+impl DiffStats {
+    pub fn new(opts: &EncodeOptions) -> Self {
+        let zstd = match opts.compression {
+            CompressionMode::Zstd(level) => level,
+            _ => DEFAULT_ZSTD_LEVEL,
+        };
+        let brotli = match opts.compression {
+            CompressionMode::Brotli(quality) => quality,
+            _ => DEFAULT_BROTLI_QUALITY,
+        };
+        let codecs = [
+            CompressionMode::None,
+            CompressionMode::Rle,
+            CompressionMode::Deflate,
+            CompressionMode::Zstd(zstd),
+            CompressionMode::Brotli(brotli),
+        ];
+        Self {
+            codecs,
+            selected: codecs.iter().position(|c| c.id() == opts.compression.id()).unwrap_or(0),
+            tiles: opts.tiles,
+            tagged: opts.repeat_records,
+            width: opts.width,
+            height: opts.height,
+            fps: opts.fps,
+            frame_bits: opts.width as u64 * opts.height as u64 * opts.bit_depth.bits() as u64,
+            frames: Vec::new(),
+            encode: EncodeStats::default(),
+        }
+    }
+
+    /// `source`를 끝까지 인코딩하면서 모은다 (blob은 만들지 않는다)
+    pub fn collect<S: FrameSource + ?Sized>(source: &mut S, opts: &EncodeOptions) -> Result<Self> {
+        let mut stats = Self::new(opts);
+        let encode = encode_frames(source, opts, &mut stats)?;
+        stats.encode = encode;
+        Ok(stats)
+    }
+
+    /// `codecs[codec]`로 인코딩했을 때 blob 크기
+    pub fn projected_bytes(&self, codec: usize) -> u64 {
+        let selected: u64 = self.frames.iter().map(|f| f.bytes[self.selected]).sum();
+        let total: u64 = self.frames.iter().map(|f| f.bytes[codec]).sum();
+        self.encode.bytes - selected + total
+    }
+
+    /// delta 프레임마다 바뀐 비트 비율을 `DENSITY_BUCKETS` 구간으로 센다
+    pub fn histogram(&self) -> [u32; DENSITY_BUCKETS.len()] {
+        let mut counts = [0; DENSITY_BUCKETS.len()];
+        for bits in self.frames.iter().filter_map(|f| f.changed_bits) {
+            let fraction = bits as f64 / self.frame_bits.max(1) as f64;
+            let bucket = DENSITY_BUCKETS.iter().position(|&max| fraction <= max).unwrap_or(DENSITY_BUCKETS.len() - 1);
+            counts[bucket] += 1;
+        }
+        counts
+    }
+
+    /// 고른 코덱 기준으로 가장 큰 delta 프레임 `n`개 (같으면 바뀐 비트가 많은 쪽, 그다음 앞 프레임)
+    pub fn most_expensive(&self, n: usize) -> Vec<FrameCost> {
+        let mut deltas: Vec<FrameCost> = self.frames.iter().copied().filter(|f| f.changed_bits.is_some()).collect();
+        deltas.sort_by(|a, b| {
+            b.bytes[self.selected]
+                .cmp(&a.bytes[self.selected])
+                .then(b.changed_bits.cmp(&a.changed_bits))
+                .then(a.index.cmp(&b.index))
+        });
+        deltas.truncate(n);
+        deltas
+    }
+
+    /// 입력 프레임 번호의 재생 시각 (초)
+    pub fn seconds(&self, index: u32) -> f64 {
+        index as f64 / self.fps as f64
+    }
+
+    fn fraction(&self, bits: u64) -> f64 {
+        bits as f64 / self.frame_bits.max(1) as f64
+    }
+
+    /// 사람이 읽는 보고서
+    pub fn text_report(&self, overhead: &PdfOverhead) -> String {
+        let e = &self.encode;
+        let deltas = e.stored_frames.saturating_sub(1);
+        let mut out = format!(
+            "Frames: {} ({} stored: 1 keyframe, {} delta, {} unchanged), {}x{} @ {} fps ({})\n",
+            e.frames,
+            e.stored_frames,
+            deltas,
+            e.zero_diff_frames,
+            self.width,
+            self.height,
+            self.fps,
+            format_duration(self.seconds(e.frames))
+        );
+        if deltas > 0 {
+            out.push_str(&format!(
+                "Changed bits per delta frame (of {}, mean {:.2}%):\n",
+                self.frame_bits,
+                self.fraction(e.delta_bits) / deltas as f64 * 100.0
+            ));
+            let counts = self.histogram();
+            let max = counts.iter().copied().max().unwrap_or(0).max(1);
+            for (i, &count) in counts.iter().enumerate() {
+                let label = if i == 0 { "0".to_string() } else { format!("<= {}%", DENSITY_BUCKETS[i] * 100.0) };
+                let bar = "#".repeat((count as usize * BAR_WIDTH).div_ceil(max as usize));
+                out.push_str(&format!("  {:<9} {:>8}  {}\n", label, count, bar));
+            }
+            out.push_str(&format!("Most expensive delta frames ({}):\n", self.codecs[self.selected].name()));
+            for f in self.most_expensive(STATS_TOP_FRAMES) {
+                let bits = f.changed_bits.unwrap_or(0);
+                out.push_str(&format!(
+                    "  frame {:>6} at {:>10}  {:>8} bytes  {:>8} changed bits ({:.2}%)\n",
+                    f.index,
+                    format_timestamp(self.seconds(f.index)),
+                    f.bytes[self.selected],
+                    bits,
+                    self.fraction(bits) * 100.0
+                ));
+            }
+        }
+        out.push_str(&format!("Projected blob size (raw {} bytes):\n", e.raw_bytes));
+        for (i, codec) in self.codecs.iter().enumerate() {
+            let bytes = self.projected_bytes(i);
+            out.push_str(&format!(
+                "  {:<8} {:>12} bytes ({:.1}% of raw){}\n",
+                codec.name(),
+                bytes,
+                bytes as f64 / e.raw_bytes.max(1) as f64 * 100.0,
+                if i == self.selected { "  <- --compress" } else { "" }
+            ));
+        }
+        let pdf = self.projected_bytes(self.selected) + overhead.audio_bytes + overhead.other_bytes;
+        out.push_str(&format!(
+            "Projected PDF size: ~{} bytes ({:.1} MiB; blob {} + audio {} in {} track{} + other {})\n",
+            pdf,
+            pdf as f64 / (1024.0 * 1024.0),
+            self.projected_bytes(self.selected),
+            overhead.audio_bytes,
+            overhead.audio_tracks,
+            if overhead.audio_tracks == 1 { "" } else { "s" },
+            overhead.other_bytes
+        ));
+        out
+    }
+
+    /// `--stats-format json`. 필드 순서가 고정이라 sweep 결과끼리 비교하기 쉽다.
+    pub fn json_report(&self, overhead: &PdfOverhead) -> String {
+        let e = &self.encode;
+        let histogram: Vec<String> = self
+            .histogram()
+            .iter()
+            .zip(DENSITY_BUCKETS)
+            .map(|(count, max)| format!("{{ \"max_changed_fraction\": {}, \"frames\": {} }}", max, count))
+            .collect();
+        let top: Vec<String> = self
+            .most_expensive(STATS_TOP_FRAMES)
+            .iter()
+            .map(|f| {
+                format!(
+                    "{{ \"frame\": {}, \"seconds\": {:.3}, \"bytes\": {}, \"changed_bits\": {} }}",
+                    f.index,
+                    self.seconds(f.index),
+                    f.bytes[self.selected],
+                    f.changed_bits.unwrap_or(0)
+                )
+            })
+            .collect();
+        let projected: Vec<String> = self
+            .codecs
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("\"{}\": {}", c.name(), self.projected_bytes(i)))
+            .collect();
+        let blob = self.projected_bytes(self.selected);
+        let changed: Vec<String> = self.frames.iter().filter_map(|f| f.changed_bits).map(|b| b.to_string()).collect();
+        format!(
+            "{{\n  \"width\": {},\n  \"height\": {},\n  \"fps\": {},\n  \"frame_count\": {},\n  \"stored_frames\": {},\n  \"zero_diff_frames\": {},\n  \"frame_bits\": {},\n  \"delta_bits\": {},\n  \"density_histogram\": [\n    {}\n  ],\n  \"most_expensive_frames\": [\n    {}\n  ],\n  \"compression\": \"{}\",\n  \"raw_bytes\": {},\n  \"projected_blob_bytes\": {{ {} }},\n  \"audio_bytes\": {},\n  \"audio_tracks\": {},\n  \"other_bytes\": {},\n  \"projected_pdf_bytes\": {},\n  \"changed_bits\": [{}]\n}}\n",
+            self.width,
+            self.height,
+            self.fps,
+            e.frames,
+            e.stored_frames,
+            e.zero_diff_frames,
+            self.frame_bits,
+            e.delta_bits,
+            histogram.join(",\n    "),
+            top.join(",\n    "),
+            self.codecs[self.selected].name(),
+            e.raw_bytes,
+            projected.join(", "),
+            overhead.audio_bytes,
+            overhead.audio_tracks,
+            overhead.other_bytes,
+            blob + overhead.audio_bytes + overhead.other_bytes,
+            changed.join(", ")
+        )
+    }
+}
+
+impl FrameSink for DiffStats {
+    /// 모든 코덱으로 압축해 보고 고른 코덱의 크기를 돌려준다.
+    /// repeat record는 코덱과 상관없이 크기가 같아서 `frames`에 넣지 않는다.
+    fn write_frame(&mut self, record: &FrameRecord) -> Result<usize> {
+        let (index, data, changed_bits) = match *record {
+            FrameRecord::Delta { index, data, changed_bits } => (index, data, changed_bits),
+            FrameRecord::Repeat(_) => return Ok(REPEAT_RECORD_LEN),
+        };
+        let mut bytes = [0u64; 5];
+        for (size, &codec) in bytes.iter_mut().zip(&self.codecs) {
+            *size = self.tagged as u64
+                + match codec {
+                    CompressionMode::None if !self.tiles => data.len() as u64,
+                    CompressionMode::None => 4 + data.len() as u64,
+                    _ => 4 + compress_blob(data, codec)?.len() as u64,
+                };
+        }
+        self.frames.push(FrameCost { index, changed_bits, bytes });
+        Ok(bytes[self.selected] as usize)
+    }
+}
+
+/// 보고서용 재생 시각: `1:02.345`, 1시간이 넘으면 `1:02:03.456`
+pub fn format_timestamp(secs: f64) -> String {
+    let ms = (secs * 1000.0).round() as u64;
+    let (h, m, s, ms) = (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000);
+    if h > 0 {
+        format!("{}:{:02}:{:02}.{:03}", h, m, s, ms)
+    } else {
+        format!("{}:{:02}.{:03}", m, s, ms)
+    }
+}
@@ -0,0 +1,517 @@
+//! gray → 1bit 이진화 기준
+
+use crate::bits::{pack_bits, pack_bits_rows};
+use anyhow::{bail, Context, Result};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// 이진화 기준
+/// - Fixed: 고정 threshold (px <= t 이면 black)
+/// - Otsu: 프레임마다 히스토그램으로 threshold를 새로 계산
+/// - AdaptiveMean: 프레임마다 전체 픽셀 평균을 threshold로 쓴다 (히스토그램 없이 한 번 훑는다)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThresholdMode {
+    Fixed(u8),
+    Otsu,
+    AdaptiveMean,
+}
+
+impl ThresholdMode {
+    /// 이 프레임에 적용할 실제 threshold 값
+    pub fn resolve(self, gray: &[u8]) -> u8 {
+        match self {
+            ThresholdMode::Fixed(t) => t,
+            ThresholdMode::Otsu => otsu_threshold(gray),
+            ThresholdMode::AdaptiveMean => mean_threshold(gray),
+        }
+    }
+}
+
+impl std::str::FromStr for ThresholdMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(ThresholdMode::Otsu);
+        }
+        if s.eq_ignore_ascii_case("mean") {
+            return Ok(ThresholdMode::AdaptiveMean);
+        }
+        let t: u8 = s
+            .parse()
+            .with_context(|| format!("invalid threshold '{}' (expected 0-255, 'auto' or 'mean')", s))?;
+        Ok(ThresholdMode::Fixed(t))
+    }
+}
+
+/// `--adaptive`: 전역 threshold 대신 픽셀마다 주변 `block x block` 창의 평균에서 `offset`을 뺀 값을 쓴다.
+/// 비네팅이나 그라데이션 배경처럼 밝기가 고르지 않은 영상용. 창은 프레임 밖으로 나가면 잘린다.
+/// 창보다 넓은 단색 영역은 평균이 자기 값이라 `offset > 0`이면 white가 된다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveThreshold {
+    /// 창 크기 (홀수, 3 이상)
+    pub block: u16,
+    /// 평균에서 뺄 값 (`px <= mean - offset` 이면 black)
+    pub offset: i16,
+}
+
+/// `--adaptive-block` 기본값
+pub const DEFAULT_ADAPTIVE_BLOCK: u16 = 15;
+/// `--adaptive-offset` 기본값
+pub const DEFAULT_ADAPTIVE_OFFSET: i16 = 8;
+
+impl Default for AdaptiveThreshold {
+    fn default() -> Self {
+        Self { block: DEFAULT_ADAPTIVE_BLOCK, offset: DEFAULT_ADAPTIVE_OFFSET }
+    }
+}
+
+impl AdaptiveThreshold {
+    pub fn validate(&self) -> Result<()> {
+        if self.block < 3 || self.block.is_multiple_of(2) {
+            bail!("adaptive block size must be odd and at least 3 (got {})", self.block);
+        }
+        Ok(())
+    }
+
+    /// 픽셀마다 쓸 threshold (`mean - offset`을 0..=255로 자른 값). integral image로 창 평균을 구한다.
+    pub fn thresholds(&self, gray: &[u8], width: usize) -> Vec<u8> {
+        if width == 0 {
+            return Vec::new();
+        }
+        let height = gray.len() / width;
+        // (width+1) x (height+1) 누적합, 첫 행/열은 0
+        let mut sums = vec![0u64; (width + 1) * (height + 1)];
+        for y in 0..height {
+            let mut row = 0u64;
+            for x in 0..width {
+                row += gray[y * width + x] as u64;
+                sums[(y + 1) * (width + 1) + x + 1] = sums[y * (width + 1) + x + 1] + row;
+            }
+        }
+        let r = self.block as usize / 2;
+        let mut out = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let (y0, y1) = (y.saturating_sub(r), (y + r + 1).min(height));
+            for x in 0..width {
+                let (x0, x1) = (x.saturating_sub(r), (x + r + 1).min(width));
+                let sum = sums[y1 * (width + 1) + x1] + sums[y0 * (width + 1) + x0]
+                    - sums[y0 * (width + 1) + x1]
+                    - sums[y1 * (width + 1) + x0];
+                let mean = (sum / ((x1 - x0) * (y1 - y0)) as u64) as i32;
+                out.push((mean - self.offset as i32).clamp(0, 255) as u8);
+            }
+        }
+        out
+    }
+}
+
+/// 켠 비트(1)가 어느 쪽 픽셀인지 (`--polarity`). 1-bit blob에서만 쓴다.
+/// 바뀌는 것은 저장하는 비트뿐이고 화면은 같다 (`White`면 헤더 플래그를 보고 디코더가 되돌린다).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Polarity {
+    /// 1=black (threshold 이하, 지금까지의 규약)
+    #[default]
+    Black,
+    /// 1=white: 어두운 배경에 밝은 선처럼 밝은 쪽이 적은 영상용
+    White,
+    /// 앞 N 프레임을 black으로 이진화해 보고, 픽셀 절반 넘게 켜지면 `White`
+    Auto(u32),
+}
+
+/// `--polarity auto`가 보는 앞 프레임 수
+pub const DEFAULT_POLARITY_SAMPLE: u32 = 30;
+
+impl Polarity {
+    /// 켠 비트를 white로 저장한다
+    pub fn is_white(self) -> bool {
+        self == Polarity::White
+    }
+
+    /// 앞 프레임들에서 켜진 픽셀 수로 `Auto`를 고른다 (적은 쪽 색이 1이 되게, 딱 절반이면 `Black`)
+    pub fn from_sample(set_bits: u64, pixels: u64) -> Self {
+        if set_bits * 2 > pixels {
+            Polarity::White
+        } else {
+            Polarity::Black
+        }
+    }
+}
+
+/// `black`, `white`, `auto`, `auto:N` (N = 볼 앞 프레임 수)
+impl std::str::FromStr for Polarity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.split_once(':') {
+            None if s == "black" => Polarity::Black,
+            None if s == "white" => Polarity::White,
+            None if s == "auto" => Polarity::Auto(DEFAULT_POLARITY_SAMPLE),
+            Some(("auto", n)) => {
+                let n: u32 = n.trim().parse().with_context(|| format!("invalid --polarity sample count '{}'", n))?;
+                if n == 0 {
+                    bail!("--polarity auto:N needs at least 1 frame");
+                }
+                Polarity::Auto(n)
+            }
+            _ => bail!("unknown polarity '{}' (expected black, white, auto or auto:N)", s),
+        })
+    }
+}
+
+/// threshold 주변에 패턴을 섞는 디더링
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DitherMode {
+    /// 픽셀마다 threshold 그대로
+    #[default]
+    None,
+    /// 4x4 Bayer ordered dithering. 픽셀끼리 의존성이 없어서 병렬로 돌리기 쉽다.
+    /// `scale`은 threshold를 흔드는 폭 (±scale/2 정도)
+    Bayer4x4 { scale: i32 },
+}
+
+/// `--dither-scale` 기본값
+pub const DEFAULT_DITHER_SCALE: i32 = 64;
+
+/// Bayer 4x4 index matrix (0..=15)
+pub const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+impl std::str::FromStr for DitherMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "none" => DitherMode::None,
+            "bayer" => DitherMode::Bayer4x4 { scale: DEFAULT_DITHER_SCALE },
+            _ => bail!("unknown dither mode '{}' (expected none, bayer)", s),
+        })
+    }
+}
+
+/// 픽셀 하나 (x, y) → 1=black, 0=white
+#[inline]
+fn binarize_px(px: u8, x: usize, y: usize, threshold: u8, dither: DitherMode) -> u8 {
+    match dither {
+        DitherMode::None => (px <= threshold) as u8,
+        DitherMode::Bayer4x4 { scale } => {
+            let t = threshold as i32 + (BAYER_4X4[y % 4][x % 4] as i32 - 8) * scale / 16;
+            (px as i32 <= t) as u8
+        }
+    }
+}
+
+/// `--bit-depth 2`용: Bayer 패턴만큼 밝기를 흔들어서 뒤의 `px >> 6` 양자화가 ordered dithering이 되게 한다.
+/// `scale`은 흔드는 폭 (기본 64 = 한 단계). `DitherMode::None`이면 그대로 복사한다.
+pub fn dither_levels(gray: &[u8], width: usize, dither: DitherMode) -> Vec<u8> {
+    match dither {
+        DitherMode::None => gray.to_vec(),
+        DitherMode::Bayer4x4 { scale } => gray
+            .iter()
+            .enumerate()
+            .map(|(i, &px)| {
+                let (x, y) = (i % width, i / width);
+                (px as i32 + (BAYER_4X4[y % 4][x % 4] as i32 * 2 - 15) * scale / 32).clamp(0, 255) as u8
+            })
+            .collect(),
+    }
+}
+
+/// gray 프레임 (width 단위 행) → bits01 (1=black, 0=white)
+pub fn binarize_frame(gray: &[u8], width: usize, threshold: u8, dither: DitherMode) -> Vec<u8> {
+    gray.iter()
+        .enumerate()
+        .map(|(i, &px)| binarize_px(px, i % width, i / width, threshold, dither))
+        .collect()
+}
+
+/// `binarize_frame`의 rayon 버전. 행 묶음 단위로 나눠서 미리 잡아둔 bits01에 쓴다.
+/// 결과는 `binarize_frame`과 같다.
+#[cfg(feature = "parallel")]
+pub fn binarize_frame_par(gray: &[u8], width: usize, threshold: u8, dither: DitherMode) -> Vec<u8> {
+    if width == 0 {
+        return Vec::new();
+    }
+    let mut bits01 = vec![0u8; gray.len()];
+    bits01
+        .par_chunks_mut(width)
+        .zip(gray.par_chunks(width))
+        .with_min_len(PAR_MIN_ROWS)
+        .enumerate()
+        .for_each(|(y, (dst, src))| {
+            for (x, (d, &px)) in dst.iter_mut().zip(src).enumerate() {
+                *d = binarize_px(px, x, y, threshold, dither);
+            }
+        });
+    bits01
+}
+
+/// 워커 하나가 최소한 맡는 행 수 (작은 프레임에서 작업을 너무 잘게 쪼개지 않도록)
+#[cfg(feature = "parallel")]
+const PAR_MIN_ROWS: usize = 16;
+
+/// 출력 1바이트 = 8픽셀 묶음 하나. `first`는 `group[0]`의 gray 안 픽셀 번호 (dither 좌표용).
+#[inline]
+fn pack_group(group: &[u8], first: usize, width: usize, threshold: u8, dither: DitherMode) -> u8 {
+    match dither {
+        DitherMode::None => {
+            group.iter().enumerate().fold(0, |acc, (j, &px)| acc | (((px <= threshold) as u8) << (7 - j)))
+        }
+        _ => group.iter().enumerate().fold(0, |acc, (j, &px)| {
+            let i = first + j;
+            acc | (binarize_px(px, i % width, i / width, threshold, dither) << (7 - j))
+        }),
+    }
+}
+
+/// `binarize_frame` + `pack_bits`(`row_align`이면 `pack_bits_rows`)를 한 번에 한다.
+/// bits01 중간 버퍼 없이 8픽셀씩 바로 MSB-first 바이트로 만들고, `out`은 프레임마다 재사용한다.
+pub fn binarize_pack_into(
+    gray: &[u8],
+    width: usize,
+    threshold: u8,
+    dither: DitherMode,
+    row_align: bool,
+    out: &mut Vec<u8>,
+) {
+    out.clear();
+    if width == 0 {
+        return;
+    }
+    if row_align {
+        for (y, row) in gray.chunks(width).enumerate() {
+            out.extend(row.chunks(8).enumerate().map(|(k, g)| pack_group(g, y * width + k * 8, width, threshold, dither)));
+        }
+    } else {
+        out.extend(gray.chunks(8).enumerate().map(|(k, g)| pack_group(g, k * 8, width, threshold, dither)));
+    }
+}
+
+/// `binarize_pack_into`의 rayon 버전 (행 정렬이면 행 단위, 아니면 출력 바이트 단위로 나눈다)
+#[cfg(feature = "parallel")]
+pub fn binarize_pack_into_par(
+    gray: &[u8],
+    width: usize,
+    threshold: u8,
+    dither: DitherMode,
+    row_align: bool,
+    out: &mut Vec<u8>,
+) {
+    out.clear();
+    if width == 0 {
+        return;
+    }
+    if row_align {
+        let stride = width.div_ceil(8);
+        out.resize(stride * gray.len().div_ceil(width), 0);
+        out.par_chunks_mut(stride)
+            .zip(gray.par_chunks(width))
+            .with_min_len(PAR_MIN_ROWS)
+            .enumerate()
+            .for_each(|(y, (dst, row))| {
+                for (k, (d, g)) in dst.iter_mut().zip(row.chunks(8)).enumerate() {
+                    *d = pack_group(g, y * width + k * 8, width, threshold, dither);
+                }
+            });
+    } else {
+        out.resize(gray.len().div_ceil(8), 0);
+        out.par_iter_mut()
+            .zip(gray.par_chunks(8))
+            .with_min_len(PAR_MIN_ROWS * width.div_ceil(8))
+            .enumerate()
+            .for_each(|(k, (d, g))| *d = pack_group(g, k * 8, width, threshold, dither));
+    }
+}
+
+/// `binarize_pack_into`의 `--adaptive` 버전: threshold가 픽셀마다 `thresholds[i]`다 (dither는 그 위에 더한다).
+pub fn binarize_pack_adaptive_into(
+    gray: &[u8],
+    width: usize,
+    thresholds: &[u8],
+    dither: DitherMode,
+    row_align: bool,
+    out: &mut Vec<u8>,
+) {
+    out.clear();
+    if width == 0 {
+        return;
+    }
+    let bits01: Vec<u8> = gray
+        .iter()
+        .zip(thresholds)
+        .enumerate()
+        .map(|(i, (&px, &t))| binarize_px(px, i % width, i / width, t, dither))
+        .collect();
+    *out = if row_align { pack_bits_rows(&bits01, width) } else { pack_bits(&bits01) };
+}
+
+/// 프레임 전체 픽셀의 산술 평균 (소수점 아래는 버린다). 어두운 쪽과 밝은 쪽 면적이 비슷하면 Otsu와 거의 같고,
+/// 한쪽이 화면 대부분을 차지하면 평균이 그쪽으로 쏠려서 작은 물체 가장자리가 얇아지거나 두꺼워진다.
+/// 단색(빈) 프레임은 평균이 자기 값이라 흰 화면도 black이 되므로 Otsu처럼 `UNIFORM_THRESHOLD`를 쓴다.
+pub fn mean_threshold(gray: &[u8]) -> u8 {
+    let Some(&first) = gray.first() else {
+        return UNIFORM_THRESHOLD;
+    };
+    let (mut sum, mut uniform) = (0u64, true);
+    for &px in gray {
+        sum += px as u64;
+        uniform &= px == first;
+    }
+    if uniform {
+        return UNIFORM_THRESHOLD;
+    }
+    (sum / gray.len() as u64) as u8
+}
+
+/// 나눌 곳이 없는 (단색, 빈) 프레임에서 `otsu_threshold`와 `mean_threshold`가 쓰는 값 (0..=255의 가운데)
+pub const UNIFORM_THRESHOLD: u8 = 127;
+
+/// Otsu threshold: 256-bin 히스토그램에서 between-class variance가 최대가 되는 t.
+/// `px <= t` 가 black 이므로 t는 어두운 클래스의 마지막 값이다.
+/// 값이 하나뿐인 프레임(단색, 빈 프레임)은 나눌 곳이 없으므로 `UNIFORM_THRESHOLD`를 돌려준다:
+/// 어두운 단색 화면은 black, 밝은 단색 화면은 white가 된다.
+pub fn otsu_threshold(gray: &[u8]) -> u8 {
+    let mut hist = [0u64; 256];
+    for &px in gray {
+        hist[px as usize] += 1;
+    }
+
+    let total = gray.len() as f64;
+    let sum_all: f64 = hist.iter().enumerate().map(|(v, &n)| v as f64 * n as f64).sum();
+
+    let mut best_t = None;
+    let mut best_var = -1.0f64;
+    let mut w0 = 0.0f64; // 어두운 클래스 픽셀 수
+    let mut sum0 = 0.0f64;
+    for (t, &n) in hist.iter().enumerate() {
+        w0 += n as f64;
+        sum0 += t as f64 * n as f64;
+        let w1 = total - w0;
+        if w0 == 0.0 {
+            continue;
+        }
+        if w1 == 0.0 {
+            break;
+        }
+        let mu0 = sum0 / w0;
+        let mu1 = (sum_all - sum0) / w1;
+        let var = w0 * w1 * (mu0 - mu1) * (mu0 - mu1);
+        if var > best_var {
+            best_var = var;
+            best_t = Some(t as u8);
+        }
+    }
+    best_t.unwrap_or(UNIFORM_THRESHOLD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift64* (테스트용 결정적 난수)
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn frame(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    /// `binarize_pack_into`(한 번에 threshold + packing)가 `binarize_frame` + `pack_bits`와 같은지,
+    /// w*h가 8의 배수가 아닌 크기의 난수 프레임으로 확인한다.
+    #[test]
+    fn fast_pack_matches_naive_on_odd_sizes() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let mut out = Vec::new();
+        for _ in 0..200 {
+            let width = 1 + (rng.next() % 45) as usize;
+            let height = 1 + (rng.next() % 23) as usize;
+            let gray = rng.frame(width * height);
+            let threshold = rng.next() as u8;
+            for dither in [DitherMode::None, DitherMode::Bayer4x4 { scale: DEFAULT_DITHER_SCALE }] {
+                let bits01 = binarize_frame(&gray, width, threshold, dither);
+                for row_align in [false, true] {
+                    let naive = if row_align { pack_bits_rows(&bits01, width) } else { pack_bits(&bits01) };
+                    binarize_pack_into(&gray, width, threshold, dither, row_align, &mut out);
+                    let case = format!("{}x{} threshold {} {:?} row_align {}", width, height, threshold, dither, row_align);
+                    assert_eq!(out, naive, "{}", case);
+                    #[cfg(feature = "parallel")]
+                    {
+                        binarize_pack_into_par(&gray, width, threshold, dither, row_align, &mut out);
+                        assert_eq!(out, naive, "par {}", case);
+                    }
+                }
+                #[cfg(feature = "parallel")]
+                assert_eq!(binarize_frame_par(&gray, width, threshold, dither), bits01);
+            }
+        }
+    }
+
+    #[test]
+    fn otsu_splits_bimodal_histogram_between_the_peaks() {
+        // 40 근처와 200 근처 두 덩어리: 경계는 어두운 덩어리의 끝
+        let mut gray = Vec::new();
+        for v in 30..=50u8 {
+            gray.extend(std::iter::repeat_n(v, 10));
+        }
+        for v in 190..=210u8 {
+            gray.extend(std::iter::repeat_n(v, 30));
+        }
+        let t = otsu_threshold(&gray);
+        assert_eq!(t, 50);
+        let bits = binarize_frame(&gray, gray.len(), t, DitherMode::None);
+        assert_eq!(bits.iter().filter(|&&b| b == 1).count(), 21 * 10);
+    }
+
+    #[test]
+    fn mean_threshold_is_the_floored_mean() {
+        assert_eq!(mean_threshold(&[10, 20, 30, 40]), 25);
+        assert_eq!(mean_threshold(&[0, 255]), 127);
+        assert_eq!(mean_threshold(&[0, 0, 0, 255]), 63);
+        // 소수점 아래는 버린다: 254.5 → 254, 0.99 → 0
+        assert_eq!(mean_threshold(&[254, 255]), 254);
+        assert_eq!(mean_threshold(&[[1u8; 99].as_slice(), &[0]].concat()), 0);
+        // 큰 프레임에서도 넘치지 않는다
+        let mut big = vec![255; 4096 * 4096];
+        big[0] = 0;
+        assert_eq!(mean_threshold(&big), 254);
+    }
+
+    /// 평균을 그대로 쓰면 `px <= t`라서 흰 단색 화면도 전부 black이 된다
+    #[test]
+    fn mean_threshold_of_empty_and_uniform_frames() {
+        assert_eq!(mean_threshold(&[]), UNIFORM_THRESHOLD);
+        for (v, black) in [(0u8, true), (77, true), (200, false), (255, false)] {
+            assert_eq!(mean_threshold(&[v; 33]), UNIFORM_THRESHOLD, "value {}", v);
+            assert!(binarize_frame(&[v; 33], 11, mean_threshold(&[v; 33]), DitherMode::None).iter().all(|&b| (b == 1) == black), "value {}", v);
+        }
+        assert_eq!(ThresholdMode::AdaptiveMean.resolve(&[10, 20, 30, 40]), 25);
+    }
+
+    /// 단색 프레임은 나눌 곳이 없다. 0을 돌려주면 어두운 회색 화면이 전부 white가 된다.
+    #[test]
+    fn otsu_uniform_frames_use_the_midpoint() {
+        for (v, black) in [(0u8, true), (30, true), (127, true), (128, false), (255, false)] {
+            let gray = vec![v; 64];
+            let t = otsu_threshold(&gray);
+            assert_eq!(t, UNIFORM_THRESHOLD, "value {}", v);
+            assert!(binarize_frame(&gray, 8, t, DitherMode::None).iter().all(|&b| (b == 1) == black), "value {}", v);
+        }
+        assert_eq!(otsu_threshold(&[]), UNIFORM_THRESHOLD);
+        // 값이 두 개면 그 사이를 나눈다
+        assert_eq!(otsu_threshold(&[250, 250, 255]), 250);
+    }
+
+    #[test]
+    fn fast_pack_reuses_buffer_without_stale_bytes() {
+        let mut out = vec![0xaa; 64];
+        binarize_pack_into(&[0, 255, 0], 3, 128, DitherMode::None, false, &mut out);
+        assert_eq!(out, [0b1010_0000]);
+    }
+}
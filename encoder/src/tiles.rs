@@ -0,0 +1,154 @@
+//! dirty-rect 프레임 레코드: 바뀐 16x16 타일의 XOR diff만 저장한다 (`--tiles`)
+//!
+//! 타일 레코드 (frame1..):
+//! ```text
+//! u16 tile_count
+//! tile_count * { u16 tile_index, tile bits }
+//! ```
+//! - tile_index = ty * tiles_x + tx (tiles_x = ceil(w/16))
+//! - tile bits: 타일 안 픽셀(가장자리 타일은 더 작다)을 행 순서로 MSB-first packing,
+//!   ceil(tw*th/8) bytes. 값은 prev XOR cur.
+
+use anyhow::{bail, Result};
+
+pub const TILE_SIZE: usize = 16;
+
+/// 프레임 bitset 기하 정보 (`BlobHeader`에서 가져온다)
+#[derive(Debug, Clone, Copy)]
+pub struct TileGrid {
+    pub width: usize,
+    pub height: usize,
+    /// bitset 한 행의 비트 수 (`BlobHeader::row_bits`)
+    pub row_bits: usize,
+}
+
+impl TileGrid {
+    pub fn tiles_x(&self) -> usize {
+        self.width.div_ceil(TILE_SIZE)
+    }
+
+    pub fn tile_count(&self) -> usize {
+        self.tiles_x() * self.height.div_ceil(TILE_SIZE)
+    }
+
+    /// 타일 하나의 (x0, y0, tw, th)
+    fn rect(&self, index: usize) -> (usize, usize, usize, usize) {
+        let (tx, ty) = (index % self.tiles_x(), index / self.tiles_x());
+        let (x0, y0) = (tx * TILE_SIZE, ty * TILE_SIZE);
+        (x0, y0, TILE_SIZE.min(self.width - x0), TILE_SIZE.min(self.height - y0))
+    }
+
+    /// 가장 큰 타일 레코드 크기 (모든 타일이 바뀐 경우)
+    pub fn max_record_len(&self) -> usize {
+        2 + self.tile_count() * (2 + (TILE_SIZE * TILE_SIZE).div_ceil(8))
+    }
+}
+
+fn get_bit(buf: &[u8], i: usize) -> bool {
+    (buf[i / 8] >> (7 - (i % 8))) & 1 != 0
+}
+
+fn flip_bit(buf: &mut [u8], i: usize) {
+    buf[i / 8] ^= 1 << (7 - (i % 8));
+}
+
+/// 전체 XOR diff bitset → 바뀐 타일만 담은 레코드
+pub fn encode_tile_diff(diff: &[u8], grid: &TileGrid) -> Result<Vec<u8>> {
+    if grid.tile_count() > u16::MAX as usize {
+        bail!("{}x{} has too many 16x16 tiles for --tiles", grid.width, grid.height);
+    }
+    let mut out = vec![0u8; 2];
+    let mut count = 0u16;
+    let mut tile = Vec::with_capacity((TILE_SIZE * TILE_SIZE).div_ceil(8));
+    for index in 0..grid.tile_count() {
+        let (x0, y0, tw, th) = grid.rect(index);
+        tile.clear();
+        tile.resize((tw * th).div_ceil(8), 0);
+        let mut dirty = false;
+        for y in 0..th {
+            for x in 0..tw {
+                if get_bit(diff, (y0 + y) * grid.row_bits + x0 + x) {
+                    flip_bit(&mut tile, y * tw + x);
+                    dirty = true;
+                }
+            }
+        }
+        if dirty {
+            out.extend_from_slice(&(index as u16).to_le_bytes());
+            out.extend_from_slice(&tile);
+            count += 1;
+        }
+    }
+    out[..2].copy_from_slice(&count.to_le_bytes());
+    Ok(out)
+}
+
+/// 타일 레코드를 현재 프레임 bitset에 XOR 한다.
+pub fn apply_tile_diff(cur: &mut [u8], record: &[u8], grid: &TileGrid) -> Result<()> {
+    if record.len() < 2 {
+        bail!("tile record truncated");
+    }
+    let count = u16::from_le_bytes([record[0], record[1]]) as usize;
+    let mut off = 2;
+    for _ in 0..count {
+        if off + 2 > record.len() {
+            bail!("tile record truncated");
+        }
+        let index = u16::from_le_bytes([record[off], record[off + 1]]) as usize;
+        off += 2;
+        if index >= grid.tile_count() {
+            bail!("tile index {} out of range ({} tiles)", index, grid.tile_count());
+        }
+        let (x0, y0, tw, th) = grid.rect(index);
+        let len = (tw * th).div_ceil(8);
+        let Some(tile) = record.get(off..off + len) else {
+            bail!("tile record truncated");
+        };
+        off += len;
+        for y in 0..th {
+            for x in 0..tw {
+                if get_bit(tile, y * tw + x) {
+                    flip_bit(cur, (y0 + y) * grid.row_bits + x0 + x);
+                }
+            }
+        }
+    }
+    if off != record.len() {
+        bail!("tile record has {} trailing bytes", record.len() - off);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_changed_pixel_is_one_tile_update() {
+        let grid = TileGrid { width: 64, height: 48, row_bits: 64 };
+        let mut diff = vec![0u8; 64 * 48 / 8];
+        // (x=37, y=21) → 타일 (2, 1)
+        flip_bit(&mut diff, 21 * 64 + 37);
+        let record = encode_tile_diff(&diff, &grid).unwrap();
+        assert_eq!(u16::from_le_bytes([record[0], record[1]]), 1);
+        assert_eq!(record.len(), 2 + 2 + 32);
+        assert_eq!(u16::from_le_bytes([record[2], record[3]]) as usize, grid.tiles_x() + 2);
+
+        let mut cur = vec![0u8; diff.len()];
+        apply_tile_diff(&mut cur, &record, &grid).unwrap();
+        assert_eq!(cur, diff);
+    }
+
+    #[test]
+    fn unchanged_frame_is_empty_record() {
+        let grid = TileGrid { width: 20, height: 10, row_bits: 24 };
+        assert_eq!(encode_tile_diff(&[0u8; 30], &grid).unwrap(), [0, 0]);
+    }
+
+    #[test]
+    fn tile_count_must_fit_u16() {
+        // 65536 타일: count가 u16을 넘친다
+        let grid = TileGrid { width: 256 * TILE_SIZE, height: 256 * TILE_SIZE, row_bits: 256 * TILE_SIZE };
+        assert!(encode_tile_diff(&[], &grid).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+//! wasm-bindgen 바인딩 (`--features wasm`): 브라우저에서 CLI `decode`/`verify`와 같은 코드로 blob을 검사한다
+//!
+//! ```sh
+//! cargo build --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/badapple_encoder.wasm
+//! ```
+
+use crate::decode::{decode_frame_gray, verify_blob};
+use wasm_bindgen::prelude::*;
+
+/// anyhow context 체인까지 한 줄로 JS `Error`에 담는다
+fn js_error(e: anyhow::Error) -> JsError {
+    JsError::new(&format!("{:#}", e))
+}
+
+/// `index`번 프레임의 gray8 픽셀 (w*h, 행 우선, 0=black). 암호화된 blob은 `key`가 있어야 한다.
+#[wasm_bindgen(js_name = decodeFrame)]
+pub fn decode_frame_js(blob: &[u8], index: u32, key: Option<Vec<u8>>) -> Result<Vec<u8>, JsError> {
+    decode_frame_gray(blob, index, key.as_deref()).map_err(js_error)
+}
+
+/// 헤더, CRC, 모든 프레임 레코드 검사 (`verify`와 같다). 실패하면 이유를 담은 `Error`를 던진다.
+#[wasm_bindgen(js_name = verifyBlob)]
+pub fn verify_blob_js(blob: &[u8], key: Option<Vec<u8>>) -> Result<(), JsError> {
+    verify_blob(blob, key.as_deref()).map_err(js_error)
+}
@@ -0,0 +1,181 @@
+//! PDF 저장: `build_pdf`가 만든 `PdfDocument`를 파일로 쓴다
+//!
+//! 객체 본문은 lopdf의 writer로 직렬화하지만(`object_bytes`), 파일 첨부(`AttachmentData::File`)의 EmbeddedFile
+//! stream은 `Document` 안에 dict만 있고 content가 비어 있다. 쓸 때 dict 뒤에 파일을 조금씩 복사하므로
+//! 큰 BA.bin도 PDF를 만드는 동안 메모리에 통째로 올라오지 않는다.
+//! xref는 `Document`의 형식을 따라 table(1.4) 또는 압축하지 않은 xref stream(1.5+)으로 쓴다.
+//! `--linearize`(`linearize.rs`)도 여기 직렬화(`Chunk`)를 같이 쓴다.
+
+use crate::pdf::PdfDocument;
+use anyhow::{bail, Context, Result};
+use lopdf::{
+    content::{Content, Operation},
+    xref::XrefType,
+    Dictionary, Object, ObjectId, Stream,
+};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+/// 원래 trailer에서 버리는 key (xref 위치/형식은 여기서 새로 쓴다)
+pub(crate) const TRAILER_XREF_KEYS: [&[u8]; 9] = [b"Size", b"Prev", b"XRefStm", b"Type", b"W", b"Index", b"Length", b"Filter", b"DecodeParms"];
+
+/// 저장할 때 파일에서 채우는 stream content
+#[derive(Debug, Clone)]
+pub(crate) struct FileStream {
+    pub path: PathBuf,
+    /// `build_pdf`가 MD5를 구할 때 읽은 크기 (`/Length`, `/Params /Size`)
+    pub len: u64,
+}
+
+/// 파일에서 채울 stream (EmbeddedFile 객체 번호 → 파일)
+pub(crate) type FileStreams = BTreeMap<ObjectId, FileStream>;
+
+/// 직렬화한 간접 객체 `N 0 obj ... endobj`. 파일 stream이면 `head`(`... stream\n`)와 `tail`(`\nendstream\nendobj\n`)
+/// 사이에 파일 내용이 들어간다.
+pub(crate) struct Chunk {
+    head: Vec<u8>,
+    file: Option<FileStream>,
+    tail: Vec<u8>,
+}
+
+impl Chunk {
+    /// `num`번 객체로 직렬화한다. `file`이 있으면 `obj`는 content가 빈 stream이어야 한다.
+    pub fn new(num: u32, obj: Object, file: Option<&FileStream>) -> Result<Self> {
+        let Some(file) = file else {
+            return Ok(Self { head: indirect_object(num, obj)?, file: None, tail: Vec::new() });
+        };
+        let Object::Stream(stream) = obj else {
+            bail!("file-backed pdf object {} is not a stream", num);
+        };
+        let mut dict = stream.dict;
+        dict.set("Length", file.len as i64);
+        let mut head = format!("{} 0 obj\n", num).into_bytes();
+        head.extend(object_bytes(Object::Dictionary(dict))?);
+        head.extend_from_slice(b"stream\n");
+        Ok(Self { head, file: Some(file.clone()), tail: b"\nendstream\nendobj\n".to_vec() })
+    }
+
+    pub fn len(&self) -> usize {
+        self.head.len() + self.file.as_ref().map_or(0, |f| f.len as usize) + self.tail.len()
+    }
+
+    /// `out`에 쓴다. 파일은 `build_pdf` 때와 크기가 같아야 한다 (그 사이에 바뀌면 `/CheckSum`이 틀린다).
+    pub fn write_to(&self, out: &mut dyn Write) -> Result<()> {
+        out.write_all(&self.head)?;
+        if let Some(file) = &self.file {
+            let src = File::open(&file.path).with_context(|| format!("failed to read {}", file.path.display()))?;
+            let mut src = src.take(file.len + 1);
+            let copied = io::copy(&mut src, out).with_context(|| format!("failed to copy {} into the pdf", file.path.display()))?;
+            if copied != file.len {
+                bail!("{} changed while the pdf was written ({} bytes, expected {})", file.path.display(), copied, file.len);
+            }
+        }
+        out.write_all(&self.tail)?;
+        Ok(())
+    }
+}
+
+/// `pdf`를 `out`에 쓴다. 객체는 번호 순서, 세대는 0 (`build_pdf`는 세대 0만 만든다).
+pub(crate) fn write_pdf(pdf: &PdfDocument, out: &mut dyn Write) -> Result<()> {
+    let doc = &pdf.doc;
+    let mut pos = 0u64;
+    emit(out, &mut pos, format!("%PDF-{}\n", doc.version).as_bytes())?;
+    emit(out, &mut pos, b"%\xE2\xE3\xCF\xD3\n")?;
+
+    let mut offsets: BTreeMap<u32, u64> = BTreeMap::new();
+    for (&id, obj) in &doc.objects {
+        offsets.insert(id.0, pos);
+        let chunk = Chunk::new(id.0, obj.clone(), pdf.files.get(&id))?;
+        chunk.write_to(out)?;
+        pos += chunk.len() as u64;
+    }
+
+    let mut trailer = Dictionary::new();
+    for (key, value) in doc.trailer.iter().filter(|(k, _)| !TRAILER_XREF_KEYS.contains(&k.as_slice())) {
+        trailer.set(key.clone(), value.clone());
+    }
+    let size = offsets.keys().next_back().map_or(1, |n| n + 1);
+    let xref_offset = pos;
+    match doc.reference_table.cross_reference_type {
+        XrefType::CrossReferenceTable => {
+            let mut xref = format!("xref\n0 {}\n", size).into_bytes();
+            xref.extend_from_slice(FREE_ENTRY.as_bytes());
+            for n in 1..size {
+                xref.extend_from_slice(offsets.get(&n).map_or(FREE_ENTRY.to_string(), |&off| xref_entry(off)).as_bytes());
+            }
+            trailer.set("Size", size as i64);
+            xref.extend_from_slice(b"trailer\n");
+            xref.extend(object_bytes(Object::Dictionary(trailer))?);
+            xref.push(b'\n');
+            emit(out, &mut pos, &xref)?;
+        }
+        XrefType::CrossReferenceStream => {
+            // xref stream 자신이 마지막 번호를 받는다
+            offsets.insert(size, xref_offset);
+            let width = (u64::BITS - xref_offset.leading_zeros()).div_ceil(8).max(1) as usize;
+            let mut rows = Vec::with_capacity((size as usize + 1) * (width + 3));
+            for n in 0..=size {
+                match offsets.get(&n) {
+                    Some(&off) => {
+                        rows.push(1);
+                        rows.extend_from_slice(&off.to_be_bytes()[8 - width..]);
+                        rows.extend_from_slice(&[0, 0]);
+                    }
+                    None => {
+                        rows.push(0);
+                        rows.resize(rows.len() + width, 0);
+                        rows.extend_from_slice(if n == 0 { &[0xff, 0xff] } else { &[0, 0] });
+                    }
+                }
+            }
+            trailer.set("Type", "XRef");
+            trailer.set("Size", (size + 1) as i64);
+            trailer.set("W", vec![1.into(), (width as i64).into(), 2.into()]);
+            let mut stream = Stream::new(trailer, rows);
+            stream.allows_compression = false;
+            emit(out, &mut pos, &indirect_object(size, Object::Stream(stream))?)?;
+        }
+    }
+    emit(out, &mut pos, format!("startxref\n{}\n%%EOF\n", xref_offset).as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+fn emit(out: &mut dyn Write, pos: &mut u64, bytes: &[u8]) -> Result<()> {
+    out.write_all(bytes)?;
+    *pos += bytes.len() as u64;
+    Ok(())
+}
+
+/// xref table의 빈 항목 (0번과 번호가 빈 자리)
+const FREE_ENTRY: &str = "0000000000 65535 f \n";
+
+pub(crate) fn xref_entry(offset: u64) -> String {
+    format!("{:010} 00000 n \n", offset)
+}
+
+/// `N 0 obj ... endobj`. 객체 본문은 lopdf의 writer로 쓴다.
+pub(crate) fn indirect_object(num: u32, obj: Object) -> Result<Vec<u8>> {
+    let mut out = format!("{} 0 obj\n", num).into_bytes();
+    out.extend(object_bytes(obj)?);
+    out.extend_from_slice(b"\nendobj\n");
+    Ok(out)
+}
+
+/// lopdf가 `Document::save`에서 쓰는 것과 같은 직렬화. `lopdf::Writer`는 공개돼 있지 않아서
+/// 같은 `Writer::write_object`를 부르는 `Content::encode`에 연산자 없는 operand 하나로 넘긴다
+/// (뒤에 붙는 구분 공백 한 칸은 뗀다). stream `/Length`는 lopdf처럼 dict 값을 그대로 쓰므로 여기서 맞춘다.
+pub(crate) fn object_bytes(mut obj: Object) -> Result<Vec<u8>> {
+    if let Object::Stream(stream) = &mut obj {
+        stream.dict.set("Length", stream.content.len() as i64);
+    }
+    let mut out = Content { operations: vec![Operation::new("", vec![obj])] }
+        .encode()
+        .context("failed to serialize pdf object")?;
+    out.pop();
+    Ok(out)
+}
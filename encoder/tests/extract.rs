@@ -0,0 +1,158 @@
+//! encode → PDF → `read_attachments` / `extract` → decode 왕복
+
+#![cfg(feature = "native")]
+
+use badapple_encoder::{
+    build_pdf, decode_frame, encode_blob, pack_bits, read_attachments, Attachment, AttachmentData, EncodeOptions, PdfOptions,
+    RawSource,
+};
+use lopdf::{dictionary, Document, Object};
+use std::{fs, process::Command};
+
+/// 자식 프로세스 출력은 실패했을 때만 보여준다
+fn run(cmd: &mut Command) {
+    let out = cmd.output().unwrap();
+    assert!(out.status.success(), "{:?} failed:\n{}", cmd, String::from_utf8_lossy(&out.stderr));
+}
+
+const W: usize = 20;
+const H: usize = 6;
+
+/// 20x6, 5 프레임: 세로 줄이 오른쪽으로 움직인다
+fn frames() -> Vec<Vec<u8>> {
+    (0..5)
+        .map(|f| (0..W * H).map(|i| if i % W == 3 * f + 1 { 0 } else { 255 }).collect())
+        .collect()
+}
+
+fn packed(gray: &[u8]) -> Vec<u8> {
+    pack_bits(&gray.iter().map(|&px| (px == 0) as u8).collect::<Vec<_>>())
+}
+
+fn encode(frames: &[Vec<u8>]) -> Vec<u8> {
+    let opts = EncodeOptions { width: W as u16, height: H as u16, ..EncodeOptions::default() };
+    encode_blob(&mut RawSource::new(frames.concat().as_slice(), true), &opts).unwrap()
+}
+
+fn attachment_bytes<'a>(atts: &'a [Attachment], name: &str) -> &'a [u8] {
+    let att = atts.iter().find(|a| a.name == name).unwrap_or_else(|| panic!("{} not extracted", name));
+    match &att.data {
+        AttachmentData::Bytes(b) => b,
+        AttachmentData::File(p) => panic!("{} extracted as a file {}", name, p.display()),
+    }
+}
+
+fn assert_frames(blob: &[u8], frames: &[Vec<u8>]) {
+    for (i, gray) in frames.iter().enumerate() {
+        assert_eq!(decode_frame(blob, i as u32, None).unwrap(), packed(gray), "frame {}", i);
+    }
+}
+
+fn opts() -> PdfOptions {
+    PdfOptions { start_url: "https://example.com/play.html".to_string(), ..PdfOptions::default() }
+}
+
+#[test]
+fn encode_extract_decode_round_trip() {
+    let frames = frames();
+    let blob = encode(&frames);
+    let dir = tempfile::tempdir().unwrap();
+    let pdf = dir.path().join("out.pdf");
+    let blob_path = dir.path().join("BA.bin");
+    fs::write(&blob_path, &blob).unwrap();
+    let atts = [
+        Attachment::file("BA.bin", "application/octet-stream", &blob_path),
+        Attachment::bytes("AU.ogg", "audio/ogg", b"OggS".to_vec()),
+    ];
+    build_pdf(&opts(), &atts).unwrap().save(&pdf).unwrap();
+
+    let extracted = read_attachments(&Document::load(&pdf).unwrap()).unwrap();
+    assert_eq!(extracted.len(), 2);
+    assert_eq!(attachment_bytes(&extracted, "AU.ogg"), b"OggS");
+    let ba = attachment_bytes(&extracted, "BA.bin");
+    assert_eq!(ba, blob.as_slice());
+    assert_frames(ba, &frames);
+}
+
+/// `/Kids` name tree, stream filter, name tree에 없고 `/AF`에만 있는 filespec
+#[test]
+fn extract_follows_kids_filters_and_af() {
+    let frames = frames();
+    let blob = encode(&frames);
+    let mut doc = build_pdf(&opts(), &[Attachment::bytes("BA.bin", "application/octet-stream", blob.clone())]).unwrap().into_document().unwrap();
+
+    let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    let names_id = doc.get_dictionary(catalog_id).unwrap().get(b"Names").unwrap().as_reference().unwrap();
+    let flat = doc.get_dictionary(names_id).unwrap().get(b"EmbeddedFiles").unwrap().as_dict().unwrap().clone();
+    let ba_spec = flat.get(b"Names").unwrap().as_array().unwrap()[1].as_reference().unwrap();
+
+    // BA.bin EF stream을 deflate로 바꾼다
+    let ef = doc.get_dictionary(ba_spec).unwrap().get(b"EF").unwrap().as_dict().unwrap();
+    let ef_id = ef.get(b"F").unwrap().as_reference().unwrap();
+    doc.get_object_mut(ef_id).unwrap().as_stream_mut().unwrap().compress().unwrap();
+    assert!(doc.get_object(ef_id).unwrap().as_stream().unwrap().dict.has(b"Filter"));
+
+    // 평평한 /Names 배열 → /Kids 한 단계
+    let leaf = doc.add_object(dictionary! {
+        "Names" => flat.get(b"Names").unwrap().clone(),
+        "Limits" => vec![Object::string_literal("BA.bin"), Object::string_literal("BA.bin")],
+    });
+    doc.get_object_mut(names_id)
+        .unwrap()
+        .as_dict_mut()
+        .unwrap()
+        .set("EmbeddedFiles", dictionary! { "Kids" => vec![Object::Reference(leaf)] });
+
+    // /AF에만 있는 첨부
+    let extra_ef = doc.add_object(lopdf::Stream::new(
+        dictionary! { "Type" => "EmbeddedFile", "Subtype" => "text/plain" },
+        b"af only".to_vec(),
+    ));
+    let extra_spec = doc.add_object(dictionary! {
+        "Type" => "Filespec",
+        "F" => Object::string_literal("notes.txt"),
+        "UF" => Object::string_literal("notes.txt"),
+        "EF" => dictionary! { "F" => Object::Reference(extra_ef) },
+    });
+    doc.get_object_mut(catalog_id)
+        .unwrap()
+        .as_dict_mut()
+        .unwrap()
+        .set("AF", vec![Object::Reference(ba_spec), Object::Reference(extra_spec)]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let pdf = dir.path().join("kids.pdf");
+    doc.save(&pdf).unwrap();
+    let extracted = read_attachments(&Document::load(&pdf).unwrap()).unwrap();
+    assert_eq!(extracted.len(), 2);
+    assert_eq!(attachment_bytes(&extracted, "notes.txt"), b"af only");
+    assert_frames(attachment_bytes(&extracted, "BA.bin"), &frames);
+}
+
+/// CLI: raw stdin → PDF → `extract --name BA.bin` → `decode`
+#[test]
+fn cli_extract_then_decode() {
+    let bin = env!("CARGO_BIN_EXE_badapple_encoder");
+    let frames = frames();
+    let dir = tempfile::tempdir().unwrap();
+    let raw = dir.path().join("frames.raw");
+    fs::write(&raw, frames.concat()).unwrap();
+    let pdf = dir.path().join("out.pdf");
+    run(Command::new(bin)
+        .args(["-", "none"])
+        .arg(&pdf)
+        .args([&W.to_string(), &H.to_string(), "30", "128", "0", "https://example.com/"])
+        .stdin(fs::File::open(&raw).unwrap()));
+
+    let out = dir.path().join("x");
+    run(Command::new(bin).arg("extract").arg(&pdf).arg("--out").arg(&out).args(["--name", "BA.bin"]));
+    let names: Vec<_> = fs::read_dir(&out).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(names, ["BA.bin"]);
+
+    let ba = out.join("BA.bin");
+    assert_frames(&fs::read(&ba).unwrap(), &frames);
+    let pbm = dir.path().join("f3.pbm");
+    run(Command::new(bin).arg("decode").arg(&ba).arg("3").arg(&pbm));
+    let image = fs::read(&pbm).unwrap();
+    assert!(image.starts_with(format!("P4\n{} {}\n", W, H).as_bytes()));
+}
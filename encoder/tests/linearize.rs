@@ -0,0 +1,169 @@
+//! `--linearize`: `save_linearized` 결과를 다시 읽어서 구조와 hint table을 확인한다.
+//! `qpdf`가 PATH에 있으면 `qpdf --check`와 `qpdf --is-linearized`도 돌린다 (없으면 그 부분만 건너뛴다).
+
+#![cfg(feature = "native")]
+
+use badapple_encoder::{
+    build_pdf, read_attachments, save_linearized, Attachment, AttachmentData, BitDepth, FlipbookPage, PdfOptions, Thumbnail,
+};
+use lopdf::{Document, Object};
+use std::{fs, path::Path, process::Command};
+
+fn thumbnail(seed: u8) -> Thumbnail {
+    Thumbnail {
+        width: 16,
+        height: 8,
+        bit_depth: BitDepth::Binary,
+        invert: false,
+        bits: (0..16u8).map(|i| i.wrapping_mul(37) ^ seed).collect(),
+    }
+}
+
+/// START 페이지 (포스터) + flipbook 3 페이지 + 첨부 2개 (BA.bin은 파일 첨부로 `path` 옆에 둔다)
+fn write_linearized(path: &Path) {
+    let opts = PdfOptions {
+        start_url: "https://example.com/play.html?v=(1)".to_string(),
+        thumbnail: Some(thumbnail(0)),
+        flipbook: (1..=3).map(|i| FlipbookPage { frame: i * 10, image: thumbnail(i as u8) }).collect(),
+        ..PdfOptions::default()
+    };
+    let blob = path.with_extension("bin");
+    fs::write(&blob, vec![0x5a; 4000]).unwrap();
+    let atts = [
+        Attachment::file("BA.bin", "application/octet-stream", &blob),
+        Attachment::bytes("AU.ogg", "audio/ogg", b"OggS audio".to_vec()),
+    ];
+    let mut doc = build_pdf(&opts, &atts).unwrap();
+    save_linearized(&mut doc, path).unwrap();
+}
+
+fn int(dict: &lopdf::Dictionary, key: &[u8]) -> u64 {
+    dict.get(key).unwrap().as_i64().unwrap() as u64
+}
+
+/// `at`에서 시작하는 `N 0 obj`의 N
+fn object_number_at(bytes: &[u8], at: usize) -> u32 {
+    let end = at + bytes[at..].iter().position(|&b| b == b' ').unwrap();
+    std::str::from_utf8(&bytes[at..end]).unwrap().parse().unwrap()
+}
+
+/// hint table용 big-endian 비트 읽기
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Bits<'_> {
+    fn read(&mut self, bits: u32) -> u64 {
+        let mut v = 0;
+        for _ in 0..bits {
+            v = (v << 1) | ((self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1) as u64;
+            self.pos += 1;
+        }
+        v
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+
+    /// 페이지마다 `min + delta` 하나씩
+    fn items(&mut self, pages: usize, min: u64, bits: u32) -> Vec<u64> {
+        let v = (0..pages).map(|_| min + self.read(bits)).collect();
+        self.align();
+        v
+    }
+}
+
+#[test]
+fn linearized_pdf_structure_and_hints() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("lin.pdf");
+    write_linearized(&path);
+    let bytes = fs::read(&path).unwrap();
+    let doc = Document::load(&path).unwrap();
+
+    // linearization dict는 파일 맨 앞 객체
+    let lin_start = bytes.windows(6).position(|w| w == b" 0 obj").unwrap();
+    let lin_start = bytes[..lin_start].iter().rposition(|&b| b == b'\n').unwrap() + 1;
+    let lin_num = object_number_at(&bytes, lin_start);
+    let lin = doc.get_dictionary((lin_num, 0)).unwrap();
+    assert_eq!(int(lin, b"L"), bytes.len() as u64);
+    let pages = doc.get_pages();
+    assert_eq!(int(lin, b"N"), 4);
+    assert_eq!(lin.get(b"O").unwrap().as_i64().unwrap() as u32, pages[&1].0);
+
+    // 첨부와 START 링크는 그대로
+    let atts = read_attachments(&doc).unwrap();
+    assert_eq!(atts.len(), 2);
+    let ba = atts.iter().find(|a| a.name == "BA.bin").unwrap();
+    assert!(matches!(&ba.data, AttachmentData::Bytes(b) if *b == vec![0x5a; 4000]));
+
+    // page offset hint table: 페이지 offset + content offset 자리에 그 페이지의 /Contents 객체가 있어야 한다
+    let h = lin.get(b"H").unwrap().as_array().unwrap();
+    let (hint_offset, hint_len) = (h[0].as_i64().unwrap() as usize, h[1].as_i64().unwrap() as usize);
+    let hint_num = object_number_at(&bytes, hint_offset);
+    let hint = doc.get_object((hint_num, 0)).unwrap().as_stream().unwrap();
+    let mut r = Bits { data: &hint.content, pos: 0 };
+    let n = pages.len();
+    let _min_objects = r.read(32);
+    let first_page = r.read(32) as usize;
+    let objects_bits = r.read(16) as u32;
+    let min_page_len = r.read(32);
+    let page_len_bits = r.read(16) as u32;
+    let min_content_offset = r.read(32);
+    let content_offset_bits = r.read(16) as u32;
+    let min_content_len = r.read(32);
+    let content_len_bits = r.read(16) as u32;
+    let shared_bits = r.read(16) as u32;
+    let identifier_bits = r.read(16) as u32;
+    let numerator_bits = r.read(16) as u32;
+    let _denominator = r.read(16);
+    let _objects = r.items(n, 0, objects_bits);
+    let page_lens = r.items(n, min_page_len, page_len_bits);
+    let shared_counts = r.items(n, 0, shared_bits);
+    let total_shared: u64 = shared_counts.iter().sum();
+    r.items(total_shared as usize, 0, identifier_bits);
+    r.items(total_shared as usize, 0, numerator_bits);
+    let content_offsets = r.items(n, min_content_offset, content_offset_bits);
+    let content_lens = r.items(n, min_content_len, content_len_bits);
+
+    // hint table의 offset은 hint stream이 없는 것처럼 센다 (페이지는 모두 hint stream 뒤)
+    let mut page_start = first_page + hint_len;
+    assert_eq!(first_page, hint_offset);
+    for i in 0..n {
+        let page = doc.get_dictionary(pages[&(i as u32 + 1)]).unwrap();
+        let contents = page.get(b"Contents").unwrap().as_reference().unwrap();
+        let at = page_start + content_offsets[i] as usize;
+        let object = &bytes[at..at + content_lens[i] as usize];
+        assert!(object.starts_with(format!("{} 0 obj\n", contents.0).as_bytes()), "page {}", i + 1);
+        assert!(object.ends_with(b"endobj\n"), "page {}", i + 1);
+        assert!(content_offsets[i] + content_lens[i] <= page_lens[i]);
+        page_start += page_lens[i] as usize;
+    }
+    match doc.get_object(pages[&1]).unwrap() {
+        Object::Dictionary(d) => assert!(d.has(b"Annots")),
+        other => panic!("page 1 is {:?}", other),
+    }
+}
+
+#[test]
+fn qpdf_accepts_linearized_pdf() {
+    if Command::new("qpdf").arg("--version").output().is_err() {
+        eprintln!("qpdf not found on PATH, skipping");
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("lin.pdf");
+    write_linearized(&path);
+    for args in [["--check"], ["--is-linearized"]] {
+        let out = Command::new("qpdf").args(args).arg(&path).output().unwrap();
+        assert!(
+            out.status.success(),
+            "qpdf {} failed:\n{}{}",
+            args[0],
+            String::from_utf8_lossy(&out.stdout),
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! `build_pdf` 결과를 저장하고 lopdf로 다시 열어서 catalog, 첨부, START 페이지를 확인한다.
+
+#![cfg(feature = "native")]
+
+use badapple_encoder::{
+    build_pdf, decode_frame_gray, encode_blob_to, read_attachments, Attachment, EncodeOptions, OpenAction, PdfOptions, RawSource, Thumbnail,
+};
+use lopdf::{Dictionary, Document, Object};
+use std::io::Cursor;
+
+const URL: &str = "https://example.com/play.html";
+
+fn opts() -> PdfOptions {
+    PdfOptions { start_url: URL.to_string(), ..PdfOptions::default() }
+}
+
+fn attachments() -> Vec<Attachment> {
+    vec![
+        Attachment::bytes("BA.bin", "application/octet-stream", vec![1, 2, 3]),
+        Attachment::bytes("AU.ogg", "audio/ogg", b"OggS".to_vec()),
+    ]
+}
+
+fn save_and_load(opts: &PdfOptions, atts: &[Attachment]) -> Document {
+    let mut bytes = Vec::new();
+    build_pdf(opts, atts).unwrap().save_to(&mut bytes).unwrap();
+    Document::load_mem(&bytes).unwrap()
+}
+
+fn catalog(doc: &Document) -> &Dictionary {
+    doc.catalog().unwrap()
+}
+
+fn deref<'a>(doc: &'a Document, obj: &'a Object) -> &'a Object {
+    match obj {
+        Object::Reference(id) => doc.get_object(*id).unwrap(),
+        other => other,
+    }
+}
+
+/// 1페이지의 (유일한) Link annotation
+fn link(doc: &Document) -> &Dictionary {
+    let page = doc.get_dictionary(doc.get_pages()[&1]).unwrap();
+    let annots = page.get(b"Annots").unwrap().as_array().unwrap();
+    assert_eq!(annots.len(), 1);
+    deref(doc, &annots[0]).as_dict().unwrap()
+}
+
+fn text(obj: &Object) -> String {
+    String::from_utf8(obj.as_str().unwrap().to_vec()).unwrap()
+}
+
+#[test]
+fn embedded_player_is_attached_and_opened_by_start() {
+    let mut atts = attachments();
+    atts.push(Attachment::bytes("player.html", "text/html", b"<html></html>".to_vec()));
+    let doc = save_and_load(&PdfOptions { embedded_player: Some("player.html".to_string()), ..opts() }, &atts);
+
+    // name tree key는 정렬되어 있고, 첨부는 이름으로 모두 찾을 수 있다
+    let names = deref(&doc, catalog(&doc).get(b"Names").unwrap()).as_dict().unwrap();
+    let tree = names.get(b"EmbeddedFiles").unwrap().as_dict().unwrap().get(b"Names").unwrap().as_array().unwrap();
+    let keys: Vec<String> = tree.iter().step_by(2).map(text).collect();
+    assert_eq!(keys, ["AU.ogg", "BA.bin", "player.html"]);
+    let extracted = read_attachments(&doc).unwrap();
+    for att in &atts {
+        let found = extracted.iter().find(|a| a.name == att.name).unwrap_or_else(|| panic!("{} missing", att.name));
+        assert_eq!(found.mime, att.mime);
+    }
+
+    // START는 첨부 플레이어를 열고, 못 열면 start_url로 간다
+    let action = link(&doc).get(b"A").unwrap().as_dict().unwrap();
+    assert_eq!(action.get(b"S").unwrap().as_name().unwrap(), b"JavaScript");
+    let js = text(action.get(b"JS").unwrap());
+    assert!(js.contains("exportDataObject") && js.contains("player.html"), "{}", js);
+    assert!(js.contains(&format!("app.launchURL(\"{}\"", URL)), "{}", js);
+}
+
+#[test]
+fn start_is_a_uri_action_without_a_player() {
+    let doc = save_and_load(&opts(), &attachments());
+    let action = link(&doc).get(b"A").unwrap().as_dict().unwrap();
+    assert_eq!(action.get(b"S").unwrap().as_name().unwrap(), b"URI");
+    assert_eq!(text(action.get(b"URI").unwrap()), URL);
+}
+
+#[test]
+fn player_must_be_attached_and_names_unique() {
+    let missing = build_pdf(&PdfOptions { embedded_player: Some("player.html".to_string()), ..opts() }, &attachments());
+    assert!(format!("{:#}", missing.err().unwrap()).contains("not among the attachments"));
+
+    let mut atts = attachments();
+    atts.push(Attachment::bytes("BA.bin", "application/octet-stream", vec![4]));
+    let dup = build_pdf(&opts(), &atts);
+    assert!(format!("{:#}", dup.err().unwrap()).contains("duplicate attachment name BA.bin"));
+}
+
+/// 12x4, 6 프레임: 3x2 black 사각형이 오른쪽으로 한 칸씩 움직인다 (0 = black)
+fn moving_square() -> Vec<u8> {
+    (0..6)
+        .flat_map(|f| (0..48).map(move |i| if (1..3).contains(&(i / 12)) && (f + 2..f + 5).contains(&(i % 12)) { 0 } else { 255 }))
+        .collect()
+}
+
+#[test]
+fn poster_frame_is_drawn_in_the_poster_rect() {
+    let enc = EncodeOptions { width: 12, height: 4, poster_frame: 3, ..EncodeOptions::default() };
+    let mut blob = Cursor::new(Vec::new());
+    let stats = encode_blob_to(&mut RawSource::new(moving_square().as_slice(), true), &enc, &mut blob).unwrap();
+    let blob = blob.into_inner();
+    let poster = stats.poster.unwrap();
+
+    // 포스터는 행 정렬 (stride 2) bitset이고 decode_frame(3)과 같은 그림이다
+    let gray = decode_frame_gray(&blob, 3, None).unwrap();
+    let rows: Vec<u8> = (0..4)
+        .flat_map(|y| {
+            let row = &gray[y * 12..y * 12 + 12];
+            [0, 8].map(|x0| row.iter().skip(x0).take(8).enumerate().fold(0u8, |b, (i, &px)| b | ((px == 0) as u8) << (7 - i)))
+        })
+        .collect();
+    assert_eq!(poster, rows);
+
+    let thumb = Thumbnail { width: 12, height: 4, bit_depth: enc.bit_depth, invert: false, bits: poster.clone() };
+    let rect = [100.0, 500.0, 500.0, 600.0];
+    let doc = save_and_load(&PdfOptions { thumbnail: Some(thumb), thumbnail_rect: Some(rect), ..opts() }, &attachments());
+    let page = doc.get_dictionary(doc.get_pages()[&1]).unwrap();
+    let resources = deref(&doc, page.get(b"Resources").unwrap()).as_dict().unwrap();
+    let xobjects = resources.get(b"XObject").unwrap().as_dict().unwrap();
+    let image = deref(&doc, xobjects.get(b"Im1").unwrap()).as_stream().unwrap();
+    let int = |key: &[u8]| image.dict.get(key).unwrap().as_i64().unwrap();
+    assert_eq!((int(b"Width"), int(b"Height"), int(b"BitsPerComponent")), (12, 4, 1));
+    // lopdf는 image stream을 풀어 주지 않는다 (작은 이미지는 압축하지 않은 채로 남는다)
+    let mut data = image.clone();
+    data.dict.remove(b"Subtype");
+    let data = if data.dict.has(b"Filter") { data.decompressed_content().unwrap() } else { data.content };
+    assert_eq!(data, poster);
+
+    // 400x100 영역에 12x4 이미지: 25배 (높이에 맞춤), 가로는 가운데
+    let contents = doc.get_page_content(doc.get_pages()[&1]).unwrap();
+    let contents = String::from_utf8_lossy(&contents);
+    assert!(contents.contains("300.000 0 0 100.000 150.000 500.000 cm\n/Im1 Do"), "{}", contents);
+}
+
+fn real(obj: &Object) -> f32 {
+    obj.as_float().or_else(|_| obj.as_i64().map(|v| v as f32)).unwrap()
+}
+
+#[test]
+fn appearance_border_and_open_action_toggle_independently() {
+    for bits in 0..8 {
+        let (appearance, border, open_url) = (bits & 1 != 0, bits & 2 != 0, bits & 4 != 0);
+        let mut opts = opts();
+        opts.page.button.appearance = appearance;
+        opts.page.button.border = border;
+        opts.page.button.stroke_gray = 0.25;
+        opts.open_action = if open_url { OpenAction::Url } else { OpenAction::None };
+        let doc = save_and_load(&opts, &attachments());
+        let annot = link(&doc);
+        let case = format!("appearance={} border={} open_url={}", appearance, border, open_url);
+
+        // /AP /N는 버튼 크기의 Form XObject
+        match annot.get(b"AP") {
+            Ok(ap) => {
+                assert!(appearance, "{}", case);
+                let form = deref(&doc, ap.as_dict().unwrap().get(b"N").unwrap()).as_stream().unwrap();
+                assert_eq!(form.dict.get(b"Subtype").unwrap().as_name().unwrap(), b"Form", "{}", case);
+                let [x1, y1, x2, y2] = opts.page.button_rect();
+                let bbox: Vec<f32> = form.dict.get(b"BBox").unwrap().as_array().unwrap().iter().map(real).collect();
+                assert_eq!(bbox, [0.0, 0.0, (x2 - x1) as f32, (y2 - y1) as f32], "{}", case);
+                assert!(!form.content.is_empty(), "{}", case);
+            }
+            Err(_) => assert!(!appearance, "{}", case),
+        }
+
+        // 테두리: /Border 두께와 /BS, /C
+        let border_w: Vec<f32> = annot.get(b"Border").unwrap().as_array().unwrap().iter().map(real).collect();
+        assert_eq!(border_w, if border { [0.0, 0.0, 2.0] } else { [0.0, 0.0, 0.0] }, "{}", case);
+        assert_eq!(annot.get(b"BS").is_ok(), border, "{}", case);
+        if border {
+            let bs = annot.get(b"BS").unwrap().as_dict().unwrap();
+            assert_eq!(real(bs.get(b"W").unwrap()), 2.0, "{}", case);
+            assert_eq!(bs.get(b"S").unwrap().as_name().unwrap(), b"S", "{}", case);
+            let color: Vec<f32> = annot.get(b"C").unwrap().as_array().unwrap().iter().map(real).collect();
+            assert_eq!(color, [0.25; 3], "{}", case);
+        }
+
+        // /OpenAction은 START와 같은 URI action
+        match catalog(&doc).get(b"OpenAction") {
+            Ok(action) => {
+                assert!(open_url, "{}", case);
+                let action = deref(&doc, action).as_dict().unwrap();
+                assert_eq!(action.get(b"S").unwrap().as_name().unwrap(), b"URI", "{}", case);
+                assert_eq!(text(action.get(b"URI").unwrap()), URL, "{}", case);
+            }
+            Err(_) => assert!(!open_url, "{}", case),
+        }
+        // START 자체는 어느 조합에서도 같다
+        assert_eq!(text(annot.get(b"A").unwrap().as_dict().unwrap().get(b"URI").unwrap()), URL, "{}", case);
+    }
+}
@@ -0,0 +1,125 @@
+//! `PdfDocument::save`: 파일 첨부는 `build_pdf`에서 읽지 않고 저장할 때 파일에서 복사한다.
+//! 저장한 PDF의 xref가 모든 객체를 가리키는지 직접 확인하고, lopdf로 다시 열어서 첨부를 비교한다.
+//! `verify_pdf`가 빠진 첨부와 다른 곳을 가리키는 START 링크를 거부하는지도 본다.
+
+#![cfg(feature = "native")]
+
+use badapple_encoder::{build_pdf, read_attachments, verify_pdf, Attachment, AttachmentData, PdfOptions, PdfVersion};
+use lopdf::Document;
+use std::fs;
+
+fn opts(version: PdfVersion) -> PdfOptions {
+    PdfOptions { start_url: "https://example.com/play.html".to_string(), version, ..PdfOptions::default() }
+}
+
+fn number(bytes: &[u8]) -> u64 {
+    std::str::from_utf8(bytes).unwrap().trim().parse().unwrap()
+}
+
+/// 마지막 `startxref`가 가리키는 xref(table 또는 압축하지 않은 stream)의 `(객체 번호, offset)`
+fn xref_offsets(pdf: &[u8]) -> Vec<(u32, usize)> {
+    let tail = pdf.windows(9).rposition(|w| w == b"startxref").unwrap() + 10;
+    let end = tail + pdf[tail..].iter().position(|&b| b == b'\n').unwrap();
+    let xref = number(&pdf[tail..end]) as usize;
+    let lines = |at: usize| pdf[at..].split(|&b| b == b'\n');
+    if pdf[xref..].starts_with(b"xref\n") {
+        let mut lines = lines(xref + 5);
+        let header = lines.next().unwrap();
+        let count = number(header.split(|&b| b == b' ').nth(1).unwrap());
+        return lines
+            .take(count as usize)
+            .enumerate()
+            .filter(|(_, e)| e.ends_with(b"n "))
+            .map(|(n, e)| (n as u32, number(&e[..10]) as usize))
+            .collect();
+    }
+    let body = pdf[xref..].windows(7).position(|w| w == b"stream\n").unwrap() + xref + 7;
+    let dict = std::str::from_utf8(&pdf[xref..body]).unwrap();
+    let w: Vec<usize> = dict.split("/W[").nth(1).unwrap().split(']').next().unwrap().split(' ').map(|v| v.parse().unwrap()).collect();
+    let size: usize = dict.split("/Size ").nth(1).unwrap().split(|c: char| !c.is_ascii_digit()).next().unwrap().parse().unwrap();
+    let row = w.iter().sum::<usize>();
+    (0..size)
+        .filter(|n| pdf[body + n * row] == 1)
+        .map(|n| {
+            let off = pdf[body + n * row + 1..body + n * row + 1 + w[1]].iter().fold(0, |v, &b| v << 8 | b as usize);
+            (n as u32, off)
+        })
+        .collect()
+}
+
+#[test]
+fn file_attachment_is_copied_when_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let blob: Vec<u8> = (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let blob_path = dir.path().join("BA.bin");
+    fs::write(&blob_path, &blob).unwrap();
+    let atts = [
+        Attachment::file("BA.bin", "application/octet-stream", &blob_path),
+        Attachment::bytes("AU.ogg", "audio/ogg", b"OggS".to_vec()),
+    ];
+    for version in [PdfVersion::V14, PdfVersion::V15, PdfVersion::V17, PdfVersion::V20] {
+        let pdf = build_pdf(&opts(version), &atts).unwrap();
+        // build_pdf는 파일 첨부 content를 읽어 두지 않는다
+        let ef = pdf.doc.objects.values().filter_map(|o| o.as_stream().ok()).find(|s| s.dict.get(b"Length").and_then(|l| l.as_i64()).ok() == Some(blob.len() as i64));
+        assert!(ef.unwrap().content.is_empty(), "{:?}", version);
+
+        let path = dir.path().join(format!("{}.pdf", version.as_str()));
+        pdf.save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.starts_with(format!("%PDF-{}\n", version.as_str()).as_bytes()));
+        let offsets = xref_offsets(&bytes);
+        assert!(offsets.len() >= pdf.doc.objects.len(), "{:?}", version);
+        for (n, off) in offsets {
+            assert!(bytes[off..].starts_with(format!("{} 0 obj\n", n).as_bytes()), "{:?} object {}", version, n);
+        }
+
+        let extracted = read_attachments(&Document::load(&path).unwrap()).unwrap();
+        let ba = extracted.iter().find(|a| a.name == "BA.bin").unwrap();
+        assert!(matches!(&ba.data, AttachmentData::Bytes(b) if *b == blob), "{:?}", version);
+        verify_pdf(&path, &atts, "https://example.com/play.html").unwrap();
+    }
+}
+
+#[test]
+fn into_document_reads_file_attachments() {
+    let dir = tempfile::tempdir().unwrap();
+    let blob_path = dir.path().join("BA.bin");
+    fs::write(&blob_path, b"blob bytes").unwrap();
+    let atts = [Attachment::file("BA.bin", "application/octet-stream", &blob_path)];
+    let mut doc = build_pdf(&opts(PdfVersion::V17), &atts).unwrap().into_document().unwrap();
+    let path = dir.path().join("out.pdf");
+    doc.save(&path).unwrap();
+    verify_pdf(&path, &atts, "https://example.com/play.html").unwrap();
+}
+
+#[test]
+fn file_changed_after_build_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let blob_path = dir.path().join("BA.bin");
+    fs::write(&blob_path, b"0123456789").unwrap();
+    let pdf = build_pdf(&opts(PdfVersion::V17), &[Attachment::file("BA.bin", "application/octet-stream", &blob_path)]).unwrap();
+    fs::write(&blob_path, b"0123").unwrap();
+    let err = pdf.save(&dir.path().join("out.pdf")).unwrap_err();
+    assert!(format!("{:#}", err).contains("changed while the pdf was written"), "{:#}", err);
+}
+
+#[test]
+fn verify_rejects_a_missing_attachment() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.pdf");
+    let au = Attachment::bytes("AU.ogg", "audio/ogg", b"OggS".to_vec());
+    build_pdf(&opts(PdfVersion::V17), std::slice::from_ref(&au)).unwrap().save(&path).unwrap();
+    let expected = [Attachment::bytes("BA.bin", "application/octet-stream", vec![1, 2, 3]), au];
+    let err = verify_pdf(&path, &expected, "https://example.com/play.html").unwrap_err();
+    assert!(format!("{:#}", err).contains("attachment BA.bin is missing"), "{:#}", err);
+}
+
+#[test]
+fn verify_rejects_a_start_link_to_another_url() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.pdf");
+    let atts = [Attachment::bytes("BA.bin", "application/octet-stream", vec![1, 2, 3])];
+    build_pdf(&opts(PdfVersion::V17), &atts).unwrap().save(&path).unwrap();
+    let err = verify_pdf(&path, &atts, "https://example.com/other.html").unwrap_err();
+    assert!(format!("{:#}", err).contains("does not point at start_url https://example.com/other.html"), "{:#}", err);
+}
@@ -0,0 +1,156 @@
+//! START 링크 `/URI`: escape한 URL이 lopdf로 저장하고 다시 읽어도 그대로인지,
+//! `start_url` 템플릿의 자리 표시자(`expand_start_url`)가 값으로 바뀌는지
+
+#![cfg(feature = "native")]
+
+use badapple_encoder::{build_pdf, expand_start_url, normalize_start_url, start_url_placeholders, PdfOptions, StartUrlVars};
+use lopdf::{Document, Object};
+
+fn save(start_url: &str) -> Vec<u8> {
+    let opts = PdfOptions { start_url: start_url.to_string(), ..PdfOptions::default() };
+    let mut bytes = Vec::new();
+    build_pdf(&opts, &[]).unwrap().save_to(&mut bytes).unwrap();
+    bytes
+}
+
+/// 저장했다가 다시 연 PDF의 1페이지 Link annotation `/A /URI`
+fn written_uri(start_url: &str) -> Vec<u8> {
+    let doc = Document::load_mem(&save(start_url)).unwrap();
+    let page = *doc.get_pages().get(&1).unwrap();
+    let annots = doc.get_dictionary(page).unwrap().get(b"Annots").unwrap().as_array().unwrap();
+    let annot = doc.dereference(&annots[0]).unwrap().1.as_dict().unwrap();
+    let action = doc.dereference(annot.get(b"A").unwrap()).unwrap().1.as_dict().unwrap();
+    assert_eq!(action.get(b"S").unwrap().as_name_str().unwrap(), "URI");
+    match action.get(b"URI").unwrap() {
+        Object::String(s, _) => s.clone(),
+        other => panic!("/URI is {:?}", other),
+    }
+}
+
+/// `%XX`를 bytes로 되돌린다 (`normalize_start_url`의 반대)
+fn percent_decode(s: &[u8]) -> String {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] == b'%' {
+            out.push(u8::from_str_radix(std::str::from_utf8(&s[i + 1..i + 3]).unwrap(), 16).unwrap());
+            i += 3;
+        } else {
+            out.push(s[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).unwrap()
+}
+
+fn assert_round_trip(url: &str, expected: &str) {
+    assert_eq!(normalize_start_url(url).unwrap(), expected);
+    let uri = written_uri(url);
+    assert_eq!(uri, expected.as_bytes(), "{}", url);
+    assert_eq!(percent_decode(&uri), url);
+}
+
+#[test]
+fn parentheses_are_kept_and_escaped_by_the_writer() {
+    // `(`/`)`는 URI에 쓸 수 있는 글자라 그대로 두고, literal string 안에서는 lopdf가 `\(`로 쓴다
+    assert_round_trip("https://host/a(1)/b((2)).html", "https://host/a(1)/b((2)).html");
+    assert_round_trip("https://host/x)(y", "https://host/x)(y");
+    let pdf = save("https://host/x)(y");
+    assert!(pdf.windows(21).any(|w| w == br"(https://host/x\)\(y)"));
+}
+
+#[test]
+fn backslash_is_percent_encoded() {
+    assert_round_trip("https://host/a\\b\\", "https://host/a%5Cb%5C");
+}
+
+#[test]
+fn spaces_are_percent_encoded() {
+    assert_round_trip("https://host/my player/play.html?t=a b", "https://host/my%20player/play.html?t=a%20b");
+}
+
+#[test]
+fn first_hash_starts_the_fragment() {
+    assert_round_trip("https://host/play.html#frame=10", "https://host/play.html#frame=10");
+    assert_round_trip("https://host/play.html#a#b", "https://host/play.html#a%23b");
+}
+
+#[test]
+fn unicode_is_utf8_percent_encoded() {
+    assert_round_trip("https://host/배드애플/play.html?t=é", "https://host/%EB%B0%B0%EB%93%9C%EC%95%A0%ED%94%8C/play.html?t=%C3%A9");
+}
+
+#[test]
+fn existing_escapes_are_left_alone() {
+    let url = "https://host/a%28b%29?q=%E2%9C%93";
+    assert_eq!(normalize_start_url(url).unwrap(), url);
+    assert_eq!(written_uri(url), url.as_bytes());
+}
+
+#[test]
+fn rejects_non_http_or_bad_hosts() {
+    for url in ["ftp://host/", "play.html", "https:///path", "https://호스트/", "https://host:80a/", "https://[zz]/"] {
+        assert!(normalize_start_url(url).is_err(), "{}", url);
+    }
+}
+
+fn vars() -> StartUrlVars {
+    StartUrlVars {
+        width: 320,
+        height: 240,
+        fps: 29.97,
+        frames: 6572,
+        bytes: 1234567,
+        blob_sha256: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".to_string(),
+    }
+}
+
+#[test]
+fn each_placeholder_is_expanded() {
+    for (template, expected) in [
+        ("{w}", "320"),
+        ("{h}", "240"),
+        ("{fps}", "29.97"),
+        ("{frames}", "6572"),
+        ("{bytes}", "1234567"),
+        ("{blob_sha256}", "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"),
+        ("https://host/p.html?s={w}x{h}&n={frames}", "https://host/p.html?s=320x240&n=6572"),
+        ("https://host/play.html", "https://host/play.html"),
+    ] {
+        assert_eq!(expand_start_url(template, &vars()).unwrap(), expected, "{}", template);
+    }
+    assert_eq!(start_url_placeholders("https://host/{w}/{h}?v={blob_sha256:8}").unwrap(), ["w", "h", "blob_sha256"]);
+}
+
+#[test]
+fn hash_prefix_length() {
+    let url = expand_start_url("https://host/BA.bin?v={blob_sha256:8}", &vars()).unwrap();
+    assert_eq!(url, "https://host/BA.bin?v=9f86d081");
+    assert_eq!(expand_start_url("{blob_sha256:1}", &vars()).unwrap(), "9");
+    assert_eq!(expand_start_url("{blob_sha256:64}", &vars()).unwrap(), vars().blob_sha256);
+    for bad in ["{blob_sha256:0}", "{blob_sha256:65}", "{blob_sha256:x}", "{blob_sha256:}", "{w:2}"] {
+        assert!(expand_start_url(bad, &vars()).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn unknown_placeholder_is_an_error() {
+    let err = format!("{:#}", expand_start_url("https://host/?t={title}", &vars()).unwrap_err());
+    assert!(err.contains("unknown placeholder {title}") && err.contains("{blob_sha256:N}"), "{}", err);
+    assert!(start_url_placeholders("{W}").is_err());
+    assert!(start_url_placeholders("{}").is_err());
+}
+
+#[test]
+fn doubled_braces_are_literal() {
+    assert_eq!(expand_start_url("https://host/{{w}}/{w}", &vars()).unwrap(), "https://host/{w}/320");
+    assert_eq!(expand_start_url("{{{h}}}", &vars()).unwrap(), "{240}");
+    assert_eq!(start_url_placeholders("{{w}}").unwrap(), Vec::<String>::new());
+    // 리터럴 중괄호는 URI에 쓸 수 없는 글자라 normalize에서 escape된다
+    let url = expand_start_url("https://host/a{{b}}", &vars()).unwrap();
+    assert_eq!(normalize_start_url(&url).unwrap(), "https://host/a%7Bb%7D");
+    for bad in ["https://host/{w", "https://host/w}", "{", "}"] {
+        let err = format!("{:#}", expand_start_url(bad, &vars()).unwrap_err());
+        assert!(err.contains("literal brace"), "{}: {}", bad, err);
+    }
+}